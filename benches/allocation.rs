@@ -0,0 +1,43 @@
+//! Criterion benchmarks for the workloads in `cell_gc::bench_support`. Run
+//! with `cargo bench --features bench-support`.
+
+extern crate cell_gc;
+extern crate criterion;
+
+use cell_gc::bench_support;
+use cell_gc::GcHeap;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+fn binary_trees(c: &mut Criterion) {
+    c.bench_function("binary_trees", |b| {
+        b.iter(|| {
+            let mut heap = GcHeap::new();
+            heap.enter(|hs| black_box(bench_support::binary_trees(hs, 10)))
+        })
+    });
+}
+
+fn list_churn(c: &mut Criterion) {
+    c.bench_function("list_churn", |b| {
+        b.iter(|| {
+            let mut heap = GcHeap::new();
+            heap.enter(|hs| black_box(bench_support::list_churn(hs, 100, 100)))
+        })
+    });
+}
+
+fn large_vector(c: &mut Criterion) {
+    c.bench_function("large_vector", |b| {
+        b.iter(|| {
+            let mut heap = GcHeap::new();
+            heap.enter(|hs| {
+                let v = bench_support::large_vector(hs, 10_000);
+                black_box(v.len());
+            })
+        })
+    });
+}
+
+criterion_group!(benches, binary_trees, list_churn, large_vector);
+criterion_main!(benches);
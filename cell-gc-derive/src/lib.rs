@@ -9,7 +9,7 @@ use proc_macro::TokenStream;
 use syn::Ident;
 use quote::Tokens;
 
-#[proc_macro_derive(IntoHeap)]
+#[proc_macro_derive(IntoHeap, attributes(into_heap, gc_ref_derive))]
 pub fn derive_into_heap(input: TokenStream) -> TokenStream {
     let source = input.to_string();
     let ast = syn::parse_derive_input(&source).unwrap();
@@ -136,6 +136,282 @@ fn ty_to_static(ty: &mut syn::Ty, heap_lifetime: &syn::Lifetime) {
     }
 }
 
+// Does a field of (source, not storage) type `ty` support being carried
+// along by a derived `Adopt` impl?
+//
+// This can't be a `where`-clause bound the way `trace()`'s requirements are:
+// storage types are generic only in the sense of no longer mentioning `'h`,
+// so a bound like `where ValueStorage: Adopt` is fully concrete, and rustc
+// checks fully concrete bounds at the `impl` itself rather than deferring
+// them to the call site. That's fatal for a recursive type like `Shype`
+// (whose own bound would need to prove itself) and for any type that embeds
+// another derived type inline (the embedding type's `impl` fails outright if
+// the embedded type turns out not to support `Adopt`, instead of simply not
+// applying).
+//
+// So instead this is a syntactic, best-effort check performed once per
+// field, and the whole `Adopt` impl for a struct or enum is only emitted if
+// every field passes it. A field whose type is `own_ref_name` (or wraps it
+// in an `Option`) --- that is, a `FooRef<'h>` field of `Foo` itself --- is
+// assumed to support it: that's a reference back to the very type currently
+// being derived, so its `Adopt` impl existing is exactly the question this
+// function is in the middle of answering, and the recursive call in the
+// generated method body resolves against that same impl once it exists,
+// the same way any other recursive method call would. A field referring to
+// some *other* derived type via `*Ref` can't be vouched for the same way,
+// since this macro invocation has no way to know whether that other type
+// ends up supporting `Adopt`, so such fields are conservatively treated as
+// unsupported, same as a directly-embedded (non-`*Ref`) foreign type.
+fn ty_supports_adopt(ty: &syn::Ty, own_ref_name: &str) -> bool {
+    match *ty {
+        syn::Ty::Tup(ref elem_types) =>
+            elem_types.iter().all(|ty| ty_supports_adopt(ty, own_ref_name)),
+        syn::Ty::Path(_, ref path) => {
+            let segment = path.segments.last().unwrap();
+            let ident: &str = segment.ident.as_ref();
+            let type_arg = || match segment.parameters {
+                syn::PathParameters::AngleBracketed(ref data) => data.types.last(),
+                syn::PathParameters::Parenthesized(_) => None,
+            };
+            match ident {
+                "Option" => type_arg().map_or(false, |ty| ty_supports_adopt(ty, own_ref_name)),
+                "VecRef" => type_arg().map_or(false, |ty| ty_supports_adopt(ty, own_ref_name)),
+                "EphemeronRef" | "WeakRef" | "FinalizedRef" |
+                "GcLeaf" | "Box" | "Arc" | "Rc" => false,
+                "String" | "bool" | "char" |
+                "i8" | "i16" | "i32" | "i64" | "isize" |
+                "u8" | "u16" | "u32" | "u64" | "usize" |
+                "f32" | "f64" => true,
+                _ if ident == own_ref_name => true,
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+// Whether `ty` is plain, non-GC data that can go straight through
+// `serde::Serialize`/`Deserialize` with no help from the heap.
+//
+// Unlike `ty_supports_adopt`, a field referring back into the heap ---
+// even a `FooRef<'h>` field of `Foo` itself --- is conservatively treated
+// as unsupported here. Serializing one soundly means either following the
+// pointer (which needs an id scheme to survive a cycle, or even just two
+// fields pointing at the same object) or writing out some kind of
+// placeholder, and this doesn't attempt either yet: see `serde`'s docs on
+// `#[derive(IntoHeap)]` for what that would take.
+fn ty_supports_serde(ty: &syn::Ty) -> bool {
+    match *ty {
+        syn::Ty::Tup(ref elem_types) => elem_types.iter().all(ty_supports_serde),
+        syn::Ty::Path(_, ref path) => {
+            let segment = path.segments.last().unwrap();
+            let ident: &str = segment.ident.as_ref();
+            let type_arg = || match segment.parameters {
+                syn::PathParameters::AngleBracketed(ref data) => data.types.last(),
+                syn::PathParameters::Parenthesized(_) => None,
+            };
+            match ident {
+                "Option" => type_arg().is_some_and(ty_supports_serde),
+                "PhantomData" => true,
+                "String" | "bool" | "char" |
+                "i8" | "i16" | "i32" | "i64" | "isize" |
+                "u8" | "u16" | "u32" | "u64" | "usize" |
+                "f32" | "f64" => true,
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+// Look for `#[into_heap(ref_name = "...")]` among a struct's attributes, so
+// users who don't want the default `FooRef` name (a clash with some other
+// `Foo`-prefixed type in scope, say) can pick their own.
+fn ref_name_override(attrs: &[syn::Attribute]) -> Option<String> {
+    for attr in attrs {
+        if let syn::MetaItem::List(ref ident, ref nested) = attr.value {
+            if ident.as_ref() == "into_heap" {
+                for item in nested {
+                    if let syn::NestedMetaItem::MetaItem(
+                        syn::MetaItem::NameValue(ref key, syn::Lit::Str(ref value, _)),
+                    ) = *item
+                    {
+                        if key.as_ref() == "ref_name" {
+                            return Some(value.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+// Look for a bare word like `#[into_heap(no_setter)]` among a field's (or
+// struct's) attributes.
+fn has_into_heap_flag(attrs: &[syn::Attribute], flag: &str) -> bool {
+    for attr in attrs {
+        if let syn::MetaItem::List(ref ident, ref nested) = attr.value {
+            if ident.as_ref() == "into_heap" {
+                for item in nested {
+                    if let syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) = *item {
+                        if word.as_ref() == flag {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+// Look for `#[gc_ref_derive(...)]` among a struct's or enum's attributes,
+// returning the bare words inside (`PartialOrd`, `Ord`, `Display`, ...). The
+// generated `Ref` type always gets `#[derive(Clone, Debug, PartialEq, Eq)]`
+// plus a hand-written `Hash` forwarding to its `GcRef`; this attribute lets
+// callers opt into more forwarding impls of the same kind.
+fn gc_ref_derive_list(attrs: &[syn::Attribute]) -> Vec<String> {
+    for attr in attrs {
+        if let syn::MetaItem::List(ref ident, ref nested) = attr.value {
+            if ident.as_ref() == "gc_ref_derive" {
+                return nested
+                    .iter()
+                    .filter_map(|item| match *item {
+                        syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) => {
+                            Some(word.as_ref().to_string())
+                        }
+                        _ => None,
+                    })
+                    .collect();
+            }
+        }
+    }
+    Vec::new()
+}
+
+// Build the impls a `Ref` type opted into with `#[gc_ref_derive(...)]`.
+//
+// * `PartialOrd`/`Ord` order by address, the same way the ever-present
+//   `Hash` impl hashes by address.
+// * `Display` delegates to `display_getter`, an expression reading the
+//   referent's one meaningful value (a single field's getter, or an enum
+//   `Ref`'s `get()`) --- there's no way to guess how to `Display` a `Ref`
+//   with more than one field, so `display_getter` is `None` in that case,
+//   and asking for `Display` on one is a usage error caught here instead of
+//   further down in generated code.
+fn ref_type_extra_derives(
+    attrs: &[syn::Attribute],
+    ref_type_name: &Ident,
+    impl_generics: &syn::ImplGenerics,
+    ty_generics: &syn::TyGenerics,
+    where_clause: &syn::WhereClause,
+    display_getter: Option<Tokens>,
+) -> Tokens {
+    let derives = gc_ref_derive_list(attrs);
+
+    let partial_ord_impl = if derives.iter().any(|d| d == "PartialOrd") {
+        quote! {
+            impl #impl_generics ::std::cmp::PartialOrd for #ref_type_name #ty_generics
+                #where_clause
+            {
+                #[inline]
+                fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {
+                    self.0.address().partial_cmp(&other.0.address())
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let ord_impl = if derives.iter().any(|d| d == "Ord") {
+        quote! {
+            impl #impl_generics ::std::cmp::Ord for #ref_type_name #ty_generics
+                #where_clause
+            {
+                #[inline]
+                fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
+                    self.0.address().cmp(&other.0.address())
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let display_impl = if derives.iter().any(|d| d == "Display") {
+        let getter = display_getter.unwrap_or_else(|| {
+            panic!(
+                "#[gc_ref_derive(Display)] needs a single field (or, for an \
+                 enum, its own Display impl) to delegate to"
+            )
+        });
+        quote! {
+            impl #impl_generics ::std::fmt::Display for #ref_type_name #ty_generics
+                #where_clause
+            {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                    ::std::fmt::Display::fmt(&#getter, f)
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        #partial_ord_impl
+        #ord_impl
+        #display_impl
+    }
+}
+
+// Whether a field's storage type is plain data with no GC pointer buried
+// inside it, safe to expose a `&mut` to directly for `update_<field>` (see
+// the accessors generated below). This is the opposite question from
+// `ty_supports_adopt`: a `FooRef<'h>` field's storage is a live `Pointer`,
+// which `Adopt` knows how to walk and deep-copy but which nothing outside
+// this macro's own generated code should ever get a `&mut` to (see
+// `Pointer`'s docs) --- so such fields, unlike in `ty_supports_adopt`, are
+// excluded here. `GcLeaf<T>` and the primitive types, on the other hand,
+// store their value as-is with nothing for the collector to trace, so a
+// `&mut` to them is exactly as safe as a `&mut` to any other plain Rust
+// value.
+fn ty_is_leaf_storage(ty: &syn::Ty) -> bool {
+    match *ty {
+        syn::Ty::Path(_, ref path) => {
+            let segment = path.segments.last().unwrap();
+            let ident: &str = segment.ident.as_ref();
+            matches!(
+                ident,
+                "GcLeaf" |
+                "String" | "bool" | "char" |
+                "i8" | "i16" | "i32" | "i64" | "isize" |
+                "u8" | "u16" | "u32" | "u64" | "usize" |
+                "f32" | "f64"
+            )
+        }
+        _ => false,
+    }
+}
+
+// Unlike structs, enums don't get a `Ref` type (and therefore aren't
+// directly `heap.alloc`-able) unless asked for: an enum field nested inside
+// some other `#[derive(IntoHeap)]` struct doesn't need one, since callers
+// reach it through the containing struct's own `Ref`. Opt in with
+// `#[into_heap(make_ref)]` for the default `FooRef` name, or
+// `#[into_heap(ref_name = "...")]` to opt in and also pick a name.
+fn enum_ref_name(attrs: &[syn::Attribute], name_str: &str) -> Option<String> {
+    if let Some(name) = ref_name_override(attrs) {
+        return Some(name);
+    }
+    if has_into_heap_flag(attrs, "make_ref") {
+        return Some(name_str.to_string() + "Ref");
+    }
+    None
+}
+
 fn field_storage_type(field_ty: &syn::Ty, heap_lifetime: &syn::Lifetime) -> Tokens {
     let mut field_ty_as_static = field_ty.clone();
     ty_to_static(&mut field_ty_as_static, heap_lifetime);
@@ -148,19 +424,35 @@ fn impl_into_heap_for_struct(ast: &syn::DeriveInput, data: &syn::VariantData) ->
     let name = &ast.ident;
     let name_str: &str = name.as_ref();
     let storage_type_name: Ident = Ident::from(name_str.to_string() + "Storage");
+    let own_ref_name = ref_name_override(&ast.attrs).unwrap_or_else(|| name_str.to_string() + "Ref");
     let vis = &ast.vis;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    // Whatever the first lifetime parameter is called becomes "the heap
+    // lifetime" for the rest of codegen --- there's nothing special about
+    // the name `'h` used in this crate's own examples, it's just a
+    // convention callers are free to ignore.
     let heap_lifetime = &ast.generics
         .lifetimes
         .first()
-        .expect("lifetime parameter required")
+        .expect("#[derive(IntoHeap)] requires a lifetime parameter, e.g. struct Foo<'h> { ... }")
         .lifetime;
 
-    // The "Storage" type for a struct or enum must have the static lifetime.
+    // The "Storage" type for a struct or enum must have the static lifetime,
+    // since it's what actually lives in the heap, outliving any particular
+    // caller's borrow of it. That's fine for the heap lifetime itself (see
+    // `ty_to_static`, which rewrites it to `'static` in storage position),
+    // but a *second* lifetime parameter would need the same treatment, and
+    // unlike the heap lifetime --- which by construction only ever appears
+    // on `*Ref` fields, whose storage really is just an owned `Pointer` ---
+    // there's no way to know in general that erasing a second, unrelated
+    // lifetime to `'static` wouldn't let a real borrow dangle. So for now,
+    // exactly one lifetime parameter is allowed.
     let mut storage_generics = ast.generics.clone();
     storage_generics.lifetimes.remove(0);  // Remove heap lifetime.
     assert!(storage_generics.lifetimes.is_empty(),
-            "IntoHeap struct must have exactly one lifetime parameter");
+            "IntoHeap struct must have exactly one lifetime parameter (the heap lifetime); \
+             a second lifetime would require storing possibly-borrowed data in the GC heap, \
+             which isn't supported");
     let (storage_impl_generics, storage_ty_generics, storage_where_clause) =
         storage_generics.split_for_impl();
 
@@ -175,27 +467,546 @@ fn impl_into_heap_for_struct(ast: &syn::DeriveInput, data: &syn::VariantData) ->
 
             // 1. The in-heap representation of the struct.
             let storage_struct = quote! {
-                #vis struct #storage_type_name #storage_impl_generics #storage_where_clause {
-                    #( #field_vis #field_names: #field_storage_types ),*
-                }
+                #vis struct #storage_type_name #storage_impl_generics #storage_where_clause {
+                    #( #field_vis #field_names: #field_storage_types ),*
+                }
+            };
+
+            // 2. IntoHeap implementation.
+            // Body of the trace() method.
+            let trace_fields: Vec<Tokens> = fields
+                .iter()
+                .map(|f| {
+                    let name = &f.ident;
+                    quote! {
+                        ::cell_gc::traits::InHeap::trace(&self.#name, tracer);
+                    }
+                })
+                .collect();
+
+            // Oddly you can't use the same identifier more than once in the
+            // same loop. So create an alias.
+            let field_names_1 = field_names;
+
+            let into_heap = quote! {
+                impl #impl_generics ::cell_gc::traits::InHeap
+                    for #storage_type_name #storage_ty_generics
+                    #where_clause
+                {
+                    unsafe fn trace<R>(&self, tracer: &mut R)
+                        where R: ::cell_gc::traits::Tracer
+                    {
+                        #( #trace_fields )*
+
+                        // Quiet unused variable warnings when `$(...)*` expands
+                        // to nothing.
+                        let _ = tracer;
+                    }
+                }
+
+                impl #impl_generics ::cell_gc::traits::IntoHeapBase
+                    for #name #ty_generics
+                    #where_clause
+                {
+                    type In = #storage_type_name #storage_ty_generics;
+
+                    fn into_heap(self) -> Self::In {
+                        #storage_type_name {
+                            #(
+                                #field_names:
+                                    ::cell_gc::traits::IntoHeapBase::into_heap(
+                                        self.#field_names_1)
+                            ),*
+                        }
+                    }
+
+                    unsafe fn from_heap(storage: &Self::In) -> Self {
+                        #name {
+                            #(
+                                #field_names:
+                                    ::cell_gc::traits::IntoHeapBase::from_heap(
+                                        &storage.#field_names_1)
+                            ),*
+                        }
+                    }
+                }
+
+                unsafe impl #impl_generics ::cell_gc::traits::IntoHeap<#heap_lifetime>
+                    for #name #ty_generics
+                    #where_clause
+                {}
+            };
+
+            // 2a. Adopt implementation, so `GcHeapSession::adopt` works for
+            // this type without hand-written code. Only emitted if every
+            // field supports it (see `ty_supports_adopt`); a struct with a
+            // field type that doesn't (see `Adopt`'s docs for which those
+            // are) simply doesn't get this impl, so `GcHeapSession::adopt`
+            // won't compile for it.
+            let adopt_impl = if fields.iter().all(|f| ty_supports_adopt(&f.ty, &own_ref_name)) {
+                quote! {
+                    impl #storage_impl_generics ::cell_gc::traits::Adopt
+                        for #storage_type_name #storage_ty_generics
+                    {
+                        unsafe fn adopt(&self, adopter: &mut ::cell_gc::adopt::Adopter) -> Self {
+                            #storage_type_name {
+                                #(
+                                    #field_names:
+                                        ::cell_gc::traits::Adopt::adopt(
+                                            &self.#field_names_1, adopter)
+                                ),*
+                            }
+                        }
+                    }
+                }
+            } else {
+                quote! {}
+            };
+
+            // 2b. GcSerialize implementation, gated by the same
+            // `ty_supports_adopt` check as the `Adopt` impl above: the two
+            // traits are supported by exactly the same set of field types,
+            // for the same reason (see `GcSerialize`'s docs).
+            let serialize_impl = if fields.iter().all(|f| ty_supports_adopt(&f.ty, &own_ref_name)) {
+                quote! {
+                    impl #storage_impl_generics ::cell_gc::traits::GcSerialize
+                        for #storage_type_name #storage_ty_generics
+                    {
+                        unsafe fn write_fields(
+                            &self,
+                            ctx: &mut ::cell_gc::serialize::Serializer,
+                            buf: &mut Vec<u8>,
+                        ) {
+                            #(
+                                ::cell_gc::traits::GcSerialize::write_fields(
+                                    &self.#field_names_1, ctx, buf);
+                            )*
+
+                            // Quiet unused variable warnings when `$(...)*`
+                            // expands to nothing.
+                            let _ = ctx;
+                            let _ = buf;
+                        }
+
+                        #[allow(unused_variables)]
+                        unsafe fn read_fields(
+                            ctx: &mut ::cell_gc::serialize::Deserializer,
+                            buf: &mut ::cell_gc::serialize::Cursor,
+                        ) -> Self {
+                            #storage_type_name {
+                                #(
+                                    #field_names:
+                                        ::cell_gc::traits::GcSerialize::read_fields(ctx, buf)
+                                ),*
+                            }
+                        }
+                    }
+                }
+            } else {
+                quote! {}
+            };
+
+            // 3. IntoHeapAllocation implementation.
+            let ref_type_name: Ident = Ident::from(own_ref_name.clone());
+            let into_heap_allocation = quote! {
+                impl #impl_generics ::cell_gc::traits::IntoHeapAllocation<#heap_lifetime>
+                    for #name #ty_generics
+                    #where_clause
+                {
+                    type Ref = #ref_type_name #ty_generics;
+
+                    fn wrap_gc_ref(gc_ref: ::cell_gc::GcRef<#heap_lifetime, #name #ty_generics>)
+                        -> Self::Ref
+                    {
+                        #ref_type_name(gc_ref)
+                    }
+
+                    fn into_gc_ref(wrapped_ref: Self::Ref)
+                        -> ::cell_gc::GcRef<#heap_lifetime, #name #ty_generics>
+                    {
+                        wrapped_ref.0
+                    }
+                }
+            };
+
+            // 3a. Optional serde support, gated behind the `serde` feature
+            // *and* an explicit `#[into_heap(serde)]` opt-in on the struct.
+            // Unlike `serialize_impl` above, this can't just key off the
+            // field types: the generated code needs `::serde` in scope,
+            // and nothing about deriving `IntoHeap` implies the crate using
+            // it has an `extern crate serde;` to satisfy that, the way it's
+            // guaranteed to have `extern crate cell_gc;`. So a struct has to
+            // ask for this before it gets it; asking for it when a field
+            // doesn't support it (see `ty_supports_serde`, which is
+            // stricter than `ty_supports_adopt`: it doesn't even allow a
+            // field pointing back into the heap, since serializing one
+            // soundly needs an id scheme to survive sharing and cycles,
+            // which this doesn't attempt) is a compile error.
+            //
+            // `Serialize` reads the ref type through its own getters, the
+            // same way a hand-written impl would. `Deserialize` alone has
+            // nowhere to get a heap to allocate into, so deserializing goes
+            // through a `DeserializeSeed` (`FooSeed`) that carries one,
+            // producing a `FooRef` instead of a bare `Foo`.
+            let wants_serde = has_into_heap_flag(&ast.attrs, "serde");
+            if wants_serde {
+                for f in fields.iter() {
+                    if !ty_supports_serde(&f.ty) {
+                        panic!(
+                            "#[into_heap(serde)] on `{}` requires every field to be \
+                             plain data (see cell_gc_derive::ty_supports_serde); \
+                             `{}` isn't",
+                            name_str, f.ident.as_ref().unwrap());
+                    }
+                }
+            }
+            let serde_impl = if wants_serde {
+                let fields_name: Ident = Ident::from(format!("{}SerdeFields", name_str));
+                let seed_name: Ident = Ident::from(format!("{}Seed", name_str));
+                let num_fields = field_names.len();
+                quote! {
+                    #[cfg(feature = "serde")]
+                    impl #impl_generics ::serde::Serialize for #ref_type_name #ty_generics
+                        #where_clause
+                    {
+                        fn serialize<S>(&self, serializer: S)
+                            -> ::std::result::Result<S::Ok, S::Error>
+                            where S: ::serde::Serializer,
+                        {
+                            use ::serde::ser::SerializeStruct;
+                            let mut state =
+                                serializer.serialize_struct(stringify!(#name), #num_fields)?;
+                            #(
+                                state.serialize_field(
+                                    stringify!(#field_names), &self.#field_names_1())?;
+                            )*
+                            state.end()
+                        }
+                    }
+
+                    #[cfg(feature = "serde")]
+                    #[derive(::serde::Deserialize)]
+                    struct #fields_name #impl_generics #where_clause {
+                        #( #field_names: #field_types ),*
+                    }
+
+                    /// Deserializes a `#ref_type_name`, allocated into
+                    /// `heap`. See the `serde` support note on
+                    /// `#[derive(IntoHeap)]` for why this is a
+                    /// `DeserializeSeed` rather than a plain `Deserialize`
+                    /// impl.
+                    #[cfg(feature = "serde")]
+                    #vis struct #seed_name<'a, #heap_lifetime> {
+                        pub heap: &'a mut ::cell_gc::GcHeapSession<#heap_lifetime>,
+                    }
+
+                    #[cfg(feature = "serde")]
+                    impl<'de, 'a, #heap_lifetime> ::serde::de::DeserializeSeed<'de>
+                        for #seed_name<'a, #heap_lifetime>
+                    {
+                        type Value = #ref_type_name #ty_generics;
+
+                        fn deserialize<D>(self, deserializer: D)
+                            -> ::std::result::Result<Self::Value, D::Error>
+                            where D: ::serde::Deserializer<'de>,
+                        {
+                            let fields = <#fields_name as ::serde::Deserialize>::deserialize(deserializer)?;
+                            Ok(self.heap.alloc(#name {
+                                #( #field_names: fields.#field_names_1 ),*
+                            }))
+                        }
+                    }
+                }
+            } else {
+                quote! {}
+            };
+
+            // 4. #ref_type_name: A safe reference to the struct
+            let ref_type = quote! {
+                #[derive(Clone, Debug, PartialEq, Eq)]
+                #vis struct #ref_type_name #impl_generics
+                    (::cell_gc::GcRef<#heap_lifetime, #name #ty_generics>)
+                    #where_clause;
+            };
+
+            // 5. The ref type also gets an IntoHeap impl...
+            let ref_type_into_heap = quote! {
+                impl #impl_generics ::cell_gc::traits::IntoHeapBase
+                    for #ref_type_name #ty_generics
+                    #where_clause
+                {
+                    type In = <::cell_gc::GcRef<#heap_lifetime, #name #ty_generics>
+                               as ::cell_gc::traits::IntoHeapBase>::In;
+
+                    fn into_heap(self) -> Self::In {
+                        self.0.into_heap()
+                    }
+
+                    unsafe fn from_heap(storage: &Self::In) -> Self {
+                        #ref_type_name(::cell_gc::GcRef::<#heap_lifetime, #name #ty_generics>::new(*storage))
+                    }
+                }
+
+                unsafe impl #impl_generics ::cell_gc::traits::IntoHeap<#heap_lifetime>
+                    for #ref_type_name #ty_generics
+                    #where_clause
+                {}
+            };
+
+            // 6. The ref type also hashes...
+            let ref_type_hash = quote! {
+                impl #impl_generics ::std::hash::Hash for #ref_type_name #ty_generics
+                    #where_clause
+                {
+                    #[inline]
+                    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                        self.0.hash(state);
+                    }
+                }
+            };
+
+            // 7. Getters and setters. A field marked `#[into_heap(no_setter)]`
+            // gets only a getter, so a struct can keep an invariant like
+            // "length always matches buffer" by simply not exposing a way to
+            // overwrite the field on its own; each accessor's visibility
+            // (`pub`, `pub(crate)`, private, ...) just follows the
+            // visibility already written on the field in the struct
+            // definition, same as it always has.
+            let settable_fields: Vec<&syn::Field> = fields
+                .iter()
+                .filter(|f| !has_into_heap_flag(&f.attrs, "no_setter"))
+                .collect();
+            let setter_vis: &Vec<_> = &settable_fields.iter().map(|f| &f.vis).collect();
+            let setter_field_names: &Vec<_> = &settable_fields.iter().map(|f| &f.ident).collect();
+            let setter_field_types: &Vec<_> = &settable_fields.iter().map(|f| &f.ty).collect();
+            let field_setter_names: Vec<_> = settable_fields
+                .iter()
+                .map(|f| {
+                    let field_str: &str = f.ident.as_ref().unwrap().as_ref();
+                    Ident::from(format!("set_{}", field_str))
+                })
+                .collect();
+
+            // Field marked `#[into_heap(no_setter)]` don't get `update_*`
+            // either, for the same reason they don't get a setter. Beyond
+            // that, `update_<field>` is only offered for a field whose
+            // storage is plain data (see `ty_is_leaf_storage`) --- a
+            // `FooRef<'h>` field's storage is a live `Pointer`, and hackers
+            // getting to mutate one directly would bypass every guarantee
+            // `Pointer` depends on this macro alone to uphold. This is the
+            // whole point of `update_<field>`: for a field like a big
+            // `String`, it lets a caller mutate the in-heap value in place,
+            // instead of the round trip `set_<field>` requires of copying
+            // the whole field out with the getter, then converting a new
+            // value back into storage.
+            let updatable_fields: Vec<&&syn::Field> = settable_fields
+                .iter()
+                .filter(|f| ty_is_leaf_storage(&f.ty))
+                .collect();
+            let update_field_names: &Vec<_> = &updatable_fields.iter().map(|f| &f.ident).collect();
+            let update_field_types: &Vec<_> = &updatable_fields.iter().map(|f| &f.ty).collect();
+            let update_vis: &Vec<_> = &updatable_fields.iter().map(|f| &f.vis).collect();
+            let field_update_names: Vec<_> = updatable_fields
+                .iter()
+                .map(|f| {
+                    let field_str: &str = f.ident.as_ref().unwrap().as_ref();
+                    Ident::from(format!("update_{}", field_str))
+                })
+                .collect();
+
+            let accessors = quote! {
+                impl #impl_generics #ref_type_name #ty_generics #where_clause {
+                    #(
+                        #[allow(dead_code)]
+                        #field_vis fn #field_names(&self) -> #field_types {
+                            ::cell_gc::borrow_flag::check_not_borrowed(self.0.address());
+                            let ptr = self.0.as_ptr();
+                            unsafe {
+                                ::cell_gc::traits::IntoHeapBase::from_heap(
+                                    &(*ptr).#field_names_1)
+                            }
+                        }
+                    )*
+
+                    #(
+                        #[allow(dead_code)]
+                        #setter_vis fn #field_setter_names(&self, v: #setter_field_types) {
+                            ::cell_gc::borrow_flag::check_not_borrowed(self.0.address());
+                            let ptr = self.0.as_mut_ptr();
+                            let u = ::cell_gc::traits::IntoHeapBase::into_heap(v);
+                            unsafe {
+                                (*ptr).#setter_field_names = u;
+                            }
+                        }
+                    )*
+
+                    #(
+                        #[allow(dead_code)]
+                        #update_vis fn #field_update_names(&self, f: impl FnOnce(&mut #update_field_types)) {
+                            let _guard = ::cell_gc::borrow_flag::BorrowGuard::new(self.0.address());
+                            let ptr = self.0.as_mut_ptr();
+                            unsafe {
+                                f(&mut (*ptr).#update_field_names);
+                            }
+                        }
+                    )*
+
+                    ///// Get all fields at once.
+                    //pub fn get(&self) -> #name {
+                    //    ::cell_gc::traits::IntoHeapBase::from_heap(self.0.ptr())
+                    //}
+
+                    #[allow(dead_code)]
+                    pub fn as_mut_ptr(&self) -> *mut #storage_type_name #storage_ty_generics {
+                        self.0.as_mut_ptr()
+                    }
+
+                    /// See `GcRef::with_storage`.
+                    #[allow(dead_code)]
+                    pub unsafe fn with_storage<R>(
+                        &self,
+                        f: impl FnOnce(&#storage_type_name #storage_ty_generics) -> R,
+                    ) -> R {
+                        self.0.with_storage(f)
+                    }
+
+                    /// See `GcRef::address`.
+                    #[allow(dead_code)]
+                    pub fn address(&self) -> usize {
+                        self.0.address()
+                    }
+
+                    /// See `GcRef::object_id`.
+                    #[allow(dead_code)]
+                    pub fn object_id(&self) -> usize {
+                        self.0.object_id()
+                    }
+
+                    /// See `GcRef::age`.
+                    #[allow(dead_code)]
+                    pub fn age(&self) -> u8 {
+                        self.0.age()
+                    }
+
+                    /// See `GcRef::is_tenured`.
+                    #[allow(dead_code)]
+                    pub fn is_tenured(&self) -> bool {
+                        self.0.is_tenured()
+                    }
+
+                    /// See `GcRef::get_user_flag`.
+                    #[allow(dead_code)]
+                    pub fn get_user_flag(&self, index: u8) -> bool {
+                        self.0.get_user_flag(index)
+                    }
+
+                    /// See `GcRef::set_user_flag`.
+                    #[allow(dead_code)]
+                    pub fn set_user_flag(&self, index: u8, value: bool) {
+                        self.0.set_user_flag(index, value)
+                    }
+                }
+            };
+
+            let display_getter = if field_names.len() == 1 {
+                let field_name = &field_names[0];
+                Some(quote! { self.#field_name() })
+            } else {
+                None
+            };
+            let ref_type_extra = ref_type_extra_derives(
+                &ast.attrs,
+                &ref_type_name,
+                &impl_generics,
+                &ty_generics,
+                where_clause,
+                display_getter,
+            );
+
+            quote! {
+                #storage_struct
+                #into_heap
+                #adopt_impl
+                #serialize_impl
+                #into_heap_allocation
+                #serde_impl
+                #ref_type
+                #ref_type_into_heap
+                #ref_type_hash
+                #ref_type_extra
+                #accessors
+            }
+        }
+        syn::VariantData::Tuple(ref fields) => {
+            let field_vis: &Vec<_> = &fields.iter().map(|f| &f.vis).collect();
+            let field_types: &Vec<_> = &fields.iter().map(|f| &f.ty).collect();
+            let field_storage_types: &Vec<_> = &fields.iter()
+                .map(|f| field_storage_type(&f.ty, heap_lifetime))
+                .collect();
+
+            // Tuple structs have no field names to hang the generated code
+            // off of, so make up bindings (`f0`, `f1`, ...) to destructure
+            // into, and use those everywhere a named-field struct would use
+            // `field_names`.
+            let bindings: &Vec<Ident> = &(0..fields.len())
+                .map(|n| Ident::from(format!("f{}", n)))
+                .collect();
+            // Note: these are `Ident`s, not `usize`s, even though all they'll
+            // ever hold is a plain decimal number --- interpolating a
+            // `usize` directly (as `#i`) would print it as `0usize`, which
+            // is invalid where a tuple index is expected (`(*ptr).0usize`).
+            let indices: &Vec<Ident> = &(0..fields.len())
+                .map(|n| Ident::from(n.to_string()))
+                .collect();
+            let getter_names: &Vec<Ident> = &(0..fields.len())
+                .map(|n| Ident::from(format!("get{}", n)))
+                .collect();
+
+            // A field marked `#[into_heap(no_setter)]` gets no `setN`
+            // method; see the matching comment in the named-field-struct
+            // case above.
+            let settable: Vec<(usize, &syn::Field)> = fields
+                .iter()
+                .enumerate()
+                .filter(|&(_, f)| !has_into_heap_flag(&f.attrs, "no_setter"))
+                .collect();
+            let setter_vis: &Vec<_> = &settable.iter().map(|&(_, f)| &f.vis).collect();
+            let setter_field_types: &Vec<_> = &settable.iter().map(|&(_, f)| &f.ty).collect();
+            let setter_indices: &Vec<Ident> = &settable
+                .iter()
+                .map(|&(i, _)| Ident::from(i.to_string()))
+                .collect();
+            let setter_names: &Vec<Ident> = &settable
+                .iter()
+                .map(|&(i, _)| Ident::from(format!("set{}", i)))
+                .collect();
+
+            // `updateN` methods; see the matching comment in the
+            // named-field-struct case above.
+            let updatable: Vec<(usize, &syn::Field)> = settable
+                .iter()
+                .cloned()
+                .filter(|&(_, f)| ty_is_leaf_storage(&f.ty))
+                .collect();
+            let update_vis: &Vec<_> = &updatable.iter().map(|&(_, f)| &f.vis).collect();
+            let update_field_types: &Vec<_> = &updatable.iter().map(|&(_, f)| &f.ty).collect();
+            let update_indices: &Vec<Ident> = &updatable
+                .iter()
+                .map(|&(i, _)| Ident::from(i.to_string()))
+                .collect();
+            let update_names: &Vec<Ident> = &updatable
+                .iter()
+                .map(|&(i, _)| Ident::from(format!("update{}", i)))
+                .collect();
+
+            // 1. The in-heap representation of the struct.
+            let storage_struct = quote! {
+                #vis struct #storage_type_name #storage_impl_generics
+                    (#( #field_vis #field_storage_types ),*)
+                    #storage_where_clause;
             };
 
             // 2. IntoHeap implementation.
-            // Body of the trace() method.
-            let trace_fields: Vec<Tokens> = fields
-                .iter()
-                .map(|f| {
-                    let name = &f.ident;
-                    quote! {
-                        ::cell_gc::traits::InHeap::trace(&self.#name, tracer);
-                    }
-                })
-                .collect();
-
-            // Oddly you can't use the same identifier more than once in the
-            // same loop. So create an alias.
-            let field_names_1 = field_names;
-
             let into_heap = quote! {
                 impl #impl_generics ::cell_gc::traits::InHeap
                     for #storage_type_name #storage_ty_generics
@@ -204,7 +1015,11 @@ fn impl_into_heap_for_struct(ast: &syn::DeriveInput, data: &syn::VariantData) ->
                     unsafe fn trace<R>(&self, tracer: &mut R)
                         where R: ::cell_gc::traits::Tracer
                     {
-                        #( #trace_fields )*
+                        let &#storage_type_name ( #(ref #bindings),* ) = self;
+
+                        #(
+                            ::cell_gc::traits::InHeap::trace(#bindings, tracer);
+                        )*
 
                         // Quiet unused variable warnings when `$(...)*` expands
                         // to nothing.
@@ -219,23 +1034,17 @@ fn impl_into_heap_for_struct(ast: &syn::DeriveInput, data: &syn::VariantData) ->
                     type In = #storage_type_name #storage_ty_generics;
 
                     fn into_heap(self) -> Self::In {
-                        #storage_type_name {
-                            #(
-                                #field_names:
-                                    ::cell_gc::traits::IntoHeapBase::into_heap(
-                                        self.#field_names_1)
-                            ),*
-                        }
+                        let #name ( #(#bindings),* ) = self;
+                        #storage_type_name (
+                            #( ::cell_gc::traits::IntoHeapBase::into_heap(#bindings) ),*
+                        )
                     }
 
                     unsafe fn from_heap(storage: &Self::In) -> Self {
-                        #name {
-                            #(
-                                #field_names:
-                                    ::cell_gc::traits::IntoHeapBase::from_heap(
-                                        &storage.#field_names_1)
-                            ),*
-                        }
+                        let &#storage_type_name ( #(ref #bindings),* ) = storage;
+                        #name (
+                            #( ::cell_gc::traits::IntoHeapBase::from_heap(#bindings) ),*
+                        )
                     }
                 }
 
@@ -245,8 +1054,68 @@ fn impl_into_heap_for_struct(ast: &syn::DeriveInput, data: &syn::VariantData) ->
                 {}
             };
 
+            // 2a. Adopt implementation; see the matching comment in the
+            // named-field-struct case above.
+            let adopt_impl = if fields.iter().all(|f| ty_supports_adopt(&f.ty, &own_ref_name)) {
+                quote! {
+                    impl #storage_impl_generics ::cell_gc::traits::Adopt
+                        for #storage_type_name #storage_ty_generics
+                    {
+                        unsafe fn adopt(&self, adopter: &mut ::cell_gc::adopt::Adopter) -> Self {
+                            let &#storage_type_name ( #(ref #bindings),* ) = self;
+                            #storage_type_name (
+                                #( ::cell_gc::traits::Adopt::adopt(#bindings, adopter) ),*
+                            )
+                        }
+                    }
+                }
+            } else {
+                quote! {}
+            };
+
+            // 2b. GcSerialize implementation; see the matching comment in the
+            // named-field-struct case above.
+            let serialize_impl = if fields.iter().all(|f| ty_supports_adopt(&f.ty, &own_ref_name)) {
+                quote! {
+                    impl #storage_impl_generics ::cell_gc::traits::GcSerialize
+                        for #storage_type_name #storage_ty_generics
+                    {
+                        unsafe fn write_fields(
+                            &self,
+                            ctx: &mut ::cell_gc::serialize::Serializer,
+                            buf: &mut Vec<u8>,
+                        ) {
+                            let &#storage_type_name ( #(ref #bindings),* ) = self;
+                            #(
+                                ::cell_gc::traits::GcSerialize::write_fields(#bindings, ctx, buf);
+                            )*
+
+                            // Quiet unused variable warnings when `$(...)*`
+                            // expands to nothing.
+                            let _ = ctx;
+                            let _ = buf;
+                        }
+
+                        #[allow(unused_variables)]
+                        unsafe fn read_fields(
+                            ctx: &mut ::cell_gc::serialize::Deserializer,
+                            buf: &mut ::cell_gc::serialize::Cursor,
+                        ) -> Self {
+                            #storage_type_name (
+                                #(
+                                    <#field_storage_types as ::cell_gc::traits::GcSerialize>
+                                        ::read_fields(ctx, buf)
+                                ),*
+                            )
+                        }
+                    }
+                }
+            } else {
+                quote! {}
+            };
+
             // 3. IntoHeapAllocation implementation.
-            let ref_type_name: Ident = Ident::from(name_str.to_string() + "Ref");
+            let ref_type_name: Ident = Ident::from(own_ref_name.clone());
             let into_heap_allocation = quote! {
                 impl #impl_generics ::cell_gc::traits::IntoHeapAllocation<#heap_lifetime>
                     for #name #ty_generics
@@ -268,7 +1137,7 @@ fn impl_into_heap_for_struct(ast: &syn::DeriveInput, data: &syn::VariantData) ->
                 }
             };
 
-            // 4. #ref_type_name: A safe reference to the struct
+            // 4. #ref_type_name: A safe reference to the struct.
             let ref_type = quote! {
                 #[derive(Clone, Debug, PartialEq, Eq)]
                 #vis struct #ref_type_name #impl_generics
@@ -312,63 +1181,125 @@ fn impl_into_heap_for_struct(ast: &syn::DeriveInput, data: &syn::VariantData) ->
                 }
             };
 
-            // 7. Getters and setters.
-            let field_setter_names: Vec<_> = fields
-                .iter()
-                .map(|f| {
-                    let field_str: &str = f.ident.as_ref().unwrap().as_ref();
-                    Ident::from(format!("set_{}", field_str))
-                })
-                .collect();
+            // 7. Getters and setters, named by position (`get0`/`set0`,
+            // `get1`/`set1`, ...) since tuple fields have no names to base
+            // accessor names on.
             let accessors = quote! {
                 impl #impl_generics #ref_type_name #ty_generics #where_clause {
                     #(
                         #[allow(dead_code)]
-                        #field_vis fn #field_names(&self) -> #field_types {
+                        #field_vis fn #getter_names(&self) -> #field_types {
+                            ::cell_gc::borrow_flag::check_not_borrowed(self.0.address());
                             let ptr = self.0.as_ptr();
                             unsafe {
-                                ::cell_gc::traits::IntoHeapBase::from_heap(
-                                    &(*ptr).#field_names_1)
+                                ::cell_gc::traits::IntoHeapBase::from_heap(&(*ptr).#indices)
                             }
                         }
                     )*
 
                     #(
                         #[allow(dead_code)]
-                        #field_vis fn #field_setter_names(&self, v: #field_types) {
+                        #setter_vis fn #setter_names(&self, v: #setter_field_types) {
+                            ::cell_gc::borrow_flag::check_not_borrowed(self.0.address());
                             let ptr = self.0.as_mut_ptr();
                             let u = ::cell_gc::traits::IntoHeapBase::into_heap(v);
                             unsafe {
-                                (*ptr).#field_names = u;
+                                (*ptr).#setter_indices = u;
                             }
                         }
                     )*
 
-                    ///// Get all fields at once.
-                    //pub fn get(&self) -> #name {
-                    //    ::cell_gc::traits::IntoHeapBase::from_heap(self.0.ptr())
-                    //}
+                    #(
+                        #[allow(dead_code)]
+                        #update_vis fn #update_names(&self, f: impl FnOnce(&mut #update_field_types)) {
+                            let _guard = ::cell_gc::borrow_flag::BorrowGuard::new(self.0.address());
+                            let ptr = self.0.as_mut_ptr();
+                            unsafe {
+                                f(&mut (*ptr).#update_indices);
+                            }
+                        }
+                    )*
 
                     #[allow(dead_code)]
                     pub fn as_mut_ptr(&self) -> *mut #storage_type_name #storage_ty_generics {
                         self.0.as_mut_ptr()
                     }
+
+                    /// See `GcRef::with_storage`.
+                    #[allow(dead_code)]
+                    pub unsafe fn with_storage<R>(
+                        &self,
+                        f: impl FnOnce(&#storage_type_name #storage_ty_generics) -> R,
+                    ) -> R {
+                        self.0.with_storage(f)
+                    }
+
+                    /// See `GcRef::address`.
+                    #[allow(dead_code)]
+                    pub fn address(&self) -> usize {
+                        self.0.address()
+                    }
+
+                    /// See `GcRef::object_id`.
+                    #[allow(dead_code)]
+                    pub fn object_id(&self) -> usize {
+                        self.0.object_id()
+                    }
+
+                    /// See `GcRef::age`.
+                    #[allow(dead_code)]
+                    pub fn age(&self) -> u8 {
+                        self.0.age()
+                    }
+
+                    /// See `GcRef::is_tenured`.
+                    #[allow(dead_code)]
+                    pub fn is_tenured(&self) -> bool {
+                        self.0.is_tenured()
+                    }
+
+                    /// See `GcRef::get_user_flag`.
+                    #[allow(dead_code)]
+                    pub fn get_user_flag(&self, index: u8) -> bool {
+                        self.0.get_user_flag(index)
+                    }
+
+                    /// See `GcRef::set_user_flag`.
+                    #[allow(dead_code)]
+                    pub fn set_user_flag(&self, index: u8, value: bool) {
+                        self.0.set_user_flag(index, value)
+                    }
                 }
             };
 
+            let display_getter = if getter_names.len() == 1 {
+                let getter_name = &getter_names[0];
+                Some(quote! { self.#getter_name() })
+            } else {
+                None
+            };
+            let ref_type_extra = ref_type_extra_derives(
+                &ast.attrs,
+                &ref_type_name,
+                &impl_generics,
+                &ty_generics,
+                where_clause,
+                display_getter,
+            );
+
             quote! {
                 #storage_struct
                 #into_heap
+                #adopt_impl
+                #serialize_impl
                 #into_heap_allocation
                 #ref_type
                 #ref_type_into_heap
                 #ref_type_hash
+                #ref_type_extra
                 #accessors
             }
         }
-        syn::VariantData::Tuple(ref _fields) => {
-            panic!("#[derive(IntoHeap)] does not support tuple structs");
-        }
         syn::VariantData::Unit => {
             panic!("#[derive(IntoHeap)] does not support unit structs");
         }
@@ -380,19 +1311,26 @@ fn impl_into_heap_for_enum(ast: &syn::DeriveInput, variants: &[syn::Variant]) ->
     let name = &ast.ident;
     let name_str: &str = name.as_ref();
     let storage_type_name: Ident = Ident::from(name_str.to_string() + "Storage");
+    let ref_name = enum_ref_name(attrs, name_str);
     let vis = &ast.vis;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    // See the matching comment in `impl_into_heap_for_struct`: any lifetime
+    // name works here, it's just called the "heap lifetime" internally.
     let heap_lifetime = &ast.generics
         .lifetimes
         .first()
-        .expect("lifetime parameter required")
+        .expect("#[derive(IntoHeap)] requires a lifetime parameter, e.g. enum Foo<'h> { ... }")
         .lifetime;
 
-    // The "Storage" type for a struct or enum must have the static lifetime.
+    // The "Storage" type for a struct or enum must have the static lifetime;
+    // see `impl_into_heap_for_struct` for why a second lifetime isn't
+    // supported.
     let mut storage_generics = ast.generics.clone();
     storage_generics.lifetimes.remove(0);  // Remove heap lifetime.
     assert!(storage_generics.lifetimes.is_empty(),
-            "IntoHeap enum must have exactly one lifetime parameter");
+            "IntoHeap enum must have exactly one lifetime parameter (the heap lifetime); \
+             a second lifetime would require storing possibly-borrowed data in the GC heap, \
+             which isn't supported");
     let (storage_impl_generics, storage_ty_generics, storage_where_clause) =
         storage_generics.split_for_impl();
 
@@ -438,7 +1376,6 @@ fn impl_into_heap_for_enum(ast: &syn::DeriveInput, variants: &[syn::Variant]) ->
         }
     });
     let storage_enum = quote! {
-        #( #attrs )*
         #vis enum #storage_type_name #storage_impl_generics
             #storage_where_clause
         {
@@ -528,6 +1465,190 @@ fn impl_into_heap_for_enum(ast: &syn::DeriveInput, variants: &[syn::Variant]) ->
         }
     });
 
+    // Adopt implementation, built the same way as for structs: only emitted
+    // if every field of every variant supports it (see `ty_supports_adopt`).
+    // Unlike the struct case, no self-reference is allowed for here: a field
+    // whose type is the enum's own `*Ref` (see `enum_ref_name`) counts as
+    // unsupported, so such an enum gets no `Adopt` impl.
+    let all_fields_support_adopt = variants.iter().all(|v| {
+        match v.data {
+            syn::VariantData::Struct(ref fields) | syn::VariantData::Tuple(ref fields) =>
+                fields.iter().all(|f| ty_supports_adopt(&f.ty, "")),
+            syn::VariantData::Unit => true,
+        }
+    });
+
+    let adopt_arms = variants.iter().map(|v| {
+        let ident = &v.ident;
+        match v.data {
+            syn::VariantData::Struct(ref fields) => {
+                let field_names: &Vec<_> = &fields.iter().map(|f| &f.ident).collect();
+                let field_names_1 = field_names;
+                quote! {
+                    #storage_type_name::#ident { #(ref #field_names),* } => {
+                        #storage_type_name::#ident {
+                            #(
+                                #field_names_1:
+                                    ::cell_gc::traits::Adopt::adopt(#field_names, adopter)
+                            ),*
+                        }
+                    }
+                }
+            }
+            syn::VariantData::Tuple(ref fields) => {
+                let bindings: &Vec<Ident> = &(0..fields.len())
+                    .map(|n| Ident::from(format!("x{}", n)))
+                    .collect();
+                quote! {
+                    #storage_type_name::#ident( #(ref #bindings),* ) => {
+                        #storage_type_name::#ident(
+                            #(
+                                ::cell_gc::traits::Adopt::adopt(#bindings, adopter)
+                            ),*
+                        )
+                    }
+                }
+            }
+            syn::VariantData::Unit => {
+                quote! {
+                    #storage_type_name::#ident => #storage_type_name::#ident
+                }
+            }
+        }
+    });
+
+    let adopt_impl = if all_fields_support_adopt {
+        quote! {
+            impl #storage_impl_generics ::cell_gc::traits::Adopt
+                for #storage_type_name #storage_ty_generics
+            {
+                unsafe fn adopt(&self, adopter: &mut ::cell_gc::adopt::Adopter) -> Self {
+                    let result = match *self {
+                        #( #adopt_arms ),*
+                    };
+
+                    // Quiet an unused variable warning when every variant is
+                    // a unit variant.
+                    let _ = adopter;
+
+                    result
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // GcSerialize implementation, gated the same way as `adopt_impl` above
+    // (see its comment). Since a `#[derive(IntoHeap)]` enum's storage type
+    // has one variant per source variant, and no discriminant of its own to
+    // reuse, each variant's data is prefixed with its own index as a `u32`
+    // so `read_fields` knows which variant to reconstruct.
+    let write_fields_arms = variants.iter().enumerate().map(|(idx, v)| {
+        let ident = &v.ident;
+        let idx = idx as u32;
+        match v.data {
+            syn::VariantData::Struct(ref fields) => {
+                let field_names: &Vec<_> = &fields.iter().map(|f| &f.ident).collect();
+                quote! {
+                    #storage_type_name::#ident { #(ref #field_names),* } => {
+                        ::cell_gc::serialize::Codec::encode(&#idx, buf);
+                        #(
+                            ::cell_gc::traits::GcSerialize::write_fields(#field_names, ctx, buf);
+                        )*
+                    }
+                }
+            }
+            syn::VariantData::Tuple(ref fields) => {
+                let bindings: &Vec<Ident> = &(0..fields.len())
+                    .map(|n| Ident::from(format!("x{}", n)))
+                    .collect();
+                quote! {
+                    #storage_type_name::#ident( #(ref #bindings),* ) => {
+                        ::cell_gc::serialize::Codec::encode(&#idx, buf);
+                        #(
+                            ::cell_gc::traits::GcSerialize::write_fields(#bindings, ctx, buf);
+                        )*
+                    }
+                }
+            }
+            syn::VariantData::Unit => {
+                quote! {
+                    #storage_type_name::#ident => {
+                        ::cell_gc::serialize::Codec::encode(&#idx, buf);
+                    }
+                }
+            }
+        }
+    });
+
+    let read_fields_arms = variants.iter().enumerate().map(|(idx, v)| {
+        let ident = &v.ident;
+        let idx = idx as u32;
+        match v.data {
+            syn::VariantData::Struct(ref fields) => {
+                let field_names: &Vec<_> = &fields.iter().map(|f| &f.ident).collect();
+                quote! {
+                    #idx => #storage_type_name::#ident {
+                        #(
+                            #field_names:
+                                ::cell_gc::traits::GcSerialize::read_fields(ctx, buf)
+                        ),*
+                    }
+                }
+            }
+            syn::VariantData::Tuple(ref fields) => {
+                let reads = (0..fields.len()).map(|_| quote! {
+                    ::cell_gc::traits::GcSerialize::read_fields(ctx, buf)
+                });
+                quote! {
+                    #idx => #storage_type_name::#ident( #(#reads),* )
+                }
+            }
+            syn::VariantData::Unit => {
+                quote! {
+                    #idx => #storage_type_name::#ident
+                }
+            }
+        }
+    });
+
+    let serialize_impl = if all_fields_support_adopt {
+        quote! {
+            impl #storage_impl_generics ::cell_gc::traits::GcSerialize
+                for #storage_type_name #storage_ty_generics
+            {
+                #[allow(unused_variables)]
+                unsafe fn write_fields(
+                    &self,
+                    ctx: &mut ::cell_gc::serialize::Serializer,
+                    buf: &mut Vec<u8>,
+                ) {
+                    match *self {
+                        #( #write_fields_arms ),*
+                    }
+                }
+
+                #[allow(unused_variables)]
+                unsafe fn read_fields(
+                    ctx: &mut ::cell_gc::serialize::Deserializer,
+                    buf: &mut ::cell_gc::serialize::Cursor,
+                ) -> Self {
+                    let discriminant = <u32 as ::cell_gc::serialize::Codec>::decode(buf);
+                    match discriminant {
+                        #( #read_fields_arms, )*
+                        other => panic!(
+                            "cell-gc: corrupt serialized data (bad enum discriminant {})",
+                            other
+                        ),
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     let trace_arms = variants.iter().map(|v| {
         let ident = &v.ident;
         match v.data {
@@ -605,8 +1726,171 @@ fn impl_into_heap_for_enum(ast: &syn::DeriveInput, variants: &[syn::Variant]) ->
         {}
     };
 
+    // If asked for (see `enum_ref_name`), generate a `Ref` type so the enum
+    // can be allocated directly with `heap.alloc(...)`, mirroring the
+    // `IntoHeapAllocation`/`Ref`/`Ref`'s-own-`IntoHeap` trio
+    // `impl_into_heap_for_struct` always generates for structs. Since an
+    // enum's variants don't share a common set of fields, the `Ref` gets
+    // whole-value `get()`/`set()` accessors instead of per-field ones.
+    let ref_type_items = if let Some(ref_name) = ref_name {
+        let ref_type_name: Ident = Ident::from(ref_name);
+        let into_heap_allocation = quote! {
+            impl #impl_generics ::cell_gc::traits::IntoHeapAllocation<#heap_lifetime>
+                for #name #ty_generics
+                #where_clause
+            {
+                type Ref = #ref_type_name #ty_generics;
+
+                fn wrap_gc_ref(gc_ref: ::cell_gc::GcRef<#heap_lifetime, #name #ty_generics>)
+                    -> Self::Ref
+                {
+                    #ref_type_name(gc_ref)
+                }
+
+                fn into_gc_ref(wrapped_ref: Self::Ref)
+                    -> ::cell_gc::GcRef<#heap_lifetime, #name #ty_generics>
+                {
+                    wrapped_ref.0
+                }
+            }
+        };
+
+        let ref_type = quote! {
+            #[derive(Clone, Debug, PartialEq, Eq)]
+            #vis struct #ref_type_name #impl_generics
+                (::cell_gc::GcRef<#heap_lifetime, #name #ty_generics>)
+                #where_clause;
+        };
+
+        let ref_type_into_heap = quote! {
+            impl #impl_generics ::cell_gc::traits::IntoHeapBase
+                for #ref_type_name #ty_generics
+                #where_clause
+            {
+                type In = <::cell_gc::GcRef<#heap_lifetime, #name #ty_generics>
+                           as ::cell_gc::traits::IntoHeapBase>::In;
+
+                fn into_heap(self) -> Self::In {
+                    self.0.into_heap()
+                }
+
+                unsafe fn from_heap(storage: &Self::In) -> Self {
+                    #ref_type_name(::cell_gc::GcRef::<#heap_lifetime, #name #ty_generics>::new(*storage))
+                }
+            }
+
+            unsafe impl #impl_generics ::cell_gc::traits::IntoHeap<#heap_lifetime>
+                for #ref_type_name #ty_generics
+                #where_clause
+            {}
+        };
+
+        let ref_type_hash = quote! {
+            impl #impl_generics ::std::hash::Hash for #ref_type_name #ty_generics
+                #where_clause
+            {
+                #[inline]
+                fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                    self.0.hash(state);
+                }
+            }
+        };
+
+        let accessors = quote! {
+            impl #impl_generics #ref_type_name #ty_generics #where_clause {
+                /// Get the value of the referent, as an owned `#name`.
+                #[allow(dead_code)]
+                #vis fn get(&self) -> #name #ty_generics {
+                    ::cell_gc::borrow_flag::check_not_borrowed(self.0.address());
+                    unsafe {
+                        ::cell_gc::traits::IntoHeapBase::from_heap(&*self.0.as_ptr())
+                    }
+                }
+
+                /// Overwrite the referent with a new value.
+                #[allow(dead_code)]
+                #vis fn set(&self, v: #name #ty_generics) {
+                    ::cell_gc::borrow_flag::check_not_borrowed(self.0.address());
+                    let ptr = self.0.as_mut_ptr();
+                    let u = ::cell_gc::traits::IntoHeapBase::into_heap(v);
+                    unsafe {
+                        *ptr = u;
+                    }
+                }
+
+                /// See `GcRef::with_storage`.
+                #[allow(dead_code)]
+                pub unsafe fn with_storage<R>(
+                    &self,
+                    f: impl FnOnce(&#storage_type_name #storage_ty_generics) -> R,
+                ) -> R {
+                    self.0.with_storage(f)
+                }
+
+                /// See `GcRef::address`.
+                #[allow(dead_code)]
+                pub fn address(&self) -> usize {
+                    self.0.address()
+                }
+
+                /// See `GcRef::object_id`.
+                #[allow(dead_code)]
+                pub fn object_id(&self) -> usize {
+                    self.0.object_id()
+                }
+
+                /// See `GcRef::age`.
+                #[allow(dead_code)]
+                pub fn age(&self) -> u8 {
+                    self.0.age()
+                }
+
+                /// See `GcRef::is_tenured`.
+                #[allow(dead_code)]
+                pub fn is_tenured(&self) -> bool {
+                    self.0.is_tenured()
+                }
+
+                /// See `GcRef::get_user_flag`.
+                #[allow(dead_code)]
+                pub fn get_user_flag(&self, index: u8) -> bool {
+                    self.0.get_user_flag(index)
+                }
+
+                /// See `GcRef::set_user_flag`.
+                #[allow(dead_code)]
+                pub fn set_user_flag(&self, index: u8, value: bool) {
+                    self.0.set_user_flag(index, value)
+                }
+            }
+        };
+
+        let ref_type_extra = ref_type_extra_derives(
+            attrs,
+            &ref_type_name,
+            &impl_generics,
+            &ty_generics,
+            where_clause,
+            Some(quote! { self.get() }),
+        );
+
+        quote! {
+            #into_heap_allocation
+            #ref_type
+            #ref_type_into_heap
+            #ref_type_hash
+            #ref_type_extra
+            #accessors
+        }
+    } else {
+        quote! {}
+    };
+
     quote! {
         #storage_enum
         #into_heap
+        #adopt_impl
+        #serialize_impl
+        #ref_type_items
     }
 }
@@ -281,6 +281,12 @@ pub fn eval_compiled<'h>(
             }
 
             op::CALL | op::TAIL_CALL => {
+                // Every call and tail call is a place a long-running Scheme
+                // computation loops back through this interpreter, so it's
+                // the natural cooperative yield point for GC work; see
+                // `GcHeapSession::safepoint`.
+                hs.safepoint();
+
                 let argc = insns.get(pc) as usize;
                 pc += 1;
 
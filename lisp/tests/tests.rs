@@ -55,6 +55,7 @@ test!(Core, print);
 test!(Core, set);
 test!(Core, symbols);
 test!(Core, tail_calls);
+test!(Expanded, tail_calls_expanded);
 test!(Core, vectors);
 
 test!(Full, r5rs_pitfall);
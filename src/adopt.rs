@@ -0,0 +1,76 @@
+//! Cross-heap object migration. See `GcHeapSession::adopt`.
+
+use heap::GcHeap;
+use pages::UninitializedAllocation;
+use ptr::{Pointer, UntypedPointer};
+use std::collections::HashMap;
+use traits::Adopt;
+
+/// Threads the destination heap and a memo table through one
+/// `GcHeapSession::adopt` call, so `Adopt` impls can recursively copy nested
+/// GC references without duplicating shared substructure or looping forever
+/// on a cycle.
+///
+/// Public only because `#[derive(IntoHeap)]`-generated code needs to name it
+/// in the `Adopt` impls it emits, the same trade-off `Pointer<U>` makes (see
+/// its docs) --- application code should never construct one directly.
+pub struct Adopter<'a> {
+    dest: &'a mut GcHeap,
+    memo: HashMap<UntypedPointer, UntypedPointer>,
+}
+
+impl<'a> Adopter<'a> {
+    pub(crate) fn new(dest: &'a mut GcHeap) -> Adopter<'a> {
+        Adopter {
+            dest,
+            memo: HashMap::new(),
+        }
+    }
+
+    /// Adopt the object `ptr` points to, returning its counterpart in the
+    /// destination heap, allocating and recursively adopting it if this is
+    /// the first time this call to `adopt` has reached it.
+    ///
+    /// Memoized on `ptr`'s address so that a graph with sharing or cycles
+    /// comes out the other side with the same shape, instead of being copied
+    /// once per incoming edge (sharing) or recursing forever (cycles).
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live allocation of type `U` in a heap this
+    /// `Adopter` is allowed to read from.
+    pub(crate) unsafe fn adopt_ptr<U: Adopt>(&mut self, ptr: Pointer<U>) -> Pointer<U> {
+        let source: UntypedPointer = ptr.into();
+        if let Some(&dest) = self.memo.get(&source) {
+            return dest.as_typed_ptr::<U>();
+        }
+
+        // Reserve the destination slot, and memoize it, *before* recursing
+        // into `ptr`'s fields: if the object graph loops back around to
+        // `ptr`, that recursive call needs to find this entry rather than
+        // adopting `ptr` all over again.
+        let allocation = self.alloc_storage::<U>();
+        let dest_ptr = allocation.ptr();
+        self.memo.insert(source, dest_ptr.into());
+
+        let copy = ptr.as_ref().adopt(self);
+        allocation.init(copy);
+        dest_ptr
+    }
+
+    /// Allocate space for a `U` in the destination heap without ever
+    /// triggering a collection there. This matters because nothing this
+    /// function allocates is reachable from any of the destination heap's
+    /// roots yet --- it's only reachable via `self.memo`, which the
+    /// destination heap's GC knows nothing about --- so a collection
+    /// partway through an adoption could reclaim it out from under us.
+    fn alloc_storage<U: Adopt>(&mut self) -> UninitializedAllocation<U> {
+        let mut page_set = self.dest.get_page_set::<U>();
+        if let Some(allocation) = unsafe { page_set.try_fast_alloc() } {
+            return allocation;
+        }
+        page_set.reserve(1);
+        unsafe { page_set.try_fast_alloc() }
+            .expect("cell-gc: adopt: just reserved a page for this allocation")
+    }
+}
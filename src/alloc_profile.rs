@@ -0,0 +1,174 @@
+//! Optional per-type (and per-call-site) allocation profiling, gated behind
+//! the `alloc-profile` feature. See `GcHeap::allocation_report`.
+//!
+//! When the feature is off, `AllocProfiler` is a zero-cost stand-in: its
+//! `record` method is an empty inlined no-op, and `allocation_report` always
+//! returns an empty report.
+
+use std::collections::HashMap;
+
+/// Allocation counts and bytes recorded for a single `IntoHeapAllocation`
+/// type, and (when the `alloc-profile` feature is on) broken down by call
+/// site.
+#[derive(Clone, Debug)]
+pub struct TypeAllocationStats {
+    /// The type's name, from `std::any::type_name`.
+    pub type_name: &'static str,
+
+    /// Total number of objects of this type allocated so far.
+    pub count: usize,
+
+    /// Total bytes allocated for this type so far (an estimate based on
+    /// `std::mem::size_of`; it doesn't include the mark word or any
+    /// separately heap-allocated data an object owns, e.g. a `Vec`'s
+    /// backing buffer).
+    pub bytes: usize,
+
+    /// Allocation counts and bytes keyed by `"file:line:column"` of the
+    /// `hs.alloc()` (or `hs.try_alloc()`) call that requested them. Empty
+    /// unless the `alloc-profile` feature is enabled.
+    pub by_caller: HashMap<String, CallerStats>,
+}
+
+/// Allocation counts and bytes attributed to a single call site, across
+/// every type allocated there. See `TypeAllocationStats::by_caller`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CallerStats {
+    /// Total number of objects allocated at this call site so far.
+    pub count: usize,
+
+    /// Total bytes allocated at this call site so far (see
+    /// `TypeAllocationStats::bytes` for what "bytes" means here).
+    pub bytes: usize,
+}
+
+/// A snapshot of allocation activity across every type allocated so far. See
+/// `GcHeap::allocation_report`.
+pub type AllocationReport = Vec<TypeAllocationStats>;
+
+/// One row of `GcHeap::top_allocation_sites`: a call site and the total
+/// bytes it has allocated so far, across every type allocated there.
+#[derive(Clone, Debug)]
+pub struct AllocationSite {
+    /// `"file:line:column"` of the `hs.alloc()` (or `hs.try_alloc()`) call.
+    pub site: String,
+
+    /// Total bytes allocated at this call site so far. Cumulative, the same
+    /// as `TypeAllocationStats::bytes`, not the current live total --- this
+    /// crate doesn't track which call site is responsible for a still-live
+    /// object, only how much each site has ever requested. For hunting down
+    /// which code path is responsible for heap growth, cumulative volume is
+    /// usually exactly what you want: it's dominated by whichever loop keeps
+    /// allocating, live or not.
+    pub bytes: usize,
+
+    /// Total objects allocated at this call site so far.
+    pub count: usize,
+}
+
+#[cfg(feature = "alloc-profile")]
+mod imp {
+    use super::{AllocationReport, AllocationSite, CallerStats, TypeAllocationStats};
+    use std::any::TypeId;
+    use std::collections::HashMap;
+    use std::panic::Location;
+
+    #[derive(Default)]
+    struct Entry {
+        type_name: &'static str,
+        count: usize,
+        bytes: usize,
+        by_caller: HashMap<String, CallerStats>,
+    }
+
+    /// Records allocation counts and bytes per type and call site. See the
+    /// `alloc_profile` module docs.
+    #[derive(Default)]
+    pub struct AllocProfiler {
+        entries: HashMap<TypeId, Entry>,
+    }
+
+    impl AllocProfiler {
+        pub fn record(
+            &mut self,
+            type_id: TypeId,
+            type_name: &'static str,
+            bytes: usize,
+            caller: &'static Location<'static>,
+        ) {
+            let entry = self.entries.entry(type_id).or_insert_with(Entry::default);
+            entry.type_name = type_name;
+            entry.count += 1;
+            entry.bytes += bytes;
+            let key = format!("{}:{}:{}", caller.file(), caller.line(), caller.column());
+            let caller_stats = entry.by_caller.entry(key).or_insert_with(CallerStats::default);
+            caller_stats.count += 1;
+            caller_stats.bytes += bytes;
+        }
+
+        pub fn report(&self) -> AllocationReport {
+            self.entries
+                .values()
+                .map(|entry| TypeAllocationStats {
+                    type_name: entry.type_name,
+                    count: entry.count,
+                    bytes: entry.bytes,
+                    by_caller: entry.by_caller.clone(),
+                })
+                .collect()
+        }
+
+        pub fn top_allocation_sites(&self, n: usize) -> Vec<AllocationSite> {
+            let mut by_site: HashMap<String, (usize, usize)> = HashMap::new();
+            for entry in self.entries.values() {
+                for (site, stats) in &entry.by_caller {
+                    let totals = by_site.entry(site.clone()).or_insert((0, 0));
+                    totals.0 += stats.bytes;
+                    totals.1 += stats.count;
+                }
+            }
+            let mut sites: Vec<AllocationSite> = by_site
+                .into_iter()
+                .map(|(site, (bytes, count))| AllocationSite { site, bytes, count })
+                .collect();
+            sites.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+            sites.truncate(n);
+            sites
+        }
+    }
+}
+
+#[cfg(not(feature = "alloc-profile"))]
+mod imp {
+    use super::{AllocationReport, AllocationSite};
+    use std::any::TypeId;
+    use std::panic::Location;
+
+    /// No-op stand-in used when the `alloc-profile` feature is disabled, so
+    /// `GcHeap` doesn't need to `cfg`-gate every call site. See the
+    /// `alloc_profile` module docs.
+    #[derive(Default)]
+    pub struct AllocProfiler;
+
+    impl AllocProfiler {
+        #[inline(always)]
+        pub fn record(
+            &mut self,
+            _type_id: TypeId,
+            _type_name: &'static str,
+            _bytes: usize,
+            _caller: &'static Location<'static>,
+        ) {
+        }
+
+        pub fn report(&self) -> AllocationReport {
+            Vec::new()
+        }
+
+        pub fn top_allocation_sites(&self, _n: usize) -> Vec<AllocationSite> {
+            Vec::new()
+        }
+    }
+}
+
+pub(crate) use self::imp::AllocProfiler;
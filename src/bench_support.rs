@@ -0,0 +1,314 @@
+//! Standard allocation workloads for benchmarking this crate's collector,
+//! gated behind the `bench-support` feature. `benches/allocation.rs` is a
+//! Criterion harness built on these; an embedder comparing collector
+//! configurations (see `GcHeap::set_collection_policy`,
+//! `GcHeap::set_page_source`) against a consistent baseline can also call
+//! them directly instead of inventing its own workloads.
+//!
+//! Each workload takes a `&mut GcHeapSession` so it can be run against a
+//! heap the caller has already configured, and returns a value derived from
+//! what it allocated, so a benchmark harness has something to feed
+//! `criterion::black_box` and the optimizer can't discard the work.
+//!
+//! `TreeNode`/`ListNode` and their `Ref` types below can't be written with
+//! `#[derive(IntoHeap)]`: the derive's generated code refers to its own
+//! crate as `::cell_gc`, which only resolves from a downstream crate with
+//! `extern crate cell_gc;` --- not from inside `cell_gc` itself. So, like
+//! `GcCell`/`GcCellRef` in `gc_cell.rs`, they're written out by hand instead,
+//! following the same shape the macro would otherwise generate.
+
+use borrow_flag;
+use collections::VecRef;
+use gc_ref::GcRef;
+use heap::GcHeapSession;
+use ptr::Pointer;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use traits::{InHeap, IntoHeap, IntoHeapAllocation, IntoHeapBase, Tracer};
+
+struct TreeNode<'h> {
+    left: Option<TreeNodeRef<'h>>,
+    right: Option<TreeNodeRef<'h>>,
+}
+
+#[doc(hidden)]
+struct TreeNodeStorage {
+    left: Option<Pointer<TreeNodeStorage>>,
+    right: Option<Pointer<TreeNodeStorage>>,
+}
+
+impl InHeap for TreeNodeStorage {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        self.left.trace(tracer);
+        self.right.trace(tracer);
+    }
+}
+
+impl<'h> IntoHeapBase for TreeNode<'h> {
+    type In = TreeNodeStorage;
+
+    fn into_heap(self) -> TreeNodeStorage {
+        TreeNodeStorage {
+            left: self.left.into_heap(),
+            right: self.right.into_heap(),
+        }
+    }
+
+    unsafe fn from_heap(storage: &TreeNodeStorage) -> TreeNode<'h> {
+        TreeNode {
+            left: IntoHeapBase::from_heap(&storage.left),
+            right: IntoHeapBase::from_heap(&storage.right),
+        }
+    }
+}
+
+unsafe impl<'h> IntoHeap<'h> for TreeNode<'h> {}
+
+impl<'h> IntoHeapAllocation<'h> for TreeNode<'h> {
+    type Ref = TreeNodeRef<'h>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, TreeNode<'h>>) -> TreeNodeRef<'h> {
+        TreeNodeRef(gc_ref)
+    }
+
+    fn into_gc_ref(r: TreeNodeRef<'h>) -> GcRef<'h, TreeNode<'h>> {
+        r.0
+    }
+}
+
+/// A reference to a `TreeNode` allocated in the heap. Hand-written for the
+/// reason given in the module docs, in the shape `#[derive(IntoHeap)]`
+/// would generate, but with only the getters this module uses.
+struct TreeNodeRef<'h>(GcRef<'h, TreeNode<'h>>);
+
+impl<'h> TreeNodeRef<'h> {
+    #[allow(dead_code)]
+    fn left(&self) -> Option<TreeNodeRef<'h>> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let ptr = self.0.as_ptr();
+        unsafe { IntoHeapBase::from_heap(&(*ptr).left) }
+    }
+
+    #[allow(dead_code)]
+    fn right(&self) -> Option<TreeNodeRef<'h>> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let ptr = self.0.as_ptr();
+        unsafe { IntoHeapBase::from_heap(&(*ptr).right) }
+    }
+}
+
+impl<'h> Clone for TreeNodeRef<'h> {
+    fn clone(&self) -> TreeNodeRef<'h> {
+        TreeNodeRef(self.0.clone())
+    }
+}
+
+impl<'h> fmt::Debug for TreeNodeRef<'h> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TreeNodeRef {{ ptr: {:p} }}", self.0.as_ptr())
+    }
+}
+
+impl<'h> PartialEq for TreeNodeRef<'h> {
+    fn eq(&self, other: &TreeNodeRef<'h>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<'h> Eq for TreeNodeRef<'h> {}
+
+impl<'h> Hash for TreeNodeRef<'h> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h> IntoHeapBase for TreeNodeRef<'h> {
+    type In = <GcRef<'h, TreeNode<'h>> as IntoHeapBase>::In;
+
+    fn into_heap(self) -> Self::In {
+        self.0.into_heap()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> TreeNodeRef<'h> {
+        TreeNodeRef(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h> IntoHeap<'h> for TreeNodeRef<'h> {}
+
+fn build_tree<'h>(hs: &mut GcHeapSession<'h>, depth: u32) -> TreeNodeRef<'h> {
+    let (left, right) = if depth == 0 {
+        (None, None)
+    } else {
+        (Some(build_tree(hs, depth - 1)), Some(build_tree(hs, depth - 1)))
+    };
+    hs.alloc(TreeNode { left: left, right: right })
+}
+
+fn count_tree(node: &TreeNodeRef) -> usize {
+    1 + node.left().as_ref().map_or(0, count_tree) + node.right().as_ref().map_or(0, count_tree)
+}
+
+/// The "binary trees" benchmark from the Computer Language Benchmarks
+/// Game: build a complete binary tree `depth` levels deep, then walk it to
+/// count its nodes. Exercises a lot of short-lived allocations of a small,
+/// uniform, pointer-heavy type --- the tree itself doesn't outlive this
+/// call, so it also exercises the collector's ability to reclaim a large,
+/// newly-dead subgraph.
+///
+/// Returns the number of nodes in the tree (`2^(depth + 1) - 1`), so a
+/// benchmark can assert it actually built the tree it meant to.
+pub fn binary_trees<'h>(hs: &mut GcHeapSession<'h>, depth: u32) -> usize {
+    let root = build_tree(hs, depth);
+    count_tree(&root)
+}
+
+struct ListNode<'h> {
+    value: i64,
+    next: Option<ListNodeRef<'h>>,
+}
+
+#[doc(hidden)]
+struct ListNodeStorage {
+    value: i64,
+    next: Option<Pointer<ListNodeStorage>>,
+}
+
+impl InHeap for ListNodeStorage {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        self.next.trace(tracer);
+    }
+}
+
+impl<'h> IntoHeapBase for ListNode<'h> {
+    type In = ListNodeStorage;
+
+    fn into_heap(self) -> ListNodeStorage {
+        ListNodeStorage {
+            value: self.value,
+            next: self.next.into_heap(),
+        }
+    }
+
+    unsafe fn from_heap(storage: &ListNodeStorage) -> ListNode<'h> {
+        ListNode {
+            value: storage.value,
+            next: IntoHeapBase::from_heap(&storage.next),
+        }
+    }
+}
+
+unsafe impl<'h> IntoHeap<'h> for ListNode<'h> {}
+
+impl<'h> IntoHeapAllocation<'h> for ListNode<'h> {
+    type Ref = ListNodeRef<'h>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, ListNode<'h>>) -> ListNodeRef<'h> {
+        ListNodeRef(gc_ref)
+    }
+
+    fn into_gc_ref(r: ListNodeRef<'h>) -> GcRef<'h, ListNode<'h>> {
+        r.0
+    }
+}
+
+/// A reference to a `ListNode` allocated in the heap. Hand-written for the
+/// reason given in the module docs, in the shape `#[derive(IntoHeap)]`
+/// would generate, but with only the getters this module uses.
+struct ListNodeRef<'h>(GcRef<'h, ListNode<'h>>);
+
+impl<'h> ListNodeRef<'h> {
+    #[allow(dead_code)]
+    fn value(&self) -> i64 {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let ptr = self.0.as_ptr();
+        unsafe { (*ptr).value }
+    }
+
+    #[allow(dead_code)]
+    fn next(&self) -> Option<ListNodeRef<'h>> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let ptr = self.0.as_ptr();
+        unsafe { IntoHeapBase::from_heap(&(*ptr).next) }
+    }
+}
+
+impl<'h> Clone for ListNodeRef<'h> {
+    fn clone(&self) -> ListNodeRef<'h> {
+        ListNodeRef(self.0.clone())
+    }
+}
+
+impl<'h> fmt::Debug for ListNodeRef<'h> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ListNodeRef {{ value: {:?}, ptr: {:p} }}", self.value(), self.0.as_ptr())
+    }
+}
+
+impl<'h> PartialEq for ListNodeRef<'h> {
+    fn eq(&self, other: &ListNodeRef<'h>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<'h> Eq for ListNodeRef<'h> {}
+
+impl<'h> Hash for ListNodeRef<'h> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h> IntoHeapBase for ListNodeRef<'h> {
+    type In = <GcRef<'h, ListNode<'h>> as IntoHeapBase>::In;
+
+    fn into_heap(self) -> Self::In {
+        self.0.into_heap()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> ListNodeRef<'h> {
+        ListNodeRef(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h> IntoHeap<'h> for ListNodeRef<'h> {}
+
+/// List churn: build a singly linked list `list_len` nodes long, then
+/// discard it and do that `iterations` times in a row, without ever
+/// rooting more than one list at once. Exercises steady-state allocation
+/// churn against a collector that should be reclaiming each list before
+/// the next one is built --- the workload a generational or incremental
+/// collector configuration is meant to help with.
+///
+/// Returns the sum of every value ever allocated, across every iteration,
+/// so a benchmark can assert it did the work it meant to.
+pub fn list_churn<'h>(hs: &mut GcHeapSession<'h>, iterations: usize, list_len: usize) -> i64 {
+    let mut total = 0;
+    for _ in 0..iterations {
+        let mut list: Option<ListNodeRef<'h>> = None;
+        for i in 0..list_len {
+            let value = i as i64;
+            total += value;
+            list = Some(hs.alloc(ListNode { value: value, next: list }));
+        }
+    }
+    total
+}
+
+/// Large vectors: allocate a single `VecRef<i64>` and push `len` elements
+/// onto it one at a time, rooting it for the whole call. Exercises a
+/// large, growing, contiguous allocation instead of many small ones ---
+/// including the trace cost of a page-sized (or bigger) object, and the
+/// reallocations `VecRef::push` does as it grows (see `VecRef`'s docs).
+///
+/// Returns the vector, still rooted, so a benchmark can go on to measure
+/// something else against it (e.g. a collection with it live) instead of
+/// only the cost of building it.
+pub fn large_vector<'h>(hs: &mut GcHeapSession<'h>, len: usize) -> VecRef<'h, i64> {
+    let v = hs.alloc(Vec::new());
+    for i in 0..len {
+        v.push(i as i64);
+    }
+    v
+}
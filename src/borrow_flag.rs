@@ -0,0 +1,92 @@
+//! Debug-mode, per-object exclusive-borrow tracking for the accessors
+//! `#[derive(IntoHeap)]` generates, along the lines of `RefCell`'s runtime
+//! borrow check.
+//!
+//! `update_<field>` hands its callback a `&mut` into a field's in-heap
+//! storage. If that callback reenters the *same* object through another
+//! generated getter or setter --- easy to do by accident once callbacks are
+//! involved --- the getter or setter would alias that live `&mut`, silently
+//! reading or writing through it instead of producing an obviously wrong
+//! answer. Every generated getter, setter, and `update_<field>` call goes
+//! through this module so that instead panics with a clear message.
+//!
+//! Entirely compiled away when `debug_assertions` is off, so release builds
+//! pay nothing for it.
+
+#[cfg(debug_assertions)]
+use std::cell::RefCell;
+#[cfg(debug_assertions)]
+use std::collections::HashSet;
+
+#[cfg(debug_assertions)]
+thread_local! {
+    static BORROWED: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+}
+
+#[cfg(debug_assertions)]
+fn panic_reentrant() -> ! {
+    panic!(
+        "cell-gc: reentrant access to an object through a getter or setter \
+         while one of its update_<field> callbacks is still running (this \
+         would alias the &mut the callback was given)"
+    );
+}
+
+/// Held for the duration of a generated `update_<field>` callback. Panics
+/// on construction if `address` is already borrowed, and releases the
+/// borrow when dropped.
+#[cfg(debug_assertions)]
+pub struct BorrowGuard(usize);
+
+#[cfg(debug_assertions)]
+impl BorrowGuard {
+    /// Mark `address` as exclusively borrowed. Panics if it already is.
+    pub fn new(address: usize) -> BorrowGuard {
+        BORROWED.with(|b| {
+            if !b.borrow_mut().insert(address) {
+                panic_reentrant();
+            }
+        });
+        BorrowGuard(address)
+    }
+}
+
+#[cfg(debug_assertions)]
+impl Drop for BorrowGuard {
+    fn drop(&mut self) {
+        BORROWED.with(|b| {
+            b.borrow_mut().remove(&self.0);
+        });
+    }
+}
+
+/// Panic if `address` is currently exclusively borrowed by an in-progress
+/// `update_<field>` callback. Called by every generated getter and setter.
+#[cfg(debug_assertions)]
+#[inline]
+pub fn check_not_borrowed(address: usize) {
+    BORROWED.with(|b| {
+        if b.borrow().contains(&address) {
+            panic_reentrant();
+        }
+    });
+}
+
+/// Release-build stand-in for the debug-mode [`BorrowGuard`]: holding one
+/// costs nothing and checks nothing.
+#[cfg(not(debug_assertions))]
+pub struct BorrowGuard;
+
+#[cfg(not(debug_assertions))]
+impl BorrowGuard {
+    /// Release-build stand-in for [`BorrowGuard::new`]; does nothing.
+    #[inline(always)]
+    pub fn new(_address: usize) -> BorrowGuard {
+        BorrowGuard
+    }
+}
+
+/// Release-build stand-in for [`check_not_borrowed`]; does nothing.
+#[cfg(not(debug_assertions))]
+#[inline(always)]
+pub fn check_not_borrowed(_address: usize) {}
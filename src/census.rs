@@ -0,0 +1,80 @@
+//! A point-in-time snapshot of live object counts by type, for tests that
+//! want to assert exactly how many objects an operation allocated (and that
+//! it didn't leak any). See `GcHeap::census` and `Census::diff`.
+
+use heap::GcHeap;
+use std::collections::HashMap;
+
+/// A snapshot of how many objects of each type were live in a heap at some
+/// point, taken by `GcHeap::census`.
+///
+/// Comparing two censuses with `Census::diff` reports the net change in live
+/// count for each type in between, which is a lot less fragile than
+/// comparing total page counts before and after an operation: `assert_eq!(
+/// before.diff(&after), &[CensusDelta { type_name: ..., delta: 10 }])` says
+/// exactly what allocated and what didn't.
+#[derive(Clone, Debug, Default)]
+pub struct Census {
+    counts: HashMap<&'static str, usize>,
+}
+
+impl Census {
+    pub(crate) fn take(heap: &GcHeap) -> Census {
+        let counts = heap
+            .types()
+            .into_iter()
+            .map(|info| (info.name, info.live_count))
+            .collect();
+        Census { counts }
+    }
+
+    /// How many live objects of `type_name` this census counted. A type this
+    /// census never saw an allocation of reads as 0, the same as a type with
+    /// no live objects.
+    pub fn live_count(&self, type_name: &str) -> usize {
+        self.counts.get(type_name).cloned().unwrap_or(0)
+    }
+
+    /// Compute the change in live counts from `self` to `later`: one entry
+    /// per type whose live count differs between the two censuses, sorted
+    /// by type name. Types with no change don't appear.
+    ///
+    /// A positive `delta` means `later` had more live objects of that type
+    /// (net allocations since `self` was taken); negative means fewer ---
+    /// which, since neither census forces a collection, you'll typically
+    /// only see right after one.
+    pub fn diff(&self, later: &Census) -> Vec<CensusDelta> {
+        let mut type_names: Vec<&'static str> = self
+            .counts
+            .keys()
+            .chain(later.counts.keys())
+            .cloned()
+            .collect();
+        type_names.sort();
+        type_names.dedup();
+
+        type_names
+            .into_iter()
+            .filter_map(|type_name| {
+                let delta = later.live_count(type_name) as isize - self.live_count(type_name) as isize;
+                if delta == 0 {
+                    None
+                } else {
+                    Some(CensusDelta { type_name, delta })
+                }
+            })
+            .collect()
+    }
+}
+
+/// One entry in the list returned by `Census::diff`: how the live count of a
+/// single type changed between two censuses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CensusDelta {
+    /// The type's name, from `std::any::type_name`.
+    pub type_name: &'static str,
+
+    /// The change in live count. Positive is net allocations, negative is
+    /// net frees.
+    pub delta: isize,
+}
@@ -0,0 +1,83 @@
+//! A message-passing channel for moving GC values between heaps.
+//!
+//! Each `GcHeap` is meant to be used by one thread at a time (see
+//! `unsafe impl Send for GcHeap`), so passing a live `GcRef` from a value on
+//! one heap over to a session running on another heap isn't possible: the
+//! reference is only meaningful relative to the heap it points into. This
+//! module gives you an actor-style alternative instead. `send` serializes
+//! the value out of the sender's heap with `GcHeapSession::serialize`, which
+//! already knows how to preserve cycles and shared substructure, and ships
+//! the resulting bytes across a plain `std::sync::mpsc` channel; `recv`
+//! deserializes them again, allocating a fresh, independent copy of the same
+//! object graph in the receiver's heap.
+//!
+//! This is a deep copy, not a move: the value sent is unaffected, and the
+//! sender can go on using it (or send it again) afterward. The channel
+//! itself carries plain bytes, not a fixed GC type: like
+//! `GcHeapSession::serialize`/`deserialize`, the value type is named at each
+//! `send`/`recv` call rather than fixed by the channel, since a type with a
+//! `'h` parameter is only nameable relative to one particular heap session,
+//! and a sender and receiver are, by design, never in the same one.
+
+use gc_ref::PinnedRef;
+use heap::GcHeapSession;
+use std::io;
+use std::sync::mpsc;
+use traits::{GcSerialize, IntoHeapAllocation};
+
+/// Create a new channel for moving values between heaps, the way
+/// `std::sync::mpsc::channel` does for plain values.
+pub fn channel() -> (Sender, Receiver) {
+    let (tx, rx) = mpsc::channel();
+    (Sender { tx: tx }, Receiver { rx: rx })
+}
+
+/// The sending half of a `channel`. Cloneable, like `mpsc::Sender`, so
+/// several sessions (usually on several threads) can share one receiver.
+#[derive(Clone)]
+pub struct Sender {
+    tx: mpsc::Sender<Vec<u8>>,
+}
+
+/// The receiving half of a `channel`.
+pub struct Receiver {
+    rx: mpsc::Receiver<Vec<u8>>,
+}
+
+impl Sender {
+    /// Serialize `value` out of `session`'s heap and send the bytes to the
+    /// matching `Receiver`.
+    ///
+    /// Requires `T::In: GcSerialize`, which `#[derive(IntoHeap)]` provides
+    /// automatically under the same conditions it provides `Adopt`; see
+    /// `Adopt`'s docs for which types don't get it.
+    pub fn send<'h, T>(&self, session: &GcHeapSession<'h>, value: &PinnedRef<T>) -> io::Result<()>
+    where
+        T: IntoHeapAllocation<'h>,
+        T::In: GcSerialize,
+    {
+        let mut buf = Vec::new();
+        session.serialize(value, &mut buf)?;
+        self.tx
+            .send(buf)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "channel receiver dropped"))
+    }
+}
+
+impl Receiver {
+    /// Block until a value arrives, then deserialize it into a fresh copy in
+    /// `session`'s heap and return a reference to it.
+    ///
+    /// Requires `T::In: GcSerialize`, like `Sender::send`.
+    pub fn recv<'h, T>(&self, session: &mut GcHeapSession<'h>) -> io::Result<T::Ref>
+    where
+        T: IntoHeapAllocation<'h>,
+        T::In: GcSerialize,
+    {
+        let buf = self
+            .rx
+            .recv()
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "channel sender dropped"))?;
+        session.deserialize::<T>(&mut &buf[..])
+    }
+}
@@ -1,12 +1,19 @@
 //! Collections for use with GC references.
 
+use adopt::Adopter;
+use borrow_flag;
 use gc_ref::GcRef;
+use heap::GcHeapSession;
 use ptr::Pointer;
+use serialize::{Codec, Cursor, Deserializer, Serializer};
 use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::mem;
 use std::ops::Range;
-use traits::{InHeap, IntoHeap, IntoHeapAllocation, IntoHeapBase, Tracer};
+use traits::{Adopt, GcSerialize, InHeap, IntoHeap, IntoHeapAllocation, IntoHeapBase, Tracer};
 
 impl<U: InHeap> InHeap for Vec<U> {
     unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
@@ -16,6 +23,26 @@ impl<U: InHeap> InHeap for Vec<U> {
     }
 }
 
+impl<U: Adopt> Adopt for Vec<U> {
+    unsafe fn adopt(&self, adopter: &mut Adopter) -> Vec<U> {
+        self.iter().map(|r| r.adopt(adopter)).collect()
+    }
+}
+
+impl<U: GcSerialize> GcSerialize for Vec<U> {
+    unsafe fn write_fields(&self, ctx: &mut Serializer, buf: &mut Vec<u8>) {
+        (self.len() as u64).encode(buf);
+        for u in self {
+            u.write_fields(ctx, buf);
+        }
+    }
+
+    unsafe fn read_fields(ctx: &mut Deserializer, buf: &mut Cursor) -> Vec<U> {
+        let len = u64::decode(buf) as usize;
+        (0..len).map(|_| U::read_fields(ctx, buf)).collect()
+    }
+}
+
 impl<T: IntoHeapBase> IntoHeapBase for Vec<T> {
     type In = Vec<T::In>;
 
@@ -244,6 +271,14 @@ impl<'h, T: IntoHeap<'h>> VecRef<'h, T> {
 
     /// Appends an element to the back of a collection.
     ///
+    /// This is already amortized O(1): the in-heap storage is a plain
+    /// `std::vec::Vec`, so growing it reallocates its backing buffer
+    /// geometrically (like any other `Vec`) rather than copying every
+    /// existing element through `into_heap`/`from_heap`; only `value`
+    /// itself pays that conversion. See `capacity`/`reserve` to control
+    /// growth explicitly, and `tests/vec_ref_growth.rs` for a check that
+    /// reallocation count stays logarithmic in the number of pushes.
+    ///
     /// ### Panics
     ///
     /// Panics if the number of elements in the vector overflows a `usize`.
@@ -342,6 +377,99 @@ impl<'h, T: IntoHeap<'h>> VecRef<'h, T> {
             self.with_storage_mut(|v| mem::swap(&mut tmp, v));
         }
     }
+
+    /// Copy the elements in `range` out of the GC-managed heap, as an
+    /// ordinary, non-GC-managed `Vec`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn get_range(&self, range: Range<usize>) -> Vec<T> {
+        unsafe { self.with_storage(|v| v[range].iter().map(|u| T::from_heap(u)).collect()) }
+    }
+
+    /// Appends every element produced by `iter` to the end of the vector.
+    pub fn extend<I: IntoIterator<Item = T>>(&self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+
+    /// Retains only the elements for which `f` returns `true`, removing the
+    /// rest.
+    ///
+    /// This clones each element out of the heap to pass to `f`, the same
+    /// way `sort_by` does.
+    pub fn retain<F>(&self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut tmp = vec![];
+        unsafe {
+            self.with_storage_mut(|v| mem::swap(&mut tmp, v));
+        }
+        tmp.retain(|u| {
+            let t = unsafe { T::from_heap(u) };
+            f(&t)
+        });
+        unsafe {
+            self.with_storage_mut(|v| mem::swap(&mut tmp, v));
+        }
+    }
+
+    /// Returns `true` if the vector contains an element equal to `value`.
+    pub fn contains(&self, value: T) -> bool
+    where
+        T: PartialEq,
+    {
+        unsafe { self.with_storage(|v| v.iter().any(|u| T::from_heap(u) == value)) }
+    }
+
+    /// Binary searches the vector, which must already be sorted according
+    /// to `compare`, for an element.
+    ///
+    /// If found, returns `Ok` with the index of a matching element.
+    /// Otherwise, returns `Err` with the index where a matching element
+    /// could be inserted to keep the vector sorted.
+    pub fn binary_search_by<F>(&self, mut compare: F) -> Result<usize, usize>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        unsafe {
+            self.with_storage(|v| {
+                v.binary_search_by(|u| {
+                    let t = T::from_heap(u);
+                    compare(&t)
+                })
+            })
+        }
+    }
+
+    /// Returns an iterator that copies elements out of the vector one at a
+    /// time, unlike `get_all`, which copies the whole vector up front.
+    ///
+    /// This is cheap to create: `VecRefIter` holds a clone of this
+    /// `VecRef`, which is just a reference into the heap.
+    pub fn iter(&self) -> VecRefIter<'h, T> {
+        VecRef(self.0.clone()).into_iter()
+    }
+
+    /// Calls `f` once for each element of the vector, copying elements out
+    /// one at a time rather than materializing a `Vec` of all of them.
+    pub fn for_each<F: FnMut(T)>(&self, mut f: F) {
+        for value in self.iter() {
+            f(value);
+        }
+    }
+
+    /// Replaces every element of the vector with the result of calling `f`
+    /// on it, one element at a time, without materializing a second `Vec`.
+    pub fn map_in_place<F: FnMut(T) -> T>(&self, mut f: F) {
+        for i in 0..self.len() {
+            let value = f(self.get(i));
+            self.set(i, value);
+        }
+    }
 }
 
 /// An iterator over a GC-heap-allocated vector.
@@ -379,3 +507,2357 @@ impl<'h, T: IntoHeap<'h>> DoubleEndedIterator for VecRefIter<'h, T> {
         self.indexes.next_back().map(|i| self.data.get(i))
     }
 }
+
+/// A growable string, allocated in the heap, that can be mutated in place.
+///
+/// A plain `String` field in a `#[derive(IntoHeap)]` struct works fine, but
+/// every getter call clones the whole string out of the heap; there's no
+/// way to mutate it in place. `GcString` is to `String` what `VecRef` is to
+/// `Vec`: it lives in its own heap allocation, and `push_str` appends to
+/// that allocation directly instead of copying the string out, growing it,
+/// and copying it back in.
+///
+/// To allocate one, call `heap.alloc` on a plain `GcString`. It returns a
+/// `GcStringRef`.
+///
+/// ```rust
+/// use cell_gc::collections::GcString;
+///
+/// cell_gc::with_heap(|heap| {
+///     let s = heap.alloc(GcString::from("hello"));
+///     s.push_str(", world");
+///     assert_eq!(s.len(), 12);
+///     assert_eq!(s.slice(0..5), "hello");
+///     assert_eq!(s.as_string(), "hello, world");
+/// });
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct GcString(String);
+
+impl GcString {
+    /// Create a new, empty `GcString`, e.g. `heap.alloc(GcString::new())`.
+    pub fn new() -> GcString {
+        GcString(String::new())
+    }
+}
+
+impl From<String> for GcString {
+    fn from(s: String) -> GcString {
+        GcString(s)
+    }
+}
+
+impl<'a> From<&'a str> for GcString {
+    fn from(s: &'a str) -> GcString {
+        GcString(s.to_string())
+    }
+}
+
+impl InHeap for GcString {
+    #[inline]
+    unsafe fn trace<R: Tracer>(&self, _tracer: &mut R) {}
+}
+
+impl IntoHeapBase for GcString {
+    type In = GcString;
+
+    fn into_heap(self) -> GcString {
+        self
+    }
+
+    unsafe fn from_heap(storage: &GcString) -> GcString {
+        storage.clone()
+    }
+}
+
+unsafe impl<'h> IntoHeap<'h> for GcString {}
+
+impl<'h> IntoHeapAllocation<'h> for GcString {
+    type Ref = GcStringRef<'h>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, GcString>) -> GcStringRef<'h> {
+        GcStringRef(gc_ref)
+    }
+
+    fn into_gc_ref(r: GcStringRef<'h>) -> GcRef<'h, GcString> {
+        r.0
+    }
+}
+
+/// A reference to a `GcString` allocated in the heap. See `GcString`'s docs.
+pub struct GcStringRef<'h>(GcRef<'h, GcString>);
+
+impl<'h> GcStringRef<'h> {
+    unsafe fn with_storage<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b GcString) -> R,
+        'v: 'b,
+    {
+        f(&*self.0.as_ptr())
+    }
+
+    unsafe fn with_storage_mut<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b mut GcString) -> R,
+        'v: 'b,
+    {
+        f(&mut *self.0.as_mut_ptr())
+    }
+
+    /// Returns the length of this string, in bytes.
+    pub fn len(&self) -> usize {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe { self.with_storage(|s| s.0.len()) }
+    }
+
+    /// Returns `true` if this string has no characters.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends `s` to the end of this string in place.
+    pub fn push_str(&self, s: &str) {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        unsafe {
+            self.with_storage_mut(|gs| gs.0.push_str(s));
+        }
+    }
+
+    /// Appends a single character to the end of this string in place.
+    pub fn push(&self, c: char) {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        unsafe {
+            self.with_storage_mut(|gs| gs.0.push(c));
+        }
+    }
+
+    /// Copies the byte range `range` out of the heap as an ordinary,
+    /// non-GC-managed `String`.
+    ///
+    /// ### Panics
+    ///
+    /// Panics if `range` is out of bounds, or its endpoints don't fall on
+    /// `char` boundaries, exactly like slicing a `str` would.
+    pub fn slice(&self, range: Range<usize>) -> String {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe { self.with_storage(|gs| gs.0[range].to_string()) }
+    }
+
+    /// Copies the whole string out of the heap as an ordinary,
+    /// non-GC-managed `String`.
+    pub fn as_string(&self) -> String {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe { self.with_storage(|gs| gs.0.clone()) }
+    }
+
+    /// Removes all characters from this string.
+    pub fn clear(&self) {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        unsafe {
+            self.with_storage_mut(|gs| gs.0.clear());
+        }
+    }
+
+    /// See `GcRef::object_id`.
+    pub fn object_id(&self) -> usize {
+        self.0.object_id()
+    }
+}
+
+impl<'h> Clone for GcStringRef<'h> {
+    fn clone(&self) -> GcStringRef<'h> {
+        GcStringRef(self.0.clone())
+    }
+}
+
+impl<'h> fmt::Debug for GcStringRef<'h> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GcStringRef {{ ptr: {:p} }}", self.0.as_ptr())
+    }
+}
+
+impl<'h> PartialEq for GcStringRef<'h> {
+    fn eq(&self, other: &GcStringRef<'h>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<'h> Eq for GcStringRef<'h> {}
+
+impl<'h> Hash for GcStringRef<'h> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h> IntoHeapBase for GcStringRef<'h> {
+    type In = <GcRef<'h, GcString> as IntoHeapBase>::In;
+
+    fn into_heap(self) -> Self::In {
+        self.0.into_heap()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> GcStringRef<'h> {
+        GcStringRef(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h> IntoHeap<'h> for GcStringRef<'h> {}
+
+impl<K: InHeap + Eq + Hash, V: InHeap> InHeap for HashMap<K, V> {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        for (k, v) in self {
+            k.trace(tracer);
+            v.trace(tracer);
+        }
+    }
+}
+
+impl<K: IntoHeapBase + Eq + Hash, V: IntoHeapBase> IntoHeapBase for HashMap<K, V>
+where
+    K::In: Eq + Hash,
+{
+    type In = HashMap<K::In, V::In>;
+
+    fn into_heap(self) -> HashMap<K::In, V::In> {
+        self.into_iter().map(|(k, v)| (k.into_heap(), v.into_heap())).collect()
+    }
+
+    unsafe fn from_heap(storage: &HashMap<K::In, V::In>) -> HashMap<K, V> {
+        storage.iter().map(|(k, v)| (K::from_heap(k), V::from_heap(v))).collect()
+    }
+}
+
+unsafe impl<'h, K: IntoHeap<'h> + Eq + Hash, V: IntoHeap<'h>> IntoHeap<'h> for HashMap<K, V>
+where
+    K::In: Eq + Hash,
+{
+}
+
+impl<'h, K: IntoHeap<'h> + Eq + Hash, V: IntoHeap<'h>> IntoHeapAllocation<'h> for HashMap<K, V>
+where
+    K::In: Eq + Hash,
+{
+    type Ref = GcHashMap<'h, K, V>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, HashMap<K, V>>) -> GcHashMap<'h, K, V> {
+        GcHashMap(gc_ref)
+    }
+
+    fn into_gc_ref(wrapped_ref: GcHashMap<'h, K, V>) -> GcRef<'h, HashMap<K, V>> {
+        wrapped_ref.0
+    }
+}
+
+/// A reference to a GC-heap-allocated `HashMap`, with keys and values traced
+/// by the collector.
+///
+/// To allocate a hash map in the heap, call `heap.alloc` on a plain old
+/// `HashMap`. It returns a `GcHashMap` object with `insert`/`get`/`remove`
+/// accessors.
+///
+/// ```rust
+/// use cell_gc::collections::GcHashMap;
+/// use std::collections::HashMap;
+///
+/// cell_gc::with_heap(|heap| {
+///     let map: GcHashMap<i32, i32> = heap.alloc(HashMap::new());
+///     map.insert(1, 100);
+///     map.insert(2, 200);
+///     assert_eq!(map.get(1), Some(100));
+///     assert_eq!(map.len(), 2);
+///     map.remove(1);
+///     assert_eq!(map.get(1), None);
+/// });
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GcHashMap<'h, K: IntoHeap<'h> + Eq + Hash, V: IntoHeap<'h>>(GcRef<'h, HashMap<K, V>>)
+where
+    K::In: Eq + Hash;
+
+impl<'h, K: IntoHeap<'h> + Eq + Hash, V: IntoHeap<'h>> Hash for GcHashMap<'h, K, V>
+where
+    K::In: Eq + Hash,
+{
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h, K: IntoHeap<'h> + Eq + Hash, V: IntoHeap<'h>> IntoHeapBase for GcHashMap<'h, K, V>
+where
+    K::In: Eq + Hash,
+{
+    type In = <GcRef<'h, HashMap<K, V>> as IntoHeapBase>::In;
+
+    fn into_heap(self) -> Self::In {
+        self.0.into_heap()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> GcHashMap<'h, K, V> {
+        GcHashMap(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h, K: IntoHeap<'h> + Eq + Hash, V: IntoHeap<'h>> IntoHeap<'h> for GcHashMap<'h, K, V>
+where
+    K::In: Eq + Hash,
+{
+}
+
+impl<'h, K, V> GcHashMap<'h, K, V>
+where
+    K: IntoHeap<'h> + Eq + Hash,
+    K::In: Eq + Hash,
+    V: IntoHeap<'h>,
+{
+    unsafe fn with_storage<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b HashMap<K::In, V::In>) -> R,
+        'v: 'b,
+    {
+        f(&*self.0.as_ptr())
+    }
+
+    unsafe fn with_storage_mut<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b mut HashMap<K::In, V::In>) -> R,
+        'v: 'b,
+    {
+        f(&mut *self.0.as_mut_ptr())
+    }
+
+    /// Inserts a key-value pair into the map, returning the previous value
+    /// associated with `key`, if any.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let uk = key.into_heap();
+        let uv = value.into_heap();
+        unsafe { self.with_storage_mut(|m| m.insert(uk, uv).map(|old| V::from_heap(&old))) }
+    }
+
+    /// Returns a clone of the value associated with `key`, if any.
+    pub fn get(&self, key: K) -> Option<V> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let uk = key.into_heap();
+        unsafe { self.with_storage(|m| m.get(&uk).map(|v| V::from_heap(v))) }
+    }
+
+    /// Returns `true` if the map contains a value for `key`.
+    pub fn contains_key(&self, key: K) -> bool {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let uk = key.into_heap();
+        unsafe { self.with_storage(|m| m.contains_key(&uk)) }
+    }
+
+    /// Removes `key` from the map, returning its value if it was present.
+    pub fn remove(&self, key: K) -> Option<V> {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        let uk = key.into_heap();
+        unsafe { self.with_storage_mut(|m| m.remove(&uk).map(|v| V::from_heap(&v))) }
+    }
+
+    /// Returns the number of key-value pairs in the map.
+    pub fn len(&self) -> usize {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe { self.with_storage(|m| m.len()) }
+    }
+
+    /// Returns `true` if the map contains no key-value pairs.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all key-value pairs from the map.
+    pub fn clear(&self) {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        unsafe { self.with_storage_mut(|m| m.clear()) }
+    }
+
+    /// Returns a snapshot of the map's key-value pairs, cloned out of the
+    /// heap.
+    ///
+    /// As with `VecRef::get_all`, cell-gc never hands out Rust references
+    /// into the heap, so this collects owned copies rather than returning a
+    /// lazy iterator borrowing the map's storage.
+    pub fn iter(&self) -> Vec<(K, V)> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe {
+            self.with_storage(|m| {
+                m.iter().map(|(k, v)| (K::from_heap(k), V::from_heap(v))).collect()
+            })
+        }
+    }
+
+    /// See `GcRef::object_id`.
+    pub fn object_id(&self) -> usize {
+        self.0.object_id()
+    }
+}
+
+impl<K: InHeap + Ord, V: InHeap> InHeap for BTreeMap<K, V> {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        for (k, v) in self {
+            k.trace(tracer);
+            v.trace(tracer);
+        }
+    }
+}
+
+impl<K: IntoHeapBase + Ord, V: IntoHeapBase> IntoHeapBase for BTreeMap<K, V>
+where
+    K::In: Ord,
+{
+    type In = BTreeMap<K::In, V::In>;
+
+    fn into_heap(self) -> BTreeMap<K::In, V::In> {
+        self.into_iter().map(|(k, v)| (k.into_heap(), v.into_heap())).collect()
+    }
+
+    unsafe fn from_heap(storage: &BTreeMap<K::In, V::In>) -> BTreeMap<K, V> {
+        storage.iter().map(|(k, v)| (K::from_heap(k), V::from_heap(v))).collect()
+    }
+}
+
+unsafe impl<'h, K: IntoHeap<'h> + Ord, V: IntoHeap<'h>> IntoHeap<'h> for BTreeMap<K, V>
+where
+    K::In: Ord,
+{
+}
+
+impl<'h, K: IntoHeap<'h> + Ord, V: IntoHeap<'h>> IntoHeapAllocation<'h> for BTreeMap<K, V>
+where
+    K::In: Ord,
+{
+    type Ref = GcBTreeMap<'h, K, V>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, BTreeMap<K, V>>) -> GcBTreeMap<'h, K, V> {
+        GcBTreeMap(gc_ref)
+    }
+
+    fn into_gc_ref(wrapped_ref: GcBTreeMap<'h, K, V>) -> GcRef<'h, BTreeMap<K, V>> {
+        wrapped_ref.0
+    }
+}
+
+/// A reference to a GC-heap-allocated `BTreeMap`, ordered by key, with keys
+/// and values traced by the collector.
+///
+/// To allocate a B-tree map in the heap, call `heap.alloc` on a plain old
+/// `BTreeMap`. It returns a `GcBTreeMap` object with `insert`/`get`/`remove`
+/// accessors, plus `range`, `first`, and `last` for the ordered access a
+/// `GcHashMap` can't provide.
+///
+/// ```rust
+/// use cell_gc::collections::GcBTreeMap;
+/// use std::collections::BTreeMap;
+///
+/// cell_gc::with_heap(|heap| {
+///     let map: GcBTreeMap<i32, i32> = heap.alloc(BTreeMap::new());
+///     map.insert(3, 300);
+///     map.insert(1, 100);
+///     map.insert(2, 200);
+///     assert_eq!(map.first(), Some((1, 100)));
+///     assert_eq!(map.last(), Some((3, 300)));
+///     assert_eq!(map.range(1..3), vec![(1, 100), (2, 200)]);
+/// });
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GcBTreeMap<'h, K: IntoHeap<'h> + Ord, V: IntoHeap<'h>>(GcRef<'h, BTreeMap<K, V>>)
+where
+    K::In: Ord;
+
+impl<'h, K: IntoHeap<'h> + Ord, V: IntoHeap<'h>> Hash for GcBTreeMap<'h, K, V>
+where
+    K::In: Ord,
+{
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h, K: IntoHeap<'h> + Ord, V: IntoHeap<'h>> IntoHeapBase for GcBTreeMap<'h, K, V>
+where
+    K::In: Ord,
+{
+    type In = <GcRef<'h, BTreeMap<K, V>> as IntoHeapBase>::In;
+
+    fn into_heap(self) -> Self::In {
+        self.0.into_heap()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> GcBTreeMap<'h, K, V> {
+        GcBTreeMap(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h, K: IntoHeap<'h> + Ord, V: IntoHeap<'h>> IntoHeap<'h> for GcBTreeMap<'h, K, V>
+where
+    K::In: Ord,
+{
+}
+
+impl<'h, K, V> GcBTreeMap<'h, K, V>
+where
+    K: IntoHeap<'h> + Ord,
+    K::In: Ord,
+    V: IntoHeap<'h>,
+{
+    unsafe fn with_storage<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b BTreeMap<K::In, V::In>) -> R,
+        'v: 'b,
+    {
+        f(&*self.0.as_ptr())
+    }
+
+    unsafe fn with_storage_mut<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b mut BTreeMap<K::In, V::In>) -> R,
+        'v: 'b,
+    {
+        f(&mut *self.0.as_mut_ptr())
+    }
+
+    /// Inserts a key-value pair into the map, returning the previous value
+    /// associated with `key`, if any.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let uk = key.into_heap();
+        let uv = value.into_heap();
+        unsafe { self.with_storage_mut(|m| m.insert(uk, uv).map(|old| V::from_heap(&old))) }
+    }
+
+    /// Returns a clone of the value associated with `key`, if any.
+    pub fn get(&self, key: K) -> Option<V> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let uk = key.into_heap();
+        unsafe { self.with_storage(|m| m.get(&uk).map(|v| V::from_heap(v))) }
+    }
+
+    /// Returns `true` if the map contains a value for `key`.
+    pub fn contains_key(&self, key: K) -> bool {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let uk = key.into_heap();
+        unsafe { self.with_storage(|m| m.contains_key(&uk)) }
+    }
+
+    /// Removes `key` from the map, returning its value if it was present.
+    pub fn remove(&self, key: K) -> Option<V> {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        let uk = key.into_heap();
+        unsafe { self.with_storage_mut(|m| m.remove(&uk).map(|v| V::from_heap(&v))) }
+    }
+
+    /// Returns the number of key-value pairs in the map.
+    pub fn len(&self) -> usize {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe { self.with_storage(|m| m.len()) }
+    }
+
+    /// Returns `true` if the map contains no key-value pairs.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all key-value pairs from the map.
+    pub fn clear(&self) {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        unsafe { self.with_storage_mut(|m| m.clear()) }
+    }
+
+    /// Returns the first key-value pair in the map, ordered by key, or
+    /// `None` if the map is empty.
+    pub fn first(&self) -> Option<(K, V)> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe {
+            self.with_storage(|m| m.iter().next().map(|(k, v)| (K::from_heap(k), V::from_heap(v))))
+        }
+    }
+
+    /// Returns the last key-value pair in the map, ordered by key, or
+    /// `None` if the map is empty.
+    pub fn last(&self) -> Option<(K, V)> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe {
+            self.with_storage(|m| {
+                m.iter().next_back().map(|(k, v)| (K::from_heap(k), V::from_heap(v)))
+            })
+        }
+    }
+
+    /// Returns a snapshot of the key-value pairs whose keys fall in
+    /// `range`, in order, cloned out of the heap.
+    ///
+    /// As with `VecRef::get_all`, cell-gc never hands out Rust references
+    /// into the heap, so this collects owned copies rather than returning a
+    /// lazy iterator borrowing the map's storage.
+    pub fn range(&self, range: Range<K>) -> Vec<(K, V)> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let start = range.start.into_heap();
+        let end = range.end.into_heap();
+        unsafe {
+            self.with_storage(|m| {
+                m.range(start..end).map(|(k, v)| (K::from_heap(k), V::from_heap(v))).collect()
+            })
+        }
+    }
+
+    /// Returns a snapshot of the map's key-value pairs, in order, cloned
+    /// out of the heap.
+    ///
+    /// As with `VecRef::get_all`, cell-gc never hands out Rust references
+    /// into the heap, so this collects owned copies rather than returning a
+    /// lazy iterator borrowing the map's storage.
+    pub fn iter(&self) -> Vec<(K, V)> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe {
+            self.with_storage(|m| {
+                m.iter().map(|(k, v)| (K::from_heap(k), V::from_heap(v))).collect()
+            })
+        }
+    }
+
+    /// See `GcRef::object_id`.
+    pub fn object_id(&self) -> usize {
+        self.0.object_id()
+    }
+}
+
+impl<'h> IntoHeapAllocation<'h> for () {
+    type Ref = GcRef<'h, ()>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, ()>) -> GcRef<'h, ()> {
+        gc_ref
+    }
+
+    fn into_gc_ref(gc_ref: GcRef<'h, ()>) -> GcRef<'h, ()> {
+        gc_ref
+    }
+}
+
+/// A hash set allocated in the heap, with elements traced by the collector.
+///
+/// Built on `GcHashMap<T, ()>`, the same way `std::collections::HashSet` is
+/// built on `HashMap<T, ()>`.
+///
+/// ```rust
+/// use cell_gc::collections::GcHashSet;
+///
+/// cell_gc::with_heap(|heap| {
+///     let set: GcHashSet<i32> = GcHashSet::new(heap);
+///     assert!(set.insert(1));
+///     assert!(set.insert(2));
+///     assert!(!set.insert(1));
+///     assert!(set.contains(1));
+///     assert_eq!(set.len(), 2);
+///     assert!(set.remove(1));
+///     assert!(!set.contains(1));
+/// });
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GcHashSet<'h, T: IntoHeap<'h> + Eq + Hash>(GcHashMap<'h, T, ()>)
+where
+    T::In: Eq + Hash;
+
+impl<'h, T: IntoHeap<'h> + Eq + Hash> Hash for GcHashSet<'h, T>
+where
+    T::In: Eq + Hash,
+{
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h, T> GcHashSet<'h, T>
+where
+    T: IntoHeap<'h> + Eq + Hash,
+    T::In: Eq + Hash,
+{
+    /// Allocate a new, empty `GcHashSet` in the heap.
+    pub fn new(hs: &mut GcHeapSession<'h>) -> GcHashSet<'h, T> {
+        GcHashSet(hs.alloc(HashMap::new()))
+    }
+
+    /// Adds `value` to the set. Returns `true` if the value was not already
+    /// present.
+    pub fn insert(&self, value: T) -> bool {
+        self.0.insert(value, ()).is_none()
+    }
+
+    /// Returns `true` if the set contains `value`.
+    pub fn contains(&self, value: T) -> bool {
+        self.0.contains_key(value)
+    }
+
+    /// Removes `value` from the set. Returns `true` if it was present.
+    pub fn remove(&self, value: T) -> bool {
+        self.0.remove(value).is_some()
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the set has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Removes all elements from the set.
+    pub fn clear(&self) {
+        self.0.clear()
+    }
+
+    /// Returns a snapshot of the set's elements, cloned out of the heap.
+    ///
+    /// As with `VecRef::get_all`, cell-gc never hands out Rust references
+    /// into the heap, so this collects owned copies rather than returning a
+    /// lazy iterator borrowing the set's storage.
+    pub fn iter(&self) -> Vec<T> {
+        self.0.iter().into_iter().map(|(k, _)| k).collect()
+    }
+
+    /// Returns the elements that are in either `self` or `other`, with no
+    /// duplicates.
+    pub fn union(&self, other: &GcHashSet<'h, T>) -> Vec<T> {
+        let mut seen = ::std::collections::HashSet::new();
+        let mut result = Vec::new();
+        for item in self.iter().into_iter().chain(other.iter()) {
+            let uk = item.into_heap();
+            if !seen.contains(&uk) {
+                result.push(unsafe { T::from_heap(&uk) });
+                seen.insert(uk);
+            }
+        }
+        result
+    }
+
+    /// Returns the elements that are in both `self` and `other`, with no
+    /// duplicates.
+    pub fn intersection(&self, other: &GcHashSet<'h, T>) -> Vec<T> {
+        let other_keys: ::std::collections::HashSet<_> =
+            other.iter().into_iter().map(|item| item.into_heap()).collect();
+        let mut seen = ::std::collections::HashSet::new();
+        let mut result = Vec::new();
+        for item in self.iter() {
+            let uk = item.into_heap();
+            if other_keys.contains(&uk) && !seen.contains(&uk) {
+                result.push(unsafe { T::from_heap(&uk) });
+                seen.insert(uk);
+            }
+        }
+        result
+    }
+
+    /// See `GcRef::object_id`.
+    pub fn object_id(&self) -> usize {
+        self.0.object_id()
+    }
+}
+
+/// An ordered set allocated in the heap, with elements traced by the
+/// collector.
+///
+/// Built on `GcBTreeMap<T, ()>`, the same way `std::collections::BTreeSet`
+/// is built on `BTreeMap<T, ()>`.
+///
+/// ```rust
+/// use cell_gc::collections::GcBTreeSet;
+///
+/// cell_gc::with_heap(|heap| {
+///     let set: GcBTreeSet<i32> = GcBTreeSet::new(heap);
+///     assert!(set.insert(2));
+///     assert!(set.insert(1));
+///     assert!(!set.insert(1));
+///     assert_eq!(set.iter(), vec![1, 2]);
+/// });
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GcBTreeSet<'h, T: IntoHeap<'h> + Ord>(GcBTreeMap<'h, T, ()>)
+where
+    T::In: Ord;
+
+impl<'h, T: IntoHeap<'h> + Ord> Hash for GcBTreeSet<'h, T>
+where
+    T::In: Ord,
+{
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h, T> GcBTreeSet<'h, T>
+where
+    T: IntoHeap<'h> + Ord,
+    T::In: Ord,
+{
+    /// Allocate a new, empty `GcBTreeSet` in the heap.
+    pub fn new(hs: &mut GcHeapSession<'h>) -> GcBTreeSet<'h, T> {
+        GcBTreeSet(hs.alloc(BTreeMap::new()))
+    }
+
+    /// Adds `value` to the set. Returns `true` if the value was not already
+    /// present.
+    pub fn insert(&self, value: T) -> bool {
+        self.0.insert(value, ()).is_none()
+    }
+
+    /// Returns `true` if the set contains `value`.
+    pub fn contains(&self, value: T) -> bool {
+        self.0.contains_key(value)
+    }
+
+    /// Removes `value` from the set. Returns `true` if it was present.
+    pub fn remove(&self, value: T) -> bool {
+        self.0.remove(value).is_some()
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the set has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Removes all elements from the set.
+    pub fn clear(&self) {
+        self.0.clear()
+    }
+
+    /// Returns a snapshot of the set's elements, in order, cloned out of
+    /// the heap.
+    ///
+    /// As with `VecRef::get_all`, cell-gc never hands out Rust references
+    /// into the heap, so this collects owned copies rather than returning a
+    /// lazy iterator borrowing the set's storage.
+    pub fn iter(&self) -> Vec<T> {
+        self.0.iter().into_iter().map(|(k, _)| k).collect()
+    }
+
+    /// Returns the elements that are in either `self` or `other`, in
+    /// order, with no duplicates.
+    pub fn union(&self, other: &GcBTreeSet<'h, T>) -> Vec<T> {
+        let mut merged: BTreeMap<T::In, ()> = BTreeMap::new();
+        for item in self.iter().into_iter().chain(other.iter()) {
+            merged.insert(item.into_heap(), ());
+        }
+        merged.into_iter().map(|(k, _)| unsafe { T::from_heap(&k) }).collect()
+    }
+
+    /// Returns the elements that are in both `self` and `other`, in order,
+    /// with no duplicates.
+    pub fn intersection(&self, other: &GcBTreeSet<'h, T>) -> Vec<T> {
+        let other_keys: BTreeMap<T::In, ()> =
+            other.iter().into_iter().map(|item| (item.into_heap(), ())).collect();
+        let mut result = BTreeMap::new();
+        for item in self.iter() {
+            let uk = item.into_heap();
+            if other_keys.contains_key(&uk) {
+                result.insert(uk, ());
+            }
+        }
+        result.into_iter().map(|(k, _)| unsafe { T::from_heap(&k) }).collect()
+    }
+
+    /// See `GcRef::object_id`.
+    pub fn object_id(&self) -> usize {
+        self.0.object_id()
+    }
+}
+
+impl<U: InHeap> InHeap for VecDeque<U> {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        for r in self {
+            r.trace(tracer);
+        }
+    }
+}
+
+impl<U: Adopt> Adopt for VecDeque<U> {
+    unsafe fn adopt(&self, adopter: &mut Adopter) -> VecDeque<U> {
+        self.iter().map(|r| r.adopt(adopter)).collect()
+    }
+}
+
+impl<U: GcSerialize> GcSerialize for VecDeque<U> {
+    unsafe fn write_fields(&self, ctx: &mut Serializer, buf: &mut Vec<u8>) {
+        (self.len() as u64).encode(buf);
+        for u in self {
+            u.write_fields(ctx, buf);
+        }
+    }
+
+    unsafe fn read_fields(ctx: &mut Deserializer, buf: &mut Cursor) -> VecDeque<U> {
+        let len = u64::decode(buf) as usize;
+        (0..len).map(|_| U::read_fields(ctx, buf)).collect()
+    }
+}
+
+impl<T: IntoHeapBase> IntoHeapBase for VecDeque<T> {
+    type In = VecDeque<T::In>;
+
+    fn into_heap(self) -> VecDeque<T::In> {
+        self.into_iter().map(|x| x.into_heap()).collect()
+    }
+
+    unsafe fn from_heap(storage: &VecDeque<T::In>) -> VecDeque<T> {
+        storage.iter().map(|x| T::from_heap(x)).collect()
+    }
+}
+
+unsafe impl<'h, T: IntoHeap<'h>> IntoHeap<'h> for VecDeque<T> {}
+
+impl<'h, T: IntoHeap<'h>> IntoHeapAllocation<'h> for VecDeque<T> {
+    type Ref = GcDeque<'h, T>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, VecDeque<T>>) -> GcDeque<'h, T> {
+        GcDeque(gc_ref)
+    }
+
+    fn into_gc_ref(wrapped_ref: GcDeque<'h, T>) -> GcRef<'h, VecDeque<T>> {
+        wrapped_ref.0
+    }
+}
+
+/// A double-ended queue, allocated in the heap, with elements traced by
+/// the collector.
+///
+/// `VecRef::remove(0)` is O(n), which makes `VecRef` a poor fit for work
+/// queues and BFS frontiers. `GcDeque` is backed by a ring buffer, like
+/// `std::collections::VecDeque`, so `push_front`/`pop_front` are O(1).
+///
+/// To allocate one, call `heap.alloc` on a plain `VecDeque`.
+///
+/// ```rust
+/// use cell_gc::collections::GcDeque;
+/// use std::collections::VecDeque;
+///
+/// cell_gc::with_heap(|heap| {
+///     let q: GcDeque<i32> = heap.alloc(VecDeque::new());
+///     q.push_back(1);
+///     q.push_back(2);
+///     q.push_front(0);
+///     assert_eq!(q.pop_front(), Some(0));
+///     assert_eq!(q.pop_back(), Some(2));
+///     assert_eq!(q.len(), 1);
+/// });
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GcDeque<'h, T: IntoHeap<'h>>(GcRef<'h, VecDeque<T>>);
+
+impl<'h, T: IntoHeap<'h>> Hash for GcDeque<'h, T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> IntoHeapBase for GcDeque<'h, T> {
+    type In = Pointer<VecDeque<T::In>>;
+
+    fn into_heap(self) -> Pointer<VecDeque<T::In>> {
+        self.0.ptr()
+    }
+
+    unsafe fn from_heap(storage: &Pointer<VecDeque<T::In>>) -> GcDeque<'h, T> {
+        GcDeque(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h, T: IntoHeap<'h>> IntoHeap<'h> for GcDeque<'h, T> {}
+
+impl<'h, T: IntoHeap<'h>> GcDeque<'h, T> {
+    unsafe fn with_storage<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b VecDeque<T::In>) -> R,
+        'v: 'b,
+    {
+        f(&*self.0.as_ptr())
+    }
+
+    unsafe fn with_storage_mut<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b mut VecDeque<T::In>) -> R,
+        'v: 'b,
+    {
+        f(&mut *self.0.as_mut_ptr())
+    }
+
+    /// Prepends `value` to the front of the deque.
+    pub fn push_front(&self, value: T) {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        let value = value.into_heap();
+        unsafe {
+            self.with_storage_mut(|d| d.push_front(value));
+        }
+    }
+
+    /// Appends `value` to the back of the deque.
+    pub fn push_back(&self, value: T) {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        let value = value.into_heap();
+        unsafe {
+            self.with_storage_mut(|d| d.push_back(value));
+        }
+    }
+
+    /// Removes and returns the element at the front of the deque, or
+    /// `None` if it is empty.
+    pub fn pop_front(&self) -> Option<T> {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        unsafe { self.with_storage_mut(|d| d.pop_front().map(|v| T::from_heap(&v))) }
+    }
+
+    /// Removes and returns the element at the back of the deque, or
+    /// `None` if it is empty.
+    pub fn pop_back(&self) -> Option<T> {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        unsafe { self.with_storage_mut(|d| d.pop_back().map(|v| T::from_heap(&v))) }
+    }
+
+    /// Returns the element at the front of the deque, or `None` if it is
+    /// empty, without removing it.
+    pub fn front(&self) -> Option<T> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe { self.with_storage(|d| d.front().map(|v| T::from_heap(v))) }
+    }
+
+    /// Returns the element at the back of the deque, or `None` if it is
+    /// empty, without removing it.
+    pub fn back(&self) -> Option<T> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe { self.with_storage(|d| d.back().map(|v| T::from_heap(v))) }
+    }
+
+    /// Returns the number of elements in the deque.
+    pub fn len(&self) -> usize {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe { self.with_storage(|d| d.len()) }
+    }
+
+    /// Returns `true` if the deque has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all elements from the deque.
+    pub fn clear(&self) {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        unsafe {
+            self.with_storage_mut(|d| d.clear());
+        }
+    }
+
+    /// Returns a snapshot of the deque's elements, from front to back,
+    /// cloned out of the heap.
+    ///
+    /// As with `VecRef::get_all`, cell-gc never hands out Rust references
+    /// into the heap, so this collects owned copies rather than returning a
+    /// lazy iterator borrowing the deque's storage.
+    pub fn get_all(&self) -> Vec<T> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe { self.with_storage(|d| d.iter().map(|v| T::from_heap(v)).collect()) }
+    }
+
+    /// See `GcRef::object_id`.
+    pub fn object_id(&self) -> usize {
+        self.0.object_id()
+    }
+}
+
+macro_rules! gc_numeric_vec_impl {
+    ($name:ident, $name_ref:ident, $elem:ty, $doc:expr) => {
+        #[doc = $doc]
+        #[derive(Clone, Debug, Default, PartialEq)]
+        pub struct $name(Vec<$elem>);
+
+        impl $name {
+            /// Create a new, empty vector, e.g. `heap.alloc(GcF64Vec::new())`.
+            pub fn new() -> $name {
+                $name(Vec::new())
+            }
+
+            /// Create a vector of `len` copies of `value`.
+            pub fn from_elem(value: $elem, len: usize) -> $name {
+                $name(vec![value; len])
+            }
+        }
+
+        impl From<Vec<$elem>> for $name {
+            fn from(v: Vec<$elem>) -> $name {
+                $name(v)
+            }
+        }
+
+        impl<'a> From<&'a [$elem]> for $name {
+            fn from(v: &'a [$elem]) -> $name {
+                $name(v.to_vec())
+            }
+        }
+
+        impl InHeap for $name {
+            #[inline]
+            unsafe fn trace<R: Tracer>(&self, _tracer: &mut R) {}
+        }
+
+        impl IntoHeapBase for $name {
+            type In = $name;
+
+            fn into_heap(self) -> $name {
+                self
+            }
+
+            unsafe fn from_heap(storage: &$name) -> $name {
+                storage.clone()
+            }
+        }
+
+        unsafe impl<'h> IntoHeap<'h> for $name {}
+
+        impl<'h> IntoHeapAllocation<'h> for $name {
+            type Ref = $name_ref<'h>;
+
+            fn wrap_gc_ref(gc_ref: GcRef<'h, $name>) -> $name_ref<'h> {
+                $name_ref(gc_ref)
+            }
+
+            fn into_gc_ref(r: $name_ref<'h>) -> GcRef<'h, $name> {
+                r.0
+            }
+        }
+
+        /// A reference to a
+        #[doc = $doc]
+        /// allocated in the heap. See the docs of the vector type itself.
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct $name_ref<'h>(GcRef<'h, $name>);
+
+        impl<'h> Hash for $name_ref<'h> {
+            #[inline]
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.hash(state);
+            }
+        }
+
+        impl<'h> IntoHeapBase for $name_ref<'h> {
+            type In = <GcRef<'h, $name> as IntoHeapBase>::In;
+
+            fn into_heap(self) -> Self::In {
+                self.0.into_heap()
+            }
+
+            unsafe fn from_heap(storage: &Self::In) -> $name_ref<'h> {
+                $name_ref(GcRef::new(*storage))
+            }
+        }
+
+        unsafe impl<'h> IntoHeap<'h> for $name_ref<'h> {}
+
+        impl<'h> $name_ref<'h> {
+            unsafe fn with_storage<'v, 'b, R, F>(&'v self, f: F) -> R
+            where
+                F: FnOnce(&'b Vec<$elem>) -> R,
+                'v: 'b,
+            {
+                f(&(*self.0.as_ptr()).0)
+            }
+
+            unsafe fn with_storage_mut<'v, 'b, R, F>(&'v self, f: F) -> R
+            where
+                F: FnOnce(&'b mut Vec<$elem>) -> R,
+                'v: 'b,
+            {
+                f(&mut (*self.0.as_mut_ptr()).0)
+            }
+
+            /// Returns the number of elements in the vector.
+            pub fn len(&self) -> usize {
+                borrow_flag::check_not_borrowed(self.0.address());
+                unsafe { self.with_storage(|v| v.len()) }
+            }
+
+            /// Returns `true` if the vector has no elements.
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// The capacity of this vector.
+            pub fn capacity(&self) -> usize {
+                borrow_flag::check_not_borrowed(self.0.address());
+                unsafe { self.with_storage(|v| v.capacity()) }
+            }
+
+            /// Get the element `index` from the vector.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `index` is out of bounds.
+            pub fn get(&self, index: usize) -> $elem {
+                borrow_flag::check_not_borrowed(self.0.address());
+                unsafe { self.with_storage(|v| v[index]) }
+            }
+
+            /// Set element `index` of the vector to `value`.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `index` is out of bounds.
+            pub fn set(&self, index: usize, value: $elem) {
+                let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+                unsafe {
+                    self.with_storage_mut(|v| v[index] = value);
+                }
+            }
+
+            /// Appends `value` to the end of the vector.
+            pub fn push(&self, value: $elem) {
+                let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+                unsafe {
+                    self.with_storage_mut(|v| v.push(value));
+                }
+            }
+
+            /// Removes all elements from the vector.
+            pub fn clear(&self) {
+                let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+                unsafe {
+                    self.with_storage_mut(|v| v.clear());
+                }
+            }
+
+            /// Overwrites every element of the vector with `value`.
+            pub fn fill(&self, value: $elem) {
+                let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+                unsafe {
+                    self.with_storage_mut(|v| {
+                        for slot in v.iter_mut() {
+                            *slot = value;
+                        }
+                    });
+                }
+            }
+
+            /// Overwrites the contents of this vector with a copy of `src`,
+            /// resizing as needed.
+            pub fn copy_from_slice(&self, src: &[$elem]) {
+                let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+                unsafe {
+                    self.with_storage_mut(|v| {
+                        v.clear();
+                        v.extend_from_slice(src);
+                    });
+                }
+            }
+
+            /// Copies the vector out of the heap as an ordinary, non-GC-managed
+            /// `Vec`.
+            pub fn get_all(&self) -> Vec<$elem> {
+                borrow_flag::check_not_borrowed(self.0.address());
+                unsafe { self.with_storage(|v| v.clone()) }
+            }
+
+            /// Runs `f` with a borrow of the vector's contiguous storage as a
+            /// plain `&[$elem]` slice.
+            ///
+            /// This is the fast path for numeric code: unlike `get`/`get_all`,
+            /// it doesn't copy every element, just hands out a scoped
+            /// reference straight into the heap's storage.
+            pub fn as_slice_with<R, F>(&self, f: F) -> R
+            where
+                F: FnOnce(&[$elem]) -> R,
+            {
+                borrow_flag::check_not_borrowed(self.0.address());
+                unsafe { self.with_storage(|v| f(v)) }
+            }
+
+            /// Runs `f` with a `mut` borrow of the vector's contiguous
+            /// storage as a plain `&mut [$elem]` slice.
+            pub fn as_mut_slice_with<R, F>(&self, f: F) -> R
+            where
+                F: FnOnce(&mut [$elem]) -> R,
+            {
+                let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+                unsafe { self.with_storage_mut(|v| f(v)) }
+            }
+
+            /// See `GcRef::object_id`.
+            pub fn object_id(&self) -> usize {
+                self.0.object_id()
+            }
+        }
+    };
+}
+
+gc_numeric_vec_impl!(
+    GcF64Vec,
+    GcF64VecRef,
+    f64,
+    "A vector of `f64`s allocated in the heap, stored contiguously and \
+     unboxed, for numerics workloads and Scheme bytevector-like data that \
+     `VecRef<f64>` would box element by element."
+);
+
+gc_numeric_vec_impl!(
+    GcI32Vec,
+    GcI32VecRef,
+    i32,
+    "A vector of `i32`s allocated in the heap, stored contiguously and \
+     unboxed. See `GcF64Vec`."
+);
+
+gc_numeric_vec_impl!(
+    GcU8Vec,
+    GcU8VecRef,
+    u8,
+    "A vector of `u8`s allocated in the heap, stored contiguously and \
+     unboxed. The natural backing store for Scheme bytevectors. See \
+     `GcF64Vec`."
+);
+
+/// In-heap storage for a `GcGrid<T>`: a flat, row-major buffer plus its
+/// dimensions.
+pub struct GcGridStorage<U> {
+    width: usize,
+    height: usize,
+    data: Vec<U>,
+}
+
+impl<U: InHeap> InHeap for GcGridStorage<U> {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        for u in &self.data {
+            u.trace(tracer);
+        }
+    }
+}
+
+/// A two-dimensional, GC-managed grid of elements traced by the collector.
+///
+/// A `Vec<VecRef<T>>` of rows works, but is a second, independent heap
+/// allocation per row; `GcGrid` is a single flat, row-major buffer, which
+/// is the natural shape for a game map or cellular-automaton generation.
+///
+/// To allocate one, call `heap.alloc` on a plain `GcGrid::new(width,
+/// height, fill)`.
+///
+/// ```rust
+/// use cell_gc::collections::GcGrid;
+///
+/// cell_gc::with_heap(|heap| {
+///     let grid = heap.alloc(GcGrid::new(3, 2, 0));
+///     grid.set(1, 0, 10);
+///     grid.set(2, 1, 20);
+///     assert_eq!(grid.get(1, 0), 10);
+///     assert_eq!(grid.row(1), vec![0, 0, 20]);
+/// });
+/// ```
+pub struct GcGrid<T> {
+    width: usize,
+    height: usize,
+    data: Vec<T>,
+}
+
+impl<T: Clone> GcGrid<T> {
+    /// Create a `width` by `height` grid, with every cell initialized to a
+    /// clone of `fill`.
+    pub fn new(width: usize, height: usize, fill: T) -> GcGrid<T> {
+        GcGrid {
+            width,
+            height,
+            data: vec![fill; width * height],
+        }
+    }
+}
+
+impl<T: IntoHeapBase> IntoHeapBase for GcGrid<T> {
+    type In = GcGridStorage<T::In>;
+
+    fn into_heap(self) -> GcGridStorage<T::In> {
+        GcGridStorage {
+            width: self.width,
+            height: self.height,
+            data: self.data.into_iter().map(|x| x.into_heap()).collect(),
+        }
+    }
+
+    unsafe fn from_heap(storage: &GcGridStorage<T::In>) -> GcGrid<T> {
+        GcGrid {
+            width: storage.width,
+            height: storage.height,
+            data: storage.data.iter().map(|x| T::from_heap(x)).collect(),
+        }
+    }
+}
+
+unsafe impl<'h, T: IntoHeap<'h>> IntoHeap<'h> for GcGrid<T> {}
+
+impl<'h, T: IntoHeap<'h>> IntoHeapAllocation<'h> for GcGrid<T> {
+    type Ref = GcGridRef<'h, T>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, GcGrid<T>>) -> GcGridRef<'h, T> {
+        GcGridRef(gc_ref)
+    }
+
+    fn into_gc_ref(wrapped_ref: GcGridRef<'h, T>) -> GcRef<'h, GcGrid<T>> {
+        wrapped_ref.0
+    }
+}
+
+/// A reference to a `GcGrid` allocated in the heap. See `GcGrid`'s docs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GcGridRef<'h, T: IntoHeap<'h>>(GcRef<'h, GcGrid<T>>);
+
+impl<'h, T: IntoHeap<'h>> Hash for GcGridRef<'h, T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> IntoHeapBase for GcGridRef<'h, T> {
+    type In = Pointer<GcGridStorage<T::In>>;
+
+    fn into_heap(self) -> Self::In {
+        self.0.ptr()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> GcGridRef<'h, T> {
+        GcGridRef(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h, T: IntoHeap<'h>> IntoHeap<'h> for GcGridRef<'h, T> {}
+
+impl<'h, T: IntoHeap<'h>> GcGridRef<'h, T> {
+    unsafe fn with_storage<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b GcGridStorage<T::In>) -> R,
+        'v: 'b,
+    {
+        f(&*self.0.as_ptr())
+    }
+
+    unsafe fn with_storage_mut<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b mut GcGridStorage<T::In>) -> R,
+        'v: 'b,
+    {
+        f(&mut *self.0.as_mut_ptr())
+    }
+
+    /// The width of the grid, in cells.
+    pub fn width(&self) -> usize {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe { self.with_storage(|g| g.width) }
+    }
+
+    /// The height of the grid, in cells.
+    pub fn height(&self) -> usize {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe { self.with_storage(|g| g.height) }
+    }
+
+    /// Get the element at column `x`, row `y`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` or `y` is out of bounds.
+    pub fn get(&self, x: usize, y: usize) -> T {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe {
+            self.with_storage(|g| {
+                assert!(x < g.width && y < g.height, "GcGrid index out of bounds");
+                T::from_heap(&g.data[y * g.width + x])
+            })
+        }
+    }
+
+    /// Set the element at column `x`, row `y` to `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` or `y` is out of bounds.
+    pub fn set(&self, x: usize, y: usize, value: T) {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        let value = value.into_heap();
+        unsafe {
+            self.with_storage_mut(|g| {
+                assert!(x < g.width && y < g.height, "GcGrid index out of bounds");
+                let width = g.width;
+                g.data[y * width + x] = value;
+            });
+        }
+    }
+
+    /// Copies row `y` out of the heap, from left to right.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is out of bounds.
+    pub fn row(&self, y: usize) -> Vec<T> {
+        borrow_flag::check_not_borrowed(self.0.address());
+        unsafe {
+            self.with_storage(|g| {
+                assert!(y < g.height, "GcGrid row index out of bounds");
+                g.data[y * g.width..(y + 1) * g.width]
+                    .iter()
+                    .map(|u| T::from_heap(u))
+                    .collect()
+            })
+        }
+    }
+
+    /// Copies every row out of the heap, from top to bottom.
+    pub fn rows(&self) -> Vec<Vec<T>> {
+        (0..self.height()).map(|y| self.row(y)).collect()
+    }
+
+    /// Resizes the grid to `new_width` by `new_height`.
+    ///
+    /// Cells within both the old and new bounds keep their values; any
+    /// newly added cells are set to a clone of `fill`.
+    pub fn resize(&self, new_width: usize, new_height: usize, fill: T)
+    where
+        T: Clone,
+    {
+        let _guard = borrow_flag::BorrowGuard::new(self.0.address());
+        unsafe {
+            self.with_storage_mut(|g| {
+                let mut new_data: Vec<T::In> = (0..new_width * new_height)
+                    .map(|_| fill.clone().into_heap())
+                    .collect();
+                let common_width = g.width.min(new_width);
+                let common_height = g.height.min(new_height);
+                for y in 0..common_height {
+                    for x in 0..common_width {
+                        let value = T::from_heap(&g.data[y * g.width + x]);
+                        new_data[y * new_width + x] = value.into_heap();
+                    }
+                }
+                g.width = new_width;
+                g.height = new_height;
+                g.data = new_data;
+            });
+        }
+    }
+
+    /// See `GcRef::object_id`.
+    pub fn object_id(&self) -> usize {
+        self.0.object_id()
+    }
+}
+
+/// Log2 of the branching factor of a `GcPersistentVector`'s trie: each node
+/// has up to `PVEC_BRANCHING` (`1 << PVEC_BITS`) children (or, at the leaf
+/// level, that many elements).
+const PVEC_BITS: u32 = 5;
+const PVEC_BRANCHING: usize = 1 << PVEC_BITS;
+const PVEC_MASK: usize = PVEC_BRANCHING - 1;
+
+/// One node of a `GcPersistentVector`'s trie: either a leaf, holding up to
+/// `PVEC_BRANCHING` elements directly in `values`, or an internal node,
+/// holding up to `PVEC_BRANCHING` pointers to child nodes in `children`.
+/// Which one applies is determined by the depth at which the node is
+/// reached, not stored on the node itself; exactly one of the two fields is
+/// non-empty.
+///
+/// Nodes are never mutated after they're allocated: `push_back` and `set`
+/// build replacement nodes along the path to the change and leave every
+/// other node, and therefore every subtree hanging off that path, shared
+/// between the old and new trees.
+struct PVecNode<U: InHeap> {
+    values: Vec<U>,
+    children: Vec<Pointer<PVecNode<U>>>,
+}
+
+impl<U: InHeap> InHeap for PVecNode<U> {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        for u in &self.values {
+            u.trace(tracer);
+        }
+        for c in &self.children {
+            c.trace(tracer);
+        }
+    }
+}
+
+impl<U: InHeap + Clone> IntoHeapBase for PVecNode<U> {
+    type In = Self;
+
+    fn into_heap(self) -> Self {
+        self
+    }
+
+    unsafe fn from_heap(storage: &Self) -> Self {
+        PVecNode {
+            values: storage.values.clone(),
+            children: storage.children.clone(),
+        }
+    }
+}
+
+unsafe impl<'h, U: InHeap + Clone> IntoHeap<'h> for PVecNode<U> {}
+
+impl<'h, U: InHeap + Clone> IntoHeapAllocation<'h> for PVecNode<U> {
+    type Ref = GcRef<'h, PVecNode<U>>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, PVecNode<U>>) -> GcRef<'h, PVecNode<U>> {
+        gc_ref
+    }
+
+    fn into_gc_ref(wrapped_ref: GcRef<'h, PVecNode<U>>) -> GcRef<'h, PVecNode<U>> {
+        wrapped_ref
+    }
+}
+
+/// The in-heap header of a `GcPersistentVector`: its length, the depth of
+/// its trie (encoded as a bit shift), and the root node, if any.
+///
+/// This type does double duty, like `GcString`: it's both the pre-heap
+/// value and its own in-heap storage. Unlike the mutable collections above,
+/// a fresh header is allocated on every `push_back` and `set`, since those
+/// operations never modify a `GcPersistentVector` in place.
+pub struct PVecRoot<U: InHeap> {
+    len: usize,
+    shift: u32,
+    root: Option<Pointer<PVecNode<U>>>,
+}
+
+impl<U: InHeap> InHeap for PVecRoot<U> {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        self.root.trace(tracer);
+    }
+}
+
+impl<U: InHeap> IntoHeapBase for PVecRoot<U> {
+    type In = Self;
+
+    fn into_heap(self) -> Self {
+        self
+    }
+
+    unsafe fn from_heap(storage: &Self) -> Self {
+        PVecRoot {
+            len: storage.len,
+            shift: storage.shift,
+            root: storage.root,
+        }
+    }
+}
+
+unsafe impl<'h, U: InHeap> IntoHeap<'h> for PVecRoot<U> {}
+
+impl<'h, U: InHeap> IntoHeapAllocation<'h> for PVecRoot<U> {
+    type Ref = GcRef<'h, PVecRoot<U>>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, PVecRoot<U>>) -> GcRef<'h, PVecRoot<U>> {
+        gc_ref
+    }
+
+    fn into_gc_ref(wrapped_ref: GcRef<'h, PVecRoot<U>>) -> GcRef<'h, PVecRoot<U>> {
+        wrapped_ref
+    }
+}
+
+/// Builds a fresh minimal chain of nodes at depth `shift / PVEC_BITS + 1`
+/// holding a single element, `value`: a leaf `[value]`, wrapped in internal
+/// nodes with a single child each until it reaches `shift`.
+///
+/// Returns a `GcRef`, not a bare `Pointer`: a node built partway through
+/// this recursion isn't reachable from anything the collector treats as a
+/// root yet (it's not linked into `self`'s trie until the caller stores it
+/// there), so it would be free to collect out from under a later `hs.alloc`
+/// call in the same chain if nothing kept it pinned in the meantime. Every
+/// `GcRef` local below stays alive --- and so keeps its node pinned --- for
+/// as long as its subtree isn't yet wrapped in the node that will make it
+/// reachable, which is exactly why `.ptr()` (which doesn't drop the
+/// `GcRef`, unlike consuming it) only ever gets called at the point of
+/// writing a child slot.
+unsafe fn pvec_new_path<'h, U: InHeap + Clone>(
+    hs: &mut GcHeapSession<'h>,
+    shift: u32,
+    value: U,
+) -> GcRef<'h, PVecNode<U>> {
+    if shift == 0 {
+        hs.alloc(PVecNode {
+            values: vec![value],
+            children: vec![],
+        })
+    } else {
+        let child = pvec_new_path(hs, shift - PVEC_BITS, value);
+        hs.alloc(PVecNode {
+            values: vec![],
+            children: vec![child.ptr()],
+        })
+    }
+}
+
+/// Path-copies the nodes from `node` down to the rightmost slot at `shift`,
+/// appending `value` there (`len` is the index of the not-yet-appended
+/// element, i.e. the vector's length before this push).
+///
+/// Returns a `GcRef` for the same reason `pvec_new_path` does; see its docs.
+unsafe fn pvec_push_into<'h, U: InHeap + Clone>(
+    hs: &mut GcHeapSession<'h>,
+    node: Pointer<PVecNode<U>>,
+    shift: u32,
+    len: usize,
+    value: U,
+) -> GcRef<'h, PVecNode<U>> {
+    if shift == 0 {
+        let mut values = node.as_ref().values.clone();
+        values.push(value);
+        hs.alloc(PVecNode {
+            values,
+            children: vec![],
+        })
+    } else {
+        let mut children = node.as_ref().children.clone();
+        let index = (len >> shift) & PVEC_MASK;
+        if index < children.len() {
+            let child = pvec_push_into(hs, children[index], shift - PVEC_BITS, len, value);
+            children[index] = child.ptr();
+            hs.alloc(PVecNode {
+                values: vec![],
+                children,
+            })
+        } else {
+            let child = pvec_new_path(hs, shift - PVEC_BITS, value);
+            children.push(child.ptr());
+            hs.alloc(PVecNode {
+                values: vec![],
+                children,
+            })
+        }
+    }
+}
+
+/// Path-copies the nodes from `node` down to the leaf holding `index`,
+/// replacing that element with `value`.
+///
+/// Returns a `GcRef` for the same reason `pvec_new_path` does; see its docs.
+unsafe fn pvec_set_into<'h, U: InHeap + Clone>(
+    hs: &mut GcHeapSession<'h>,
+    node: Pointer<PVecNode<U>>,
+    shift: u32,
+    index: usize,
+    value: U,
+) -> GcRef<'h, PVecNode<U>> {
+    if shift == 0 {
+        let mut values = node.as_ref().values.clone();
+        values[index & PVEC_MASK] = value;
+        hs.alloc(PVecNode {
+            values,
+            children: vec![],
+        })
+    } else {
+        let mut children = node.as_ref().children.clone();
+        let i = (index >> shift) & PVEC_MASK;
+        let child = pvec_set_into(hs, children[i], shift - PVEC_BITS, index, value);
+        children[i] = child.ptr();
+        hs.alloc(PVecNode {
+            values: vec![],
+            children,
+        })
+    }
+}
+
+/// A persistent (immutable, structurally-shared) vector.
+///
+/// `push_back` and `set` don't modify a `GcPersistentVector` in place; they
+/// return a brand new `GcPersistentVectorRef` for the updated vector, which
+/// shares as much of the old trie as possible with the original. This is
+/// the natural backing store for a Scheme `vector-immutable` or a
+/// Clojure-style persistent data structure: since the GC traces shared
+/// subtrees from every version that references them, holding on to an old
+/// version is always safe.
+///
+/// Because every operation may allocate new nodes, and unlike the mutable
+/// collections above, `GcPersistentVectorRef` isn't built by passing a
+/// plain value to `heap.alloc`; construct an empty one with
+/// `GcPersistentVectorRef::new`, and thread `hs` through `push_back`/`set`:
+///
+/// ```rust
+/// use cell_gc::collections::GcPersistentVectorRef;
+///
+/// cell_gc::with_heap(|hs| {
+///     let v0 = GcPersistentVectorRef::new(hs);
+///     let v1 = v0.push_back(hs, 10);
+///     let v2 = v1.push_back(hs, 20);
+///     let v3 = v2.set(hs, 0, 99);
+///
+///     assert_eq!(v1.get(0), 10);
+///     assert_eq!(v2.get(1), 20);
+///     assert_eq!(v3.get(0), 99);
+///     assert_eq!(v2.get(0), 10); // v2 is untouched by the update that made v3
+///     assert_eq!(v3.len(), 2);
+/// });
+/// ```
+pub struct GcPersistentVectorRef<'h, T: IntoHeap<'h>>(GcRef<'h, PVecRoot<T::In>>)
+where
+    T::In: Clone;
+
+impl<'h, T: IntoHeap<'h>> Clone for GcPersistentVectorRef<'h, T>
+where
+    T::In: Clone,
+{
+    fn clone(&self) -> Self {
+        GcPersistentVectorRef(self.0.clone())
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> Hash for GcPersistentVectorRef<'h, T>
+where
+    T::In: Clone,
+{
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> IntoHeapBase for GcPersistentVectorRef<'h, T>
+where
+    T::In: Clone,
+{
+    type In = Pointer<PVecRoot<T::In>>;
+
+    fn into_heap(self) -> Self::In {
+        self.0.ptr()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> GcPersistentVectorRef<'h, T> {
+        GcPersistentVectorRef(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h, T: IntoHeap<'h>> IntoHeap<'h> for GcPersistentVectorRef<'h, T> where T::In: Clone {}
+
+impl<'h, T: IntoHeap<'h>> GcPersistentVectorRef<'h, T>
+where
+    T::In: Clone,
+{
+    /// Creates a new, empty persistent vector.
+    pub fn new(hs: &mut GcHeapSession<'h>) -> GcPersistentVectorRef<'h, T> {
+        let root = hs.alloc(PVecRoot {
+            len: 0,
+            shift: 0,
+            root: None,
+        });
+        GcPersistentVectorRef(root)
+    }
+
+    unsafe fn with_storage<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b PVecRoot<T::In>) -> R,
+        'v: 'b,
+    {
+        f(&*self.0.as_ptr())
+    }
+
+    /// The number of elements in the vector.
+    pub fn len(&self) -> usize {
+        unsafe { self.with_storage(|r| r.len) }
+    }
+
+    /// Returns `true` if the vector has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a clone of the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn get(&self, index: usize) -> T {
+        unsafe {
+            self.with_storage(|r| {
+                assert!(index < r.len, "GcPersistentVector index out of bounds");
+                let mut node = r.root.expect("GcPersistentVector: non-empty but no root");
+                let mut shift = r.shift;
+                while shift > 0 {
+                    let i = (index >> shift) & PVEC_MASK;
+                    node = node.as_ref().children[i];
+                    shift -= PVEC_BITS;
+                }
+                T::from_heap(&node.as_ref().values[index & PVEC_MASK])
+            })
+        }
+    }
+
+    /// Returns a new vector with `value` appended, sharing every subtree
+    /// this operation doesn't need to touch with `self`.
+    pub fn push_back(&self, hs: &mut GcHeapSession<'h>, value: T) -> GcPersistentVectorRef<'h, T> {
+        let value = value.into_heap();
+        let (len, shift, root) = unsafe { self.with_storage(|r| (r.len, r.shift, r.root)) };
+        let (new_shift, new_root) = match root {
+            None => (0, unsafe { pvec_new_path(hs, 0, value) }),
+            Some(root) => {
+                let capacity = 1usize << (shift + PVEC_BITS);
+                if len == capacity {
+                    let new_path = unsafe { pvec_new_path(hs, shift, value) };
+                    let new_root = hs.alloc(PVecNode {
+                        values: vec![],
+                        children: vec![root, new_path.ptr()],
+                    });
+                    (shift + PVEC_BITS, new_root)
+                } else {
+                    (shift, unsafe { pvec_push_into(hs, root, shift, len, value) })
+                }
+            }
+        };
+        let new_header = hs.alloc(PVecRoot {
+            len: len + 1,
+            shift: new_shift,
+            root: Some(new_root.ptr()),
+        });
+        GcPersistentVectorRef(new_header)
+    }
+
+    /// Returns a new vector with the element at `index` replaced by
+    /// `value`, sharing every subtree this operation doesn't need to touch
+    /// with `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set(
+        &self,
+        hs: &mut GcHeapSession<'h>,
+        index: usize,
+        value: T,
+    ) -> GcPersistentVectorRef<'h, T> {
+        let (len, shift, root) = unsafe { self.with_storage(|r| (r.len, r.shift, r.root)) };
+        assert!(index < len, "GcPersistentVector index out of bounds");
+        let root = root.expect("GcPersistentVector: non-empty but no root");
+        let value = value.into_heap();
+        let new_root = unsafe { pvec_set_into(hs, root, shift, index, value) };
+        let new_header = hs.alloc(PVecRoot {
+            len,
+            shift,
+            root: Some(new_root.ptr()),
+        });
+        GcPersistentVectorRef(new_header)
+    }
+
+    /// Returns a snapshot of the vector's elements, cloned out of the heap.
+    ///
+    /// As with `VecRef::get_all`, cell-gc never hands out Rust references
+    /// into the heap, so this collects owned copies.
+    pub fn get_all(&self) -> Vec<T> {
+        (0..self.len()).map(|i| self.get(i)).collect()
+    }
+
+    /// See `GcRef::object_id`.
+    pub fn object_id(&self) -> usize {
+        self.0.object_id()
+    }
+}
+
+/// Log2 of the branching factor of a `GcPersistentMap`'s hash trie: each
+/// internal node has `PMAP_BRANCHING` (`1 << PMAP_BITS`) child slots,
+/// indexed by a 5-bit chunk of the key's hash.
+const PMAP_BITS: u32 = 5;
+const PMAP_BRANCHING: usize = 1 << PMAP_BITS;
+const PMAP_MASK: u64 = (PMAP_BRANCHING - 1) as u64;
+
+/// The trie is a fixed 13 levels deep, enough to consume every bit of a
+/// 64-bit hash (`13 * PMAP_BITS == 65 > 64`); unlike a real HAMT, depth
+/// never varies with how many keys happen to collide on a prefix, which
+/// avoids ever having to split a leaf back into an internal node.
+const PMAP_MAX_SHIFT: u32 = 13 * PMAP_BITS;
+
+fn pmap_hash<U: Hash>(value: &U) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// One node of a `GcPersistentMap`'s hash trie: either a leaf, holding
+/// every entry whose hash agrees on all `PMAP_MAX_SHIFT` bits consumed so
+/// far in `entries` (almost always exactly one, since hash collisions that
+/// deep are astronomically unlikely), or an internal node, holding
+/// `PMAP_BRANCHING` child slots in `children`, indexed by hash chunk.
+/// Which one applies is determined by the depth at which the node is
+/// reached, not stored on the node itself; exactly one of the two fields is
+/// non-empty.
+struct PMapNode<K: InHeap, V: InHeap> {
+    entries: Vec<(K, V)>,
+    children: Vec<Option<Pointer<PMapNode<K, V>>>>,
+}
+
+impl<K: InHeap, V: InHeap> InHeap for PMapNode<K, V> {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        for entry in &self.entries {
+            entry.trace(tracer);
+        }
+        for child in &self.children {
+            child.trace(tracer);
+        }
+    }
+}
+
+impl<K: InHeap + Clone, V: InHeap + Clone> IntoHeapBase for PMapNode<K, V> {
+    type In = Self;
+
+    fn into_heap(self) -> Self {
+        self
+    }
+
+    unsafe fn from_heap(storage: &Self) -> Self {
+        PMapNode {
+            entries: storage.entries.clone(),
+            children: storage.children.clone(),
+        }
+    }
+}
+
+unsafe impl<'h, K: InHeap + Clone, V: InHeap + Clone> IntoHeap<'h> for PMapNode<K, V> {}
+
+impl<'h, K: InHeap + Clone, V: InHeap + Clone> IntoHeapAllocation<'h> for PMapNode<K, V> {
+    type Ref = GcRef<'h, PMapNode<K, V>>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, PMapNode<K, V>>) -> GcRef<'h, PMapNode<K, V>> {
+        gc_ref
+    }
+
+    fn into_gc_ref(wrapped_ref: GcRef<'h, PMapNode<K, V>>) -> GcRef<'h, PMapNode<K, V>> {
+        wrapped_ref
+    }
+}
+
+/// The in-heap header of a `GcPersistentMap`: its length and its root node.
+///
+/// This type does double duty, like `GcString`: it's both the pre-heap
+/// value and its own in-heap storage. A fresh header is allocated on every
+/// `insert`, since that never modifies a `GcPersistentMap` in place. Unlike
+/// `PVecRoot`, the root here is never absent, even when the map is empty:
+/// the trie's depth is fixed, so an empty map is just a root node with
+/// every child slot empty.
+pub struct PMapRootHeader<K: InHeap, V: InHeap> {
+    len: usize,
+    root: Pointer<PMapNode<K, V>>,
+}
+
+impl<K: InHeap, V: InHeap> InHeap for PMapRootHeader<K, V> {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        self.root.trace(tracer);
+    }
+}
+
+impl<K: InHeap, V: InHeap> IntoHeapBase for PMapRootHeader<K, V> {
+    type In = Self;
+
+    fn into_heap(self) -> Self {
+        self
+    }
+
+    unsafe fn from_heap(storage: &Self) -> Self {
+        PMapRootHeader {
+            len: storage.len,
+            root: storage.root,
+        }
+    }
+}
+
+unsafe impl<'h, K: InHeap, V: InHeap> IntoHeap<'h> for PMapRootHeader<K, V> {}
+
+impl<'h, K: InHeap, V: InHeap> IntoHeapAllocation<'h> for PMapRootHeader<K, V> {
+    type Ref = GcRef<'h, PMapRootHeader<K, V>>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, PMapRootHeader<K, V>>) -> GcRef<'h, PMapRootHeader<K, V>> {
+        gc_ref
+    }
+
+    fn into_gc_ref(wrapped_ref: GcRef<'h, PMapRootHeader<K, V>>) -> GcRef<'h, PMapRootHeader<K, V>> {
+        wrapped_ref
+    }
+}
+
+/// Builds a fresh minimal chain of nodes from `shift` down to
+/// `PMAP_MAX_SHIFT`, indexed by the corresponding chunks of `hash`, holding
+/// a single entry, `(key, value)`.
+///
+/// Returns a `GcRef`, not a bare `Pointer`: a node built partway through
+/// this recursion isn't reachable from anything the collector treats as a
+/// root yet (it's not linked into `self`'s trie until the caller stores it
+/// there), so it would be free to collect out from under a later `hs.alloc`
+/// call in the same chain if nothing kept it pinned in the meantime. Every
+/// `GcRef` local below stays alive --- and so keeps its node pinned --- for
+/// as long as its subtree isn't yet wrapped in the node that will make it
+/// reachable, which is exactly why `.ptr()` (which doesn't drop the
+/// `GcRef`, unlike consuming it) only ever gets called at the point of
+/// writing a child slot.
+unsafe fn pmap_new_path<'h, K: InHeap + Clone, V: InHeap + Clone>(
+    hs: &mut GcHeapSession<'h>,
+    hash: u64,
+    shift: u32,
+    key: K,
+    value: V,
+) -> GcRef<'h, PMapNode<K, V>> {
+    if shift >= PMAP_MAX_SHIFT {
+        hs.alloc(PMapNode {
+            entries: vec![(key, value)],
+            children: vec![],
+        })
+    } else {
+        let idx = ((hash >> shift) & PMAP_MASK) as usize;
+        let child = pmap_new_path(hs, hash, shift + PMAP_BITS, key, value);
+        let mut children = vec![None; PMAP_BRANCHING];
+        children[idx] = Some(child.ptr());
+        hs.alloc(PMapNode {
+            entries: vec![],
+            children,
+        })
+    }
+}
+
+/// Path-copies the nodes from `node` down to the entry for `key`, inserting
+/// or overwriting it with `value`. Returns the new node and whether `key`
+/// wasn't already present (i.e. whether the map's length grew).
+///
+/// Returns a `GcRef` for the same reason `pmap_new_path` does; see its docs.
+unsafe fn pmap_insert_into<'h, K: InHeap + Eq + Clone, V: InHeap + Clone>(
+    hs: &mut GcHeapSession<'h>,
+    node: Pointer<PMapNode<K, V>>,
+    hash: u64,
+    shift: u32,
+    key: K,
+    value: V,
+) -> (GcRef<'h, PMapNode<K, V>>, bool) {
+    if shift >= PMAP_MAX_SHIFT {
+        let mut entries = node.as_ref().entries.clone();
+        match entries.iter().position(|&(ref k, _)| *k == key) {
+            Some(pos) => {
+                entries[pos] = (key, value);
+                (
+                    hs.alloc(PMapNode {
+                        entries,
+                        children: vec![],
+                    }),
+                    false,
+                )
+            }
+            None => {
+                entries.push((key, value));
+                (
+                    hs.alloc(PMapNode {
+                        entries,
+                        children: vec![],
+                    }),
+                    true,
+                )
+            }
+        }
+    } else {
+        let idx = ((hash >> shift) & PMAP_MASK) as usize;
+        let mut children = node.as_ref().children.clone();
+        // Kept as a `GcRef` (not unwrapped to a bare `Pointer` here) so it
+        // stays pinned through the `hs.alloc` below, which is what actually
+        // links it into a reachable node; see this function's docs.
+        let (new_child, inserted) = match children[idx] {
+            Some(child) => pmap_insert_into(hs, child, hash, shift + PMAP_BITS, key, value),
+            None => (
+                pmap_new_path(hs, hash, shift + PMAP_BITS, key, value),
+                true,
+            ),
+        };
+        children[idx] = Some(new_child.ptr());
+        (
+            hs.alloc(PMapNode {
+                entries: vec![],
+                children,
+            }),
+            inserted,
+        )
+    }
+}
+
+/// A persistent (immutable, structurally-shared) hash map: a hash trie
+/// with a fixed depth, in the spirit of Clojure's `PersistentHashMap`,
+/// though without that structure's bitmap-compressed nodes.
+///
+/// `insert` doesn't modify a `GcPersistentMap` in place; it returns a
+/// brand new `GcPersistentMapRef` for the updated map, which shares as
+/// much of the old trie as possible with the original. Along with
+/// `GcPersistentVectorRef`, this is the natural backing store for
+/// Clojure-style persistent data structures: since the GC traces shared
+/// subtries from every version that references them, holding on to an old
+/// version is always safe.
+///
+/// As with `GcPersistentVectorRef`, construct an empty one with
+/// `GcPersistentMapRef::new`, and thread `hs` through `insert`:
+///
+/// ```rust
+/// use cell_gc::collections::GcPersistentMapRef;
+///
+/// cell_gc::with_heap(|hs| {
+///     let m0: GcPersistentMapRef<String, i32> = GcPersistentMapRef::new(hs);
+///     let m1 = m0.insert(hs, "a".to_string(), 1);
+///     let m2 = m1.insert(hs, "b".to_string(), 2);
+///
+///     assert_eq!(m2.get("a".to_string()), Some(1));
+///     assert_eq!(m2.get("b".to_string()), Some(2));
+///     assert_eq!(m1.get("b".to_string()), None); // m1 is untouched by the insert that made m2
+///     assert_eq!(m2.len(), 2);
+/// });
+/// ```
+pub struct GcPersistentMapRef<'h, K, V>(GcRef<'h, PMapRootHeader<K::In, V::In>>)
+where
+    K: IntoHeap<'h>,
+    K::In: Eq + Hash + Clone,
+    V: IntoHeap<'h>,
+    V::In: Clone;
+
+impl<'h, K, V> Clone for GcPersistentMapRef<'h, K, V>
+where
+    K: IntoHeap<'h>,
+    K::In: Eq + Hash + Clone,
+    V: IntoHeap<'h>,
+    V::In: Clone,
+{
+    fn clone(&self) -> Self {
+        GcPersistentMapRef(self.0.clone())
+    }
+}
+
+impl<'h, K, V> Hash for GcPersistentMapRef<'h, K, V>
+where
+    K: IntoHeap<'h>,
+    K::In: Eq + Hash + Clone,
+    V: IntoHeap<'h>,
+    V::In: Clone,
+{
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h, K, V> IntoHeapBase for GcPersistentMapRef<'h, K, V>
+where
+    K: IntoHeap<'h>,
+    K::In: Eq + Hash + Clone,
+    V: IntoHeap<'h>,
+    V::In: Clone,
+{
+    type In = Pointer<PMapRootHeader<K::In, V::In>>;
+
+    fn into_heap(self) -> Self::In {
+        self.0.ptr()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> GcPersistentMapRef<'h, K, V> {
+        GcPersistentMapRef(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h, K, V> IntoHeap<'h> for GcPersistentMapRef<'h, K, V>
+where
+    K: IntoHeap<'h>,
+    K::In: Eq + Hash + Clone,
+    V: IntoHeap<'h>,
+    V::In: Clone,
+{
+}
+
+impl<'h, K, V> GcPersistentMapRef<'h, K, V>
+where
+    K: IntoHeap<'h>,
+    K::In: Eq + Hash + Clone,
+    V: IntoHeap<'h>,
+    V::In: Clone,
+{
+    /// Creates a new, empty persistent map.
+    pub fn new(hs: &mut GcHeapSession<'h>) -> GcPersistentMapRef<'h, K, V> {
+        let root = hs.alloc(PMapNode {
+            entries: vec![],
+            children: vec![None; PMAP_BRANCHING],
+        }).ptr();
+        let header = hs.alloc(PMapRootHeader { len: 0, root });
+        GcPersistentMapRef(header)
+    }
+
+    unsafe fn with_storage<'v, 'b, R, F>(&'v self, f: F) -> R
+    where
+        F: FnOnce(&'b PMapRootHeader<K::In, V::In>) -> R,
+        'v: 'b,
+    {
+        f(&*self.0.as_ptr())
+    }
+
+    /// The number of key-value pairs in the map.
+    pub fn len(&self) -> usize {
+        unsafe { self.with_storage(|r| r.len) }
+    }
+
+    /// Returns `true` if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a clone of the value associated with `key`, if any.
+    pub fn get(&self, key: K) -> Option<V> {
+        let key = key.into_heap();
+        let hash = pmap_hash(&key);
+        unsafe {
+            self.with_storage(|r| {
+                let mut node = r.root;
+                let mut shift = 0;
+                loop {
+                    let node_ref = node.as_ref();
+                    if shift >= PMAP_MAX_SHIFT {
+                        return node_ref
+                            .entries
+                            .iter()
+                            .find(|&&(ref k, _)| *k == key)
+                            .map(|&(_, ref v)| V::from_heap(v));
+                    }
+                    let idx = ((hash >> shift) & PMAP_MASK) as usize;
+                    match node_ref.children[idx] {
+                        Some(child) => {
+                            node = child;
+                            shift += PMAP_BITS;
+                        }
+                        None => return None,
+                    }
+                }
+            })
+        }
+    }
+
+    /// Returns `true` if the map contains a value for `key`.
+    pub fn contains_key(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns a new map with `key` mapped to `value`, sharing every
+    /// subtrie this operation doesn't need to touch with `self`.
+    pub fn insert(&self, hs: &mut GcHeapSession<'h>, key: K, value: V) -> GcPersistentMapRef<'h, K, V> {
+        let key = key.into_heap();
+        let value = value.into_heap();
+        let hash = pmap_hash(&key);
+        let (len, root) = unsafe { self.with_storage(|r| (r.len, r.root)) };
+        let (new_root, inserted) = unsafe { pmap_insert_into(hs, root, hash, 0, key, value) };
+        let new_header = hs.alloc(PMapRootHeader {
+            len: if inserted { len + 1 } else { len },
+            root: new_root.ptr(),
+        });
+        GcPersistentMapRef(new_header)
+    }
+
+    /// See `GcRef::object_id`.
+    pub fn object_id(&self) -> usize {
+        self.0.object_id()
+    }
+}
@@ -0,0 +1,69 @@
+//! Debugging helpers for visualizing what's in a heap. See `dump_dot`.
+
+use dump;
+use heap::GcHeap;
+use std::fmt;
+use std::io::{self, Write};
+use traits::IntoHeapAllocation;
+
+/// Write a Graphviz DOT graph of every live object in `heap` and the edges
+/// between them, to `writer`. Nodes are labeled with their Rust type name.
+/// Render it with, e.g., `dot -Tsvg dump.dot -o dump.svg`.
+///
+/// See `dump_dot_with_summary` to add a second label line per node, e.g.
+/// summarizing the value stored there.
+pub fn dump_dot<W: Write>(heap: &GcHeap, writer: W) -> io::Result<()> {
+    dump::dump_dot(heap, writer, |_id| None)
+}
+
+/// Like `dump_dot`, but `summarize` is called with each live object's id
+/// (see `GcHeap::dump` for what "id" means) and, if it returns `Some`, adds
+/// that string as a second label line for that node.
+pub fn dump_dot_with_summary<W, F>(heap: &GcHeap, writer: W, summarize: F) -> io::Result<()>
+where
+    W: Write,
+    F: FnMut(usize) -> Option<String>,
+{
+    dump::dump_dot(heap, writer, summarize)
+}
+
+/// Write the object graph reachable from `root` to `f`, one line per
+/// distinct object it can reach (including itself), safely handling the
+/// cyclic structures a plain recursive `Debug` impl would loop forever on:
+/// each object gets a `#N` back-reference number the first time it's seen
+/// and is never re-expanded, so a self-referential or mutually-referential
+/// structure prints its cycle once and stops instead of recursing forever.
+///
+/// A derived `Ref` type's own `#[derive(Debug)]` impl only ever prints its
+/// address (see `GcRef`'s `Debug` impl for why: it has no way to know at
+/// compile time whether descending into its fields is safe from a cycle),
+/// and can't be replaced --- a second `impl Debug for PairRef` alongside the
+/// derived one is a conflicting-impl error. Reach for `fmt_graph` from a
+/// separate wrapper type instead, when a value's fields --- not just its
+/// address --- are what you want to see, e.g. in a `{:?}` used only for
+/// diagnostics:
+///
+/// ```ignore
+/// struct DebugPair<'a, 'h>(&'a PairRef<'h>);
+///
+/// impl<'a, 'h> fmt::Debug for DebugPair<'a, 'h> {
+///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///         cell_gc::debug::fmt_graph::<Pair>(self.0, f)
+///     }
+/// }
+///
+/// println!("{:?}", DebugPair(&root));
+/// ```
+///
+/// Nodes are identified by Rust type name only, the same information
+/// `dump` and `dump_dot` report; `GcHeap::types` has no field-level detail
+/// either, since the macro that implements `IntoHeap` doesn't preserve
+/// field names at runtime.
+pub fn fmt_graph<'h, T>(root: &T::Ref, f: &mut fmt::Formatter) -> fmt::Result
+where
+    T: IntoHeapAllocation<'h>,
+    T::Ref: Clone,
+{
+    let ptr = T::into_gc_ref(root.clone()).ptr().into();
+    dump::fmt_graph(ptr, f)
+}
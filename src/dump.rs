@@ -0,0 +1,208 @@
+//! Serializing the heap's object graph for offline analysis. See
+//! `GcHeap::dump`.
+
+use heap::GcHeap;
+use pages::PageHeader;
+use ptr::{Pointer, UntypedPointer};
+use std::any::type_name;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+use traits::{InHeap, Tracer};
+
+/// A `Tracer` that records the addresses of an object's outgoing edges
+/// instead of marking them. As `traits::Tracer`'s docs note, a heap-snapshot
+/// tracer is exactly the kind of thing this trait was designed to support
+/// alongside `MarkingTracer`.
+struct EdgeCollector {
+    edges: Vec<usize>,
+}
+
+impl Tracer for EdgeCollector {
+    fn visit<U: InHeap>(&mut self, ptr: Pointer<U>) {
+        self.edges.push(ptr.as_usize());
+    }
+
+    fn visit_untyped(&mut self, ptr: UntypedPointer) {
+        // Same as `visit` above: just record the edge. The target itself
+        // gets its own entry (and its own outgoing edges dumped) when
+        // `collect_live_objects` reaches it in the ordinary sweep over
+        // every live object, so there's nothing further to do here.
+        self.edges.push(ptr.as_usize());
+    }
+}
+
+/// Get `U`'s type name and the addresses `ptr`'s referent points at.
+/// Monomorphized per allocation type and stored in `PageHeader::dump_fn`,
+/// the same type-erased dispatch trick `PageHeader::mark_fn` uses.
+pub(crate) unsafe fn dump_entry_point<U: InHeap>(ptr: UntypedPointer) -> (&'static str, Vec<usize>) {
+    let mut collector = EdgeCollector { edges: Vec::new() };
+    ptr.as_typed_ptr::<U>().as_ref().trace(&mut collector);
+    (type_name::<U>(), collector.edges)
+}
+
+/// One node in a heap dump: everything `dump` knows about a single live
+/// object.
+struct DumpedObject {
+    id: usize,
+    type_name: &'static str,
+    size: usize,
+    edges: Vec<usize>,
+}
+
+/// Walk every live object in `heap` and collect its dump-relevant fields, in
+/// no particular order. Shared by `dump` and `dump_dot`.
+fn collect_live_objects(heap: &GcHeap) -> Vec<DumpedObject> {
+    let mut objects = Vec::new();
+    heap.for_each_live_object(|ptr, page| {
+        let (type_name, edges) = unsafe { page.dump(ptr) };
+        objects.push(DumpedObject {
+            id: ptr.as_usize(),
+            type_name,
+            size: page.allocation_size(),
+            edges,
+        });
+    });
+    objects
+}
+
+/// Write a heap dump to `writer`: a JSON array with one object per live
+/// allocation, giving its id (its address — stable only for this run, and
+/// only useful as a label to match against the `edges` of other objects in
+/// the same dump), Rust type name, size in bytes, and the ids of the objects
+/// it directly references.
+///
+/// This only covers cell-gc's own pages; a `Vec` or `Box` field's backing
+/// buffer is not itself a separate node, since it isn't one from cell-gc's
+/// point of view either (see the "no large-object space" note in the `pages`
+/// module).
+pub(crate) fn dump<W: Write>(heap: &GcHeap, mut writer: W) -> io::Result<()> {
+    let objects = collect_live_objects(heap);
+
+    writeln!(writer, "[")?;
+    for (i, obj) in objects.iter().enumerate() {
+        write!(
+            writer,
+            "  {{\"id\": {}, \"type\": {}, \"size\": {}, \"edges\": [",
+            obj.id,
+            json_string(obj.type_name),
+            obj.size
+        )?;
+        for (j, edge) in obj.edges.iter().enumerate() {
+            if j > 0 {
+                write!(writer, ", ")?;
+            }
+            write!(writer, "{}", edge)?;
+        }
+        write!(writer, "]}}")?;
+        writeln!(writer, "{}", if i + 1 < objects.len() { "," } else { "" })?;
+    }
+    writeln!(writer, "]")
+}
+
+/// Minimal JSON string escaping. Rust type names can contain `"` (string
+/// literals in const generics) but never control characters, so this is all
+/// that's needed.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Write a Graphviz DOT graph of every live object in `heap` and the edges
+/// between them, to `writer`. Nodes are labeled with their Rust type name
+/// and, if `summarize` returns `Some` for a given object's id, a second
+/// label line with that description. See `debug::dump_dot`.
+pub(crate) fn dump_dot<W, F>(heap: &GcHeap, mut writer: W, mut summarize: F) -> io::Result<()>
+where
+    W: Write,
+    F: FnMut(usize) -> Option<String>,
+{
+    let objects = collect_live_objects(heap);
+
+    writeln!(writer, "digraph heap {{")?;
+    writeln!(writer, "  node [shape=box, fontname=\"monospace\"];")?;
+    for obj in &objects {
+        let mut label = obj.type_name.to_string();
+        if let Some(summary) = summarize(obj.id) {
+            label.push('\n');
+            label.push_str(&summary);
+        }
+        writeln!(writer, "  {} [label=\"{}\"];", obj.id, dot_escape(&label))?;
+    }
+    for obj in &objects {
+        for &edge in &obj.edges {
+            writeln!(writer, "  {} -> {};", obj.id, edge)?;
+        }
+    }
+    writeln!(writer, "}}")
+}
+
+/// Write the object graph reachable from `root` as one line per distinct
+/// object: `#N = TypeName -> [#M, #K, ...]`, where `N`, `M`, `K` are
+/// back-reference numbers assigned the first time each object is seen, in
+/// breadth-first order starting from `root` at `#0`. See `debug::fmt_graph`,
+/// the public, per-type entry point this is meant to be called through.
+///
+/// Every object is visited (and its own edges expanded into the queue) at
+/// most once, however many other objects point at it, so this terminates
+/// and prints something useful even when `root` is part of a cycle --- the
+/// entire reason a cycle-aware printer is worth having for this crate.
+pub(crate) fn fmt_graph(root: UntypedPointer, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut indices = HashMap::new();
+    let mut queue = VecDeque::new();
+    indices.insert(root.as_usize(), 0usize);
+    queue.push_back(root);
+    let mut next_index = 1;
+
+    let mut first = true;
+    while let Some(ptr) = queue.pop_front() {
+        let index = indices[&ptr.as_usize()];
+        let (type_name, edges) = unsafe { (*PageHeader::find(ptr)).dump(ptr) };
+
+        if !first {
+            writeln!(f)?;
+        }
+        first = false;
+        write!(f, "#{} = {} -> [", index, type_name)?;
+        for (i, &edge) in edges.iter().enumerate() {
+            let edge_index = *indices.entry(edge).or_insert_with(|| {
+                let i = next_index;
+                next_index += 1;
+                queue.push_back(unsafe { UntypedPointer::new(edge as *const ()) });
+                i
+            });
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "#{}", edge_index)?;
+        }
+        write!(f, "]")?;
+    }
+    Ok(())
+}
+
+/// Minimal DOT string escaping for use inside a `label="..."` attribute:
+/// quotes and backslashes need escaping, and embedded newlines need to
+/// become the literal two-character sequence `\n` DOT expects for a
+/// multi-line label.
+fn dot_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
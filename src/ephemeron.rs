@@ -0,0 +1,170 @@
+//! Key-dependent weak references.
+
+use gc_ref::GcRef;
+use ptr::Pointer;
+use std::cell::Cell;
+use std::hash::{Hash, Hasher};
+use traits::{InHeap, IntoHeap, IntoHeapAllocation, IntoHeapBase, Tracer};
+
+/// A GC-heap value pairing a `key` with a `value` that's only kept alive as
+/// long as `key` is reachable some other way.
+///
+/// This is what Scheme calls a "weak pair", and it's the building block for
+/// weak hash tables and property tables: a plain weak reference to the key,
+/// plus a strong reference to the value, would keep the value (and anything
+/// reachable from it) alive forever even after the key is gone, which is
+/// exactly the leak an ephemeron is for avoiding.
+///
+/// Both `key` and `value` must themselves be separately-heap-allocated
+/// objects (any `IntoHeapAllocation` type, i.e. something `heap.alloc`
+/// returns a `Ref` to), since resolving an ephemeron works by checking the
+/// mark bit of the key's own allocation. To pair a key with something that
+/// isn't already its own allocation, wrap it in a `#[derive(IntoHeap)]`
+/// struct first, the same way you'd use a `Box` or a separate `Vec`
+/// allocation for anything too bulky to embed directly (see the "no
+/// large-object space" note in the `pages` module).
+///
+/// Use `GcHeapSession::alloc` to allocate an `Ephemeron` in the heap; it
+/// returns an `EphemeronRef`.
+pub struct Ephemeron<'h, K: IntoHeapAllocation<'h>, V: IntoHeapAllocation<'h>> {
+    key: GcRef<'h, K>,
+    value: GcRef<'h, V>,
+}
+
+impl<'h, K: IntoHeapAllocation<'h>, V: IntoHeapAllocation<'h>> Ephemeron<'h, K, V> {
+    /// Pair `key` with `value`. Once allocated, `value` is retained only as
+    /// long as `key` is reachable through some other reference; see
+    /// `EphemeronRef::get_value`.
+    pub fn new(key: GcRef<'h, K>, value: GcRef<'h, V>) -> Ephemeron<'h, K, V> {
+        Ephemeron {
+            key: key,
+            value: value,
+        }
+    }
+}
+
+/// In-heap storage for an `Ephemeron<K, V>`.
+///
+/// `value` is a `Cell` (this being cell-gc, naturally) because the marking
+/// tracer needs to null it out through a shared `&self` when it determines,
+/// partway through a collection, that `key` didn't survive.
+pub struct EphemeronStorage<K: InHeap, V: InHeap> {
+    key: Pointer<K>,
+    value: Cell<Option<Pointer<V>>>,
+}
+
+impl<K: InHeap, V: InHeap> InHeap for EphemeronStorage<K, V> {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        if let Some(value_ptr) = self.value.get() {
+            tracer.visit_ephemeron(self.key, value_ptr, &self.value);
+        }
+    }
+}
+
+impl<'h, K, V> IntoHeapBase for Ephemeron<'h, K, V>
+where
+    K: IntoHeapAllocation<'h>,
+    V: IntoHeapAllocation<'h>,
+{
+    type In = EphemeronStorage<K::In, V::In>;
+
+    fn into_heap(self) -> Self::In {
+        EphemeronStorage {
+            key: self.key.into_heap(),
+            value: Cell::new(Some(self.value.into_heap())),
+        }
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> Ephemeron<'h, K, V> {
+        Ephemeron {
+            key: GcRef::new(storage.key),
+            value: GcRef::new(storage.value.get().expect(
+                "Ephemeron::from_heap: value already cleared by GC; check \
+                 EphemeronRef::get_value() before assuming the value is there",
+            )),
+        }
+    }
+}
+
+unsafe impl<'h, K, V> IntoHeap<'h> for Ephemeron<'h, K, V>
+where
+    K: IntoHeapAllocation<'h>,
+    V: IntoHeapAllocation<'h>,
+{
+}
+
+impl<'h, K, V> IntoHeapAllocation<'h> for Ephemeron<'h, K, V>
+where
+    K: IntoHeapAllocation<'h>,
+    V: IntoHeapAllocation<'h>,
+{
+    type Ref = EphemeronRef<'h, K, V>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, Ephemeron<'h, K, V>>) -> EphemeronRef<'h, K, V> {
+        EphemeronRef(gc_ref)
+    }
+
+    fn into_gc_ref(wrapped_ref: EphemeronRef<'h, K, V>) -> GcRef<'h, Ephemeron<'h, K, V>> {
+        wrapped_ref.0
+    }
+}
+
+/// A reference to a GC-heap-allocated `Ephemeron<K, V>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EphemeronRef<'h, K: IntoHeapAllocation<'h>, V: IntoHeapAllocation<'h>>(
+    GcRef<'h, Ephemeron<'h, K, V>>,
+);
+
+impl<'h, K: IntoHeapAllocation<'h>, V: IntoHeapAllocation<'h>> Hash for EphemeronRef<'h, K, V> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h, K, V> IntoHeapBase for EphemeronRef<'h, K, V>
+where
+    K: IntoHeapAllocation<'h>,
+    V: IntoHeapAllocation<'h>,
+{
+    type In = Pointer<EphemeronStorage<K::In, V::In>>;
+
+    fn into_heap(self) -> Self::In {
+        self.0.ptr()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> EphemeronRef<'h, K, V> {
+        EphemeronRef(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h, K, V> IntoHeap<'h> for EphemeronRef<'h, K, V>
+where
+    K: IntoHeapAllocation<'h>,
+    V: IntoHeapAllocation<'h>,
+{
+}
+
+impl<'h, K: IntoHeapAllocation<'h>, V: IntoHeapAllocation<'h>> EphemeronRef<'h, K, V> {
+    /// The ephemeron's key. This is a strong reference: holding an
+    /// `EphemeronRef` alive doesn't pin the key, but calling this method and
+    /// keeping the result around does.
+    pub fn key(&self) -> GcRef<'h, K> {
+        unsafe {
+            let storage = &*self.0.as_ptr();
+            GcRef::new(storage.key)
+        }
+    }
+
+    /// The ephemeron's value, if `key` is still reachable other than
+    /// through this ephemeron. Once a collection determines the key is
+    /// unreachable, this permanently returns `None`; the value is never
+    /// resurrected even if something else happens to allocate a new object
+    /// at the key's old address.
+    pub fn get_value(&self) -> Option<GcRef<'h, V>> {
+        unsafe {
+            let storage = &*self.0.as_ptr();
+            storage.value.get().map(|ptr| GcRef::new(ptr))
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! A handle table for holding GC references from across a C ABI boundary,
+//! motivated by embedding a cell-gc-based interpreter inside a C host: the
+//! host has no safe way to hold a pointer into the GC heap between calls,
+//! since it can't be scanned as a root and a collection could move or free
+//! whatever it points at.
+//!
+//! `ExternalHandle` is a `u64` token, cheap to pass by value across an FFI
+//! boundary, standing in for a type-erased, pinned `GcAny`. This module
+//! only provides the safe Rust-level `create`/`resolve`/`release` API;
+//! wrapping them in `#[no_mangle] extern "C" fn`s is left to the embedder,
+//! since only it knows the concrete `IntoHeapAllocation` types and calling
+//! convention it wants to expose.
+
+use gc_any::GcAny;
+use gc_ref::GcRef;
+use std::collections::HashMap;
+use traits::IntoHeapAllocation;
+
+/// A `u64` token referencing a rooted, type-erased heap object, safe to
+/// hand across a C ABI boundary in place of a pointer into the GC heap.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct ExternalHandle(u64);
+
+/// A table of type-erased objects, each kept alive (via `GcAny`'s pin, the
+/// same mechanism a live `GcRef` uses) for as long as its `ExternalHandle`
+/// is in the table.
+pub struct ExternalHandleTable<'h> {
+    next: u64,
+    entries: HashMap<u64, GcAny<'h>>,
+}
+
+impl<'h> ExternalHandleTable<'h> {
+    /// Create an empty handle table.
+    pub fn new() -> ExternalHandleTable<'h> {
+        ExternalHandleTable {
+            next: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Root `value`, type-erased, and return a handle for it, valid until
+    /// it's passed to `release`.
+    pub fn create<T: IntoHeapAllocation<'h>>(&mut self, value: GcRef<'h, T>) -> ExternalHandle {
+        let id = self.next;
+        self.next = self.next
+            .checked_add(1)
+            .expect("cell-gc: ExternalHandleTable ran out of handles");
+        self.entries.insert(id, GcAny::new(value));
+        ExternalHandle(id)
+    }
+
+    /// Get the type-erased object behind `handle`, or `None` if it's been
+    /// released (or never existed in this table). Use `GcAny::downcast` to
+    /// recover a concrete `Ref`.
+    pub fn resolve(&self, handle: ExternalHandle) -> Option<GcAny<'h>> {
+        self.entries.get(&handle.0).cloned()
+    }
+
+    /// Stop rooting the object behind `handle`. Releasing an unknown or
+    /// already-released handle is a no-op.
+    pub fn release(&mut self, handle: ExternalHandle) {
+        self.entries.remove(&handle.0);
+    }
+}
+
+impl<'h> Default for ExternalHandleTable<'h> {
+    fn default() -> Self {
+        ExternalHandleTable::new()
+    }
+}
@@ -0,0 +1,128 @@
+//! GC-managed values with a real destructor, for objects that wrap
+//! non-heap resources (file handles, textures, and the like).
+//!
+//! You can't implement `Drop` for a `#[derive(IntoHeap)]` struct (see the
+//! crate-level docs); the type gets copied into and out of the heap as part
+//! of the ordinary `into_heap`/`from_heap` dance, so `Drop` would run at
+//! times that make no sense. `Finalized<T>` sidesteps that: it stores `T`
+//! directly in the heap, untouched, and only ever hands out borrows of it
+//! (via `FinalizedRef::with`/`with_mut`), so `T::drop` runs exactly once,
+//! when the GC sweeps the (by then provably unreachable) allocation.
+use gc_ref::GcRef;
+use ptr::Pointer;
+use std::any::Any;
+use std::hash::{Hash, Hasher};
+use traits::{InHeap, IntoHeap, IntoHeapAllocation, IntoHeapBase, Tracer};
+
+/// A heap-allocated value with a real `Drop` impl, run when the GC sweeps it.
+///
+/// `T` must not itself contain any `GcRef`s or other heap references:
+/// `Finalized` doesn't trace into `T`, since by the time `T::drop` runs, the
+/// rest of the heap may already be mid-sweep. If you need a finalizer that
+/// also holds onto other GC objects, keep their identity (not a `GcRef`) in
+/// `T` and look them up again some other way.
+///
+/// Use `heap.alloc(Finalized::new(value))` to allocate one; it returns a
+/// `FinalizedRef<T>`.
+pub struct Finalized<T: Any> {
+    value: T,
+}
+
+impl<T: Any> Finalized<T> {
+    /// Wrap `value` so its `Drop` impl runs when the GC collects it.
+    pub fn new(value: T) -> Finalized<T> {
+        Finalized { value: value }
+    }
+}
+
+impl<T: Any> InHeap for Finalized<T> {
+    unsafe fn trace<R: Tracer>(&self, _tracer: &mut R) {}
+}
+
+impl<T: Any> IntoHeapBase for Finalized<T> {
+    type In = Finalized<T>;
+
+    fn into_heap(self) -> Finalized<T> {
+        self
+    }
+
+    /// Never actually called: `Finalized<T>` can only be used as a
+    /// top-level allocation (via `heap.alloc`), never as a field of another
+    /// `IntoHeap` type, so nothing ever needs to copy one back out of the
+    /// heap. Use `FinalizedRef` as the field type instead, the same way
+    /// `VecRef` (not `Vec`) is what you embed in a struct.
+    unsafe fn from_heap(_storage: &Finalized<T>) -> Finalized<T> {
+        unreachable!("Finalized<T> should never be read out of the heap; use FinalizedRef instead")
+    }
+}
+
+unsafe impl<'h, T: Any> IntoHeap<'h> for Finalized<T> {}
+
+impl<'h, T: Any> IntoHeapAllocation<'h> for Finalized<T> {
+    type Ref = FinalizedRef<'h, T>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, Finalized<T>>) -> FinalizedRef<'h, T> {
+        FinalizedRef(gc_ref)
+    }
+
+    fn into_gc_ref(wrapped_ref: FinalizedRef<'h, T>) -> GcRef<'h, Finalized<T>> {
+        wrapped_ref.0
+    }
+}
+
+/// A reference to a GC-heap-allocated `Finalized<T>`.
+///
+/// # Safety
+///
+/// `with`/`with_mut` hand out direct access to `T`. Don't stash a `&T` or
+/// `&mut T` anywhere that could outlive the closure; nothing stops a
+/// collection (and thus `T::drop`) from running as soon as the closure
+/// returns.
+pub struct FinalizedRef<'h, T: Any>(GcRef<'h, Finalized<T>>);
+
+impl<'h, T: Any> Clone for FinalizedRef<'h, T> {
+    fn clone(&self) -> FinalizedRef<'h, T> {
+        FinalizedRef(self.0.clone())
+    }
+}
+
+impl<'h, T: Any> PartialEq for FinalizedRef<'h, T> {
+    fn eq(&self, other: &FinalizedRef<'h, T>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<'h, T: Any> Eq for FinalizedRef<'h, T> {}
+
+impl<'h, T: Any> Hash for FinalizedRef<'h, T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h, T: Any> IntoHeapBase for FinalizedRef<'h, T> {
+    type In = Pointer<Finalized<T>>;
+
+    fn into_heap(self) -> Self::In {
+        self.0.ptr()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> FinalizedRef<'h, T> {
+        FinalizedRef(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h, T: Any> IntoHeap<'h> for FinalizedRef<'h, T> {}
+
+impl<'h, T: Any> FinalizedRef<'h, T> {
+    /// Run `f` with a shared borrow of the finalizable value.
+    pub fn with<R, F: FnOnce(&T) -> R>(&self, f: F) -> R {
+        unsafe { f(&(*self.0.as_ptr()).value) }
+    }
+
+    /// Run `f` with a mutable borrow of the finalizable value.
+    pub fn with_mut<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        unsafe { f(&mut (*self.0.as_mut_ptr()).value) }
+    }
+}
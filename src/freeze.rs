@@ -0,0 +1,104 @@
+//! Freezing a reachable object graph into a permanent generation. See
+//! `GcHeapSession::freeze_reachable`.
+
+use pages::{self, PageHeader};
+use ptr::{Pointer, UntypedPointer};
+use traits::{InHeap, Tracer};
+
+/// Number of direct recursive `trace` calls to make before switching to an
+/// explicit stack, mirroring `MarkingTracer`'s fuel mechanism (see its
+/// docs) so freezing a long linked list can't blow the stack.
+const FREEZE_FUEL: usize = 100;
+
+/// A `Tracer` that marks every object it visits as frozen (see
+/// `pages::freeze`) instead of marking it for the current collection.
+/// Already-frozen objects are skipped: that stops the walk at the edge of
+/// a previously frozen closure, and it doubles as this walk's own
+/// cycle/already-visited check.
+pub(crate) struct FreezeTracer {
+    fuel: usize,
+    stack: Vec<UntypedPointer>,
+}
+
+impl FreezeTracer {
+    fn freeze_to_fix_point(&mut self) {
+        while let Some(ptr) = self.stack.pop() {
+            unsafe {
+                (*PageHeader::find(ptr)).freeze(ptr, self);
+            }
+        }
+    }
+}
+
+impl Tracer for FreezeTracer {
+    fn visit<U: InHeap>(&mut self, ptr: Pointer<U>) {
+        if unsafe { pages::is_frozen(ptr) } {
+            return;
+        }
+        unsafe {
+            pages::freeze(ptr);
+        }
+
+        if self.fuel == 0 {
+            self.stack.push(ptr.into());
+            return;
+        }
+
+        self.fuel -= 1;
+        unsafe {
+            ptr.as_ref().trace(self);
+        }
+        self.fuel += 1;
+    }
+
+    fn visit_untyped(&mut self, ptr: UntypedPointer) {
+        if unsafe { pages::is_frozen_untyped(ptr) } {
+            return;
+        }
+        unsafe {
+            pages::freeze_untyped(ptr);
+        }
+
+        if self.fuel == 0 {
+            self.stack.push(ptr);
+            return;
+        }
+
+        self.fuel -= 1;
+        unsafe {
+            (*PageHeader::find(ptr)).freeze(ptr, self);
+        }
+        self.fuel += 1;
+    }
+}
+
+/// Freeze the object at `ptr` if it isn't already, tracing its edges into
+/// `tracer` so the whole closure gets frozen too. Monomorphized per
+/// allocation type and stored in `PageHeader::freeze_fn`, the same
+/// type-erased dispatch trick `PageHeader::mark_fn` uses.
+pub(crate) unsafe fn freeze_entry_point<U: InHeap>(ptr: UntypedPointer, tracer: &mut FreezeTracer) {
+    let ptr = ptr.as_typed_ptr::<U>();
+    if pages::is_frozen(ptr) {
+        // Already frozen by `visit`/`visit_untyped`, which left its edges
+        // for us (popped off the stack, or dispatched here untyped).
+        ptr.as_ref().trace(tracer);
+    } else {
+        tracer.visit(ptr);
+    }
+}
+
+/// Freeze `root` and everything reachable from it. Frozen objects are
+/// permanently exempt from marking and sweeping (see `pages::freeze`), so
+/// they cost nothing in future collections, and mutating one through a
+/// generated setter is a debug-mode error afterward (see `GcRef::as_mut_ptr`).
+/// There's no way to unfreeze an object.
+pub(crate) fn freeze_reachable(root: UntypedPointer) {
+    let mut tracer = FreezeTracer {
+        fuel: FREEZE_FUEL,
+        stack: Vec::new(),
+    };
+    unsafe {
+        (*PageHeader::find(root)).freeze(root, &mut tracer);
+    }
+    tracer.freeze_to_fix_point();
+}
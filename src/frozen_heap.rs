@@ -0,0 +1,140 @@
+//! A read-only heap shared safely across threads.
+//!
+//! `GcHeap` is `Send` but not `Sync` (see `unsafe impl Send for GcHeap` in
+//! `heap`): only one thread may run a session on it at a time, since
+//! `pages::MarkWord` --- the bits a session's ordinary bookkeeping writes on
+//! every `GcRef` pin, unpin, mark, and sweep --- is a plain, non-atomic
+//! word. Two threads touching the same object's `MarkWord` at once, even
+//! just for pin counting, is a data race no matter how carefully the rest
+//! of the code is written.
+//!
+//! A frozen object graph sidesteps this: once `GcHeapSession::freeze_reachable`
+//! has run, nothing in that closure is ever marked, swept, or mutated again
+//! (a generated setter on a frozen object panics in debug builds; see
+//! `GcRef::as_mut_ptr`). Pinning is the one operation that would otherwise
+//! still touch `MarkWord` on every accessor call --- not just on the root,
+//! but on every `GcRef`-typed field a derived accessor reaches into, via
+//! the ordinary `GcRef::new` path --- so `pages::pin`/`pages::unpin`
+//! themselves check the frozen bit first and are a complete no-op, with no
+//! `MarkWord` access at all, whenever it's set (see `pages::pin`). Frozen
+//! objects need no pin to stay alive, so this costs nothing: reading a
+//! frozen graph, root or nested field, needs no writes to `MarkWord`
+//! anywhere in the closure. `FrozenHeap` packages up a heap that's been
+//! given over entirely to one such graph and hands out `Arc<FrozenHeap>`
+//! handles that any number of threads can `read` from concurrently.
+//!
+//! This trades away the ordinary session API: a `FrozenHeap` is never
+//! `enter`ed again, so nothing reachable from its root can change or grow.
+//! For a server building one large read-only configuration or AST graph
+//! once and sharing it across request threads, that trade is usually free.
+
+use gc_ref::GcRef;
+use heap::GcHeap;
+use pages;
+use ptr::UntypedPointer;
+use std::mem::ManuallyDrop;
+use std::sync::Arc;
+use traits::IntoHeapAllocation;
+
+/// A heap, permanently frozen around one root object, that can be read
+/// from many threads at once. See the module docs.
+pub struct FrozenHeap {
+    // Kept alive only so the frozen allocations it owns stay valid; no
+    // session is ever run on it again. Boxed rather than inline: every page
+    // `heap.enter` ever allocated stashed a `*mut GcHeap` back-pointer to
+    // wherever `heap` lived at the time (see `pages::PageHeader::heap`), so
+    // once a single page exists, `GcHeap` may never move again --- moving
+    // it by value here, after the caller has already `enter`ed it, would
+    // leave every page pointing at stale, dangling memory. A `Box<GcHeap>`
+    // fixes the heap at one address for good; moving the `Box` around (as
+    // `new` does, taking one by value) only moves the pointer, not the
+    // `GcHeap` it points to.
+    #[allow(dead_code)]
+    heap: Box<GcHeap>,
+    root: UntypedPointer,
+}
+
+// Safety: everything reachable from `root` was frozen by `freeze_reachable`
+// before `new` accepted it, and freezing is one-way (there's no setter for
+// clearing `pages::MarkWord`'s frozen bit), so nothing in that closure ever
+// changes again. `read`ing the root never writes anywhere in `heap` (see
+// `GcRef::new_unpinned`), and reading any nested `GcRef` field the root can
+// reach --- via ordinary generated accessors like `.left()`/`.right()`,
+// which do go through the normal `GcRef::new` pinning path --- is made
+// equally write-free by `pages::pin`/`pages::unpin` no-op'ing on frozen
+// objects before ever taking a `&mut MarkWord` (see the module docs and
+// `pages::pin`). So concurrent reads from several threads, at any depth in
+// the frozen graph, touch the same memory the same way concurrent readers
+// of a `&'static` value would.
+unsafe impl Sync for FrozenHeap {}
+
+// Safety: `UntypedPointer` is conservatively `!Send` because most pointers
+// into the heap are only meaningful next to the `GcHeapSession` that
+// produced them, and moving one to another thread without its session
+// would be nonsense. `root` has no session to leave behind --- `GcHeap` is
+// already `Send` on its own, and `root` only ever gets read back through
+// it --- so handing the whole frozen graph to another thread is fine.
+unsafe impl Send for FrozenHeap {}
+
+impl FrozenHeap {
+    /// Take ownership of `heap` and freeze it around `root`, returning a
+    /// handle any number of threads can `read` from.
+    ///
+    /// `root` is untyped rather than a `GcRef`/`T::Ref` because it has to
+    /// survive past the end of the session that produced it (see `read`
+    /// for how it's used again): a type like `PairRef<'h>` is tied to that
+    /// session's `'h` and can't be named once `GcHeap::enter` returns,
+    /// exactly like `T::Ref` can't be `GcHeapSession::serialize`d without
+    /// naming `T` fresh at the call site. Get one with `GcRef::ptr` (or
+    /// its untyped equivalent) on the frozen root, just before `enter`
+    /// returns.
+    ///
+    /// `heap` must already be boxed by the time it's passed in here: once
+    /// `heap.enter` has run even once, every page it allocated has a
+    /// `*mut GcHeap` back-pointer baked in pointing at `heap`'s address at
+    /// the time, and only a `Box` keeps that address fixed across the move
+    /// into this function and into the `FrozenHeap` this returns (see the
+    /// `heap` field's docs).
+    ///
+    /// # Safety
+    ///
+    /// `root` must point to a live allocation in `heap`, already frozen
+    /// along with everything it can reach, via
+    /// `GcHeapSession::freeze_reachable`. This is checked with a
+    /// `debug_assert!`, but not in release builds.
+    pub unsafe fn new(heap: Box<GcHeap>, root: UntypedPointer) -> Arc<FrozenHeap> {
+        debug_assert!(
+            pages::is_frozen_untyped(root),
+            "cell-gc: FrozenHeap::new requires a root already frozen with freeze_reachable"
+        );
+        Arc::new(FrozenHeap {
+            heap: heap,
+            root: root,
+        })
+    }
+
+    /// Call `f` with a reference to the frozen root.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be the same type `new` was called with (or another type
+    /// with an identical `In` representation). `FrozenHeap` erases the
+    /// root's type when it's built, the same way `GcAny` and `GcDyn` do,
+    /// so nothing checks this for you.
+    pub unsafe fn read<'h, T, F, R>(&'h self, f: F) -> R
+    where
+        T: IntoHeapAllocation<'h>,
+        F: FnOnce(&T::Ref) -> R,
+    {
+        let ptr = self.root.as_typed_ptr::<T::In>();
+        // Not `GcRef::new`: that pins, which would write to the object's
+        // `MarkWord` --- a data race if another thread is `read`ing the
+        // same object right now. A frozen object needs no pin to stay
+        // alive, so there's nothing for a pin to buy here.
+        let gc_ref = GcRef::<'h, T>::new_unpinned(ptr);
+        // `T::Ref` owns that `GcRef` and would try to unpin it on drop;
+        // `ManuallyDrop` suppresses that, since it was never pinned.
+        let wrapped = ManuallyDrop::new(T::wrap_gc_ref(gc_ref));
+        f(&wrapped)
+    }
+}
@@ -0,0 +1,163 @@
+//! A type-erased reference to *any* heap value, with a checked downcast
+//! back to its concrete `Ref` type --- for a dynamically typed language's
+//! VM, say, that just wants to store "an object" and dispatch on its
+//! runtime type.
+//!
+//! Unlike [`GcDyn`](::GcDyn), which needs a trait (and one `gc_dyn_trait!`
+//! invocation per trait) to know what to do with its referent, `GcAny`
+//! doesn't call anything on it: it only remembers what type it is, so code
+//! holding a `GcAny` can ask for it back as a concrete `Ref` and get
+//! `Err` if it guessed wrong.
+//!
+//! ```
+//! extern crate cell_gc;
+//! #[macro_use]
+//! extern crate cell_gc_derive;
+//!
+//! use cell_gc::GcAny;
+//! use cell_gc::traits::IntoHeapAllocation;
+//!
+//! #[derive(IntoHeap)]
+//! pub struct Point<'h> {
+//!     pub x: i32,
+//!     pub y: i32,
+//!     pub phantom: ::std::marker::PhantomData<&'h u8>,
+//! }
+//!
+//! # fn main() {
+//! cell_gc::with_heap(|hs| {
+//!     let point = hs.alloc(Point { x: 1, y: 2, phantom: ::std::marker::PhantomData });
+//!     let any: GcAny = GcAny::new(Point::into_gc_ref(point));
+//!
+//!     // Downcasting to the wrong type gives the `GcAny` back, unharmed.
+//!     let any = any.downcast::<i32>().unwrap_err();
+//!
+//!     let point = any.downcast::<Point>().unwrap();
+//!     assert_eq!(point.x(), 1);
+//! });
+//! # }
+//! ```
+
+use gc_ref::GcRef;
+use heap::HeapSessionId;
+use pages::{self, PageHeader};
+use ptr::UntypedPointer;
+use std::any::TypeId;
+use std::fmt;
+use std::marker::PhantomData;
+use traits::{InHeap, IntoHeap, IntoHeapAllocation, IntoHeapBase, Tracer};
+
+/// A type-erased, GC-traced reference to any heap value, with a checked
+/// downcast back to its original `Ref` type. See the module docs.
+pub struct GcAny<'h> {
+    heap_id: HeapSessionId<'h>,
+    ptr: UntypedPointer,
+}
+
+impl<'h> GcAny<'h> {
+    /// Erase `r`'s type, keeping it alive and traced as an opaque object.
+    pub fn new<T: IntoHeapAllocation<'h>>(r: GcRef<'h, T>) -> GcAny<'h> {
+        GcAny {
+            heap_id: PhantomData,
+            ptr: r.into_pinned_ptr().into(),
+        }
+    }
+
+    /// The `TypeId` of this reference's referent's `In` type, i.e. the same
+    /// `TypeId` a `downcast::<T>()` call checks against.
+    pub fn type_id(&self) -> TypeId {
+        unsafe { (*PageHeader::find(self.ptr)).type_id() }
+    }
+
+    /// An opaque token that uniquely identifies this reference's referent,
+    /// stable for as long as the referent exists. See `GcRef::object_id`.
+    pub fn object_id(&self) -> usize {
+        self.ptr.as_usize()
+    }
+
+    /// If this reference's referent is a `T`, recover it as a `T::Ref`.
+    /// Otherwise, hand `self` back unchanged.
+    pub fn downcast<T: IntoHeapAllocation<'h>>(self) -> Result<T::Ref, GcAny<'h>> {
+        if pages::heap_type_id::<T::In>() == self.type_id() {
+            let ptr = self.ptr;
+            // `self`'s `Drop` unpins the referent right here; the
+            // `GcRef::new` right after it re-pins the very same address, so
+            // the referent is never actually left unrooted in between.
+            drop(self);
+            let gc_ref = unsafe { GcRef::new(ptr.as_typed_ptr::<T::In>()) };
+            Ok(T::wrap_gc_ref(gc_ref))
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<'h> Drop for GcAny<'h> {
+    fn drop(&mut self) {
+        unsafe {
+            pages::unpin_untyped(self.ptr);
+        }
+    }
+}
+
+impl<'h> Clone for GcAny<'h> {
+    fn clone(&self) -> GcAny<'h> {
+        unsafe {
+            pages::pin_untyped(self.ptr);
+        }
+        GcAny {
+            heap_id: self.heap_id,
+            ptr: self.ptr,
+        }
+    }
+}
+
+impl<'h> PartialEq for GcAny<'h> {
+    fn eq(&self, other: &GcAny<'h>) -> bool {
+        self.ptr == other.ptr
+    }
+}
+
+impl<'h> Eq for GcAny<'h> {}
+
+impl<'h> fmt::Debug for GcAny<'h> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GcAny {{ ptr: {:p} }}", self.ptr.as_void())
+    }
+}
+
+/// The in-heap storage form of `GcAny<'h>`: same untyped pointer, just with
+/// the `'h` erased --- there's nothing left to erase it *from*, since the
+/// field doesn't mention `'h` in the first place.
+#[doc(hidden)]
+pub struct GcAnyStorage {
+    ptr: UntypedPointer,
+}
+
+impl InHeap for GcAnyStorage {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        tracer.visit_untyped(self.ptr);
+    }
+}
+
+impl<'h> IntoHeapBase for GcAny<'h> {
+    type In = GcAnyStorage;
+
+    fn into_heap(self) -> GcAnyStorage {
+        // Same reasoning as `GcRef::into_heap`: once the pointer is copied
+        // into its new home, it's reachable by ordinary trace-from-roots
+        // again, so the pin this `GcAny` was holding (released by `self`'s
+        // `Drop` right after this call returns) isn't needed any more.
+        GcAnyStorage { ptr: self.ptr }
+    }
+
+    unsafe fn from_heap(storage: &GcAnyStorage) -> GcAny<'h> {
+        pages::pin_untyped(storage.ptr);
+        GcAny {
+            heap_id: PhantomData,
+            ptr: storage.ptr,
+        }
+    }
+}
+
+unsafe impl<'h> IntoHeap<'h> for GcAny<'h> {}
@@ -0,0 +1,161 @@
+//! A single mutable GC-managed slot, for the case where a whole
+//! `#[derive(IntoHeap)]` struct would be overkill --- a Scheme box, or a
+//! variable captured by reference from an enclosing closure, is really
+//! just one heap-allocated, `get`/`set`-able value.
+//!
+//! ```
+//! extern crate cell_gc;
+//! #[macro_use]
+//! extern crate cell_gc_derive;
+//!
+//! use cell_gc::GcCell;
+//!
+//! # fn main() {
+//! cell_gc::with_heap(|hs| {
+//!     let boxed = hs.alloc(GcCell::new(1));
+//!     assert_eq!(boxed.get(), 1);
+//!     boxed.set(2);
+//!     assert_eq!(boxed.get(), 2);
+//! });
+//! # }
+//! ```
+
+use borrow_flag;
+use gc_ref::GcRef;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use traits::{InHeap, IntoHeap, IntoHeapAllocation, IntoHeapBase, Tracer};
+
+/// A value to be allocated as a single mutable GC-managed slot. See the
+/// module docs.
+pub struct GcCell<'h, T: IntoHeap<'h>> {
+    value: T,
+    phantom: PhantomData<&'h ()>,
+}
+
+impl<'h, T: IntoHeap<'h>> GcCell<'h, T> {
+    /// Wrap `value` for allocation as a `GcCell`, e.g.
+    /// `heap.alloc(GcCell::new(value))`.
+    pub fn new(value: T) -> GcCell<'h, T> {
+        GcCell {
+            value: value,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// The in-heap storage form of `GcCell<'h, T>`.
+#[doc(hidden)]
+pub struct GcCellStorage<U: InHeap> {
+    value: U,
+}
+
+impl<U: InHeap> InHeap for GcCellStorage<U> {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        self.value.trace(tracer);
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> IntoHeapBase for GcCell<'h, T> {
+    type In = GcCellStorage<T::In>;
+
+    fn into_heap(self) -> GcCellStorage<T::In> {
+        GcCellStorage { value: self.value.into_heap() }
+    }
+
+    unsafe fn from_heap(storage: &GcCellStorage<T::In>) -> GcCell<'h, T> {
+        GcCell::new(T::from_heap(&storage.value))
+    }
+}
+
+unsafe impl<'h, T: IntoHeap<'h>> IntoHeap<'h> for GcCell<'h, T> {}
+
+impl<'h, T: IntoHeap<'h>> IntoHeapAllocation<'h> for GcCell<'h, T> {
+    type Ref = GcCellRef<'h, T>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, GcCell<'h, T>>) -> GcCellRef<'h, T> {
+        GcCellRef(gc_ref)
+    }
+
+    fn into_gc_ref(r: GcCellRef<'h, T>) -> GcRef<'h, GcCell<'h, T>> {
+        r.0
+    }
+}
+
+/// A reference to a `GcCell` allocated in the heap. See the module docs.
+///
+/// This is written by hand instead of via `#[derive(IntoHeap)]` (which
+/// can't apply to a generic type like `GcCell<'h, T>` anyway) but is
+/// otherwise exactly the kind of `Ref` type that macro generates: a
+/// newtype around a `GcRef`, with `get`/`set` in place of a named field's
+/// generated getter/setter.
+pub struct GcCellRef<'h, T: IntoHeap<'h>>(GcRef<'h, GcCell<'h, T>>);
+
+impl<'h, T: IntoHeap<'h>> GcCellRef<'h, T> {
+    /// Get the cell's current value.
+    #[allow(dead_code)]
+    pub fn get(&self) -> T {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let ptr = self.0.as_ptr();
+        unsafe { T::from_heap(&(*ptr).value) }
+    }
+
+    /// Overwrite the cell's value.
+    #[allow(dead_code)]
+    pub fn set(&self, v: T) {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let ptr = self.0.as_mut_ptr();
+        let u = v.into_heap();
+        unsafe {
+            (*ptr).value = u;
+        }
+    }
+
+    /// See `GcRef::object_id`.
+    #[allow(dead_code)]
+    pub fn object_id(&self) -> usize {
+        self.0.object_id()
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> Clone for GcCellRef<'h, T> {
+    fn clone(&self) -> GcCellRef<'h, T> {
+        GcCellRef(self.0.clone())
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> fmt::Debug for GcCellRef<'h, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GcCellRef {{ ptr: {:p} }}", self.0.as_ptr())
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> PartialEq for GcCellRef<'h, T> {
+    fn eq(&self, other: &GcCellRef<'h, T>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> Eq for GcCellRef<'h, T> {}
+
+impl<'h, T: IntoHeap<'h>> Hash for GcCellRef<'h, T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> IntoHeapBase for GcCellRef<'h, T> {
+    type In = <GcRef<'h, GcCell<'h, T>> as IntoHeapBase>::In;
+
+    fn into_heap(self) -> Self::In {
+        self.0.into_heap()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> GcCellRef<'h, T> {
+        GcCellRef(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h, T: IntoHeap<'h>> IntoHeap<'h> for GcCellRef<'h, T> {}
@@ -0,0 +1,262 @@
+//! Type-erased references to heap values that share a common trait, for
+//! heterogeneous containers --- a display list of different drawable
+//! types, say --- that don't want to be crammed into one giant enum.
+//!
+//! `cell_gc` can't offer a single, fully generic `GcDyn<'h, dyn Trait>` the
+//! way `Box<dyn Trait>` works for ordinary allocations: coercing `&Foo`
+//! into `&dyn Trait` for a `Trait` that's still an unconstrained generic
+//! parameter needs the standard library's unstable `Unsize` trait, and
+//! this crate only targets stable Rust. So instead of one generic type,
+//! [`gc_dyn_trait!`] builds a small hand-rolled vtable --- a `struct` of
+//! function pointers, one per trait method --- for one specific trait at a
+//! time, the same way dynamic dispatch worked before languages had
+//! built-in trait objects. [`GcDyn<'h, V>`](GcDyn) is the reference type
+//! built on top of a vtable of type `V`: it stores an untyped pointer into
+//! the heap plus a `&'static V` instead of a normal `Pointer<U>`, so it
+//! doesn't need to know `U` to be traced, cloned, or dropped.
+//!
+//! ```
+//! #[macro_use]
+//! extern crate cell_gc;
+//! #[macro_use]
+//! extern crate cell_gc_derive;
+//!
+//! use cell_gc::GcDyn;
+//! use cell_gc::traits::IntoHeapAllocation;
+//!
+//! // One vtable, shared by every kind of drawable object.
+//! gc_dyn_trait! {
+//!     trait Drawable / DrawableVTable / DrawableDyn {
+//!         fn area(&self) -> f64;
+//!     }
+//! }
+//!
+//! #[derive(IntoHeap)]
+//! pub struct Circle<'h> {
+//!     pub radius: f64,
+//!     pub phantom: ::std::marker::PhantomData<&'h u8>,
+//! }
+//!
+//! // Trait methods are implemented on the storage type, same as
+//! // `impl_custom_trace!` --- see that macro's docs for why.
+//! impl Drawable for CircleStorage {
+//!     fn area(&self) -> f64 {
+//!         ::std::f64::consts::PI * self.radius * self.radius
+//!     }
+//! }
+//!
+//! static CIRCLE_VTABLE: DrawableVTable = DrawableVTable::of::<CircleStorage>();
+//!
+//! # fn main() {
+//! cell_gc::with_heap(|hs| {
+//!     let circle = hs.alloc(Circle { radius: 2.0, phantom: ::std::marker::PhantomData });
+//!     let drawable: DrawableDyn = unsafe {
+//!         GcDyn::new(Circle::into_gc_ref(circle), &CIRCLE_VTABLE)
+//!     };
+//!     assert!((drawable.area() - 12.566).abs() < 0.001);
+//! });
+//! # }
+//! ```
+//!
+//! A struct or enum can hold a `GcDyn<'h, V>` field (directly, or wrapped
+//! in `Option`, `Vec`, or an array) just like any other `IntoHeap` type;
+//! its target is traced, and stays alive, exactly like an ordinary `*Ref`
+//! field's would.
+
+use gc_ref::GcRef;
+use heap::HeapSessionId;
+use pages;
+use ptr::UntypedPointer;
+use std::marker::PhantomData;
+use traits::{InHeap, IntoHeap, IntoHeapAllocation, IntoHeapBase, Tracer};
+
+/// A type-erased, GC-traced reference to a heap value implementing the
+/// trait that vtable type `V` was generated for. See the module docs.
+pub struct GcDyn<'h, V: 'static> {
+    heap_id: HeapSessionId<'h>,
+    ptr: UntypedPointer,
+    vtable: &'static V,
+}
+
+impl<'h, V: 'static> GcDyn<'h, V> {
+    /// Wrap `r` as a type-erased `GcDyn`, dispatching through `vtable`.
+    ///
+    /// # Safety
+    ///
+    /// `vtable`'s function pointers must have been built (by
+    /// [`gc_dyn_trait!`]'s generated `V::of::<U>()`) for the same `U =
+    /// T::In` that `r` actually points at. [`gc_dyn_trait!`] only ever
+    /// generates code that upholds this; hand-assembling a `V` yourself is
+    /// on you.
+    pub unsafe fn new<T>(r: GcRef<'h, T>, vtable: &'static V) -> GcDyn<'h, V>
+    where
+        T: IntoHeapAllocation<'h>,
+    {
+        GcDyn {
+            heap_id: PhantomData,
+            ptr: r.into_pinned_ptr().into(),
+            vtable: vtable,
+        }
+    }
+
+    /// This reference's vtable, for a `gc_dyn_trait!`-generated trait impl
+    /// to dispatch through.
+    #[inline]
+    pub fn vtable(&self) -> &'static V {
+        self.vtable
+    }
+
+    /// This reference's untyped target, for a `gc_dyn_trait!`-generated
+    /// trait impl to call a vtable function on.
+    #[inline]
+    pub fn ptr(&self) -> UntypedPointer {
+        self.ptr
+    }
+
+    /// An opaque token that uniquely identifies this reference's referent,
+    /// stable for as long as the referent exists. See `GcRef::object_id`.
+    pub fn object_id(&self) -> usize {
+        self.ptr.as_usize()
+    }
+}
+
+impl<'h, V: 'static> Drop for GcDyn<'h, V> {
+    fn drop(&mut self) {
+        unsafe {
+            pages::unpin_untyped(self.ptr);
+        }
+    }
+}
+
+impl<'h, V: 'static> Clone for GcDyn<'h, V> {
+    fn clone(&self) -> GcDyn<'h, V> {
+        unsafe {
+            pages::pin_untyped(self.ptr);
+        }
+        GcDyn {
+            heap_id: self.heap_id,
+            ptr: self.ptr,
+            vtable: self.vtable,
+        }
+    }
+}
+
+impl<'h, V: 'static> PartialEq for GcDyn<'h, V> {
+    fn eq(&self, other: &GcDyn<'h, V>) -> bool {
+        self.ptr == other.ptr
+    }
+}
+
+impl<'h, V: 'static> Eq for GcDyn<'h, V> {}
+
+/// The in-heap storage form of `GcDyn<'h, V>`: same untyped pointer and
+/// vtable, just with the `'h` erased --- there's nothing left to erase it
+/// *from*, since neither field mentions `'h` in the first place.
+#[doc(hidden)]
+pub struct GcDynStorage<V: 'static> {
+    ptr: UntypedPointer,
+    vtable: &'static V,
+}
+
+impl<V: 'static> InHeap for GcDynStorage<V> {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        tracer.visit_untyped(self.ptr);
+    }
+}
+
+impl<'h, V: 'static> IntoHeapBase for GcDyn<'h, V> {
+    type In = GcDynStorage<V>;
+
+    fn into_heap(self) -> GcDynStorage<V> {
+        // Same reasoning as `GcRef::into_heap`: once the pointer is copied
+        // into its new home, it's reachable by ordinary trace-from-roots
+        // again, so the pin this `GcDyn` was holding (released by `self`'s
+        // `Drop` right after this call returns) isn't needed any more.
+        GcDynStorage {
+            ptr: self.ptr,
+            vtable: self.vtable,
+        }
+    }
+
+    unsafe fn from_heap(storage: &GcDynStorage<V>) -> GcDyn<'h, V> {
+        pages::pin_untyped(storage.ptr);
+        GcDyn {
+            heap_id: PhantomData,
+            ptr: storage.ptr,
+            vtable: storage.vtable,
+        }
+    }
+}
+
+unsafe impl<'h, V: 'static> IntoHeap<'h> for GcDyn<'h, V> {}
+
+/// Declares a trait usable with [`GcDyn`], plus the vtable and reference
+/// types it needs: a `struct $VTable` of one function pointer per method,
+/// with a `$VTable::of::<U>()` that builds one for any `U: $Trait +
+/// InHeap`, and a `type $Dyn<'h> = GcDyn<'h, $VTable>` that implements
+/// `$Trait` itself by calling through the vtable.
+///
+/// `macro_rules!` can't glue identifiers together to invent the vtable and
+/// reference type names from the trait name, so all three are spelled out
+/// in the invocation. See the module docs for a full example.
+#[macro_export]
+macro_rules! gc_dyn_trait {
+    (
+        $(#[$trait_meta:meta])*
+        trait $trait_name:ident / $vtable_name:ident / $dyn_name:ident {
+            $(
+                $(#[$method_meta:meta])*
+                fn $method:ident(&self $(, $arg:ident : $arg_ty:ty)*) $(-> $ret:ty)*;
+            )*
+        }
+    ) => {
+        $(#[$trait_meta])*
+        pub trait $trait_name {
+            $(
+                $(#[$method_meta])*
+                fn $method(&self $(, $arg: $arg_ty)*) $(-> $ret)*;
+            )*
+        }
+
+        /// Function-pointer vtable for `$trait_name`, generated by
+        /// `gc_dyn_trait!`.
+        #[allow(non_snake_case)]
+        pub struct $vtable_name {
+            $($method: unsafe fn(
+                $crate::ptr::UntypedPointer
+                $(, $arg_ty)*
+            ) $(-> $ret)*,)*
+        }
+
+        impl $vtable_name {
+            /// Build the vtable for `U`'s implementation of `$trait_name`.
+            pub const fn of<U: $trait_name + $crate::traits::InHeap>() -> $vtable_name {
+                $(
+                    unsafe fn $method<U: $trait_name + $crate::traits::InHeap>(
+                        ptr: $crate::ptr::UntypedPointer
+                        $(, $arg: $arg_ty)*
+                    ) $(-> $ret)* {
+                        $trait_name::$method(ptr.as_typed_ptr::<U>().as_ref() $(, $arg)*)
+                    }
+                )*
+                $vtable_name {
+                    $($method: $method::<U>,)*
+                }
+            }
+        }
+
+        /// A type-erased reference to any heap value implementing
+        /// `$trait_name`. See the `gc_dyn` module docs.
+        pub type $dyn_name<'h> = $crate::GcDyn<'h, $vtable_name>;
+
+        impl<'h> $trait_name for $dyn_name<'h> {
+            $(
+                fn $method(&self $(, $arg: $arg_ty)*) $(-> $ret)* {
+                    unsafe {
+                        (self.vtable().$method)(self.ptr() $(, $arg)*)
+                    }
+                }
+            )*
+        }
+    };
+}
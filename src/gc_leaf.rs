@@ -1,6 +1,15 @@
 use std;
 
 /// GcLeaf can be used to embed just about anything in a GC heap type.
+///
+/// `GcLeaf<T>` has a blanket `IntoHeap` impl (see `traits`) that stores `T`
+/// directly and traces nothing, so any plain-old-data type --- an enum, a
+/// newtype, a third-party type you don't control --- can be a field of a
+/// `#[derive(IntoHeap)]` struct just by wrapping it in `GcLeaf`, without
+/// writing an `unsafe impl IntoHeap` by hand. The tradeoff is exactly what
+/// "traces nothing" implies: if `T` secretly contains a `Ref` or other GC
+/// pointer, wrapping it in `GcLeaf` will hide that pointer from the
+/// collector, so `T` needs to be truly leaf data.
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub struct GcLeaf<T: Clone + 'static> {
     // This private field has an underscore in the hopes that it is
@@ -20,6 +29,12 @@ impl<T: Clone + 'static> GcLeaf<T> {
     }
 }
 
+impl<T: Clone + 'static> From<T> for GcLeaf<T> {
+    fn from(value: T) -> GcLeaf<T> {
+        GcLeaf::new(value)
+    }
+}
+
 impl<T: Clone + 'static> std::ops::Deref for GcLeaf<T> {
     type Target = T;
     fn deref(&self) -> &T {
@@ -1,15 +1,32 @@
 use gc_leaf::GcLeaf;
-use heap::{GcHeap, HeapId, GcHeapSession, HeapSessionId};
+use heap::{ErasedTraceable, GcHeap, HeapId, GcHeapSession, HeapSessionId};
 use pages;
 use ptr::Pointer;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 use std::mem;
-use traits::{IntoHeapAllocation, IntoHeapBase};
+use traits::{IntoHeap, IntoHeapAllocation, IntoHeapBase};
 
 /// A reference to something inside the GC heap, that is valid for the current
 /// GC heap session.
+///
+/// A `GcRef` (or a generated `Ref`, which is just a `GcRef` in a wrapper ---
+/// see the crate's top-level docs) always keeps its own referent alive,
+/// automatically, for as long as the `GcRef` value itself exists in Rust:
+/// `new` pins the referent (see `pages::pin`, and `MarkWord`'s pin count),
+/// and `Drop` unpins it, so there's no way to end up holding a `GcRef` whose
+/// referent a collection has swept out from under it, whether or not that
+/// `GcRef` has been stored anywhere reachable from a root yet. This is what
+/// makes `hs.alloc(value)` immediately safe to hold in a local variable and
+/// pass around, even across further `alloc` calls that might trigger a
+/// collection, with no separate "now root this" step --- `alloc`'s result is
+/// never in an unrooted state to begin with, so there's nothing for a
+/// `_rooted`/`_unrooted` pair of constructors to distinguish. `gc_root!`,
+/// `Rooted`, and `HandleScope` exist for a different problem: keeping a
+/// value alive *without* holding a `GcRef` to it directly, e.g. a bare
+/// `#[derive(IntoHeap)]` enum passed through a callback boundary as a plain
+/// value rather than a reference.
 pub struct GcRef<'h, T: IntoHeapAllocation<'h>> {
     heap_id: HeapSessionId<'h>,
     ptr: Pointer<T::In>, // never null
@@ -28,6 +45,24 @@ impl<'h, T: IntoHeapAllocation<'h>> GcRef<'h, T> {
         }
     }
 
+    /// Construct a `GcRef` without pinning it. Unsafe for the same reasons
+    /// as `new`, plus: the caller takes over responsibility for keeping
+    /// `p`'s referent alive by some other means, since there's no pin to
+    /// drop later.
+    ///
+    /// Used by `FrozenHeap`, whose objects are permanently exempt from
+    /// collection (see `GcHeapSession::freeze_reachable`), so pinning them
+    /// would only add an unnecessary write to `pages::MarkWord` --- one
+    /// that, unlike an ordinary pin, several threads could be making to
+    /// the same word at once, since a `FrozenHeap` is meant to be read from
+    /// many threads concurrently.
+    pub(crate) unsafe fn new_unpinned(p: Pointer<T::In>) -> GcRef<'h, T> {
+        GcRef {
+            ptr: p,
+            heap_id: PhantomData,
+        }
+    }
+
     /// Get an untyped GC pointer to the referent.
     pub fn ptr(&self) -> Pointer<T::In> {
         self.ptr
@@ -39,7 +74,15 @@ impl<'h, T: IntoHeapAllocation<'h>> GcRef<'h, T> {
     }
 
     /// Get a raw, untyped mut pointer to the referent.
+    ///
+    /// Every generated `set_*` accessor goes through here, so this is where
+    /// we can catch an attempt to mutate a frozen object (see
+    /// `GcHeapSession::freeze_reachable`) in debug builds.
     pub fn as_mut_ptr(&self) -> *mut T::In {
+        debug_assert!(
+            !unsafe { pages::is_frozen(self.ptr) },
+            "cell-gc: attempted to mutate a frozen object"
+        );
         self.ptr.as_raw() as *mut T::In
     }
 
@@ -51,6 +94,92 @@ impl<'h, T: IntoHeapAllocation<'h>> GcRef<'h, T> {
         mem::forget(self); // skip unpinning destructor
         ptr
     }
+
+    /// The number of collections this reference's referent has survived.
+    ///
+    /// See the module docs on [`GcHeap`](../heap/struct.GcHeap.html) for what
+    /// this crate does and does not mean by "generation".
+    pub fn age(&self) -> u8 {
+        unsafe { pages::get_age(self.ptr) }
+    }
+
+    /// True if this reference's referent has survived enough collections to
+    /// be considered tenured (see `age()`).
+    pub fn is_tenured(&self) -> bool {
+        self.age() >= pages::TENURING_AGE
+    }
+
+    /// Get one of this reference's referent's four user flag bits.
+    ///
+    /// These bits live in the object header alongside the mark and pin
+    /// bits, but cell-gc itself never reads or writes them: they're there
+    /// for embedders that want a cheap per-object mark (a "visited" bit for
+    /// a cycle-aware printer, an "immutable" bit for literal data) without
+    /// adding a whole field to every object. They default to `false` on a
+    /// fresh allocation and survive collection untouched.
+    ///
+    /// `index` must be less than 4, or this panics.
+    pub fn get_user_flag(&self, index: u8) -> bool {
+        assert!(index < 4, "cell-gc: user flag index out of range: {}", index);
+        unsafe { pages::get_user_flag(self.ptr, index) }
+    }
+
+    /// Set or clear one of this reference's referent's four user flag bits.
+    /// See `get_user_flag`.
+    ///
+    /// `index` must be less than 4, or this panics.
+    pub fn set_user_flag(&self, index: u8, value: bool) {
+        assert!(index < 4, "cell-gc: user flag index out of range: {}", index);
+        unsafe { pages::set_user_flag(self.ptr, index, value) };
+    }
+
+    /// This reference's referent's address, as an opaque integer.
+    ///
+    /// Cell-gc never moves or compacts an object once it's allocated (see
+    /// the "On compaction" note on `GcHeap`), so this is stable for as long
+    /// as the referent exists, pinned or not --- unlike, say, a moving
+    /// collector's object address, which would only be stable between
+    /// collections. Still, prefer `object_id()` unless you specifically
+    /// want the address; naming it that invites treating it as a real
+    /// pointer (dereferencing it, doing arithmetic on it), which happens to
+    /// work today but would break the moment this crate grew a compacting
+    /// collector.
+    pub fn address(&self) -> usize {
+        self.as_ptr() as usize
+    }
+
+    /// An opaque token that uniquely identifies this reference's referent,
+    /// stable for as long as the referent exists. Two `GcRef`s (or values
+    /// derived from them, like a generated `Ref`'s `.object_id()`) compare
+    /// equal under `==` exactly when their `object_id()`s are equal, so
+    /// this is intended for things like a seen-set in a printer that needs
+    /// to detect cyclic data, or any other use of a `Ref` as a hash map key
+    /// via its `Hash` impl.
+    pub fn object_id(&self) -> usize {
+        self.address()
+    }
+
+    /// Call `f` with a direct reference to this object's in-heap storage,
+    /// instead of copying each field out through `IntoHeapBase::from_heap`.
+    /// Useful when a field is expensive to copy (a `String`, say) and the
+    /// caller only needs to peek at it.
+    ///
+    /// While `f` runs, no collection can happen: the heap this reference
+    /// belongs to refuses to `gc` for as long as a `with_storage` call is on
+    /// the stack (see `GcHeap::begin_storage_borrow`), so nothing can sweep
+    /// or move the memory `f` is looking at out from under it.
+    ///
+    /// # Safety
+    ///
+    /// `f` must not let its `&T::In` argument escape the call, and must not
+    /// allocate or otherwise trigger a collection --- doing so panics,
+    /// rather than corrupting the heap, but only once a collection is
+    /// actually attempted, so don't rely on the panic to catch every case.
+    pub unsafe fn with_storage<R>(&self, f: impl FnOnce(&T::In) -> R) -> R {
+        let heap = pages::heap_of(self.ptr);
+        let _guard = (*heap).begin_storage_borrow();
+        f(self.ptr.as_ref())
+    }
 }
 
 impl<'h, T: IntoHeapAllocation<'h>> Hash for GcRef<'h, T> {
@@ -113,6 +242,86 @@ impl<'h, T: IntoHeapAllocation<'h>> PartialEq for GcRef<'h, T> {
 impl<'h, T: IntoHeapAllocation<'h>> Eq for GcRef<'h, T> {}
 
 
+/// A pointer to a heap value that isn't tied to a `GcHeapSession<'h>`'s stack
+/// lifetime, meant for handing to C code via `as_ptr`/`as_mut_ptr`.
+///
+/// Like `GcRef`, a `PinnedRef` acts as a root: its referent won't be
+/// collected while the `PinnedRef` exists. And since cell-gc never moves
+/// objects, the raw pointer it hands out stays valid for exactly as long as
+/// the `PinnedRef` does, even across collections --- including if compaction
+/// is ever added (see the "On compaction" note on `GcHeap`), since a pinned
+/// object would simply be excluded from relocation.
+///
+/// Use `GcHeapSession::alloc_pinned` to create one, and
+/// `GcHeapSession::unpin` when C is done with the pointer, to let the
+/// referent be collected again. **Dropping a `PinnedRef` without calling
+/// `unpin` pins its referent forever**, unlike `GcRef` and `GcFrozenRef`,
+/// which unpin automatically; there's no way to run a destructor when a raw
+/// pointer stops being used by foreign code, so cell-gc can't do this for
+/// you.
+///
+/// A `PinnedRef` remembers which heap it was allocated from (see
+/// `GcHeap::id`), and `GcHeapSession::unpin` panics if it's handed a
+/// `PinnedRef` from a different heap. Ordinary `GcRef`s don't need this
+/// check: a `GcHeapSession<'h>`'s `'h` is a distinct, uncoercible type for
+/// every heap session, so mixing references from two heaps is already a
+/// compile error. A `PinnedRef` deliberately gives that up in exchange for
+/// not being tied to `'h`, so it needs the same runtime check `GcFrozenRef`
+/// uses for the same reason.
+///
+/// Not being tied to `'h` also makes `PinnedRef` the way to carry a root
+/// across a session boundary: a `GcHeap` outlives any one
+/// `GcHeapSession<'h>`, so a long-lived program can hold a `PinnedRef`
+/// while `GcHeap::enter`'s session ends, then call `enter` again --- with a
+/// fresh `'h` --- and `GcHeapSession::unpin` the same `PinnedRef` back into
+/// an ordinary `T::Ref` valid in the new session, with the heap's other
+/// contents untouched in between. See `GcHeap::enter` for an example.
+pub struct PinnedRef<T: IntoHeapBase> {
+    heap_id: HeapId,
+    ptr: Pointer<T::In>,
+}
+
+impl<T: IntoHeapBase> PinnedRef<T> {
+    pub(crate) fn new<'h>(session: &GcHeapSession<'h>, gc_ref: GcRef<'h, T>) -> PinnedRef<T>
+    where
+        T: IntoHeapAllocation<'h>,
+    {
+        PinnedRef {
+            heap_id: session.heap_id(),
+            ptr: gc_ref.into_pinned_ptr(),
+        }
+    }
+
+    /// Raw pointer to the referent. Valid until this `PinnedRef` is consumed
+    /// by `GcHeapSession::unpin`.
+    pub fn as_ptr(&self) -> *const T::In {
+        self.ptr.as_raw()
+    }
+
+    /// Raw mut pointer to the referent. See `as_ptr`.
+    pub fn as_mut_ptr(&self) -> *mut T::In {
+        self.ptr.as_raw() as *mut T::In
+    }
+
+    /// Convert back to a `GcRef`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `session` is not a session on the heap this was allocated
+    /// from.
+    pub(crate) fn unpin<'h>(self, session: &GcHeapSession<'h>) -> GcRef<'h, T>
+    where
+        T: IntoHeapAllocation<'h>,
+    {
+        session.check_heap_id(self.heap_id);
+        GcRef {
+            heap_id: PhantomData,
+            ptr: self.ptr,
+        }
+    }
+
+}
+
 /// References into the heap that survive across sessions. A `GcFrozenRef<T>`
 /// can't access the `T` value it points to, but it keeps it alive so you can
 /// access it again later.
@@ -176,3 +385,204 @@ impl<T: IntoHeapBase> Drop for GcFrozenRef<T> {
         }
     }
 }
+
+/// A persistent GC root: keeps `T`'s value (and everything it can reach)
+/// alive for as long as this handle exists, even if nothing else in the
+/// heap points to it.
+///
+/// Unlike `GcRef`, `Rooted` doesn't require `T: IntoHeapAllocation` --- it
+/// works for any `IntoHeap` value, including a `#[derive(IntoHeap)]` enum,
+/// which (per the crate's top-level docs) gets no `Ref` type unless it asks
+/// for one with `#[into_heap(make_ref)]`, and without one has no other
+/// sanctioned way to be kept alive on its own. Internally, `Rooted`
+/// moves the value into a heap-owned box and registers it with the heap as
+/// an extra thing to trace on every collection (see `GcHeap::register_root`),
+/// rather than relying on the pin-count mechanism `GcRef` uses, since that
+/// mechanism only applies to values that are themselves a distinct GC
+/// allocation.
+///
+/// Create one with `GcHeapSession::root`.
+pub struct Rooted<'h, T: IntoHeap<'h>> {
+    heap_id: HeapSessionId<'h>,
+    id: usize,
+    heap: *mut GcHeap,
+    value: Box<T::In>,
+}
+
+impl<'h, T: IntoHeap<'h>> Rooted<'h, T> {
+    pub(crate) fn new(heap: &mut GcHeap, value: T) -> Rooted<'h, T> {
+        let value = Box::new(value.into_heap());
+        let trace_ptr: *const dyn ErasedTraceable = &*value;
+        let id = unsafe { heap.register_root(trace_ptr) };
+        Rooted {
+            heap_id: PhantomData,
+            id,
+            heap: heap as *mut GcHeap,
+            value,
+        }
+    }
+
+    /// Get the rooted value.
+    pub fn get(&self) -> T {
+        unsafe { IntoHeapBase::from_heap(&*self.value) }
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> Drop for Rooted<'h, T> {
+    fn drop(&mut self) {
+        unsafe {
+            (*self.heap).unregister_root(self.id);
+        }
+    }
+}
+
+/// A GC root pushed onto its session's shadow stack, meant for short-lived
+/// local temporaries rather than the long-lived handles `Rooted` is for.
+///
+/// Cheaper to push and pop than `Rooted`, since there's no id to hand out
+/// or hash map entry to insert --- just a `Vec::push`/`Vec::pop` on the
+/// heap's shadow stack. The price is that a `ShadowRoot` **must** be
+/// dropped in LIFO order with every other `ShadowRoot` on the same heap,
+/// same as an ordinary local variable would be; `drop` panics (in debug
+/// builds) if it isn't.
+///
+/// Create one with `GcHeapSession::push_root`, or (preferably) the
+/// `gc_root!` macro, which can't accidentally violate the nesting
+/// requirement the way calling `push_root` directly and storing the guard
+/// somewhere non-local could.
+pub struct ShadowRoot<'h, T: IntoHeap<'h>> {
+    heap_id: HeapSessionId<'h>,
+    heap: *mut GcHeap,
+    value: Box<T::In>,
+}
+
+impl<'h, T: IntoHeap<'h>> ShadowRoot<'h, T> {
+    pub(crate) fn new(heap: &mut GcHeap, value: T) -> ShadowRoot<'h, T> {
+        let value = Box::new(value.into_heap());
+        let trace_ptr: *const dyn ErasedTraceable = &*value;
+        unsafe {
+            heap.push_shadow_root(trace_ptr);
+        }
+        ShadowRoot {
+            heap_id: PhantomData,
+            heap: heap as *mut GcHeap,
+            value,
+        }
+    }
+
+    /// Get the rooted value.
+    pub fn get(&self) -> T {
+        unsafe { IntoHeapBase::from_heap(&*self.value) }
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> Drop for ShadowRoot<'h, T> {
+    fn drop(&mut self) {
+        let trace_ptr: *const dyn ErasedTraceable = &*self.value;
+        unsafe {
+            (*self.heap).pop_shadow_root(trace_ptr);
+        }
+    }
+}
+
+/// A batch of `Handle`s, all released together when the scope is dropped.
+///
+/// This is the same idea as `ShadowRoot`, just batched: rather than rooting
+/// every intermediate value one at a time and unrooting them one at a time
+/// in reverse order, open one `HandleScope` per builtin call, hand every
+/// intermediate to `scope.handle(...)`, and let them all go at once when
+/// the scope ends. Internally, each `handle()` call still pushes one entry
+/// onto the heap's shadow stack, same as `ShadowRoot`; dropping the scope
+/// just truncates the stack back to where it started instead of popping
+/// one entry per `Handle`, so handles inside a scope needn't be released in
+/// the reverse of their creation order.
+///
+/// Create one with `GcHeapSession::handle_scope`.
+pub struct HandleScope<'h> {
+    heap_id: HeapSessionId<'h>,
+    heap: *mut GcHeap,
+    base: usize,
+    boxes: Vec<Box<dyn ErasedTraceable>>,
+}
+
+impl<'h> HandleScope<'h> {
+    pub(crate) fn new(heap: &mut GcHeap) -> HandleScope<'h> {
+        HandleScope {
+            heap_id: PhantomData,
+            base: heap.shadow_stack_len(),
+            heap: heap as *mut GcHeap,
+            boxes: Vec::new(),
+        }
+    }
+
+    /// Root `value` until this scope ends.
+    pub fn handle<'a, T: IntoHeap<'h>>(&'a mut self, value: T) -> Handle<'a, 'h, T> {
+        let boxed = Box::new(value.into_heap());
+        let ptr: *const T::In = &*boxed;
+        let trace_ptr: *const dyn ErasedTraceable = &*boxed;
+        unsafe {
+            (*self.heap).push_shadow_root(trace_ptr);
+        }
+        self.boxes.push(boxed as Box<dyn ErasedTraceable>);
+        Handle {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'h> Drop for HandleScope<'h> {
+    fn drop(&mut self) {
+        unsafe {
+            (*self.heap).truncate_shadow_stack(self.base);
+        }
+    }
+}
+
+/// A value rooted by a `HandleScope`, valid for as long as the scope that
+/// created it (that's what the `'a` lifetime ties it to).
+pub struct Handle<'a, 'h: 'a, T: IntoHeap<'h>> {
+    ptr: *const T::In,
+    _marker: PhantomData<(&'a T::In, HeapSessionId<'h>)>,
+}
+
+impl<'a, 'h, T: IntoHeap<'h>> Handle<'a, 'h, T> {
+    /// Get the handle's value.
+    pub fn get(&self) -> T {
+        unsafe { IntoHeapBase::from_heap(&*self.ptr) }
+    }
+}
+
+/// A `HandleScope` that can also hand one value back to its caller, so it
+/// survives the scope ending. See `escape`.
+///
+/// Create one with `GcHeapSession::escapable_handle_scope`.
+pub struct EscapableHandleScope<'h> {
+    inner: HandleScope<'h>,
+}
+
+impl<'h> EscapableHandleScope<'h> {
+    pub(crate) fn new(heap: &mut GcHeap) -> EscapableHandleScope<'h> {
+        EscapableHandleScope {
+            inner: HandleScope::new(heap),
+        }
+    }
+
+    /// Root `value` until this scope ends. See `HandleScope::handle`.
+    pub fn handle<'a, T: IntoHeap<'h>>(&'a mut self, value: T) -> Handle<'a, 'h, T> {
+        self.inner.handle(value)
+    }
+
+    /// Return `handle`'s value to the caller, so it survives this scope
+    /// ending.
+    ///
+    /// This needs no special bookkeeping: `handle.get()` returns a plain
+    /// `T`, and if `T` is (or contains) a `Ref`, that `Ref` already keeps
+    /// its own referent pinned for as long as the `Ref` itself is alive,
+    /// independent of this scope or its shadow stack entries. So the value
+    /// `escape` returns is just as rooted after this scope is dropped as it
+    /// was before.
+    pub fn escape<'a, T: IntoHeap<'h>>(&self, handle: Handle<'a, 'h, T>) -> T {
+        handle.get()
+    }
+}
@@ -73,23 +73,94 @@
 //! avoid reading pointer fields while dropping, and avoid calling into
 //! arbitrary code.
 
-use gc_ref::{GcFrozenRef, GcRef};
-use marking::{MarkingTracer, mark};
-use pages::{self, PageSet, PageSetRef, TypedPage, UninitializedAllocation};
+use adopt;
+use alloc_profile::{self, AllocationReport, AllocationSite};
+use census::Census;
+use dump;
+use freeze;
+use gc_ref::{EscapableHandleScope, GcFrozenRef, GcRef, HandleScope, PinnedRef, Rooted, ShadowRoot};
+use leak_check::{self, LeakReport};
+use marking::{IncrementalMark, MarkingTracer, mark};
+use pages::{self, GlobalPageSource, PageHeader, PageSet, PageSetRef, PageSource, TypedPage, UninitializedAllocation};
 use ptr::{Pointer, UntypedPointer};
+use serialize;
 use signposts;
-use std::any::TypeId;
+use snapshot::Snapshot;
+use stack_scan;
+use std::any::{type_name, TypeId};
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::{Hasher, BuildHasher};
+use std::io::{self, Read, Write};
 use std::marker::PhantomData;
 use std::mem;
+use std::panic::Location;
 use std::sync::{Arc, Mutex, Weak};
-use traits::{InHeap, IntoHeapAllocation};
+use std::time::{Duration, Instant};
+use traits::{Adopt, GcSerialize, InHeap, IntoHeap, IntoHeapAllocation};
+use verify;
 
 /// A universe in which you can store values that implement
 /// `IntoHeapAllocation`. The values are mutable and they can point to each
 /// other, in cycles.
+///
+/// ### On "generational" collection
+///
+/// Every collection is a full mark-and-sweep over the whole heap: there is no
+/// separate nursery, and pages are never moved or compacted. What we do have
+/// is per-object *age* tracking (see `GcRef::age()` and `GcRef::is_tenured()`):
+/// each collection an object survives bumps its age, which saturates at 3 (the
+/// largest value its two-bit field holds); from `pages::TENURING_AGE` on, the
+/// object counts as tenured.
+/// This is useful on its own (for example, to decide it's safe to intern a
+/// string only once it has proven long-lived), but it is not the same thing
+/// as a real nursery. A real generational collector would need macro-generated
+/// write barriers on every setter, so that a young object reachable only
+/// through a mutated field of a tenured object isn't missed by a
+/// tenured-skipping minor collection. Cell-gc doesn't have those barriers yet,
+/// so `force_gc` always does the full, safe thing.
+///
+/// ### On compaction
+///
+/// Similarly, cell-gc never moves an object once it's allocated. Doing so
+/// would mean walking every `Pointer<T>` reachable from the roots (and every
+/// pinned `GcRef` on the native stack) and rewriting it, which would require
+/// a relocation hook on `IntoHeap` that no type here implements. See
+/// `GcHeapSession::collect_compacting` for the (non-moving) approximation
+/// this crate offers instead.
+///
+/// ### On scoped arenas
+///
+/// There's no way to allocate into a nested region and bulk-free it without
+/// tracing, the way an arena allocator would. Pages belong to a `PageSet`
+/// keyed only by type (see the module docs on `pages`), with no notion of
+/// "everything allocated since this point"; giving objects a region tag and
+/// making sweep aware of it would be a real feature, but it isn't this one.
+/// `GcHeapSession::scope` is the honest approximation: it runs a closure and
+/// then does a full `collect_compacting`, so temporaries allocated inside
+/// the closure that didn't escape it are freed at the boundary, same as an
+/// arena would free them, just by tracing the whole heap instead of skipping
+/// the trace.
+///
+/// ### On deterministic iteration order
+///
+/// Given the same sequence of API calls, this crate's internal iteration
+/// order --- which `PageSet` a type's allocations land in, which order
+/// `mark` visits roots in, which order `GcHeap::types` lists types in --- is
+/// the same on every run, on every machine: it never depends on ASLR or on
+/// the random hash seed Rust's default `HashMap` picks per process. That's
+/// why `page_sets` and `finalizable_queue` below are keyed with
+/// `BuildTrivialHasher` instead of the default hasher, and why
+/// `persistent_roots` is a `BTreeMap` (ordered by registration id) instead
+/// of a `HashMap`.
+///
+/// What this does *not* cover is object identity as exposed by `dump`,
+/// `dump_dot`, and `debug::fmt_graph`: those necessarily print real
+/// addresses (see `dump`'s docs on why), and addresses depend on ASLR and
+/// on however the process's allocator happens to lay out memory that run.
+/// Diffing two dumps byte-for-byte across runs isn't something this crate
+/// promises; matching up the *shape* of the object graph (types, edge
+/// counts, which nodes are shared or cyclic) is.
 pub struct GcHeap {
     /// Map from heap types to the set of pages for that type.
     ///
@@ -126,18 +197,187 @@ pub struct GcHeap {
     /// when the heap grows beyond a certain factor in size. Currently this
     /// factor is about 1.5x, see `Heap::gc`.
     alloc_counter: usize,
+
+    /// If set, the heap will refuse to allocate a new page once doing so
+    /// would bring the heap's total page footprint above this many bytes,
+    /// even if a collection is tried first. See `GcHeap::with_max_size`.
+    max_size_bytes: Option<usize>,
+
+    /// Controls when `self.gc_counter` gets reset after a collection. See
+    /// `GcHeap::set_collection_policy`.
+    policy: CollectionPolicy,
+
+    /// If set, every this-many collections, any pages left completely empty
+    /// are automatically released back to the operating system (as if
+    /// `GcHeapSession::shrink_to_fit` had been called). See
+    /// `GcHeap::set_auto_release`.
+    auto_release_after: Option<usize>,
+
+    /// Number of collections since pages were last released, automatically
+    /// or via `GcHeapSession::shrink_to_fit`.
+    collections_since_release: usize,
+
+    /// One callback per live `weak_ref::WeakRef`, checked on every
+    /// collection. Each closure closes over a `Weak` back-reference to the
+    /// `WeakRef`'s slot, so it returns `false` (asking to be forgotten) once
+    /// the `WeakRef` itself has been dropped, and otherwise nulls out the
+    /// slot if the target didn't survive this collection.
+    weak_refs: Vec<Box<Fn() -> bool + Send>>,
+
+    /// Objects registered via `GcHeapSession::register_finalizable` that
+    /// haven't yet been found dead, keyed by their `In` type's `TypeId` so
+    /// `take_finalizable::<T>` can find them again. See
+    /// `resurrect_dead_finalizables`.
+    pending_finalizable: Vec<(TypeId, UntypedPointer)>,
+
+    /// Objects `resurrect_dead_finalizables` found unreachable and keeps
+    /// alive, collection after collection, until
+    /// `GcHeapSession::take_finalizable` drains them, grouped by their `In`
+    /// type's `TypeId`.
+    finalizable_queue: HashMap<TypeId, Vec<UntypedPointer>, BuildTrivialHasher>,
+
+    /// Callbacks for embedders that want to see GC activity as it happens.
+    /// See `set_gc_observer`.
+    gc_observer: Option<Box<GcObserver + Send>>,
+
+    /// Where this heap's pages come from. See `set_page_source`.
+    page_source: Box<dyn PageSource + Send>,
+
+    /// Allocation counts and bytes recorded per type (and, if the
+    /// `alloc-profile` feature is enabled, per call site). See
+    /// `allocation_report`.
+    alloc_profile: alloc_profile::AllocProfiler,
+
+    /// Roots registered with `GcHeapSession::register_expected_root`, kept
+    /// pinned forever. See `check_for_leaks`.
+    expected_roots: Vec<UntypedPointer>,
+
+    /// Whether to run a leak check when this heap is dropped. See
+    /// `enable_leak_check`.
+    leak_check_enabled: bool,
+
+    /// If set, collect before every single allocation instead of on the
+    /// heap's usual schedule. See `set_stress_mode`.
+    stress_mode: bool,
+
+    /// Whether to treat every stack slot that looks like a live object's
+    /// address as a root, in addition to the ordinary root set, during
+    /// marking. See `set_conservative_stack_scanning`.
+    conservative_stack_scanning: bool,
+
+    /// The stack pointer captured by `enter` when this heap's current
+    /// session began, if `conservative_stack_scanning` is on: the "shallow"
+    /// end of the range `mark` scans. `None` if no session is open, or
+    /// scanning is off.
+    conservative_stack_bottom: Option<usize>,
+
+    /// Number of `StorageBorrowGuard`s currently outstanding. See
+    /// `GcRef::with_storage`. While this is nonzero, `gc` refuses to run,
+    /// since a live `&T::In` could be pointing at memory a collection is
+    /// free to sweep or move data behind.
+    storage_borrows: usize,
+
+    /// Type-erased trace pointers for outstanding `Rooted` handles, keyed
+    /// by an id private to `register_root`/`unregister_root`. See
+    /// `Rooted` and `GcHeapSession::root`.
+    ///
+    /// A `BTreeMap`, not a `HashMap`, so `trace_persistent_roots` always
+    /// visits roots in ascending id (i.e. registration) order --- the same
+    /// order every run, regardless of the process's random hash seed. That
+    /// keeps mark order, and so the order a compacting collection settles
+    /// on for surviving objects, reproducible across runs for the same
+    /// sequence of `root`/`GcHeapSession::alloc` calls.
+    persistent_roots: BTreeMap<usize, *const dyn ErasedTraceable>,
+
+    /// Next id `register_root` will hand out.
+    next_root_id: usize,
+
+    /// Shadow stack of trace pointers pushed by `ShadowRoot` (typically via
+    /// the `gc_root!` macro), most-recently-pushed last. See
+    /// `GcHeapSession::push_root`.
+    shadow_stack: Vec<*const dyn ErasedTraceable>,
+
+    /// State of an in-progress incremental collection started by
+    /// `GcHeapSession::step_collection`, or `None` between collections.
+    /// See its docs.
+    incremental_collection: Option<IncrementalCollection>,
+}
+
+/// Bookkeeping for an in-progress `GcHeapSession::step_collection`, kept on
+/// `GcHeap` between calls. `mark` is the resumable mark-phase state itself
+/// (see `IncrementalMark`); `start` and `mark_duration` accumulate the
+/// timing `CollectionStats` reports once the collection finishes, the same
+/// way `gc`'s local variables of the same names do for an ordinary,
+/// uninterrupted collection.
+struct IncrementalCollection {
+    start: Instant,
+    mark_duration: Duration,
+    mark: IncrementalMark,
 }
 
+// `GcHeap` is `Send` so that, for example, a thread pool can own a pool of
+// heaps and hand them out to workers: create a heap on one thread, send it
+// to another, and `enter` it there, as long as the two `enter` calls don't
+// overlap (which `&mut GcHeap` already enforces).
+//
+// This is sound only because every type that can go into the heap is
+// required to be `Send` in its own right --- see the `Send` bounds on
+// `GcLeaf<T>`, `Box<T>`, and `Arc<T>` in `traits.rs` (there is deliberately
+// no such impl for `Rc<T>`, since `Rc`'s non-atomic refcount can never be
+// made `Send`), and the `Send` bounds on `GcHeapSession::set_gc_observer`
+// and the weak-ref callbacks registered by `WeakRef`. `page_sets` itself is
+// type-erased (`HashMap<TypeId, PageSet, _>`), so there's no way to make
+// this impl's soundness depend on a generic parameter; it depends on every
+// one of those bounds instead.
 unsafe impl Send for GcHeap {}
 
 /// An opaque unique id for heaps.
 #[derive(Clone)]
 pub struct HeapId(Weak<Mutex<Vec<UntypedPointer>>>);
 
+/// A guard returned by `GcHeap::begin_storage_borrow`, held for as long as
+/// some code has a direct `&T::In` reference into the heap. See
+/// `GcRef::with_storage`.
+pub(crate) struct StorageBorrowGuard {
+    heap: *mut GcHeap,
+}
+
+impl Drop for StorageBorrowGuard {
+    fn drop(&mut self) {
+        unsafe {
+            (*self.heap).storage_borrows -= 1;
+        }
+    }
+}
+
+/// Type-erased tracing for a `Rooted` handle's value: the heap's root
+/// registry can't be generic over every type anyone ever roots, so it
+/// stores trait objects instead. See `Rooted`.
+pub(crate) trait ErasedTraceable {
+    unsafe fn erased_trace(&self, tracer: &mut MarkingTracer);
+}
+
+impl<U: InHeap> ErasedTraceable for U {
+    unsafe fn erased_trace(&self, tracer: &mut MarkingTracer) {
+        self.trace(tracer);
+    }
+}
+
 /// What does this do? You'll never guess!
 pub type HeapSessionId<'h> = PhantomData<::std::cell::Cell<&'h mut ()>>;
 
 /// Exclusive access to a GC heap.
+///
+/// You can have any number of `GcHeap`s alive in one thread at once, each
+/// with its own `GcHeapSession`. Their `'h` lifetimes come from `enter`'s
+/// higher-ranked `for<'h> FnOnce(&mut GcHeapSession<'h>)` callback signature,
+/// which invents a fresh lifetime on every call that can't be unified with
+/// any other lifetime, including the `'h` of a different heap's session.
+/// That's what makes it a compile error to store a `GcRef<'h1, _>` where a
+/// `GcRef<'h2, _>` is expected, even if the two heaps hold the exact same
+/// type `T` --- so ordinary `GcRef`s can never be mixed up between heaps.
+/// (Types that deliberately give up the `'h` brand, like `GcFrozenRef` and
+/// `PinnedRef`, check at runtime instead; see their docs.)
 pub struct GcHeapSession<'h> {
     id: HeapSessionId<'h>,
 
@@ -163,6 +403,166 @@ where
 const GC_COUNTER_START: usize = 2048;
 const MIN_ALLOCS_BEFORE_GC: usize = GC_COUNTER_START;
 
+/// How much mark-stack work `GcHeapSession::safepoint` does per call, when
+/// there's an incremental collection open to advance.
+const SAFEPOINT_FUEL: usize = 64;
+
+/// One entry in the list returned by `GcHeap::types`.
+#[derive(Clone, Debug)]
+pub struct TypeInfo {
+    /// The type's name, from `std::any::type_name`.
+    pub name: &'static str,
+
+    /// Size in bytes of one allocation of this type, mark word included.
+    pub size: usize,
+
+    /// How many objects of this type are currently live.
+    pub live_count: usize,
+}
+
+/// Controls when a `GcHeapSession` triggers an automatic collection.
+///
+/// cell-gc schedules collections by counting allocations, not bytes:
+/// allocation sizes vary from one type to the next (see `TypeInfo::size`), so
+/// "objects allocated" is the number this crate already tracks and can
+/// compare cheaply on every `alloc` call.
+///
+/// Set the policy with `GcHeap::set_collection_policy`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CollectionPolicy {
+    /// Collect once at least this many objects have been allocated since the
+    /// last collection (or since the heap was created).
+    EveryNAllocs(usize),
+
+    /// Collect once the number of live objects has grown by this factor
+    /// since the last collection. This is the default policy, with a factor
+    /// of 4.0.
+    GrowBy(f64),
+
+    /// Never collect automatically. Only an explicit call to
+    /// `GcHeapSession::force_gc` (or `collect_compacting`) triggers a
+    /// collection.
+    Manual,
+}
+
+/// Statistics about a single collection, returned by
+/// `GcHeapSession::force_gc`, `GcHeapSession::collect_compacting`, and
+/// (once it finishes) `GcHeapSession::step_collection`.
+#[derive(Clone, Copy, Debug)]
+pub struct CollectionStats {
+    /// Wall-clock time the collection took, in total.
+    pub duration: Duration,
+
+    /// Wall-clock time the mark phase took. On a large heap, this is
+    /// typically most of `duration`; see the note on background marking
+    /// below.
+    pub mark_duration: Duration,
+
+    /// Wall-clock time the sweep phase took.
+    pub sweep_duration: Duration,
+
+    /// Number of distinct objects the mark phase found reachable.
+    pub objects_marked: usize,
+
+    /// Number of objects that were unreachable and were dropped.
+    pub objects_swept: usize,
+
+    /// Total size, in bytes, of the cells that were swept. This doesn't
+    /// count any data those objects owned via a separate heap allocation,
+    /// e.g. a `Vec`'s backing buffer (see the "large-object space" note on
+    /// the `pages` module).
+    pub bytes_freed: usize,
+
+    /// Number of now-empty pages returned to the operating system.
+    /// `force_gc` releases pages only when `GcHeap::set_auto_release` is on
+    /// and this is the collection a release is due after, so otherwise this
+    /// is 0 for it; see `GcHeapSession::collect_compacting`.
+    pub pages_released: usize,
+
+    /// Whether this collection ran past a deadline passed to
+    /// `GcHeapSession::collect_with_deadline`. Always `false` for every
+    /// other way of collecting, since they don't take a deadline to miss.
+    pub deadline_missed: bool,
+}
+
+/// Returned by `GcHeapSession::step_collection`.
+#[derive(Clone, Copy, Debug)]
+pub enum CollectionStep {
+    /// The collection isn't finished; call `step_collection` again to keep
+    /// going. Nothing may be allocated on this heap until it is.
+    InProgress,
+
+    /// The collection is finished, with these stats, exactly as if
+    /// `force_gc` had run the whole thing in one call.
+    Finished(CollectionStats),
+}
+
+/// Returned by `GcHeapSession::reserve` when a reservation can't be
+/// honored in full.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ReservationError {
+    /// The number of allocations that were requested.
+    pub requested: usize,
+
+    /// How many fewer allocations than requested the heap's size limit or
+    /// page limit would allow.
+    pub shortfall: usize,
+}
+
+/// Callbacks for embedders that want to observe GC activity as it happens,
+/// e.g. to feed a tracing or metrics framework. Set one with
+/// `GcHeap::set_gc_observer`.
+///
+/// Every method has a do-nothing default, so implementors only need to
+/// override the events they actually care about.
+pub trait GcObserver {
+    /// Called right before a collection begins marking.
+    fn on_collection_start(&mut self) {}
+
+    /// Called right after a collection finishes, including its statistics.
+    fn on_collection_end(&mut self, _stats: &CollectionStats) {}
+
+    /// Called after a collection, once, with the number of objects that
+    /// survived enough collections during it to become tenured (see
+    /// `pages::TENURING_AGE`). Not called if nothing was promoted.
+    fn on_promotion(&mut self, _count: usize) {}
+
+    /// Called whenever an allocation through `GcHeapSession::alloc` (or
+    /// `try_alloc`, `alloc_init`, `alloc_iter`) makes the heap ask the
+    /// operating system for a new page to hold allocations of the given
+    /// type. Pages created ahead of time by `GcHeapSession::reserve`
+    /// (including `alloc_iter`'s up-front reservation), or by `adopt` and
+    /// `deserialize`, are not reported.
+    fn on_page_alloc(&mut self, _type_id: TypeId) {}
+
+    /// Called whenever an empty page of the given type is returned to the
+    /// operating system (via `GcHeapSession::shrink_to_fit`,
+    /// `GcHeapSession::collect_compacting`, or `GcHeap::set_auto_release`).
+    fn on_page_release(&mut self, _type_id: TypeId) {}
+}
+
+impl Default for CollectionPolicy {
+    fn default() -> CollectionPolicy {
+        CollectionPolicy::GrowBy(4.0)
+    }
+}
+
+impl CollectionPolicy {
+    /// Given the number of objects currently live (just after a collection,
+    /// or 0 for a brand new heap), return the number of further allocations
+    /// to allow before the next automatic collection.
+    fn next_gc_counter(&self, alloc_counter: usize) -> usize {
+        match *self {
+            CollectionPolicy::EveryNAllocs(n) => cmp::max(n, 1),
+            CollectionPolicy::GrowBy(factor) => {
+                let extra = ((alloc_counter as f64) * (factor - 1.0)) as usize;
+                cmp::max(extra, MIN_ALLOCS_BEFORE_GC)
+            }
+            CollectionPolicy::Manual => usize::max_value(),
+        }
+    }
+}
+
 impl GcHeap {
     /// Create a new, empty heap.
     pub fn new() -> GcHeap {
@@ -172,9 +572,338 @@ impl GcHeap {
             dropped_frozen_ptrs: Arc::new(Mutex::new(Vec::new())),
             gc_counter: GC_COUNTER_START,
             alloc_counter: 0,
+            max_size_bytes: None,
+            policy: CollectionPolicy::default(),
+            auto_release_after: None,
+            collections_since_release: 0,
+            weak_refs: Vec::new(),
+            pending_finalizable: Vec::new(),
+            finalizable_queue: HashMap::with_hasher(BuildTrivialHasher),
+            gc_observer: None,
+            page_source: Box::new(GlobalPageSource),
+            alloc_profile: Default::default(),
+            expected_roots: Vec::new(),
+            leak_check_enabled: false,
+            stress_mode: false,
+            conservative_stack_scanning: false,
+            conservative_stack_bottom: None,
+            storage_borrows: 0,
+            persistent_roots: BTreeMap::new(),
+            next_root_id: 0,
+            shadow_stack: Vec::new(),
+            incremental_collection: None,
+        }
+    }
+
+    /// Register `observer` to be notified of GC activity from now on,
+    /// replacing any previously set observer. See `GcObserver`.
+    ///
+    /// `O: Send` because the heap itself is `Send` (see `GcHeap`'s docs on
+    /// sending heaps between threads); an observer that wasn't `Send` could
+    /// smuggle non-`Send` data along for the ride.
+    pub fn set_gc_observer<O: GcObserver + Send + 'static>(&mut self, observer: O) {
+        self.gc_observer = Some(Box::new(observer));
+    }
+
+    /// Replace this heap's `PageSource`, changing where its pages' backing
+    /// memory comes from. The default is `GlobalPageSource`, which uses
+    /// `std::alloc`.
+    ///
+    /// Call this before allocating anything: a page is freed through
+    /// whatever `PageSource` is current at the time, not the one that
+    /// allocated it, so swapping sources on a heap that already has live
+    /// pages would free them through the wrong allocator.
+    ///
+    /// `S: Send` for the same reason `set_gc_observer`'s `O` is.
+    pub fn set_page_source<S: PageSource + Send + 'static>(&mut self, source: S) {
+        self.page_source = Box::new(source);
+    }
+
+    /// Allocate `size` bytes aligned to `align` from this heap's
+    /// `PageSource`.
+    pub(crate) unsafe fn alloc_page_bytes(&mut self, size: usize, align: usize) -> *mut u8 {
+        self.page_source.alloc_page(size, align)
+    }
+
+    /// Free memory previously returned by `alloc_page_bytes` with the same
+    /// `size` and `align`.
+    pub(crate) unsafe fn dealloc_page_bytes(&mut self, ptr: *mut u8, size: usize, align: usize) {
+        self.page_source.dealloc_page(ptr, size, align);
+    }
+
+    /// Get a snapshot of allocation counts and bytes recorded so far, broken
+    /// down by type and call site. Always empty unless this crate is built
+    /// with the `alloc-profile` feature. See the `alloc_profile` module.
+    pub fn allocation_report(&self) -> AllocationReport {
+        self.alloc_profile.report()
+    }
+
+    /// Get the `n` call sites responsible for the most allocated bytes so
+    /// far (summed across every type allocated at that site), heaviest
+    /// first. Always empty unless this crate is built with the
+    /// `alloc-profile` feature. See the `alloc_profile` module and
+    /// `AllocationSite::bytes` for what "bytes" means here.
+    pub fn top_allocation_sites(&self, n: usize) -> Vec<AllocationSite> {
+        self.alloc_profile.top_allocation_sites(n)
+    }
+
+    /// Enable a leak check when this heap is dropped, in debug builds only
+    /// (a no-op in release builds; see the `debug_assertions` cfg). See
+    /// `check_for_leaks`.
+    pub fn enable_leak_check(&mut self) {
+        self.leak_check_enabled = true;
+    }
+
+    /// Run a leak check now: mark from every currently pinned root, then
+    /// again from only the roots registered with
+    /// `GcHeapSession::register_expected_root`, and report the types (and
+    /// counts) of objects alive in the first pass but not the second, i.e.
+    /// alive only because of some pin or root the caller didn't tell us to
+    /// expect.
+    ///
+    /// If `enable_leak_check` was called, this also runs automatically when
+    /// the heap is dropped (in debug builds), printing anything it finds to
+    /// stderr.
+    pub fn check_for_leaks(&mut self) -> LeakReport {
+        leak_check::check_for_leaks(self)
+    }
+
+    pub(crate) fn expected_roots(&self) -> &[UntypedPointer] {
+        &self.expected_roots
+    }
+
+    /// Turn deterministic stress-testing mode on or off. While it's on, a
+    /// full collection happens before every single allocation (instead of
+    /// on the usual schedule; see `CollectionPolicy`), so that a missing
+    /// root in embedder code or a macro-generated `mark` implementation
+    /// causes a use-after-free almost immediately instead of only under
+    /// memory pressure. Debug and test builds already poison swept memory
+    /// (see `TypedPage::sweep` in `pages`), so combine this with a debug build
+    /// to make such bugs crash close to their cause.
+    ///
+    /// This is far too slow for anything but testing.
+    pub fn set_stress_mode(&mut self, enabled: bool) {
+        self.stress_mode = enabled;
+    }
+
+    /// Turn conservative native-stack scanning on or off. While it's on,
+    /// every collection also treats each word of the stack, between where
+    /// `enter` was called and wherever the collection was actually
+    /// triggered from, that happens to hold the exact address of a live
+    /// object as an additional root --- so a `Ref` a builtin is holding in a
+    /// local variable or a `Vec<Value>` of arguments stays alive without
+    /// needing to be pinned or stored in another heap object first.
+    ///
+    /// This is a much cheaper way to prototype embedder code than hand-
+    /// rooting everything, but it's inherently imprecise: a stale word left
+    /// over in a stack slot cell-gc happens to reuse can keep an object
+    /// alive longer than it should (never *incorrectly* freed, just
+    /// occasionally leaked a little late), and it only ever scans between
+    /// `enter` and the current call, not the whole thread's stack, so a
+    /// pointer stashed below where the current session was entered won't be
+    /// found. Don't rely on it for anything you're not willing to debug by
+    /// hand; use explicit rooting once the prototype hardens.
+    pub fn set_conservative_stack_scanning(&mut self, enabled: bool) {
+        self.conservative_stack_scanning = enabled;
+        if !enabled {
+            self.conservative_stack_bottom = None;
+        }
+    }
+
+    /// The page size and alignment this heap allocates pages with. See
+    /// `pages::PageGeometry` for why this isn't currently configurable.
+    pub fn page_geometry(&self) -> pages::PageGeometry {
+        pages::geometry()
+    }
+
+    /// Check the heap for consistency, returning a description of each
+    /// problem found. An empty result doesn't prove the heap is correct,
+    /// only that this particular set of checks didn't find anything wrong;
+    /// see `verify`'s module docs for what is and isn't checked.
+    ///
+    /// With the `debug-heap-checks` feature enabled, this runs
+    /// automatically after every collection, panicking if it finds a
+    /// problem.
+    pub fn verify(&self) -> Vec<String> {
+        verify::verify(self)
+    }
+
+    pub(crate) fn notify_page_alloc(&mut self, type_id: TypeId) {
+        if let Some(observer) = self.gc_observer.as_mut() {
+            observer.on_page_alloc(type_id);
+        }
+    }
+
+    pub(crate) fn notify_page_release(&mut self, type_id: TypeId) {
+        if let Some(observer) = self.gc_observer.as_mut() {
+            observer.on_page_release(type_id);
+        }
+    }
+
+    /// The page set holding all `U` values in this heap, creating it if this
+    /// is the first `U` anyone's asked about. Used by `GcHeapSession` (for
+    /// ordinary allocation) and by `adopt::Adopter` (which only has a
+    /// `&mut GcHeap`, not a whole session, to work with).
+    pub(crate) fn get_page_set<'a, U: InHeap>(&'a mut self) -> PageSetRef<'a, U> {
+        let key = pages::heap_type_id::<U>();
+        let heap: *mut GcHeap = self;
+        self.page_sets
+            .entry(key)
+            .or_insert_with(|| unsafe { PageSet::new::<U>(heap) })
+            .downcast_mut()
+    }
+
+    /// Register a callback for `check_weak_refs` to run on every future
+    /// collection. See `weak_ref::WeakRef`.
+    pub(crate) fn register_weak_ref<F: Fn() -> bool + Send + 'static>(&mut self, f: F) {
+        self.weak_refs.push(Box::new(f));
+    }
+
+    /// Give every registered `WeakRef` a chance to notice that its target
+    /// didn't survive this collection, and drop bookkeeping for any
+    /// `WeakRef` that has itself been dropped.
+    ///
+    /// Must run after marking and before sweeping, so a `WeakRef` can still
+    /// check its target's mark bit before the object is actually freed.
+    fn check_weak_refs(&mut self) {
+        self.weak_refs.retain(|is_live| is_live());
+    }
+
+    /// Watch `ptr` (an allocation of the `In` type identified by `type_id`)
+    /// for `resurrect_dead_finalizables` to check on future collections.
+    pub(crate) fn register_finalizable(&mut self, type_id: TypeId, ptr: UntypedPointer) {
+        self.pending_finalizable.push((type_id, ptr));
+    }
+
+    /// Take every pointer `resurrect_dead_finalizables` has queued for the
+    /// `In` type identified by `type_id`, if any.
+    pub(crate) fn take_finalizable_ptrs(&mut self, type_id: TypeId) -> Vec<UntypedPointer> {
+        self.finalizable_queue.remove(&type_id).unwrap_or_default()
+    }
+
+    /// Give every object registered via `GcHeapSession::register_finalizable`
+    /// a chance to survive this collection normally; anything still unmarked
+    /// is genuinely dead, so instead of letting sweep destroy it, mark it
+    /// (so sweep leaves it alone this time) and move it to the finalizable
+    /// queue for `GcHeapSession::take_finalizable` to hand back to the
+    /// embedder later, outside of the sweep phase.
+    ///
+    /// Must run after the ordinary mark phase reaches a fix point, and
+    /// before `MarkingTracer::resolve_ephemerons`, so that a resurrected
+    /// object can still keep an `Ephemeron` key it references from being
+    /// wrongly declared dead.
+    pub(crate) fn resurrect_dead_finalizables(&mut self, tracer: &mut MarkingTracer) {
+        // Objects already queued by an earlier collection stay alive until
+        // `take_finalizable` drains them.
+        if !self.finalizable_queue.is_empty() {
+            for ptrs in self.finalizable_queue.values() {
+                for &ptr in ptrs {
+                    unsafe {
+                        (*PageHeader::find(ptr)).mark(ptr, tracer);
+                    }
+                }
+            }
+            tracer.mark_to_fix_point();
+        }
+
+        let pending = mem::replace(&mut self.pending_finalizable, Vec::new());
+        let mut resurrected_any = false;
+        for (type_id, ptr) in pending {
+            if unsafe { pages::get_mark_bit_untyped(ptr) } {
+                self.pending_finalizable.push((type_id, ptr));
+            } else {
+                unsafe {
+                    (*PageHeader::find(ptr)).mark(ptr, tracer);
+                }
+                self.finalizable_queue
+                    .entry(type_id)
+                    .or_insert_with(Vec::new)
+                    .push(ptr);
+                resurrected_any = true;
+            }
+        }
+        if resurrected_any {
+            tracer.mark_to_fix_point();
         }
     }
 
+    /// Get the current auto-release setting. See `set_auto_release`.
+    pub fn auto_release(&self) -> Option<usize> {
+        self.auto_release_after
+    }
+
+    /// If `after` is `Some(n)`, automatically release empty pages back to the
+    /// operating system every `n` collections, as if
+    /// `GcHeapSession::shrink_to_fit` were called at the end of every `n`th
+    /// collection. Pass `None` (the default) to only release pages when
+    /// `shrink_to_fit` or `collect_compacting` is called explicitly.
+    ///
+    /// This is meant for long-running embedders that would rather pay a
+    /// little extra sweeping cost periodically than hold peak RSS forever
+    /// after a workload spike.
+    pub fn set_auto_release(&mut self, after: Option<usize>) {
+        self.auto_release_after = after;
+        self.collections_since_release = 0;
+    }
+
+    /// Get the current collection-triggering policy. See
+    /// `set_collection_policy`.
+    pub fn collection_policy(&self) -> CollectionPolicy {
+        self.policy
+    }
+
+    /// Choose when this heap triggers automatic collections. The default is
+    /// `CollectionPolicy::GrowBy(4.0)`.
+    ///
+    /// This only affects automatic collections; `GcHeapSession::force_gc`
+    /// always collects immediately regardless of policy.
+    pub fn set_collection_policy(&mut self, policy: CollectionPolicy) {
+        self.policy = policy;
+        self.gc_counter = policy.next_gc_counter(self.alloc_counter);
+    }
+
+    /// Create a new, empty heap that will never grow its page footprint
+    /// beyond `max_size_bytes`.
+    ///
+    /// This is a soft, approximate limit: cell-gc allocates in whole
+    /// `pages::PAGE_SIZE` pages, shared by every object of a given type, so
+    /// the limit is checked in units of pages, not individual allocations.
+    /// Once the limit would be exceeded even after a full collection,
+    /// `GcHeapSession::try_alloc` returns `None` (and `alloc` panics) instead
+    /// of growing further.
+    pub fn with_max_size(max_size_bytes: usize) -> GcHeap {
+        let mut heap = GcHeap::new();
+        heap.max_size_bytes = Some(max_size_bytes);
+        heap
+    }
+
+    /// Get the heap size limit set by `with_max_size`, if any.
+    pub fn max_size(&self) -> Option<usize> {
+        self.max_size_bytes
+    }
+
+    /// Set (or clear) the heap size limit. See `with_max_size`.
+    pub fn set_max_size(&mut self, max_size_bytes: Option<usize>) {
+        self.max_size_bytes = max_size_bytes;
+    }
+
+    /// Create a new, empty heap whose pages come from `source` instead of
+    /// the global allocator. See `set_page_source`.
+    pub fn with_page_source<S: PageSource + Send + 'static>(source: S) -> GcHeap {
+        let mut heap = GcHeap::new();
+        heap.set_page_source(source);
+        heap
+    }
+
+    /// Total number of bytes currently occupied by pages of every type, across
+    /// the whole heap (including any free space within those pages).
+    pub fn page_bytes(&self) -> usize {
+        self.page_sets
+            .values()
+            .map(|page_set| page_set.page_count() * pages::PAGE_SIZE)
+            .sum()
+    }
+
     /// Get this heap's unique id.
     fn id(&self) -> HeapId {
         HeapId(Arc::downgrade(&self.dropped_frozen_ptrs))
@@ -223,6 +952,16 @@ impl GcHeap {
 
     /// Run some code using this GcHeap.
     ///
+    /// `enter` can be called any number of times on the same heap, one
+    /// session after another (never two at once: `&mut self` rules that
+    /// out). Each call invents its own fresh `'h`, so a `GcRef` from one
+    /// session can't leak into the next --- but the heap's contents persist
+    /// across the boundary, so a long-lived program (a REPL, a server) can
+    /// open a session, do some work, close it, and reopen later without
+    /// losing state. To keep something alive across the gap, root it with a
+    /// `PinnedRef` (see its docs) before the session closes, since an
+    /// ordinary `GcRef<'h, _>` can't outlive its session's `'h`.
+    ///
     /// # Example
     ///
     ///     use cell_gc::{GcHeap, GcLeaf};
@@ -233,10 +972,25 @@ impl GcHeap {
     ///         # hs.force_gc();
     ///     });
     ///
+    /// Bridging a value across two sessions with `PinnedRef`:
+    ///
+    ///     use cell_gc::GcHeap;
+    ///
+    ///     let mut heap = GcHeap::new();
+    ///     let pinned = heap.enter(|hs| hs.alloc_pinned(5_i32));
+    ///     // The first session is over; `pinned` isn't tied to it.
+    ///     heap.enter(|hs| {
+    ///         let r = hs.unpin(pinned);
+    ///         assert_eq!(unsafe { r.with_storage(|s| *s) }, 5);
+    ///     });
+    ///
     pub fn enter<R, F>(&mut self, f: F) -> R
     where
         F: for<'h> FnOnce(&mut GcHeapSession<'h>) -> R,
     {
+        if self.conservative_stack_scanning {
+            self.conservative_stack_bottom = Some(stack_scan::capture_stack_pointer());
+        }
         f(&mut self.open())
     }
 
@@ -252,11 +1006,11 @@ impl GcHeap {
         (*TypedPage::find(ptr)).header.heap
     }
 
-    fn take_marking_tracer(&mut self) -> MarkingTracer {
+    pub(crate) fn take_marking_tracer(&mut self) -> MarkingTracer {
         self.marking_tracer.take().expect("attempted nested GC")
     }
 
-    fn replace_marking_tracer(&mut self, tracer: MarkingTracer) {
+    pub(crate) fn replace_marking_tracer(&mut self, tracer: MarkingTracer) {
         assert!(self.marking_tracer.is_none());
         assert!(tracer.mark_stack_is_empty());
         self.marking_tracer = Some(tracer);
@@ -288,6 +1042,23 @@ impl GcHeap {
         }
     }
 
+    /// The stack pointer captured by `enter` for this heap's current
+    /// session, if `set_conservative_stack_scanning` is on. See `mark`.
+    pub(crate) fn conservative_stack_bottom(&self) -> Option<usize> {
+        self.conservative_stack_bottom
+    }
+
+    /// The address of every live (as opposed to free-listed) object in this
+    /// heap, for conservative stack scanning to check candidate stack words
+    /// against. See `mark`.
+    pub(crate) fn live_object_addresses(&self) -> HashSet<usize> {
+        let mut addresses = HashSet::new();
+        self.for_each_live_object(|ptr, _page| {
+            addresses.insert(ptr.as_usize());
+        });
+        addresses
+    }
+
     fn unpin_dropped_ptrs(&mut self) {
         let dropped_ptrs = {
             let mut guard = self.dropped_frozen_ptrs.lock().unwrap();
@@ -304,17 +1075,143 @@ impl GcHeap {
         }
     }
 
+    /// Start (or extend) a borrow of some object's in-heap storage. See
+    /// `GcRef::with_storage`. The returned guard must outlive every use of
+    /// the borrowed `&T::In`; `gc` panics if one is still outstanding when
+    /// it runs.
+    pub(crate) unsafe fn begin_storage_borrow(&mut self) -> StorageBorrowGuard {
+        self.storage_borrows += 1;
+        StorageBorrowGuard {
+            heap: self as *mut GcHeap,
+        }
+    }
+
+    /// Register a persistent root's trace pointer, returning an id
+    /// `unregister_root` can later use to remove it. See `Rooted`.
+    ///
+    /// # Safety
+    ///
+    /// `root` must stay valid (i.e. the value it points at must not move or
+    /// be dropped) until it's passed to `unregister_root`.
+    pub(crate) unsafe fn register_root(&mut self, root: *const dyn ErasedTraceable) -> usize {
+        let id = self.next_root_id;
+        self.next_root_id += 1;
+        self.persistent_roots.insert(id, root);
+        id
+    }
+
+    /// Stop tracing the root registered under `id`. See `Rooted`.
+    pub(crate) unsafe fn unregister_root(&mut self, id: usize) {
+        self.persistent_roots.remove(&id);
+    }
+
+    /// Trace every outstanding `Rooted` handle's value, so `mark` protects
+    /// whatever they reach in addition to the ordinary root set. See
+    /// `Rooted`.
+    pub(crate) fn trace_persistent_roots(&self, tracer: &mut MarkingTracer) {
+        for &root in self.persistent_roots.values() {
+            unsafe {
+                (*root).erased_trace(tracer);
+            }
+        }
+    }
+
+    /// Push a `ShadowRoot`'s trace pointer onto the shadow stack. See
+    /// `GcHeapSession::push_root`.
+    ///
+    /// # Safety
+    ///
+    /// `root` must stay valid until it's passed to `pop_shadow_root`, and
+    /// every push/pop pair must nest like ordinary local variables (i.e.
+    /// LIFO) with every other push/pop pair on this heap.
+    pub(crate) unsafe fn push_shadow_root(&mut self, root: *const dyn ErasedTraceable) {
+        self.shadow_stack.push(root);
+    }
+
+    /// Pop `root` off the shadow stack. See `push_shadow_root`.
+    pub(crate) unsafe fn pop_shadow_root(&mut self, root: *const dyn ErasedTraceable) {
+        let popped = self.shadow_stack.pop();
+        debug_assert!(
+            popped.map(|p| p as *const ()) == Some(root as *const ()),
+            "cell-gc: a ShadowRoot was dropped out of order; gc_root!() bindings must nest like ordinary local variables"
+        );
+    }
+
+    /// Trace every value currently on the shadow stack, so `mark` protects
+    /// whatever they reach in addition to the ordinary root set. See
+    /// `push_shadow_root`.
+    pub(crate) fn trace_shadow_stack(&self, tracer: &mut MarkingTracer) {
+        for &root in &self.shadow_stack {
+            unsafe {
+                (*root).erased_trace(tracer);
+            }
+        }
+    }
+
+    /// The shadow stack's current depth. See `HandleScope`.
+    pub(crate) fn shadow_stack_len(&self) -> usize {
+        self.shadow_stack.len()
+    }
+
+    /// Pop the shadow stack back down to `len` entries at once. See
+    /// `HandleScope`, which uses this to release every handle it created in
+    /// one call instead of one at a time.
+    ///
+    /// # Safety
+    ///
+    /// Every entry above `len` must not be reachable any other way (e.g.
+    /// through a live `ShadowRoot`), or that root's `Drop` will underflow
+    /// this stack later.
+    pub(crate) unsafe fn truncate_shadow_stack(&mut self, len: usize) {
+        self.shadow_stack.truncate(len);
+    }
+
     /// Perform GC.
-    fn gc(&mut self) {
+    fn gc(&mut self) -> CollectionStats {
+        assert_eq!(
+            self.storage_borrows, 0,
+            "cell-gc: cannot collect garbage while in-heap storage is borrowed (see GcRef::with_storage)"
+        );
+
+        let start = Instant::now();
+
+        if let Some(observer) = self.gc_observer.as_mut() {
+            observer.on_collection_start();
+        }
+
         self.unpin_dropped_ptrs();
-        mark(self);
+        let mark_start = Instant::now();
+        let objects_marked = mark(self);
+        self.check_weak_refs();
+        let mark_duration = mark_start.elapsed();
 
+        self.finish_gc(start, mark_duration, objects_marked)
+    }
+
+    /// The shared tail of a collection, from sweep through stats and
+    /// observer bookkeeping. `start` is when the collection as a whole
+    /// began, for `CollectionStats::duration`; `mark_duration` and
+    /// `objects_marked` are the mark phase's own results, computed by
+    /// either `gc` (all at once) or `GcHeapSession::step_collection` (a
+    /// bounded amount at a time).
+    fn finish_gc(
+        &mut self,
+        start: Instant,
+        mark_duration: Duration,
+        objects_marked: usize,
+    ) -> CollectionStats {
         let _sp = signposts::Sweeping::new();
+        let sweep_start = Instant::now();
 
         let mut num_swept = 0;
+        let mut bytes_freed = 0;
+        let mut num_promoted = 0;
         for page_set in self.page_sets.values_mut() {
             unsafe {
-                num_swept += page_set.sweep();
+                let (swept, freed, promoted) = page_set.sweep();
+                num_swept += swept;
+                bytes_freed += freed;
+                num_promoted += promoted;
             }
         }
 
@@ -324,10 +1221,48 @@ impl GcHeap {
         );
         self.alloc_counter -= num_swept;
 
-        // Schedule a GC for when the heap reaches 4x its current size. Unless
-        // the heap is really small, in which case we don't want to set the gc
-        // counter get to some ridiculously low number.
-        self.gc_counter = cmp::max(self.alloc_counter * 3, MIN_ALLOCS_BEFORE_GC);
+        // Schedule the next automatic collection according to the current
+        // policy (by default, when the heap reaches 4x its current size).
+        self.gc_counter = self.policy.next_gc_counter(self.alloc_counter);
+
+        let mut pages_released = 0;
+        if let Some(after) = self.auto_release_after {
+            self.collections_since_release += 1;
+            if self.collections_since_release >= after {
+                pages_released = self.release_empty_pages();
+                self.collections_since_release = 0;
+            }
+        }
+
+        let stats = CollectionStats {
+            duration: start.elapsed(),
+            mark_duration,
+            sweep_duration: sweep_start.elapsed(),
+            objects_marked,
+            objects_swept: num_swept,
+            bytes_freed,
+            pages_released,
+            deadline_missed: false,
+        };
+
+        if let Some(observer) = self.gc_observer.as_mut() {
+            if num_promoted > 0 {
+                observer.on_promotion(num_promoted);
+            }
+            observer.on_collection_end(&stats);
+        }
+
+        #[cfg(feature = "debug-heap-checks")]
+        {
+            let problems = self.verify();
+            assert!(
+                problems.is_empty(),
+                "cell-gc: heap consistency check failed after collection:\n{}",
+                problems.join("\n")
+            );
+        }
+
+        stats
     }
 
     fn is_empty(&self) -> bool {
@@ -335,6 +1270,100 @@ impl GcHeap {
             .values()
             .all(|page_set| page_set.all_pages_are_empty())
     }
+
+    /// Call `f` once for every live object across every type in the heap.
+    /// See `dump::dump`.
+    pub(crate) fn for_each_live_object<F: FnMut(UntypedPointer, &PageHeader)>(&self, mut f: F) {
+        for page_set in self.page_sets.values() {
+            page_set.for_each_live_object(&mut f);
+        }
+    }
+
+    /// Write a snapshot of the object graph currently in the heap to
+    /// `writer`, for offline analysis (e.g. diffing two dumps to find a
+    /// leak). See the `dump` module for the file format.
+    pub fn dump<W: Write>(&self, writer: W) -> io::Result<()> {
+        dump::dump(self, writer)
+    }
+
+    /// List every `IntoHeapAllocation` type this heap has ever allocated a
+    /// page of, with its size and current live count.
+    ///
+    /// This is the type-level counterpart to `dump`: where `dump` reports
+    /// one heap snapshot's individual objects and edges, `types` reports
+    /// what allocation types exist at all, for a debugger, a heap-usage
+    /// summary, or a serializer that needs to know up front which types it
+    /// might be asked to handle. It only knows what `dump` and `check_for_leaks`
+    /// already know per object --- name (from `std::any::type_name`) and
+    /// size --- not field names or offsets, since the macro that implements
+    /// `IntoHeap` doesn't generate that (there's no `Tracer` call it could
+    /// hang per-field metadata off of; see `traits::InHeap::trace`). A type
+    /// stays in this list even after every object of that type is collected
+    /// and its pages released, since the `PageSet` that remembers its name
+    /// and size outlives the pages themselves (see `GcHeap::page_sets`).
+    pub fn types(&self) -> Vec<TypeInfo> {
+        self.page_sets
+            .values()
+            .map(|page_set| TypeInfo {
+                name: page_set.type_name(),
+                size: page_set.allocation_size(),
+                live_count: page_set.live_object_count(),
+            })
+            .collect()
+    }
+
+    /// Snapshot the current live object count for every type this heap has
+    /// used. Compare two snapshots with `Census::diff` to see exactly what
+    /// an operation allocated (and whether it leaked anything), instead of
+    /// comparing total page counts.
+    pub fn census(&self) -> Census {
+        Census::take(self)
+    }
+
+    /// Release every page, across all types, that currently holds no live
+    /// objects. Returns the number of pages released.
+    fn release_empty_pages(&mut self) -> usize {
+        let mut released_by_type = Vec::new();
+        let total = self.page_sets
+            .iter_mut()
+            .map(|(&type_id, page_set)| {
+                let released = unsafe { page_set.release_empty_pages() };
+                if released > 0 {
+                    released_by_type.push((type_id, released));
+                }
+                released
+            })
+            .sum();
+
+        if self.gc_observer.is_some() {
+            for (type_id, count) in released_by_type {
+                for _ in 0..count {
+                    self.notify_page_release(type_id);
+                }
+            }
+        }
+
+        total
+    }
+
+    /// Drop every object in the heap, across all types, and reset every page
+    /// to empty, keeping the pages themselves allocated. Returns the number
+    /// of objects dropped.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure no live `GcRef`, `GcFrozenRef`, or `PinnedRef`
+    /// points into this heap.
+    unsafe fn clear(&mut self) -> usize {
+        let num_cleared: usize = self.page_sets
+            .values_mut()
+            .map(|page_set| page_set.clear_all())
+            .sum();
+        self.alloc_counter = 0;
+        self.gc_counter = self.policy.next_gc_counter(0);
+        self.collections_since_release = 0;
+        num_cleared
+    }
 }
 
 // GcHeap does not need its own destructor, since PageSet's destructor does all
@@ -343,19 +1372,89 @@ impl GcHeap {
 impl Drop for GcHeap {
     fn drop(&mut self) {
         let _sp = signposts::Dropping::new();
+
+        if cfg!(debug_assertions) && self.leak_check_enabled {
+            let report = self.check_for_leaks();
+            if !report.is_empty() {
+                eprintln!(
+                    "cell-gc: leak check found objects alive only because of a forgotten pin or root:"
+                );
+                for entry in &report {
+                    eprintln!("  {} x {}", entry.count, entry.type_name);
+                }
+            }
+        }
+
         self.page_sets.clear();
     }
 }
 
+/// The subset of `GcHeapSession` a builtin (a Lisp primitive procedure, or
+/// any other embedder callback that just needs to allocate, collect, and
+/// root) depends on, factored out so those can be written against `H:
+/// HeapSession<'h>` instead of the concrete `GcHeapSession<'h>`, and tested
+/// against a lightweight fake instead of a real heap.
+///
+/// `alloc` and `root` are generic over the type being allocated/rooted, so
+/// they carry a `where Self: Sized` bound: a trait with an unconstrained
+/// generic method can't go in a vtable, but one that opts out of `dyn`
+/// dispatch for just that method can, and everything below still holds for
+/// `Box<dyn HeapSession>` --- it just can't call `alloc` or `root` through
+/// it. Direct code (the overwhelmingly common case, `hs: &mut impl
+/// HeapSession<'h>`) is unaffected either way.
+pub trait HeapSession<'h> {
+    /// See `GcHeapSession::alloc`.
+    fn alloc<T: IntoHeapAllocation<'h>>(&mut self, value: T) -> T::Ref
+    where
+        Self: Sized;
+
+    /// See `GcHeapSession::root`.
+    fn root<T: IntoHeap<'h>>(&mut self, value: T) -> Rooted<'h, T>
+    where
+        Self: Sized;
+
+    /// See `GcHeapSession::force_gc`.
+    fn force_gc(&mut self) -> CollectionStats;
+
+    /// See `GcHeapSession::safepoint`.
+    fn safepoint(&mut self);
+
+    /// See `GcHeapSession::handle_scope`.
+    fn handle_scope(&mut self) -> HandleScope<'h>;
+
+    /// See `GcHeapSession::is_empty`.
+    fn is_empty(&self) -> bool;
+}
+
+impl<'h> HeapSession<'h> for GcHeapSession<'h> {
+    fn alloc<T: IntoHeapAllocation<'h>>(&mut self, value: T) -> T::Ref {
+        GcHeapSession::alloc(self, value)
+    }
+
+    fn root<T: IntoHeap<'h>>(&mut self, value: T) -> Rooted<'h, T> {
+        GcHeapSession::root(self, value)
+    }
+
+    fn force_gc(&mut self) -> CollectionStats {
+        GcHeapSession::force_gc(self)
+    }
+
+    fn safepoint(&mut self) {
+        GcHeapSession::safepoint(self)
+    }
+
+    fn handle_scope(&mut self) -> HandleScope<'h> {
+        GcHeapSession::handle_scope(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        GcHeapSession::is_empty(self)
+    }
+}
+
 impl<'h> GcHeapSession<'h> {
     fn get_page_set<'a, U: InHeap>(&'a mut self) -> PageSetRef<'a, U> {
-        let key = pages::heap_type_id::<U>();
-        let heap: *mut GcHeap = self.heap;
-        self.heap
-            .page_sets
-            .entry(key)
-            .or_insert_with(|| unsafe { PageSet::new::<U>(heap) })
-            .downcast_mut()
+        self.heap.get_page_set::<U>()
     }
 
     /// Set (or unset) the limit on the number of pages that can be used to
@@ -369,6 +1468,50 @@ impl<'h> GcHeapSession<'h> {
         self.get_page_set::<T::In>().set_page_limit(limit);
     }
 
+    /// Pre-allocate enough pages to hold at least `n` more `T` values, so
+    /// that a latency-sensitive section (an audio callback, a game frame)
+    /// can allocate up to `n` of them without ever calling into the page
+    /// allocator. See `PageSetRef::reserve` for what "enough" means if this
+    /// type already has partially-used pages.
+    ///
+    /// Fails without creating any pages if honoring the request in full
+    /// would grow the heap past `GcHeap::set_max_size` or past a page
+    /// limit set with `set_page_limit`.
+    pub fn reserve<T: IntoHeapAllocation<'h>>(&mut self, n: usize) -> Result<(), ReservationError> {
+        let capacity = pages::TypedPage::<T::In>::capacity();
+        let pages_needed = (n + capacity - 1) / capacity;
+
+        if let Some(limit) = self.get_page_set::<T::In>().page_limit() {
+            let available = limit.saturating_sub(self.get_page_set::<T::In>().page_count());
+            if pages_needed > available {
+                return Err(ReservationError { requested: n, shortfall: n - available * capacity });
+            }
+        }
+
+        if let Some(max_size_bytes) = self.heap.max_size_bytes {
+            let bytes_needed = pages_needed * pages::PAGE_SIZE;
+            let page_bytes = self.heap.page_bytes();
+            if page_bytes + bytes_needed > max_size_bytes {
+                let available = max_size_bytes.saturating_sub(page_bytes) / pages::PAGE_SIZE;
+                return Err(ReservationError { requested: n, shortfall: n - available * capacity });
+            }
+        }
+
+        let shortfall = self.get_page_set::<T::In>().reserve(n);
+        debug_assert_eq!(shortfall, 0, "checked above that this reservation would fit");
+        Ok(())
+    }
+
+    /// Number of pages currently allocated to hold `T` values.
+    ///
+    /// Since each type gets its own page chain (see the module docs on
+    /// `pages`), this is a way to see how much of the heap's `page_bytes()`
+    /// a particular type accounts for, e.g. to notice a type whose objects
+    /// are individually tiny but whose page count is surprisingly large.
+    pub fn type_page_count<T: IntoHeapAllocation<'h>>(&mut self) -> usize {
+        self.get_page_set::<T::In>().page_count()
+    }
+
     /// Allocate memory, moving `value` into the heap.
     ///
     /// If a limit has previously been set using `set_page_limit`, and we run
@@ -376,16 +1519,117 @@ impl<'h> GcHeapSession<'h> {
     /// values, and they are all full of live values), `try_alloc` first
     /// attempts to free some memory by doing garbage collection. If that
     /// doesn't work, `try_alloc` returns `None`.
+    #[track_caller]
     pub fn try_alloc<T: IntoHeapAllocation<'h>>(&mut self, value: T) -> Option<T::Ref> {
-        unsafe {
-            if let Some(allocation) = self.try_fast_alloc::<T>() {
-                let u = value.into_heap();
-                let ptr = allocation.init(u);
-                Some(T::wrap_gc_ref(GcRef::new(ptr)))
-            } else {
-                self.try_slow_alloc(value)
-            }
+        if self.heap.stress_mode {
+            // Collect before every single allocation, so a missing root
+            // shows up as soon as possible instead of surviving by luck
+            // until the next scheduled collection. See `set_stress_mode`.
+            self.heap.gc();
+        }
+        self.try_alloc_after_trigger_check(value)
+    }
+
+    /// The rest of `try_alloc`, after its stress-mode GC-trigger check:
+    /// try the fast path, then fall back to the slow path. Factored out so
+    /// `alloc_iter` can do that check once for a whole batch of values
+    /// instead of once per value.
+    #[track_caller]
+    fn try_alloc_after_trigger_check<T: IntoHeapAllocation<'h>>(&mut self, value: T) -> Option<T::Ref> {
+        assert!(
+            self.heap.incremental_collection.is_none(),
+            "cell-gc: cannot allocate while a step_collection is in progress"
+        );
+        let caller = Location::caller();
+        let result = unsafe {
+            self.try_alloc_slot::<T>().map(|allocation| {
+                let ptr = allocation.init(value.into_heap());
+                T::wrap_gc_ref(GcRef::new(ptr))
+            })
+        };
+        if result.is_some() {
+            self.heap.alloc_profile.record(
+                pages::heap_type_id::<T::In>(),
+                type_name::<T::In>(),
+                mem::size_of::<T::In>(),
+                caller,
+            );
+        }
+        result
+    }
+
+    /// Allocate space for a `T` and initialize it in place, via `init`,
+    /// instead of moving a complete `T` (converted to `T::In`) into the
+    /// heap the way `alloc` does. `init` is given a raw pointer to the
+    /// allocation's future address and must write every field of the
+    /// `T::In` value there itself before returning; see
+    /// `pages::UninitializedAllocation::init_with`.
+    ///
+    /// This is the tool for when `alloc`'s stack-then-copy path is a
+    /// measurable cost: e.g. a struct with a large fixed-size array field,
+    /// where `impl IntoHeapBase for [T; N]` builds the whole array on the
+    /// stack before it's moved into the heap, doubling both the stack space
+    /// and the copy compared to writing each element directly at its final
+    /// address. `init` can loop over such a field's raw pointer and write
+    /// each element in place instead.
+    ///
+    /// # Safety
+    ///
+    /// `init` must fully initialize every field of the `T::In` value at the
+    /// pointer it's given. cell-gc has no way to check this; a half-written
+    /// object left behind by a panicking or incomplete `init` would be
+    /// traced (and, if it survives, handed out as a `T::Ref`) as if it were
+    /// whole.
+    ///
+    /// # Panics
+    ///
+    /// If a page limit has been set, all pages are full, and GC fails to
+    /// shake anything loose. See `try_alloc_init` for a non-panicking
+    /// version.
+    #[track_caller]
+    pub unsafe fn alloc_init<T, F>(&mut self, init: F) -> T::Ref
+    where
+        T: IntoHeapAllocation<'h>,
+        F: FnOnce(*mut T::In),
+    {
+        self.try_alloc_init::<T, F>(init)
+            .expect("out of memory (gc did not collect anything)")
+    }
+
+    /// Like `alloc_init`, but returns `None` instead of panicking if a page
+    /// limit is in the way and GC doesn't free anything, the same
+    /// relationship `try_alloc` has to `alloc`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `alloc_init`.
+    #[track_caller]
+    pub unsafe fn try_alloc_init<T, F>(&mut self, init: F) -> Option<T::Ref>
+    where
+        T: IntoHeapAllocation<'h>,
+        F: FnOnce(*mut T::In),
+    {
+        assert!(
+            self.heap.incremental_collection.is_none(),
+            "cell-gc: cannot allocate while a step_collection is in progress"
+        );
+        if self.heap.stress_mode {
+            self.heap.gc();
         }
+        let caller = Location::caller();
+        let result = self.try_alloc_slot::<T>().map(|allocation| {
+            let ptr = allocation.init_with(init);
+            T::wrap_gc_ref(GcRef::new(ptr))
+        });
+        if result.is_some() {
+            self.heap.alloc_profile.record(
+                pages::heap_type_id::<T::In>(),
+                type_name::<T::In>(),
+                mem::size_of::<T::In>(),
+                caller,
+            );
+        }
+        result
     }
 
     /// Allocate space for a `T::In` value without performing GC or doing any
@@ -404,45 +1648,425 @@ impl<'h> GcHeapSession<'h> {
             })
     }
 
-    fn try_slow_alloc<T: IntoHeapAllocation<'h>>(&mut self, value: T) -> Option<T::Ref> {
+    /// Like `PageSetRef::try_alloc`, but also refuses to grow the heap's page
+    /// footprint past `GcHeap::max_size`, if one is set.
+    unsafe fn try_alloc_within_size_limit<T: IntoHeapAllocation<'h>>(&mut self) -> Option<UninitializedAllocation<T::In>> {
+        if let Some(max_size_bytes) = self.heap.max_size_bytes {
+            let needs_new_page = self.get_page_set::<T::In>().needs_new_page();
+            if needs_new_page && self.heap.page_bytes() + pages::PAGE_SIZE > max_size_bytes {
+                return None;
+            }
+        }
+        self.get_page_set::<T::In>().try_alloc()
+    }
+
+    /// Reserve an `UninitializedAllocation<T::In>` slot, doing GC or
+    /// growing the heap as needed, without writing anything into it yet.
+    /// Shared by `try_alloc_after_trigger_check` (which moves a `T` in
+    /// afterward) and `try_alloc_init` (which lets the caller initialize
+    /// the slot in place instead) --- the fast/slow-path logic itself
+    /// doesn't care which.
+    ///
+    /// # Safety
+    ///
+    /// Safe as long as GC isn't currently happening and no other
+    /// `UninitializedAllocation` already exists in this heap.
+    unsafe fn try_alloc_slot<T: IntoHeapAllocation<'h>>(&mut self) -> Option<UninitializedAllocation<T::In>> {
+        if let Some(allocation) = self.try_fast_alloc::<T>() {
+            return Some(allocation);
+        }
+
         self.heap.gc_counter = self.heap.gc_counter.saturating_sub(1);
         if self.heap.gc_counter == 0 {
             self.heap.gc();
         }
-        unsafe {
-            let allocation = match self.get_page_set::<T::In>().try_alloc() {
-                Some(p) => p,
-                None => {
-                    self.heap.gc();
-                    match self.get_page_set::<T::In>().try_alloc() {
-                        Some(p) => p,
-                        None => return None,
-                    }
+
+        let pages_before = self.get_page_set::<T::In>().page_count();
+        let allocation = match self.try_alloc_within_size_limit::<T>() {
+            Some(p) => p,
+            None => {
+                self.heap.gc();
+                match self.try_alloc_within_size_limit::<T>() {
+                    Some(p) => p,
+                    None => return None,
                 }
-            };
+            }
+        };
 
-            self.heap.alloc_counter += 1;
-            let u = value.into_heap();
-            let p = allocation.init(u);
-            let gc_ref = T::wrap_gc_ref(GcRef::new(p));
-            Some(gc_ref)
+        if self.get_page_set::<T::In>().page_count() > pages_before {
+            self.heap.notify_page_alloc(pages::heap_type_id::<T::In>());
         }
+
+        self.heap.alloc_counter += 1;
+        Some(allocation)
     }
 
     /// Allocate memory, moving `T` into the heap. This may cause garbage collection.
     ///
+    /// The returned `Ref` is never in an unrooted state, even for a moment:
+    /// see `GcRef`'s docs for why holding it is already enough to keep its
+    /// referent alive across any further allocation, with no separate
+    /// rooting step and so no unrooted counterpart to opt out of.
+    ///
     /// # Panics
     ///
     /// If a page limit has been set, all pages are full, and GC fails to shake
     /// anything loose.
+    #[track_caller]
     pub fn alloc<T: IntoHeapAllocation<'h>>(&mut self, value: T) -> T::Ref {
         self.try_alloc(value)
             .expect("out of memory (gc did not collect anything)")
     }
 
-    /// Do garbage collection.
-    pub fn force_gc(&mut self) {
-        self.heap.gc();
+    /// Allocate many `T` values at once, moving each of `values` into the
+    /// heap in turn and returning a `Ref` for each.
+    ///
+    /// Equivalent to calling `alloc` once per value, but checks the
+    /// stress-mode GC-trigger policy (see `alloc`) and reserves heap pages
+    /// (see `reserve`) once for the whole batch, rather than once per
+    /// value --- worthwhile when `values` is long (building a big list one
+    /// cons cell at a time, say), since that per-call overhead no longer
+    /// scales with the batch size.
+    ///
+    /// If `values`'s `Iterator::size_hint` underestimates its true length,
+    /// or reserving that many pages up front isn't possible (a page or
+    /// size limit set with `set_page_limit`/`GcHeap::set_max_size` is in
+    /// the way), the elements past what got reserved just grow the heap
+    /// one page at a time instead, exactly like `alloc` would.
+    ///
+    /// # Panics
+    ///
+    /// If GC fails to make room while allocating one of the values, same
+    /// as `alloc`.
+    #[track_caller]
+    pub fn alloc_iter<T, I>(&mut self, values: I) -> Vec<T::Ref>
+    where
+        T: IntoHeapAllocation<'h>,
+        I: IntoIterator<Item = T>,
+    {
+        let values = values.into_iter();
+        let (lower, _) = values.size_hint();
+
+        if self.heap.stress_mode {
+            self.heap.gc();
+        }
+
+        // Best-effort: a failed reservation isn't fatal here, since
+        // `try_alloc_after_trigger_check` below falls back to growing the
+        // heap one page at a time on its own, same as `alloc` always has.
+        let _ = self.reserve::<T>(lower);
+
+        let mut refs = Vec::with_capacity(lower);
+        for value in values {
+            refs.push(
+                self.try_alloc_after_trigger_check(value)
+                    .expect("out of memory (gc did not collect anything)"),
+            );
+        }
+        refs
+    }
+
+    /// Do garbage collection, and report what it did.
+    ///
+    /// This is a stop-the-world collection: both the mark and sweep phases
+    /// run on the calling thread, with the mutator paused for the whole
+    /// collection, so `CollectionStats::duration` (in particular
+    /// `mark_duration`, which dominates on a large heap) is exactly the
+    /// pause time your program will experience. cell-gc does not currently
+    /// have a way to run marking concurrently with the mutator on a
+    /// background thread; doing that soundly needs write barriers on every
+    /// generated setter to keep a concurrent mark's snapshot consistent
+    /// while the mutator keeps rewriting fields, which is a bigger change
+    /// than this method's contract can absorb without breaking every
+    /// existing `#[derive(IntoHeap)]` type. If pause time on one big heap is
+    /// the problem, splitting the data across several smaller per-thread
+    /// heaps (see the "Caveats" section on the crate root, and the
+    /// `channel` module for moving values between them) turns one large
+    /// pause into several small, independent ones.
+    ///
+    /// Even keeping the stop-the-world design, splitting the mark phase
+    /// itself across several worker threads isn't a drop-in change either;
+    /// see the note at the top of the `marking` module for why. See
+    /// `step_collection` for a way to shrink one large pause into several
+    /// small ones on a single thread instead.
+    pub fn force_gc(&mut self) -> CollectionStats {
+        self.heap.gc()
+    }
+
+    /// Do at most `fuel` mark-stack entries' worth of collection work and
+    /// return, instead of pausing for a whole collection like `force_gc`
+    /// does. Call it again (with the same or a different `fuel`) to keep
+    /// going; once it returns `CollectionStep::Finished`, the collection is
+    /// done, with stats exactly like `force_gc` would have returned.
+    ///
+    /// This turns one large stop-the-world pause into a series of small
+    /// ones, which is what an async runtime typically wants: call this
+    /// once per `poll`, budgeting `fuel` to whatever pause length the
+    /// runtime can tolerate, and yield to other tasks in between calls the
+    /// same way you'd yield after any other bounded slice of work.
+    ///
+    /// # The collection can't be interleaved with *this heap's* mutator
+    ///
+    /// Only the mark phase's graph walk is actually spread across calls;
+    /// see `IncrementalMark`'s docs for why the rest of a collection can't
+    /// be. That walk starts from a root set captured once, in the first
+    /// call, exactly like `force_gc`'s does --- so between that first call
+    /// and the one that returns `Finished`, nothing may allocate, free, or
+    /// mutate anything in *this* heap, or a live object could be missed and
+    /// swept as if it were garbage, since cell-gc has no write barrier to
+    /// catch that (see the note on background marking in `force_gc`'s
+    /// docs). Tasks that don't touch this heap are exactly what's safe to
+    /// run in between calls: other heaps, I/O, or any other bookkeeping
+    /// your runtime does between polls.
+    ///
+    /// # Panics
+    ///
+    /// Panics if anything is allocated on this heap between the call that
+    /// starts a collection and the one that finishes it: cell-gc can't stop
+    /// you from breaking this rule at compile time, so it panics loudly the
+    /// moment an allocation is attempted instead of quietly corrupting the
+    /// heap. There's no way to detect the same mistake for a plain field
+    /// mutation through a generated setter, though, so this doesn't catch
+    /// every way to break the rule --- only allocation is checked.
+    pub fn step_collection(&mut self, fuel: usize) -> CollectionStep {
+        assert_eq!(
+            self.heap.storage_borrows, 0,
+            "cell-gc: cannot collect garbage while in-heap storage is borrowed (see GcRef::with_storage)"
+        );
+
+        let mut collection = self.heap.incremental_collection.take().unwrap_or_else(|| {
+            if let Some(observer) = self.heap.gc_observer.as_mut() {
+                observer.on_collection_start();
+            }
+            self.heap.unpin_dropped_ptrs();
+            IncrementalCollection {
+                start: Instant::now(),
+                mark_duration: Duration::new(0, 0),
+                mark: IncrementalMark::start(&mut self.heap),
+            }
+        });
+
+        let step_start = Instant::now();
+        let reached_fix_point = collection.mark.step(fuel);
+        collection.mark_duration += step_start.elapsed();
+
+        if !reached_fix_point {
+            self.heap.incremental_collection = Some(collection);
+            return CollectionStep::InProgress;
+        }
+
+        let objects_marked = collection.mark.finish(&mut self.heap);
+        self.heap.check_weak_refs();
+        let stats = self
+            .heap
+            .finish_gc(collection.start, collection.mark_duration, objects_marked);
+        CollectionStep::Finished(stats)
+    }
+
+    /// Cooperative yield point for long-running native code.
+    ///
+    /// A builtin that loops internally --- walking a big vector, running a
+    /// user-supplied comparator over and over, anything that doesn't return
+    /// to the interpreter for a while --- should call this every so often,
+    /// the same way it would check for an interrupt flag in a runtime with
+    /// preemption. Right now that means one thing: if `step_collection` left
+    /// a collection open, this does one small `fuel`-bounded step of it,
+    /// exactly like calling `step_collection` yourself would, so a single
+    /// slow builtin can't force the *next* `step_collection` caller to wait
+    /// for a bigger chunk of mark work than they asked for. If no collection
+    /// is in progress, this returns immediately; there's nothing else for it
+    /// to do yet, since cell-gc has neither a watchdog mode (allocation caps
+    /// or wall-clock interrupts) nor a concurrent collector with barriers to
+    /// flush. Both would have a natural home here if they're ever added.
+    pub fn safepoint(&mut self) {
+        if self.heap.incremental_collection.is_some() {
+            self.step_collection(SAFEPOINT_FUEL);
+        }
+    }
+
+    /// Do as much collection work as fits before `deadline`, then return.
+    ///
+    /// Like `step_collection`, but time-bounded instead of fuel-bounded, for
+    /// callers with a concrete wall-clock budget (e.g. one video frame)
+    /// rather than a fuel count to spend: it repeatedly does small
+    /// `step_collection` slices, checking the clock between slices, and
+    /// stops as soon as it either finishes the collection or notices
+    /// `Instant::now()` is past `deadline`.
+    ///
+    /// # Deadline misses
+    ///
+    /// Only the mark phase can be interrupted between slices (see
+    /// `step_collection`); the sweep phase, once mark reaches a fix point,
+    /// always runs to completion in a single uninterrupted call, since
+    /// sweeping has no resumable design (see `IncrementalMark`'s docs). So a
+    /// deadline can be missed two different ways, both reported through the
+    /// return value instead of by, say, panicking or truncating the sweep
+    /// partway through:
+    ///
+    /// - Mark isn't finished when the deadline passes: this returns
+    ///   `CollectionStep::InProgress`, exactly as `step_collection` would if
+    ///   given too little fuel. Call it again (with a fresh deadline) to
+    ///   keep going; nothing has been skipped, there's just more marking
+    ///   left to do.
+    /// - Mark finishes, but sweeping itself overruns the deadline (this
+    ///   can't be prevented, only reported, since sweep can't be split up):
+    ///   this returns `CollectionStep::Finished` with
+    ///   `CollectionStats::deadline_missed` set, so a soft-real-time caller
+    ///   can notice and adapt its policy (e.g. shrink the next deadline, or
+    ///   switch to a policy that collects more often in smaller pieces)
+    ///   instead of silently blowing its budget every time.
+    ///
+    /// # Panics
+    ///
+    /// Same as `step_collection`: nothing may be allocated on this heap
+    /// between the call that starts a collection and the one that finishes
+    /// it.
+    pub fn collect_with_deadline(&mut self, deadline: Instant) -> CollectionStep {
+        loop {
+            let step = self.step_collection(SAFEPOINT_FUEL);
+            match step {
+                CollectionStep::Finished(mut stats) => {
+                    stats.deadline_missed = Instant::now() > deadline;
+                    return CollectionStep::Finished(stats);
+                }
+                CollectionStep::InProgress => {
+                    if Instant::now() >= deadline {
+                        return CollectionStep::InProgress;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Do garbage collection, then release any pages that ended up entirely
+    /// empty back to the allocator, and report what it did (including
+    /// `CollectionStats::pages_released`, which `force_gc` leaves 0 unless
+    /// `GcHeap::set_auto_release` is on).
+    ///
+    /// This is the closest thing cell-gc has to a compacting collector: it
+    /// doesn't move surviving objects (see the "On compaction" note on
+    /// `GcHeap`), but it does give back the memory held by pages that
+    /// happened to empty out completely, which for many workloads is most of
+    /// what fragments a long-running heap.
+    pub fn collect_compacting(&mut self) -> CollectionStats {
+        let mut stats = self.heap.gc();
+        stats.pages_released += self.heap.release_empty_pages();
+        self.heap.collections_since_release = 0;
+        stats
+    }
+
+    /// Release any pages that are currently entirely empty back to the
+    /// operating system, without forcing a collection first, and return how
+    /// many pages were released.
+    ///
+    /// Call this after a `force_gc` (or at any other point you know the heap
+    /// is quiescent) to give back memory freed by a workload spike. See also
+    /// `GcHeap::set_auto_release` to do this automatically.
+    pub fn shrink_to_fit(&mut self) -> usize {
+        self.heap.collections_since_release = 0;
+        self.heap.release_empty_pages()
+    }
+
+    /// Drop every object currently in the heap and reset its pages to empty,
+    /// while keeping the pages themselves allocated, so a REPL or test
+    /// harness can reuse the heap without paying for page allocation again.
+    /// Returns the number of objects dropped.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure no live `GcRef`, `GcFrozenRef`, or `PinnedRef`
+    /// points into this heap: unlike `force_gc`, this drops every object
+    /// unconditionally, pinned or not, which would otherwise leave such
+    /// references dangling.
+    pub unsafe fn clear(&mut self) -> usize {
+        self.heap.clear()
+    }
+
+    /// Allocate memory, moving `value` into the heap, and return a
+    /// `PinnedRef` to it suitable for handing to C code (see `PinnedRef` for
+    /// why this is different from the `T::Ref` an ordinary `alloc` returns).
+    #[track_caller]
+    pub fn alloc_pinned<T: IntoHeapAllocation<'h>>(&mut self, value: T) -> PinnedRef<T> {
+        let gc_ref = T::into_gc_ref(self.alloc(value));
+        PinnedRef::new(&self, gc_ref)
+    }
+
+    /// Pin an existing reference, without allocating anything new, so it can
+    /// be handed to `GcHeapSession::adopt` (or to C code; see `PinnedRef`)
+    /// without going through `alloc_pinned`.
+    pub fn pin<T: IntoHeapAllocation<'h>>(&self, r: T::Ref) -> PinnedRef<T> {
+        PinnedRef::new(&self, T::into_gc_ref(r))
+    }
+
+    /// Stop pinning a `PinnedRef`'s referent, converting it back into an
+    /// ordinary `T::Ref` so that it can be garbage collected again once
+    /// nothing else references it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pinned` was allocated from a different heap.
+    pub fn unpin<T: IntoHeapAllocation<'h>>(&self, pinned: PinnedRef<T>) -> T::Ref {
+        T::wrap_gc_ref(pinned.unpin(&self))
+    }
+
+    /// Run `f`, then collect and release empty pages, so that temporaries
+    /// `f` allocated and didn't return (directly or via some other object it
+    /// mutated) are freed before `scope` returns.
+    ///
+    /// This is not a real arena: it doesn't skip tracing the rest of the
+    /// heap, it just automates calling `collect_compacting` at a phase
+    /// boundary (see the "On scoped arenas" note on `GcHeap`). Anything `f`
+    /// returns, or stores somewhere still reachable from outside the scope,
+    /// survives normally.
+    pub fn scope<R, F>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut GcHeapSession<'h>) -> R,
+    {
+        let result = f(self);
+        self.collect_compacting();
+        result
+    }
+
+    /// Keep `value` (and everything it can reach) alive until the returned
+    /// `Rooted` handle is dropped, even if nothing else in the heap points
+    /// to it.
+    ///
+    /// Unlike `alloc`, this doesn't require `T: IntoHeapAllocation`, so it
+    /// works for values that have no `Ref`/pinning story of their own ---
+    /// most notably a `#[derive(IntoHeap)]` enum (see the crate's top-level
+    /// docs: an enum gets a generated `Ref` type only if it asks for one
+    /// with `#[into_heap(make_ref)]`). Before this existed,
+    /// rooting one of those meant either wrapping it in a real allocation
+    /// just to have something to pin, or stashing it in a heap-allocated
+    /// `Vec` that some other root happened to keep alive.
+    pub fn root<T: IntoHeap<'h>>(&mut self, value: T) -> Rooted<'h, T> {
+        Rooted::new(self.heap, value)
+    }
+
+    /// Root `value` on this session's shadow stack, protecting it (and
+    /// everything it can reach) until the returned `ShadowRoot` is dropped.
+    ///
+    /// Meant to be used through the `gc_root!` macro rather than directly:
+    /// unlike `root`, the returned guard must be dropped in strict LIFO
+    /// order with every other `ShadowRoot` from this session, exactly like
+    /// an ordinary local variable would be --- which is what `gc_root!`
+    /// guarantees by expanding to a `let` binding.
+    pub fn push_root<T: IntoHeap<'h>>(&mut self, value: T) -> ShadowRoot<'h, T> {
+        ShadowRoot::new(self.heap, value)
+    }
+
+    /// Open a `HandleScope`: a batch of cheap GC roots, all released
+    /// together when the scope is dropped, rather than one at a time. See
+    /// `HandleScope::handle`.
+    pub fn handle_scope(&mut self) -> HandleScope<'h> {
+        HandleScope::new(self.heap)
+    }
+
+    /// Open an `EscapableHandleScope`, like `handle_scope`, but able to hand
+    /// one value back to the caller so it survives the scope ending. See
+    /// `EscapableHandleScope::escape`.
+    pub fn escapable_handle_scope(&mut self) -> EscapableHandleScope<'h> {
+        EscapableHandleScope::new(self.heap)
     }
 
     /// Freeze a reference to a GC thing so that it can outlive the current GC
@@ -457,6 +2081,115 @@ impl<'h> GcHeapSession<'h> {
         T::wrap_gc_ref(t.thaw(&self))
     }
 
+    /// Deep-copy the object graph rooted at `root` into this heap, into a
+    /// freshly-allocated copy of every object it can reach, and return a
+    /// reference to the copy that's valid here --- preserving cycles and
+    /// shared substructure, so the copy has the same shape as the original.
+    ///
+    /// `root` doesn't have to come from a different heap (adopting a value
+    /// from this same heap just makes an independent deep copy of it), but
+    /// the intended use is pulling a result built up on a scratch heap into
+    /// a long-lived one, without hand-writing reconstruction code for every
+    /// type involved. `root` is a `PinnedRef` rather than a plain `T::Ref`
+    /// because a `T::Ref`'s `'h` is a different, non-unifiable lifetime for
+    /// every heap session (see `GcHeapSession`'s docs), so there's no way to
+    /// name "a reference from some other session" any other way; use
+    /// `GcHeapSession::pin` to get one.
+    ///
+    /// Requires `T::In: Adopt`, which `#[derive(IntoHeap)]` provides
+    /// automatically unless one of `T`'s fields is a type `Adopt` isn't
+    /// implemented for; see `Adopt`'s docs for which types those are.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a page limit set with `set_page_limit` blocks growing this
+    /// heap enough to hold the copy.
+    pub fn adopt<T>(&mut self, root: &PinnedRef<T>) -> T::Ref
+    where
+        T: IntoHeapAllocation<'h>,
+        T::In: Adopt,
+    {
+        let source_ptr = unsafe { Pointer::new(root.as_ptr()) };
+        let mut adopter = adopt::Adopter::new(self.heap);
+        let dest_ptr = unsafe { adopter.adopt_ptr(source_ptr) };
+        T::wrap_gc_ref(unsafe { GcRef::new(dest_ptr) })
+    }
+
+    /// Take a deep-copy snapshot of the object graph rooted at `root`,
+    /// pinned so it survives collections and further mutation of `root`
+    /// itself, so it can be restored later with `GcHeapSession::restore`.
+    ///
+    /// This eagerly deep-copies everything reachable from `root`, the same
+    /// as `adopt` (it's built on it); it isn't copy-on-write, so it costs
+    /// as much memory as the live graph did at the time it was taken. For a
+    /// debugger's "rewind to before this expression was evaluated"
+    /// semantics, snapshot whatever object represents the interpreter's
+    /// mutable state (e.g. the top-level environment) before evaluating,
+    /// and `restore` it if asked to rewind; anything else the evaluation
+    /// allocated along the way is unreachable from the snapshot and just
+    /// gets collected normally.
+    ///
+    /// Requires `T::In: Adopt`, like `adopt`.
+    pub fn snapshot<T>(&mut self, root: &PinnedRef<T>) -> Snapshot<T>
+    where
+        T: IntoHeapAllocation<'h>,
+        T::In: Adopt,
+    {
+        let copy = self.adopt(root);
+        Snapshot(self.pin(copy))
+    }
+
+    /// Restore a snapshot taken with `GcHeapSession::snapshot`, returning a
+    /// fresh, independent, live copy of the object graph as it was at
+    /// snapshot time. `snapshot` itself is left intact, so it can be
+    /// restored again later; drop it with `GcHeapSession::discard_snapshot`
+    /// once it's no longer needed.
+    pub fn restore<T>(&mut self, snapshot: &Snapshot<T>) -> T::Ref
+    where
+        T: IntoHeapAllocation<'h>,
+        T::In: Adopt,
+    {
+        self.adopt(&snapshot.0)
+    }
+
+    /// Stop retaining a snapshot taken with `GcHeapSession::snapshot`,
+    /// allowing its objects to be collected once nothing else references
+    /// them.
+    pub fn discard_snapshot<T: IntoHeapAllocation<'h>>(&mut self, snapshot: Snapshot<T>) {
+        self.unpin(snapshot.0);
+    }
+
+    /// Write an exact byte-for-byte encoding of the object graph rooted at
+    /// `root` to `out`, preserving cycles and shared substructure, so it can
+    /// be rebuilt later with `GcHeapSession::deserialize` --- in this heap, a
+    /// different heap, or a different run of the program entirely.
+    ///
+    /// Requires `T::In: GcSerialize`, which `#[derive(IntoHeap)]` provides
+    /// automatically under the same conditions it provides `Adopt`; see
+    /// `Adopt`'s docs for which types don't get it.
+    pub fn serialize<T>(&self, root: &PinnedRef<T>, out: &mut dyn Write) -> io::Result<()>
+    where
+        T: IntoHeapAllocation<'h>,
+        T::In: GcSerialize,
+    {
+        let ptr = unsafe { Pointer::new(root.as_ptr()) };
+        unsafe { serialize::serialize(ptr, out) }
+    }
+
+    /// Read back an object graph written by `GcHeapSession::serialize`,
+    /// allocating a fresh copy of every object it contains in this heap, and
+    /// return a reference to the root.
+    ///
+    /// Requires `T::In: GcSerialize`, like `serialize`.
+    pub fn deserialize<T>(&mut self, input: &mut dyn Read) -> io::Result<T::Ref>
+    where
+        T: IntoHeapAllocation<'h>,
+        T::In: GcSerialize,
+    {
+        let dest_ptr = unsafe { serialize::deserialize::<T::In>(self.heap, input) }?;
+        Ok(T::wrap_gc_ref(unsafe { GcRef::new(dest_ptr) }))
+    }
+
     /// Get this session's GC heap's ID.
     pub(crate) fn heap_id(&self) -> HeapId {
         self.heap.id()
@@ -467,6 +2200,65 @@ impl<'h> GcHeapSession<'h> {
         self.heap.check_id(heap_id);
     }
 
+    /// Register a callback for this session's heap to run on every future
+    /// collection. See `weak_ref::WeakRef`.
+    pub(crate) fn register_weak_ref<F: Fn() -> bool + Send + 'static>(&mut self, f: F) {
+        self.heap.register_weak_ref(f);
+    }
+
+    /// Register `r` to be finalized: once the GC determines `r`'s referent is
+    /// otherwise unreachable, it's kept alive (so its destructor doesn't run
+    /// during sweep) and moved onto a queue for `take_finalizable` to drain,
+    /// instead of being finalized immediately. This unpins `r` immediately;
+    /// from now on, the object stays alive only because it's registered here,
+    /// not because of this `T::Ref`.
+    pub fn register_finalizable<T: IntoHeapAllocation<'h>>(&mut self, r: T::Ref) {
+        let ptr = T::into_gc_ref(r).ptr();
+        self.heap
+            .register_finalizable(pages::heap_type_id::<T::In>(), ptr.into());
+    }
+
+    /// Take every `T` registered via `register_finalizable` that the GC has
+    /// found dead since the last call. Safe to call anywhere (not just during
+    /// a collection): the returned objects were resurrected onto the queue at
+    /// the end of some earlier collection and have been kept alive since.
+    pub fn take_finalizable<T: IntoHeapAllocation<'h>>(&mut self) -> Vec<T::Ref> {
+        self.heap
+            .take_finalizable_ptrs(pages::heap_type_id::<T::In>())
+            .into_iter()
+            .map(|ptr| unsafe { T::wrap_gc_ref(GcRef::new(ptr.as_typed_ptr())) })
+            .collect()
+    }
+
+    /// Tell the leak checker that `r` is an intentional, permanent root
+    /// (e.g. a global interned-symbol table), so `GcHeap::check_for_leaks`
+    /// won't flag anything reachable only from it. `r`'s referent is pinned
+    /// forever, same trade-off as `alloc_pinned`: there's no way to
+    /// unregister it later.
+    pub fn register_expected_root<T: IntoHeapAllocation<'h>>(&mut self, r: T::Ref) {
+        let ptr = T::into_gc_ref(r).into_pinned_ptr();
+        self.heap.expected_roots.push(ptr.into());
+    }
+
+    /// Freeze `root` and everything reachable from it, moving it into what
+    /// is effectively a permanent generation: frozen objects are never
+    /// traced during marking (their whole closure is frozen too, so
+    /// there's nothing left to discover) and always survive sweeping, so
+    /// they stop costing anything in future collections. Good for an
+    /// interpreter's prelude, or any other object graph that's done
+    /// changing.
+    ///
+    /// In debug builds, calling a generated setter on a frozen object
+    /// panics rather than silently violating the "this closure never
+    /// changes" assumption the rest of the GC now relies on. There's no
+    /// way to unfreeze an object.
+    pub fn freeze_reachable<T: IntoHeapAllocation<'h>>(&mut self, root: T::Ref) {
+        let gc_ref = T::into_gc_ref(root);
+        freeze::freeze_reachable(gc_ref.ptr().into());
+        // `gc_ref` drops here, unpinning `root`; it stays alive because
+        // it's now frozen.
+    }
+
     /// Returns true if all allocations have been collected. This implies that
     /// no `GcRef`s into the heap exist. You may need to call `hs.force_gc()`
     /// before this, to get predictable results.
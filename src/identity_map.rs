@@ -0,0 +1,90 @@
+//! A hash table keyed by object identity rather than value.
+
+use gc_ref::GcRef;
+use std::collections::HashMap;
+use traits::IntoHeapAllocation;
+
+/// A table keyed by object identity: two keys are the same entry exactly
+/// when they're `GcRef`s to the very same heap object, regardless of what
+/// that object contains. This is the building block for `eq?`-keyed hash
+/// tables, which can't be built on a value-keyed map like `GcHashMap`
+/// (`GcHashMap` would need `K::In: Hash + Eq` derived from `K`'s contents,
+/// and would consider two distinct but equal-content keys the same entry).
+///
+/// This works because `GcRef` already hashes and compares by its
+/// referent's identity rather than its contents, so `IdentityMap` is just
+/// a `HashMap<GcRef<'h, K>, V>` under a more explicit name. Cell-gc never
+/// moves an allocation once it's made (see the "On compaction" note on
+/// `GcHeap`), so a `GcRef`'s identity is stable for as long as its
+/// referent exists; if this crate ever grew a moving collector, `GcRef`'s
+/// `Hash`/`Eq` impls would need to switch from comparing addresses to
+/// comparing some other stable per-object token, and `IdentityMap` would
+/// pick that up for free without any changes of its own.
+///
+/// `IdentityMap` itself lives outside the GC heap, in ordinary Rust
+/// memory, the same way `GcInterned` does. Entries are held strongly: a
+/// key or value inserted here stays alive until it's removed or the whole
+/// map is dropped.
+///
+/// ```rust
+/// use cell_gc::IdentityMap;
+///
+/// cell_gc::with_heap(|hs| {
+///     let a = hs.alloc("hello".to_string());
+///     let b = hs.alloc("hello".to_string());
+///
+///     let mut map = IdentityMap::new();
+///     map.insert(&a, 1);
+///     assert_eq!(map.get(&a), Some(&1));
+///     assert_eq!(map.get(&b), None); // same contents, different object
+/// });
+/// ```
+pub struct IdentityMap<'h, K: IntoHeapAllocation<'h>, V> {
+    table: HashMap<GcRef<'h, K>, V>,
+}
+
+impl<'h, K: IntoHeapAllocation<'h>, V> Default for IdentityMap<'h, K, V> {
+    fn default() -> IdentityMap<'h, K, V> {
+        IdentityMap::new()
+    }
+}
+
+impl<'h, K: IntoHeapAllocation<'h>, V> IdentityMap<'h, K, V> {
+    /// Create a new, empty identity map.
+    pub fn new() -> IdentityMap<'h, K, V> {
+        IdentityMap {
+            table: HashMap::new(),
+        }
+    }
+
+    /// Insert `value` under `key`, returning the previous value if `key`'s
+    /// referent already had an entry.
+    pub fn insert(&mut self, key: &GcRef<'h, K>, value: V) -> Option<V> {
+        self.table.insert(key.clone(), value)
+    }
+
+    /// Look up the entry for `key`'s referent.
+    pub fn get(&self, key: &GcRef<'h, K>) -> Option<&V> {
+        self.table.get(key)
+    }
+
+    /// Remove and return the entry for `key`'s referent, if any.
+    pub fn remove(&mut self, key: &GcRef<'h, K>) -> Option<V> {
+        self.table.remove(key)
+    }
+
+    /// Returns `true` if `key`'s referent has an entry in this map.
+    pub fn contains_key(&self, key: &GcRef<'h, K>) -> bool {
+        self.table.contains_key(key)
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns `true` if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
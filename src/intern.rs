@@ -0,0 +1,77 @@
+//! A weak interning table for GC-heap values.
+
+use gc_ref::GcRef;
+use heap::GcHeapSession;
+use std::collections::HashMap;
+use std::hash::Hash;
+use traits::IntoHeapAllocation;
+use weak_ref::WeakRef;
+
+/// A table that interns values allocated in the GC heap: interning the same
+/// value twice returns the same `GcRef`.
+///
+/// Entries are held weakly, via `WeakRef`, so a value that's no longer
+/// referenced anywhere else can still be collected; it simply won't be
+/// deduplicated against once it's gone. This is the building block for
+/// symbol tables and small-string interning, where a strong intern table
+/// would keep every interned value alive forever.
+///
+/// `GcInterned` itself lives outside the GC heap, in ordinary Rust memory,
+/// the same way `WeakRef` does.
+///
+/// ```rust
+/// use cell_gc::GcInterned;
+///
+/// cell_gc::with_heap(|hs| {
+///     let mut symbols: GcInterned<String> = GcInterned::new();
+///     let a = symbols.intern(hs, "foo".to_string());
+///     let b = symbols.intern(hs, "foo".to_string());
+///     assert_eq!(a, b);
+/// });
+/// ```
+pub struct GcInterned<'h, T: IntoHeapAllocation<'h> + Eq + Hash + Clone> {
+    table: HashMap<T, WeakRef<'h, T>>,
+}
+
+impl<'h, T: IntoHeapAllocation<'h> + Eq + Hash + Clone> Default for GcInterned<'h, T> {
+    fn default() -> GcInterned<'h, T> {
+        GcInterned::new()
+    }
+}
+
+impl<'h, T: IntoHeapAllocation<'h> + Eq + Hash + Clone> GcInterned<'h, T> {
+    /// Create a new, empty intern table.
+    pub fn new() -> GcInterned<'h, T> {
+        GcInterned {
+            table: HashMap::new(),
+        }
+    }
+
+    /// Return the interned reference for `value`, allocating it in `hs` the
+    /// first time it's interned (or if the previous allocation has since
+    /// been collected).
+    pub fn intern(&mut self, hs: &mut GcHeapSession<'h>, value: T) -> T::Ref {
+        if let Some(weak) = self.table.get(&value) {
+            if let Some(gc_ref) = weak.upgrade() {
+                return T::wrap_gc_ref(gc_ref);
+            }
+        }
+
+        let wrapped = hs.alloc(value.clone());
+        let gc_ref: GcRef<'h, T> = T::into_gc_ref(wrapped);
+        self.table.insert(value, WeakRef::new(hs, &gc_ref));
+        T::wrap_gc_ref(gc_ref)
+    }
+
+    /// Returns the number of entries in the table, including any whose
+    /// target has already been collected but hasn't been looked up (and
+    /// thus pruned) since.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns `true` if the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
@@ -0,0 +1,90 @@
+//! Debug-only leak detection: finding objects that outlived every root the
+//! embedder told the heap to expect. See
+//! `GcHeapSession::register_expected_root` and `GcHeap::check_for_leaks`.
+
+use heap::GcHeap;
+use pages::{self, PageHeader};
+use ptr::UntypedPointer;
+use std::collections::HashMap;
+
+/// One line of a leak report: a type that had objects alive only because of
+/// some pin or root other than the ones registered with
+/// `GcHeapSession::register_expected_root`. See `GcHeap::check_for_leaks`.
+#[derive(Clone, Debug)]
+pub struct LeakReportEntry {
+    /// The type's name, from `std::any::type_name`.
+    pub type_name: &'static str,
+
+    /// How many objects of this type were unexpectedly alive.
+    pub count: usize,
+}
+
+/// A leak report, one entry per type with unexpectedly live objects, sorted
+/// by count, largest first. See `GcHeap::check_for_leaks`.
+pub type LeakReport = Vec<LeakReportEntry>;
+
+/// Mark from `roots` alone, skipping the usual pin scan, leaving mark bits
+/// set on everything reachable from them.
+fn mark_from(heap: &mut GcHeap, roots: &[UntypedPointer]) {
+    heap.with_marking_tracer(|_heap, tracer| {
+        for &ptr in roots {
+            unsafe {
+                (*PageHeader::find(ptr)).mark(ptr, tracer);
+            }
+        }
+        tracer.mark_to_fix_point();
+    });
+}
+
+/// Compute a leak report: types (and counts) of objects that are reachable
+/// from some currently pinned root (i.e. would survive a real collection)
+/// but are *not* reachable from any of the roots registered with
+/// `GcHeapSession::register_expected_root`.
+///
+/// This only approximates real reachability: it doesn't resolve ephemerons
+/// or run finalizers, so an ephemeron value or a finalizable object kept
+/// alive only by those mechanisms may be misreported as an unexpected leak.
+/// Good enough to point at a forgotten `GcRef`/`PinnedRef`, which is the
+/// point.
+pub(crate) fn check_for_leaks(heap: &mut GcHeap) -> LeakReport {
+    let mut all_roots = Vec::new();
+    unsafe {
+        heap.clear_mark_bits(&mut all_roots);
+    }
+    mark_from(heap, &all_roots);
+
+    let mut live_before_expected_pass = Vec::new();
+    heap.for_each_live_object(|ptr, _page| {
+        if unsafe { pages::get_mark_bit_untyped(ptr) } {
+            live_before_expected_pass.push(ptr);
+        }
+    });
+
+    let expected_roots = heap.expected_roots().to_vec();
+    let mut discarded = Vec::new();
+    unsafe {
+        heap.clear_mark_bits(&mut discarded);
+    }
+    mark_from(heap, &expected_roots);
+
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    for ptr in live_before_expected_pass {
+        if !unsafe { pages::get_mark_bit_untyped(ptr) } {
+            let type_name = unsafe { (*PageHeader::find(ptr)).dump(ptr).0 };
+            *counts.entry(type_name).or_insert(0) += 1;
+        }
+    }
+
+    // Leave mark bits clear, matching every other point outside a collection.
+    let mut discarded = Vec::new();
+    unsafe {
+        heap.clear_mark_bits(&mut discarded);
+    }
+
+    let mut report: LeakReport = counts
+        .into_iter()
+        .map(|(type_name, count)| LeakReportEntry { type_name, count })
+        .collect();
+    report.sort_by(|a, b| b.count.cmp(&a.count));
+    report
+}
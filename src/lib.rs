@@ -24,7 +24,14 @@
 //! that will break code; you'll just have to keep up until things stabilize.
 //!
 //! cell-gc is not designed to support multithread access to a single heap (like Java).
-//! Instead, you can create one heap per thread (like JavaScript).
+//! Instead, you can create one heap per thread (like JavaScript), and use
+//! `channel` to move values between them.
+//!
+//! Collection is stop-the-world: there's no way to run the mark phase on a
+//! background thread concurrently with the mutator (see the note on
+//! `GcHeapSession::force_gc`), so a single very large heap pays for its
+//! whole mark phase as one pause. Several smaller per-thread heaps, each
+//! collected independently, is the current answer to that.
 //!
 //! Currently it does not support lots of small heaps with random lifetimes (like Erlang),
 //! but I have some ideas on how to get there.
@@ -68,18 +75,40 @@
 //!     * primitive types, like `i32`
 //!     * types declared with `#[derive(IntoHeap)]`, like `IntList<'h>` and `IntListRef<'h>`
 //!     * `Box<T>` where `T` has `'static` lifetime
-//!     * `Rc<T>` where `T` has `'static` lifetime
+//!     * `Arc<T>` where `T` has `'static` lifetime (not `Rc<T>`: a heap needs
+//!       to be `Send`-able between threads, and `Rc`'s refcount isn't atomic)
 //!     * `Option<T>` where `T` is any of these types
+//!     * `[T; N]` where `T` is any of these types and `N` is a fixed size
 //!
 //!     If you try to use anything else, you'll get bizarre error messages
 //!     from `rustc`.
 //!
+//!     A fixed-size array field is stored inline, the same way a bare
+//!     `#[derive(IntoHeap)]` field is: no separate allocation, and each
+//!     slot is marked individually during collection. That makes
+//!     `[Option<NodeRef<'h>>; 8]` a good way to give a fixed-fanout tree
+//!     node (a B-tree or quadtree, say) its children without allocating a
+//!     `VecRef` per node.
+//!
+//!     Note the difference between embedding `IntList<'h>` and
+//!     `IntListRef<'h>`: a `Ref` field is a pointer to a separate heap
+//!     allocation, but a bare `IntList<'h>` field is stored inline, by
+//!     value, as part of its parent's own storage --- no second allocation,
+//!     no pointer to follow to reach it. Useful for a small type that only
+//!     ever shows up nested inside something else, like a 3D vector inside
+//!     a transform: embedding it directly avoids paying for an object of
+//!     its own.
+//!
+
 //! *   It declare a `Ref` type for you, in this case `IntListRef`.
 //!     `cell_gc` names this type by gluing `Ref` to the end of the struct
 //!     name. `IntListRef` is a smart pointer to a GC-managed `IntList`. You
 //!     need this because `cell_gc` doesn't let you have normal Rust references
 //!     to stuff in the GC heap.
 //!
+//!     If the default name would clash with something else in scope, override
+//!     it with `#[into_heap(ref_name = "...")]` on the struct.
+//!
 //!     `IntListRef` values keep in-heap `IntList` values alive; once the last
 //!     `IntListRef` pointing at an object is gone, it becomes available for
 //!     garbage collection, and eventually it'll be recycled.
@@ -91,8 +120,62 @@
 //!     field of the struct. For example, `IntList` has methods `.head()`, `.tail()`,
 //!     `.set_head(i64)`, and `.set_tail(Option<IntListRef>)`.
 //!
-//! You can also derive `IntoHeap` for an enum, but support is incomplete: no
-//! `Ref` type is generated for enums. Tuple structs are not supported.
+//!     A getter's visibility follows the field's own visibility in the
+//!     struct definition (so a private field gets a private getter, a
+//!     `pub(crate)` field gets a `pub(crate)` getter, and so on), and so
+//!     does its setter's, unless the field is marked
+//!     `#[into_heap(no_setter)]`, in which case no setter is generated at
+//!     all --- handy for a field that some invariant depends on and that
+//!     should only ever be set when the whole struct is constructed.
+//!
+//! You can also derive `IntoHeap` for an enum. By default no `Ref` type is
+//! generated for enums, since an enum field is usually reached through the
+//! `Ref` of whatever struct contains it; but if you need to `hs.alloc()` an
+//! enum value directly, add `#[into_heap(make_ref)]` (or `#[into_heap(ref_name =
+//! "...")]` to also pick the name) above the enum, and you'll get a `Ref`
+//! type with `.get() -> Enum` and `.set(Enum)` methods, instead of the
+//! per-field accessors a struct's `Ref` gets.
+//!
+//! Tuple structs work too, e.g. `struct Wrapper<'h>(ValueRef<'h>);`. Since
+//! tuple fields have no names, the `Ref` type gets accessors named by
+//! position instead: `.get0()`/`.set0(...)` for field `0`, `.get1()`/`.set1(...)`
+//! for field `1`, and so on. Unit structs are still not supported.
+//!
+//! A `Ref` type always derives `Clone`, `Debug`, `PartialEq`, and `Eq`, and
+//! also hashes by address, as if by `#[derive(Hash)]` on the pointer it
+//! wraps. Ask for more of that kind of forwarding with
+//! `#[gc_ref_derive(...)]` above the struct or enum: `PartialOrd`/`Ord`
+//! order by address the same way `Hash` hashes by it, and `Display`
+//! forwards to `Display`-formatting the referent's one field (or, for an
+//! enum `Ref`, its `.get()`) --- which only makes sense for a `Ref` with
+//! exactly one field, so asking for `Display` on any other shape is a
+//! compile-time error.
+//!
+//! The lifetime parameter doesn't have to be called `'h`; whatever name you
+//! give it is what `#[derive(IntoHeap)]` uses throughout the generated code.
+//! Exactly one lifetime parameter is required, though: a second one would
+//! mean storing possibly-borrowed data in the GC heap, which isn't safe in
+//! general and so isn't supported.
+//!
+//! ## Foreign types that hold GC references
+//!
+//! `#[derive(IntoHeap)]` only understands your own structs and enums; it
+//! can't reach inside a third-party collection like `HashMap`. If you need
+//! to store one that holds `Ref`s, and it truly has no GC pointers inside
+//! it (say, a `HashMap<String, i32>`), `GcLeaf` is the easy way in: it
+//! stores the value as-is and traces nothing.
+//!
+//! But if the collection *does* hold `Ref`s (a `HashMap<SymbolRef<'h>,
+//! ValueRef<'h>>`, say), `GcLeaf` would hide them from the collector, which
+//! could then free them out from under you. That case still needs a real
+//! storage type --- one holding `Pointer`s instead of live `Ref`s, the same
+//! shape `#[derive(IntoHeap)]` would generate for a struct field --- and an
+//! `IntoHeapBase` impl converting elements to and from it, since only you
+//! know how to walk a foreign collection's elements. What you get to skip is
+//! writing `InHeap::trace` by hand: implement `cell_gc::traits::CustomTrace`
+//! for the storage type instead, calling `cell_gc::traits::trace_field` on
+//! every `Pointer` reachable from `self`, and pass it to the
+//! `impl_custom_trace!` macro to turn that into an `InHeap` impl.
 //!
 //! ## Understanding heaps
 //!
@@ -229,19 +312,129 @@
 
 #![deny(missing_docs)]
 
+/// Optional `serde::Serialize`/`Deserialize` support for `#[derive(IntoHeap)]`
+/// types is only available with this crate's `serde` feature enabled, and
+/// only for a struct that asks for it with `#[into_heap(serde)]` (and whose
+/// fields are all plain data --- no fields pointing back into the heap). A
+/// crate that opts a type in needs its own `extern crate serde;` too, the
+/// same way it already needs its own `extern crate cell_gc_derive;` to use
+/// the derive at all.
+#[cfg(feature = "serde")]
+extern crate serde;
+
+/// Root a local variable on a `GcHeapSession`'s shadow stack for the rest of
+/// its enclosing block, so it survives any further allocation the block goes
+/// on to do.
+///
+/// ```ignore
+/// gc_root!(hs, let saved = some_value;);
+/// ```
+///
+/// expands, roughly, to a `let saved = ...` binding backed by a hidden
+/// `ShadowRoot` guard, which un-roots `saved` when it goes out of scope. See
+/// `GcHeapSession::push_root`.
+///
+/// # Safety
+///
+/// Don't pull the hidden guard out of the `let` and store it somewhere that
+/// would outlive, or be dropped out of order with, other roots pushed on the
+/// same heap session --- there's no way to do that by accident using the
+/// macro as shown above, but see `ShadowRoot`'s docs for what goes wrong if
+/// you work around it.
+#[macro_export]
+macro_rules! gc_root {
+    ($hs:expr, let $x:ident = $e:expr;) => {
+        let __cell_gc_shadow_root = $hs.push_root($e);
+        let $x = __cell_gc_shadow_root.get();
+    };
+}
+
+/// Turn a [`traits::CustomTrace`](traits/trait.CustomTrace.html) impl into
+/// an `InHeap` impl, the trait a type must have to appear as a `Pointer<_>`
+/// edge inside the GC heap.
+///
+/// ```ignore
+/// // The in-heap storage form of `SymbolTable<'h>`, holding raw `Pointer`s
+/// // instead of live `SymbolRef`/`ValueRef`s.
+/// struct SymbolTableStorage(HashMap<Pointer<SymbolStorage>, Pointer<ValueStorage>>);
+///
+/// unsafe impl cell_gc::traits::CustomTrace for SymbolTableStorage {
+///     unsafe fn trace<R: cell_gc::traits::Tracer>(&self, tracer: &mut R) {
+///         for (key, value) in &self.0 {
+///             cell_gc::traits::trace_field(key, tracer);
+///             cell_gc::traits::trace_field(value, tracer);
+///         }
+///     }
+/// }
+/// cell_gc::impl_custom_trace!(SymbolTableStorage);
+/// ```
+#[macro_export]
+macro_rules! impl_custom_trace {
+    ($t:ty) => {
+        impl $crate::traits::InHeap for $t {
+            unsafe fn trace<R: $crate::traits::Tracer>(&self, tracer: &mut R) {
+                $crate::traits::CustomTrace::trace(self, tracer);
+            }
+        }
+    };
+}
+
 pub mod traits;
+pub mod adopt;
+pub mod alloc_profile;
+#[cfg(feature = "bench-support")]
+pub mod bench_support;
+pub mod borrow_flag;
+pub mod channel;
+pub mod debug;
+mod census;
+mod dump;
+mod freeze;
+pub mod frozen_heap;
+mod leak_check;
 mod pages;
 mod heap;
 mod gc_ref;
 mod gc_leaf;
+pub mod gc_any;
+pub mod gc_cell;
+pub mod gc_dyn;
 pub mod collections;
+pub mod ephemeron;
+pub mod ffi;
+pub mod finalize;
+pub mod identity_map;
+pub mod intern;
 pub mod ptr;
 mod marking;
+pub mod serialize;
 mod signposts;
+pub mod snapshot;
+mod stack_scan;
+pub mod symbol;
+mod verify;
+pub mod wasm;
+pub mod weak_ref;
 
+pub use gc_any::GcAny;
+pub use gc_cell::{GcCell, GcCellRef};
+pub use gc_dyn::GcDyn;
 pub use gc_leaf::GcLeaf;
-pub use gc_ref::{GcFrozenRef, GcRef};
-pub use heap::{GcHeap, GcHeapSession, with_heap};
+pub use gc_ref::{EscapableHandleScope, GcFrozenRef, GcRef, Handle, HandleScope, PinnedRef, Rooted, ShadowRoot};
+pub use alloc_profile::{AllocationReport, AllocationSite, CallerStats, TypeAllocationStats};
+pub use census::{Census, CensusDelta};
+pub use channel::{Receiver, Sender};
+pub use ephemeron::{Ephemeron, EphemeronRef};
+pub use finalize::{Finalized, FinalizedRef};
+pub use frozen_heap::FrozenHeap;
+pub use heap::{CollectionPolicy, CollectionStats, CollectionStep, GcHeap, GcHeapSession, GcObserver, HeapSession, ReservationError, TypeInfo, with_heap};
+pub use identity_map::IdentityMap;
+pub use intern::GcInterned;
+pub use leak_check::{LeakReport, LeakReportEntry};
+pub use pages::{GlobalPageSource, PageGeometry, PageSource, TENURING_AGE};
+pub use snapshot::Snapshot;
+pub use symbol::{Symbol, SymbolRef, Symbols};
+pub use weak_ref::WeakRef;
 
 /// Return the number of allocations of a given type that fit in a "page".
 /// (Unstable. This is a temporary hack for testing.)
@@ -1,29 +1,210 @@
 //! Marking heap tracer and mark stack implementation.
+//!
+//! Marking here is single-threaded, and not just as a simplification: the
+//! mark bit itself (see `pages::MarkWord`) is a plain, non-atomic field, set
+//! with an ordinary read-modify-write in `pages::set_mark_bit`. Two worker
+//! threads racing to mark the same object (unavoidable in general, since
+//! two roots can both reach it) would be a data race on that bit, so
+//! splitting `MarkingTracer::mark_to_fix_point`'s mark stack across a
+//! work-stealing thread pool isn't safe as a drop-in change: it needs the
+//! mark bit itself to become atomic (or otherwise made safe to write from
+//! multiple threads), plus a fix-point barrier so every worker agrees
+//! marking is actually done before `resolve_ephemerons` runs, not just one
+//! worker's mark stack going momentarily empty while another worker is
+//! about to push more work onto it.
 
 use heap::GcHeap;
 use pages::{self, PageHeader};
 use ptr::{Pointer, UntypedPointer};
 use signposts;
+use stack_scan;
+use std::cell::Cell;
 use traits::{InHeap, Tracer};
 
-/// Perform all the marking for a collection.
-pub fn mark<'h>(heap: &mut GcHeap) {
+/// Perform all the marking for a collection, and return the number of
+/// distinct objects found reachable.
+pub fn mark<'h>(heap: &mut GcHeap) -> usize {
     let _sp = signposts::Marking::new();
 
     heap.with_marking_tracer(|heap, mut tracer| {
+        tracer.objects_marked = 0;
+
         let mut roots = vec![];
         unsafe {
             heap.clear_mark_bits(&mut roots);
         }
 
+        if let Some(bottom) = heap.conservative_stack_bottom() {
+            let top = stack_scan::capture_stack_pointer();
+            let live_objects = heap.live_object_addresses();
+            unsafe {
+                roots.extend(stack_scan::scan(top, bottom, &live_objects));
+            }
+        }
+
         for ptr in roots {
             unsafe {
                 (*PageHeader::find(ptr)).mark(ptr, &mut tracer);
             }
         }
 
+        heap.trace_persistent_roots(&mut tracer);
+        heap.trace_shadow_stack(&mut tracer);
+
         tracer.mark_to_fix_point();
-    });
+        heap.resurrect_dead_finalizables(&mut tracer);
+        tracer.resolve_ephemerons();
+
+        tracer.objects_marked
+    })
+}
+
+/// Resumable state for one incremental collection's mark phase, driven a
+/// bounded amount of work at a time by `GcHeapSession::step_collection`
+/// instead of running straight through to a fix point like `mark` does.
+///
+/// Only the mark phase --- walking the already-captured root set out to
+/// every object it can reach --- is spread across calls this way. Nothing
+/// else about a collection can be: sweeping, weak reference checks, and
+/// ephemeron/finalizer resolution all need mark bits that are accurate for
+/// the *whole* heap, so they have to wait for the fix point regardless.
+/// And root-scanning itself only happens once, in `start`, exactly like
+/// `mark`'s does, so it's unaffected by whatever the mutator does between
+/// `step` calls --- except that the mutator must not do anything to this
+/// heap at all until `finish` returns: allocating while a mark stack is
+/// half-walked would let a live object slip in “white” (unmarked, since
+/// nothing recorded it as a new root) and get swept as if it were garbage,
+/// since cell-gc has no write barrier to catch that.
+/// `GcHeapSession::step_collection` checks for this the same way
+/// `GcRef::with_storage` checks for a nested collection: not at compile
+/// time, but by panicking loudly the moment the violation is detected
+/// rather than silently corrupting the heap.
+pub(crate) struct IncrementalMark {
+    tracer: MarkingTracer,
+
+    /// `tracer.fuel` as `start` found it, restored by `finish` once this
+    /// collection is done marking. See `start`'s docs for why it's zeroed
+    /// out in between.
+    saved_fuel: usize,
+}
+
+impl IncrementalMark {
+    /// Scan roots and prime the mark stack --- the same first half of the
+    /// work `mark` does --- and return state to resume from.
+    pub(crate) fn start(heap: &mut GcHeap) -> IncrementalMark {
+        let mut tracer = heap.take_marking_tracer();
+        tracer.objects_marked = 0;
+
+        let mut roots = vec![];
+        unsafe {
+            heap.clear_mark_bits(&mut roots);
+        }
+
+        if let Some(bottom) = heap.conservative_stack_bottom() {
+            let top = stack_scan::capture_stack_pointer();
+            let live_objects = heap.live_object_addresses();
+            unsafe {
+                roots.extend(stack_scan::scan(top, bottom, &live_objects));
+            }
+        }
+
+        // For an ordinary, run-to-a-fix-point `mark`, `fuel` is exactly the
+        // right way to walk each root's subgraph: a handful of recursive
+        // `trace` calls are cheaper than pushing and popping the mark
+        // stack, and `DEFAULT_FUEL` keeps the recursion shallow enough not
+        // to blow the native stack (see the module docs). But it also means
+        // `MarkingTracer::visit` only pushes to the budget-limited
+        // `mark_stack` once fuel runs out --- up to `DEFAULT_FUEL` edges
+        // deep, comfortably past most real object graphs (including a
+        // 50-long chain) --- so a single `step` popping just one entry off
+        // that stack could still recursively mark the *entire* rest of the
+        // graph in one call, regardless of how small a budget the caller
+        // asked for.
+        //
+        // So for the whole lifetime of an incremental collection, `fuel`
+        // stays zeroed: every `visit` call, at any depth, defers to the
+        // mark stack instead of recursing, the same way a stack entry gets
+        // deferred when fuel legitimately runs out. That turns marking into
+        // a plain iterative worklist algorithm for as long as `step` is
+        // driving it, so `step_to_fix_point`'s `budget` genuinely bounds
+        // the work done per call, at every level of the graph --- not just
+        // how many stack entries get popped. `finish` restores the saved
+        // fuel before handing the tracer back, so an ordinary `mark` on the
+        // next collection gets the fast recursive path back.
+        let saved_fuel = tracer.fuel;
+        tracer.fuel = 0;
+
+        for ptr in roots {
+            unsafe {
+                (*PageHeader::find(ptr)).mark(ptr, &mut tracer);
+            }
+        }
+
+        heap.trace_persistent_roots(&mut tracer);
+        heap.trace_shadow_stack(&mut tracer);
+
+        IncrementalMark {
+            tracer: tracer,
+            saved_fuel: saved_fuel,
+        }
+    }
+
+    /// Do up to `fuel` mark-stack entries' worth of work. Returns `true`
+    /// once the mark stack reaches a fix point, `false` if there's more
+    /// left to do.
+    pub(crate) fn step(&mut self, fuel: usize) -> bool {
+        self.tracer.step_to_fix_point(fuel)
+    }
+
+    /// Finish up once `step` reports a fix point: resolve ephemerons and
+    /// dead finalizables exactly like `mark` does at the end, hand the
+    /// tracer back to `heap`, and report how many objects were marked.
+    pub(crate) fn finish(mut self, heap: &mut GcHeap) -> usize {
+        // Restore the fast recursive path `start` turned off: everything
+        // from here on (ephemeron/finalizer resolution, and the next
+        // collection's `mark`) runs to a fix point in one shot regardless
+        // of budget, so there's no reason to keep paying for the mark
+        // stack's indirect calls.
+        self.tracer.fuel = self.saved_fuel;
+
+        heap.resurrect_dead_finalizables(&mut self.tracer);
+        self.tracer.resolve_ephemerons();
+        let objects_marked = self.tracer.objects_marked;
+        heap.replace_marking_tracer(self.tracer);
+        objects_marked
+    }
+}
+
+/// An `Ephemeron` whose key wasn't yet known to be reachable the last time
+/// we looked. Type-erased so a heterogeneous collection of these can share
+/// one worklist; `try_resolve` and `clear` are monomorphized per value type,
+/// following the same dispatch trick as `PageHeader::mark_fn`.
+#[derive(Clone, Copy)]
+struct PendingEphemeron {
+    key: UntypedPointer,
+    value_slot: *const (),
+    try_resolve: unsafe fn(*const (), UntypedPointer, &mut MarkingTracer) -> bool,
+    clear: unsafe fn(*const ()),
+}
+
+unsafe fn try_resolve_entry_point<V: InHeap>(
+    value_slot: *const (),
+    key: UntypedPointer,
+    tracer: &mut MarkingTracer,
+) -> bool {
+    if !pages::get_mark_bit_untyped(key) && !pages::is_frozen_untyped(key) {
+        return false;
+    }
+    let slot = &*(value_slot as *const Cell<Option<Pointer<V>>>);
+    if let Some(value) = slot.get() {
+        tracer.visit(value);
+    }
+    true
+}
+
+unsafe fn clear_entry_point<V: InHeap>(value_slot: *const ()) {
+    let slot = &*(value_slot as *const Cell<Option<Pointer<V>>>);
+    slot.set(None);
 }
 
 /// The marking tracer is a `Tracer` that visits every edge in the live heap
@@ -44,6 +225,16 @@ pub fn mark<'h>(heap: &mut GcHeap) {
 pub struct MarkingTracer {
     fuel: usize,
     mark_stack: Vec<UntypedPointer>,
+
+    /// Number of objects marked so far this collection. Reset to 0 at the
+    /// start of each call to `mark()`.
+    objects_marked: usize,
+
+    /// `Ephemeron`s seen so far whose key wasn't marked yet, kept around so
+    /// `resolve_ephemerons` can retry them once ordinary marking reaches a
+    /// fix point. Drained (either resolved or cleared) by the end of every
+    /// call to `mark()`.
+    pending_ephemerons: Vec<PendingEphemeron>,
 }
 
 // TODO: Choose a better default value based on the average size of a trace
@@ -65,6 +256,8 @@ impl<'h> MarkingTracer {
         MarkingTracer {
             fuel: fuel,
             mark_stack: Default::default(),
+            objects_marked: 0,
+            pending_ephemerons: Default::default(),
         }
     }
 
@@ -78,14 +271,75 @@ impl<'h> MarkingTracer {
         }
     }
 
+    /// Like `mark_to_fix_point`, but do at most `budget` mark-stack entries'
+    /// worth of work before returning, so a caller can spread one
+    /// collection's mark phase across several smaller calls instead of
+    /// pausing for the whole thing at once. Returns whether the fix point
+    /// was actually reached, or there's more left to do.
+    fn step_to_fix_point(&mut self, budget: usize) -> bool {
+        for _ in 0..budget {
+            match self.mark_stack.pop() {
+                Some(ptr) => unsafe {
+                    (*PageHeader::find(ptr)).mark(ptr, self);
+                },
+                None => return true,
+            }
+        }
+        self.mark_stack.is_empty()
+    }
+
     /// Is the mark stack empty?
     pub fn mark_stack_is_empty(&self) -> bool {
         self.mark_stack.is_empty()
     }
+
+    /// Resolve every `Ephemeron` deferred by `visit_ephemeron` during this
+    /// collection.
+    ///
+    /// An ephemeron's key can become reachable partway through marking, e.g.
+    /// as a side effect of resolving some *other* ephemeron's value. So we
+    /// keep retrying the pending list, remarking to a fresh fix point after
+    /// each round that makes progress, until a full round resolves nothing.
+    /// Whatever's left at that point has a genuinely dead key, so we clear
+    /// its value slot before returning, since the value is about to be swept
+    /// and the ephemeron must not be left holding a dangling pointer to it.
+    fn resolve_ephemerons(&mut self) {
+        loop {
+            let mut progress = false;
+            let mut i = 0;
+            while i < self.pending_ephemerons.len() {
+                let entry = self.pending_ephemerons[i];
+                let resolved = unsafe { (entry.try_resolve)(entry.value_slot, entry.key, self) };
+                if resolved {
+                    self.pending_ephemerons.swap_remove(i);
+                    progress = true;
+                } else {
+                    i += 1;
+                }
+            }
+            if !progress {
+                break;
+            }
+            self.mark_to_fix_point();
+        }
+
+        for entry in self.pending_ephemerons.drain(..) {
+            unsafe {
+                (entry.clear)(entry.value_slot);
+            }
+        }
+    }
 }
 
 impl Tracer for MarkingTracer {
     fn visit<U: InHeap>(&mut self, ptr: Pointer<U>) {
+        if unsafe { pages::is_frozen(ptr) } {
+            // This object, and everything it can reach, was already
+            // permanently marked reachable when it was frozen (see
+            // `GcHeapSession::freeze_reachable`). Nothing left to discover.
+            return;
+        }
+
         let is_marked = unsafe { pages::get_mark_bit(ptr) };
         if is_marked {
             return;
@@ -94,6 +348,7 @@ impl Tracer for MarkingTracer {
         unsafe {
             pages::set_mark_bit(ptr);
         }
+        self.objects_marked += 1;
 
         if self.fuel == 0 {
             // Out of fuel. We don't want to blow the stack, so save this thing
@@ -109,4 +364,36 @@ impl Tracer for MarkingTracer {
         }
         self.fuel += 1;
     }
+
+    fn visit_untyped(&mut self, ptr: UntypedPointer) {
+        // `PageHeader::mark` is exactly `mark_entry_point`, monomorphized
+        // for whatever type actually lives at `ptr` --- the same lookup
+        // `mark()` uses for conservative-stack-scan roots above, which are
+        // untyped for the same reason a `GcDyn` edge is.
+        unsafe {
+            (*PageHeader::find(ptr)).mark(ptr, self);
+        }
+    }
+
+    fn visit_ephemeron<K: InHeap, V: InHeap>(
+        &mut self,
+        key: Pointer<K>,
+        value: Pointer<V>,
+        value_slot: &Cell<Option<Pointer<V>>>,
+    ) {
+        // Deliberately don't `self.visit(key)`: an ephemeron must not keep
+        // its own key alive, or every ephemeron would trivially "resolve"
+        // itself and the value would never be freed. We only ever *check*
+        // the key's mark bit, which some other edge has to set first.
+        if unsafe { pages::get_mark_bit(key) || pages::is_frozen(key) } {
+            self.visit(value);
+        } else {
+            self.pending_ephemerons.push(PendingEphemeron {
+                key: key.into(),
+                value_slot: value_slot as *const _ as *const (),
+                try_resolve: try_resolve_entry_point::<V>,
+                clear: clear_entry_point::<V>,
+            });
+        }
+    }
 }
@@ -1,10 +1,62 @@
 //! Allocating pages of memory from the OS and carving them into individual
 //! allocations. See TypedPage for details.
-
+//!
+//! ### Why pages aren't shared across types
+//!
+//! Each `IntoHeapAllocation` type gets its own chain of `TypedPage`s (see
+//! `GcHeap::page_sets`), so a program that declares dozens of small macro
+//! types can end up with dozens of mostly-empty 4KB pages. A size-class
+//! allocator (several types sharing a page, distinguished by a type tag on
+//! each cell) would use memory more efficiently, but it's a bigger change
+//! than it looks: `PageHeader::mark_fn` and `PageHeader::type_id` currently
+//! identify the single type stored on that page, and sweeping and marking
+//! both rely on that to stay monomorphic and cheap. Making that per-cell
+//! instead of per-page is future work; in the meantime,
+//! `GcHeapSession::type_page_count` at least lets you measure how much a
+//! given type's page chain is costing you.
+//!
+//! ### On pointer provenance
+//!
+//! `PageHeader`'s own address bookkeeping (`find`, `for_each_live`,
+//! `clear_mark_bits`, `is_empty`) reconstructs pointers from addresses it
+//! computes by masking and offsetting, via `addr_with_provenance` rather
+//! than a bare `addr as *mut _` cast, so the result keeps the provenance of
+//! the pointer the address was derived from instead of being built out of
+//! thin air. That's enough for those to run clean under Miri's strict
+//! provenance mode.
+//!
+//! `TypedPage<U>`'s own bump-pointer and free-list walk (`begin`, `end`,
+//! `init_mark_words_and_freelist`, `infallible_alloc`, `add_to_free_list`,
+//! and sweeping) still
+//! reconstructs pointers the same way `PageHeader` used to, and hasn't been
+//! migrated: it's a much larger surface, on a hotter path, and doing it
+//! without a measurable regression needs more care than this pass gives it.
+//! Until then, running the test suite under `-Zmiri-strict-provenance` will
+//! still flag those; `-Zmiri-permissive-provenance` (Miri's default) accepts
+//! them.
+//!
+//! ### Why there's no large-object space
+//!
+//! A `TypedPage<U>` cell stores `U` inline, so `U` has to be small enough
+//! that at least one of them fits in a page (`new_page` panics otherwise).
+//! In practice this is rarely a problem: bulky data doesn't need to live in
+//! a cell at all. `Vec<T>`'s `IntoHeapAllocation` impl, for instance, only
+//! puts the `Vec`'s pointer/length/capacity in the page; the backing buffer
+//! is a normal heap allocation from the system allocator, swept by `Vec`'s
+//! own `Drop` when the cell holding it is collected. The same trick works
+//! for any type with a `Box<T>` or `Vec<T>` field. So instead of a separate
+//! large-object space, cell-gc expects big data to be pushed out to the
+//! system allocator this way, and the page-fit assertion exists to catch
+//! GC types that forgot to do that.
+
+use dump;
+use freeze;
+use verify;
 use heap::GcHeap;
 use marking::MarkingTracer;
 use ptr::{Pointer, UntypedPointer};
-use std::any::TypeId;
+use std::alloc::{self, Layout};
+use std::any::{type_name, TypeId};
 use std::{cmp, mem, ptr};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
@@ -16,6 +68,40 @@ struct MarkWord(usize);
 const MARK_BIT: usize = 1;
 const ALLOCATED_BIT: usize = 2;
 
+/// Number of bits, starting at bit 2, used to track how many collections an
+/// allocation has survived. Two bits gives us room to distinguish "brand
+/// new", "survived one GC", and "tenured" without growing `MarkWord`.
+const AGE_SHIFT: usize = 2;
+const AGE_BITS: usize = 2;
+const AGE_MASK: usize = ((1 << AGE_BITS) - 1) << AGE_SHIFT;
+
+/// Set on every object in the reachable closure of a root passed to
+/// `GcHeapSession::freeze_reachable`. Frozen objects are permanently
+/// exempt from marking and sweeping: `MarkingTracer` stops as soon as it
+/// sees this bit (their whole closure is frozen too, so there's nothing
+/// left to discover), and sweep keeps them regardless of the mark bit.
+/// There's no way to clear it once set.
+const FROZEN_BIT: usize = 1 << (AGE_SHIFT + AGE_BITS);
+
+/// Four bits reserved for embedders, not interpreted by cell-gc itself.
+/// Unlike the mark bit, these survive collection untouched; unlike the age
+/// field, cell-gc never writes to them. Meant for cheap per-object marks an
+/// interpreter wants without paying for a whole extra field on every
+/// object, e.g. a "visited" bit for a cycle-aware printer or an "immutable"
+/// bit for literal data.
+const USER_FLAGS_SHIFT: usize = AGE_SHIFT + AGE_BITS + 1;
+const USER_FLAGS_BITS: usize = 4;
+const USER_FLAGS_MASK: usize = ((1 << USER_FLAGS_BITS) - 1) << USER_FLAGS_SHIFT;
+
+/// The pin count occupies the bits above the age, frozen, and user-flag
+/// fields. Pinning is therefore a little more expensive than a single
+/// increment, but pins are rare compared to ordinary field reads and writes.
+const PIN_INCREMENT: usize = 1 << (USER_FLAGS_SHIFT + USER_FLAGS_BITS);
+
+/// Number of collections an allocation must survive before it is considered
+/// "tenured". See `get_age` and `is_tenured`.
+pub const TENURING_AGE: u8 = 2;
+
 /// Add the value `*p` to the root set, protecting it from GC.
 ///
 /// A value that has been pinned *n* times stays in the root set
@@ -25,6 +111,17 @@ const ALLOCATED_BIT: usize = 2;
 ///
 /// `p` must point to a live allocation of type `U` in this heap.
 pub unsafe fn pin<U: InHeap>(p: Pointer<U>) {
+    // A frozen object is permanently exempt from collection, so pinning it
+    // would have nothing to protect --- and skipping the mutation here,
+    // before ever taking a `&mut MarkWord`, is what makes this sound to
+    // call from `FrozenHeap::read`: several threads can be dereferencing
+    // the same shared frozen substructure at once (see `frozen_heap`'s
+    // module docs), and materializing so much as one `&mut MarkWord` while
+    // another thread holds any reference to that word is undefined
+    // behavior, whether or not either side actually writes.
+    if MarkWord::peek(p, |mw| mw.is_frozen()) {
+        return;
+    }
     MarkWord::from_ptr(p, |mw| mw.pin());
 }
 
@@ -34,26 +131,135 @@ pub unsafe fn pin<U: InHeap>(p: Pointer<U>) {
 ///
 /// `p` must point to a pinned allocation of type `U` in this heap.
 pub unsafe fn unpin<U: InHeap>(p: Pointer<U>) {
+    // See the matching frozen check in `pin`.
+    if MarkWord::peek(p, |mw| mw.is_frozen()) {
+        return;
+    }
     MarkWord::from_ptr(p, |mw| mw.unpin());
 }
 
+/// Like `pin`, but for a pointer whose pointee type isn't known. Used by
+/// `GcDyn`, which doesn't have a `U: InHeap` to be generic over.
+///
+/// # Safety
+///
+/// `p` must point to a live allocation in this heap.
+pub unsafe fn pin_untyped(p: UntypedPointer) {
+    // See the matching frozen check in `pin`.
+    if MarkWord::peek_untyped(p, |mw| mw.is_frozen()) {
+        return;
+    }
+    MarkWord::from_untyped_ptr(p, |mw| mw.pin());
+}
+
 /// Unpin a heap allocation.
 ///
 /// # Safety
 ///
 /// `p` must point to a pinned allocation in this heap.
 pub unsafe fn unpin_untyped(p: UntypedPointer) {
+    // See the matching frozen check in `pin`.
+    if MarkWord::peek_untyped(p, |mw| mw.is_frozen()) {
+        return;
+    }
     MarkWord::from_untyped_ptr(p, |mw| mw.unpin());
 }
 
 pub unsafe fn get_mark_bit<U: InHeap>(p: Pointer<U>) -> bool {
-    MarkWord::from_ptr(p, |mw| mw.is_marked())
+    MarkWord::peek(p, |mw| mw.is_marked())
+}
+
+/// Like `get_mark_bit`, but for a pointer whose pointee type isn't known.
+/// Used by `Ephemeron` to check whether its key is reachable without being
+/// generic over the key type at the call site.
+pub unsafe fn get_mark_bit_untyped(p: UntypedPointer) -> bool {
+    MarkWord::peek_untyped(p, |mw| mw.is_marked())
+}
+
+/// Like `get_mark_bit_untyped`, but for the allocated bit. Used by
+/// `GcHeap::verify` to detect dangling references to freed slots.
+pub unsafe fn is_allocated_untyped(p: UntypedPointer) -> bool {
+    MarkWord::peek_untyped(p, |mw| mw.is_allocated())
+}
+
+/// Like `is_frozen`, but for a pointer whose pointee type isn't known. Used
+/// by `Ephemeron` to check whether its key is reachable without being
+/// generic over the key type at the call site.
+pub unsafe fn is_frozen_untyped(p: UntypedPointer) -> bool {
+    MarkWord::peek_untyped(p, |mw| mw.is_frozen())
 }
 
 pub unsafe fn set_mark_bit<U: InHeap>(p: Pointer<U>) {
     MarkWord::from_ptr(p, |mw| mw.mark());
 }
 
+/// True if `p`'s referent is part of a frozen closure. See
+/// `GcHeapSession::freeze_reachable`.
+///
+/// Reads through a shared `&MarkWord` rather than the usual `&mut` (see
+/// `MarkWord::peek`), since this is called on the concurrent-read path in
+/// `FrozenHeap::read` (via `GcRef::as_mut_ptr`'s debug assertion and the
+/// generated accessors it backs), where several threads may be looking at
+/// the same word at once.
+pub unsafe fn is_frozen<U: InHeap>(p: Pointer<U>) -> bool {
+    MarkWord::peek(p, |mw| mw.is_frozen())
+}
+
+/// Mark `p`'s referent as frozen (see `GcHeapSession::freeze_reachable`).
+/// There's no corresponding `unfreeze`: once set, this bit is never
+/// cleared.
+pub unsafe fn freeze<U: InHeap>(p: Pointer<U>) {
+    MarkWord::from_ptr(p, |mw| mw.freeze());
+}
+
+/// Like `freeze`, but for a pointer whose pointee type isn't known. Used by
+/// `GcDyn`.
+pub unsafe fn freeze_untyped(p: UntypedPointer) {
+    MarkWord::from_untyped_ptr(p, |mw| mw.freeze());
+}
+
+/// Get the number of collections `p`'s referent has survived so far.
+///
+/// # Safety
+///
+/// `p` must point to a live allocation of type `U` in this heap.
+pub unsafe fn get_age<U: InHeap>(p: Pointer<U>) -> u8 {
+    MarkWord::peek(p, |mw| mw.age())
+}
+
+/// Get the value of one of `p`'s referent's four embedder-owned user flag
+/// bits (see `USER_FLAGS_MASK`).
+///
+/// # Safety
+///
+/// `p` must point to a live allocation of type `U` in this heap. `index`
+/// must be less than 4.
+pub unsafe fn get_user_flag<U: InHeap>(p: Pointer<U>, index: u8) -> bool {
+    MarkWord::peek(p, |mw| mw.get_user_flag(index))
+}
+
+/// Set or clear one of `p`'s referent's four embedder-owned user flag bits
+/// (see `USER_FLAGS_MASK`).
+///
+/// # Safety
+///
+/// `p` must point to a live allocation of type `U` in this heap. `index`
+/// must be less than 4.
+pub unsafe fn set_user_flag<U: InHeap>(p: Pointer<U>, index: u8, value: bool) {
+    MarkWord::from_ptr(p, |mw| mw.set_user_flag(index, value));
+}
+
+/// Get the heap that owns `p`'s referent. Used by `GcRef::with_storage` to
+/// reach the heap's `begin_storage_borrow` guard from just a pointer, since
+/// `GcRef` itself doesn't carry a `&GcHeap`.
+///
+/// # Safety
+///
+/// `p` must point to a live allocation of type `U` in some heap.
+pub unsafe fn heap_of<U: InHeap>(p: Pointer<U>) -> *mut GcHeap {
+    (*PageHeader::find(p.into())).heap
+}
+
 const MARK_WORD_INIT: MarkWord = MarkWord(0);
 
 impl MarkWord {
@@ -71,12 +277,42 @@ impl MarkWord {
         f(&mut *(addr as *mut MarkWord))
     }
 
+    /// Like `from_ptr`, but for read-only queries: takes `f` by shared
+    /// reference rather than `&mut`.
+    ///
+    /// This matters for allocations that may be read concurrently from
+    /// several threads at once, such as the ones exposed by `FrozenHeap`:
+    /// two live `&mut MarkWord`s over the same word, even if both sides
+    /// only read through them, violate Rust's aliasing rules and are
+    /// undefined behavior, not merely a logical data race. Callers on a
+    /// possibly-concurrent read path must go through `peek`, never
+    /// `from_ptr`.
+    unsafe fn peek<U: InHeap, F, R>(ptr: Pointer<U>, f: F) -> R
+        where F: for<'a> FnOnce(&'a MarkWord) -> R
+    {
+        let addr = ptr.as_usize() - mem::size_of::<MarkWord>();
+        f(&*(addr as *const MarkWord))
+    }
+
+    /// Like `peek`, but for a pointer whose pointee type isn't known. See
+    /// `from_untyped_ptr`.
+    unsafe fn peek_untyped<F, R>(ptr: UntypedPointer, f: F) -> R
+        where F: for<'a> FnOnce(&'a MarkWord) -> R
+    {
+        let addr = ptr.as_usize() - mem::size_of::<MarkWord>();
+        f(&*(addr as *const MarkWord))
+    }
+
     fn is_allocated(&self) -> bool {
         self.0 & ALLOCATED_BIT != 0
     }
 
     fn set_allocated(&mut self) {
         self.0 |= ALLOCATED_BIT;
+        // A fresh allocation is always in the youngest generation, and
+        // starts with no user flags set, even if this memory previously
+        // held a tenured object with flags of its own.
+        self.0 &= !(AGE_MASK | USER_FLAGS_MASK);
     }
 
     fn clear_allocated(&mut self) {
@@ -96,20 +332,58 @@ impl MarkWord {
     }
 
     fn is_pinned(&self) -> bool {
-        self.0 >> 2 != 0
+        self.0 >> (USER_FLAGS_SHIFT + USER_FLAGS_BITS) != 0
+    }
+
+    fn is_frozen(&self) -> bool {
+        self.0 & FROZEN_BIT != 0
+    }
+
+    fn freeze(&mut self) {
+        self.0 |= FROZEN_BIT;
     }
 
     #[inline]
     fn pin(&mut self) {
         debug_assert!(self.is_allocated());
-        self.0 += 4;
+        self.0 += PIN_INCREMENT;
     }
 
     #[inline]
     fn unpin(&mut self) {
         debug_assert!(self.is_allocated());
         debug_assert!(self.is_pinned());
-        self.0 -= 4;
+        self.0 -= PIN_INCREMENT;
+    }
+
+    /// Number of collections this allocation has survived, saturating at
+    /// `2.pow(AGE_BITS) - 1`.
+    fn age(&self) -> u8 {
+        ((self.0 & AGE_MASK) >> AGE_SHIFT) as u8
+    }
+
+    /// Record that this (still-live) allocation has survived another
+    /// collection, saturating rather than wrapping once the age field is full.
+    fn bump_age(&mut self) {
+        let max = (1 << AGE_BITS) - 1;
+        if self.age() < max {
+            self.0 += 1 << AGE_SHIFT;
+        }
+    }
+
+    fn get_user_flag(&self, index: u8) -> bool {
+        debug_assert!((index as usize) < USER_FLAGS_BITS);
+        self.0 & (1 << (USER_FLAGS_SHIFT + index as usize)) != 0
+    }
+
+    fn set_user_flag(&mut self, index: u8, value: bool) {
+        debug_assert!((index as usize) < USER_FLAGS_BITS);
+        let bit = 1 << (USER_FLAGS_SHIFT + index as usize);
+        if value {
+            self.0 |= bit;
+        } else {
+            self.0 &= !bit;
+        }
     }
 }
 
@@ -142,34 +416,181 @@ pub(crate) const PAGE_SIZE: usize = 0x1000;
 /// TypedPage instances.
 pub(crate) const PAGE_ALIGN: usize = 0x1000;
 
+/// The page size and alignment every heap in this process uses. See
+/// `GcHeap::page_geometry`.
+pub(crate) fn geometry() -> PageGeometry {
+    PageGeometry {
+        page_size: PAGE_SIZE,
+        page_align: PAGE_ALIGN,
+    }
+}
+
+/// A heap's page size and alignment. See `GcHeap::page_geometry`.
+///
+/// This isn't currently configurable: `PageHeader::find` recovers a page's
+/// header from any interior pointer by masking off the low bits of the
+/// address, and `TypedPage::<U>::capacity()` and the page-walking code
+/// compute each page's allocation count and bounds from `PAGE_SIZE`. Both rely on
+/// the page size being a single alignment baked into every pointer
+/// computation, not a value carried by a `GcHeap` instance, so a per-heap
+/// `with_page_size` constructor isn't achievable without reworking how
+/// pages locate their own header. This type exists so callers can at least
+/// observe the geometry that's in effect, and so a future patch that does
+/// make it configurable has somewhere to report the result.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PageGeometry {
+    /// Size in bytes of each page cell-gc allocates from the OS.
+    pub page_size: usize,
+
+    /// Alignment in bytes of each page. Currently always equal to
+    /// `page_size`.
+    pub page_align: usize,
+}
+
 fn is_aligned(ptr: *const ()) -> bool {
     ptr as usize & (PAGE_ALIGN - 1) == 0
 }
 
+/// Where a `GcHeap`'s pages come from. The default, `GlobalPageSource`, gets
+/// them from `std::alloc`, but an embedder can supply its own (a fixed
+/// arena, a pool carved out of statically allocated memory, ...) via
+/// `GcHeap::set_page_source`.
+///
+/// This is one piece of what a `no_std` + `alloc` build of cell-gc would
+/// need: the page allocator no longer has to assume a full standard library
+/// is available underneath it. It isn't the whole story --- `GcHeap` and its
+/// neighbors still reach for `std::collections::HashMap`, `std::sync::Arc`/
+/// `Mutex`, `std::time::Instant` (in `collect_with_deadline`), and
+/// `std::io::{Read, Write}` (in `serialize`/`deserialize`) --- so this alone
+/// doesn't get the core crate compiling under `no_std`. Making the rest of
+/// those swappable for their `core`/`alloc`-only equivalents is a bigger,
+/// separate change.
+///
+/// # Safety
+///
+/// `alloc_page` must return either a null pointer (on failure) or a pointer
+/// to a fresh, exclusively-owned region of at least `size` bytes aligned to
+/// `align`. That region must stay valid until it's passed back to
+/// `dealloc_page` with the same `size` and `align` it was allocated with.
+pub unsafe trait PageSource {
+    /// Allocate `size` bytes aligned to `align`, or return null on failure.
+    unsafe fn alloc_page(&mut self, size: usize, align: usize) -> *mut u8;
+
+    /// Free memory previously returned by `alloc_page` with the same `size`
+    /// and `align`.
+    unsafe fn dealloc_page(&mut self, ptr: *mut u8, size: usize, align: usize);
+}
+
+/// The default `PageSource`: pages come from the process's global allocator.
+pub struct GlobalPageSource;
+
+unsafe impl PageSource for GlobalPageSource {
+    unsafe fn alloc_page(&mut self, size: usize, align: usize) -> *mut u8 {
+        alloc::alloc(Layout::from_size_align_unchecked(size, align))
+    }
+
+    unsafe fn dealloc_page(&mut self, ptr: *mut u8, size: usize, align: usize) {
+        alloc::dealloc(ptr, Layout::from_size_align_unchecked(size, align));
+    }
+}
+
 pub struct PageHeader {
     pub heap: *mut GcHeap,
     next_page: *mut PageHeader,
     type_id: TypeId,
     mark_fn: unsafe fn(UntypedPointer, &mut MarkingTracer),
+    dump_fn: unsafe fn(UntypedPointer) -> (&'static str, Vec<usize>),
+    verify_fn: unsafe fn(UntypedPointer, &mut verify::VerifyTracer),
+    freeze_fn: unsafe fn(UntypedPointer, &mut freeze::FreezeTracer),
     freelist: *mut (),
     allocation_size: usize,
 }
 
 impl PageHeader {
+    /// Reconstruct a pointer at `addr` (an address known to be within the
+    /// same page as `base`) by rewriting `base`'s address, rather than
+    /// manufacturing a pointer out of a bare integer (`addr as *mut _`) the
+    /// way an int-to-pointer cast does. Address-only arithmetic like that
+    /// loses `base`'s provenance, which strict-provenance-aware tools (e.g.
+    /// Miri run with `-Zmiri-strict-provenance`) reject; going through
+    /// `with_addr` instead keeps the result's provenance tied to the same
+    /// allocation `base` came from, which every address this is called with
+    /// is within.
+    fn addr_with_provenance(base: *const (), addr: usize) -> *const () {
+        base.with_addr(addr)
+    }
+
     pub fn find(ptr: UntypedPointer) -> *mut PageHeader {
-        let header_addr = ptr.as_usize() & !(PAGE_ALIGN - 1);
+        let base = ptr.as_void();
+        let header_addr = base.addr() & !(PAGE_ALIGN - 1);
         debug_assert!(header_addr != 0);
-        header_addr as *mut PageHeader
+        Self::addr_with_provenance(base, header_addr) as *mut PageHeader
     }
 
     pub unsafe fn mark(&self, ptr: UntypedPointer, tracer: &mut MarkingTracer) {
         (self.mark_fn)(ptr, tracer);
     }
 
+    /// Get the type name and outgoing-edge addresses of the object at `ptr`,
+    /// for `GcHeap::dump`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live allocation of this page's type.
+    pub(crate) unsafe fn dump(&self, ptr: UntypedPointer) -> (&'static str, Vec<usize>) {
+        (self.dump_fn)(ptr)
+    }
+
+    /// Trace the object at `ptr`, feeding each outgoing edge to `tracer` for
+    /// consistency checking. For `GcHeap::verify`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live allocation of this page's type.
+    pub(crate) unsafe fn verify_edges(&self, ptr: UntypedPointer, tracer: &mut verify::VerifyTracer) {
+        (self.verify_fn)(ptr, tracer)
+    }
+
+    /// Freeze the object at `ptr`, feeding its outgoing edges to `tracer`
+    /// so the whole closure gets frozen too. For `GcHeapSession::freeze_reachable`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live allocation of this page's type.
+    pub(crate) unsafe fn freeze(&self, ptr: UntypedPointer, tracer: &mut freeze::FreezeTracer) {
+        (self.freeze_fn)(ptr, tracer)
+    }
+
     pub fn type_id(&self) -> TypeId {
         self.type_id
     }
 
+    /// Size in bytes of each allocation on this page, mark word included.
+    pub(crate) fn allocation_size(&self) -> usize {
+        self.allocation_size
+    }
+
+    /// Call `f` once for every allocated (as opposed to free-listed) object
+    /// on this page.
+    pub(crate) fn for_each_live<F: FnMut(UntypedPointer)>(&self, mut f: F) {
+        let base = self as *const PageHeader as *const ();
+        let mut addr = self.begin();
+        let end = self.end();
+        while addr < end {
+            let mark_word = unsafe { &*(Self::addr_with_provenance(base, addr) as *const MarkWord) };
+            if mark_word.is_allocated() {
+                let ptr = unsafe {
+                    UntypedPointer::new(Self::addr_with_provenance(
+                        base,
+                        addr + mem::size_of::<MarkWord>(),
+                    ))
+                };
+                f(ptr);
+            }
+            addr += self.allocation_size;
+        }
+    }
+
     pub fn downcast_mut<U: InHeap>(&mut self) -> Option<&mut TypedPage<U>> {
         if heap_type_id::<U>() == self.type_id() {
             let ptr = self as *mut PageHeader as *mut TypedPage<U>;
@@ -200,16 +621,19 @@ impl PageHeader {
     }
 
     pub fn clear_mark_bits(&mut self, roots: &mut Vec<UntypedPointer>) {
+        let base = self as *mut PageHeader as *const ();
         let mut addr = self.begin();
         let end = self.end();
         while addr < end {
-            let mark_word = unsafe { &mut *(addr as *mut MarkWord) };
+            let mark_word = unsafe { &mut *(Self::addr_with_provenance(base, addr) as *mut MarkWord) };
             mark_word.unmark();
             if mark_word.is_pinned() {
-                let ptr =
-                    unsafe {
-                        UntypedPointer::new((addr + mem::size_of::<MarkWord>()) as *const ())
-                    };
+                let ptr = unsafe {
+                    UntypedPointer::new(Self::addr_with_provenance(
+                        base,
+                        addr + mem::size_of::<MarkWord>(),
+                    ))
+                };
                 roots.push(ptr);
             }
             addr += self.allocation_size;
@@ -218,10 +642,11 @@ impl PageHeader {
 
     /// True if nothing on this page is allocated.
     pub fn is_empty(&self) -> bool {
+        let base = self as *const PageHeader as *const ();
         let mut addr = self.begin();
         let end = self.end();
         while addr < end {
-            let mark_word = unsafe { &mut *(addr as *mut MarkWord) };
+            let mark_word = unsafe { &mut *(Self::addr_with_provenance(base, addr) as *mut MarkWord) };
             if mark_word.is_allocated() {
                 return false;
             }
@@ -396,40 +821,56 @@ impl<U: InHeap> TypedPage<U> {
         UninitializedAllocation { ptr }
     }
 
-    /// Sweep this page and return the number of objects swept.
-    unsafe fn sweep(&mut self) -> usize {
+    /// Sweep this page. Returns the number of objects swept, and the number
+    /// that were promoted to tenured status by surviving this collection
+    /// (see `pages::TENURING_AGE`).
+    unsafe fn sweep(&mut self) -> (usize, usize) {
         let mut num_swept = 0;
+        let mut num_promoted = 0;
 
         let mut addr = self.begin();
         let end = self.end();
         while addr < end {
             let mw = &mut *(addr as *mut MarkWord);
-            if mw.is_allocated() && !mw.is_marked() {
-                let object_ptr = (addr + mem::size_of::<MarkWord>()) as *mut U;
-                ptr::drop_in_place(object_ptr);
-                if cfg!(debug_assertions) || cfg!(test) {
-                    // Paint the unused memory with a known-bad value.
-                    const SWEPT_BYTE: u8 = 0xf4;
-                    ptr::write_bytes(object_ptr, SWEPT_BYTE, 1);
+            if mw.is_allocated() {
+                if mw.is_marked() || mw.is_frozen() {
+                    // This object is still alive: it has now survived
+                    // another collection. (Frozen objects are always
+                    // "still alive", regardless of the mark bit; see
+                    // `GcHeapSession::freeze_reachable`.)
+                    let was_tenured = mw.age() >= TENURING_AGE;
+                    mw.bump_age();
+                    if !was_tenured && mw.age() >= TENURING_AGE {
+                        num_promoted += 1;
+                    }
+                } else {
+                    let object_ptr = (addr + mem::size_of::<MarkWord>()) as *mut U;
+                    ptr::drop_in_place(object_ptr);
+                    if cfg!(debug_assertions) || cfg!(test) {
+                        // Paint the unused memory with a known-bad value.
+                        const SWEPT_BYTE: u8 = 0xf4;
+                        ptr::write_bytes(object_ptr, SWEPT_BYTE, 1);
+                    }
+                    mw.clear_allocated();
+                    self.add_to_free_list(object_ptr);
+                    num_swept += 1;
                 }
-                mw.clear_allocated();
-                self.add_to_free_list(object_ptr);
-                num_swept += 1;
             }
             addr += Self::allocation_size();
         }
 
-        num_swept
+        (num_swept, num_promoted)
     }
 }
 
-/// Sweep a page and return the number of objects swept.
+/// Sweep a page. Returns the number of objects swept and the number
+/// promoted (see `TypedPage::sweep`).
 ///
 /// # Safety
 ///
 /// This must be called only after a full mark phase, to avoid sweeping objects
 /// that are still reachable.
-unsafe fn sweep_entry_point<U: InHeap>(header: &mut PageHeader) -> usize {
+unsafe fn sweep_entry_point<U: InHeap>(header: &mut PageHeader) -> (usize, usize) {
     header.downcast_mut::<U>().expect("page header corrupted").sweep()
 }
 
@@ -439,7 +880,13 @@ unsafe fn sweep_entry_point<U: InHeap>(header: &mut PageHeader) -> usize {
 pub struct PageSet {
     heap: *mut GcHeap,
 
-    sweep_fn: unsafe fn(&mut PageHeader) -> usize,
+    sweep_fn: unsafe fn(&mut PageHeader) -> (usize, usize),
+
+    /// This page set's allocation type's name and per-object size, kept here
+    /// (rather than read off a page) so they're available even when every
+    /// page has been released. See `GcHeap::types`.
+    type_name: &'static str,
+    allocation_size: usize,
 
     /// Total number of pages in the following lists.
     page_count: usize,
@@ -490,7 +937,7 @@ impl Drop for PageSet {
                     (*page).clear_mark_bits(&mut roots_to_ignore);
                     (self.sweep_fn)(&mut *page); // drop all objects remaining in the page
                     ptr::drop_in_place(page); // drop the header
-                    Vec::from_raw_parts(page as *mut u8, 0, PAGE_SIZE); // free the page
+                    (*self.heap).dealloc_page_bytes(page as *mut u8, PAGE_SIZE, PAGE_ALIGN);
                     page = next;
                 }
             }
@@ -508,6 +955,8 @@ impl PageSet {
         PageSet {
             heap,
             sweep_fn: sweep_entry_point::<U>,
+            type_name: type_name::<U>(),
+            allocation_size: TypedPage::<U>::allocation_size(),
             page_count: 0,
             full_pages: ptr::null_mut(),
             other_pages: ptr::null_mut(),
@@ -551,18 +1000,27 @@ impl PageSet {
         self.each_page_mut(|page| page.clear_mark_bits(roots));
     }
 
-    /// Sweep all unmarked objects from all pages and return the number of
-    /// objects swept.
+    /// Sweep all unmarked objects from all pages.
     ///
     /// # Safety
     ///
     /// Safe to call only as the final part of GC.
-    pub unsafe fn sweep(&mut self) -> usize {
+    ///
+    /// Returns the number of objects swept, the total size in bytes of their
+    /// cells (not counting any separately heap-allocated data they owned,
+    /// e.g. a `Vec`'s backing buffer), and the number of surviving objects
+    /// promoted to tenured status (see `TypedPage::sweep`).
+    pub unsafe fn sweep(&mut self) -> (usize, usize, usize) {
         let mut num_swept = 0;
+        let mut bytes_freed = 0;
+        let mut num_promoted = 0;
 
         // Sweep nonfull pages.
         each_page_mut(self.other_pages, |page| {
-            num_swept += (self.sweep_fn)(page);
+            let (swept_here, promoted_here) = (self.sweep_fn)(page);
+            num_swept += swept_here;
+            bytes_freed += swept_here * page.allocation_size;
+            num_promoted += promoted_here;
         });
 
         // Sweep full pages. Much more complicated because we have to move
@@ -570,8 +1028,10 @@ impl PageSet {
         let mut prev_page = &mut self.full_pages;
         let mut page = *prev_page;
         while !page.is_null() {
-            let num_swept_this_page = (self.sweep_fn)(&mut *page);
+            let (num_swept_this_page, promoted_here) = (self.sweep_fn)(&mut *page);
             num_swept += num_swept_this_page;
+            bytes_freed += num_swept_this_page * (*page).allocation_size;
+            num_promoted += promoted_here;
             if num_swept_this_page > 0 {
                 let next_page = (*page).next_page;
 
@@ -589,7 +1049,17 @@ impl PageSet {
             }
         }
 
-        num_swept
+        (num_swept, bytes_freed, num_promoted)
+    }
+
+    /// Call `f` once for every live object across every page in this set,
+    /// passing its pointer and the `PageHeader` of the page it's on (so the
+    /// caller can look up its type name, size, and outgoing edges via
+    /// `PageHeader::dump`). See `GcHeap::dump`.
+    pub(crate) fn for_each_live_object<F: FnMut(UntypedPointer, &PageHeader)>(&self, mut f: F) {
+        self.each_page(|page| {
+            page.for_each_live(|ptr| f(ptr, page));
+        });
     }
 
     /// True if nothing is allocated in this set of pages.
@@ -599,9 +1069,116 @@ impl PageSet {
         empty
     }
 
+    /// This set's allocation type's name, from `std::any::type_name`. See
+    /// `GcHeap::types`.
+    pub(crate) fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Size in bytes of one allocation of this set's type, mark word
+    /// included. See `GcHeap::types`.
+    pub(crate) fn allocation_size(&self) -> usize {
+        self.allocation_size
+    }
+
+    /// Number of live objects currently allocated across this set's pages.
+    /// See `GcHeap::types`.
+    pub(crate) fn live_object_count(&self) -> usize {
+        let mut count = 0;
+        self.for_each_live_object(|_ptr, _page| count += 1);
+        count
+    }
+
     pub fn set_page_limit(&mut self, limit: Option<usize>) {
         self.limit = limit;
     }
+
+    /// The limit set by `set_page_limit`, if any.
+    pub fn page_limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Total number of pages currently owned by this set.
+    pub(crate) fn page_count(&self) -> usize {
+        self.page_count
+    }
+
+    /// True if allocating from this set would require asking the allocator
+    /// for a new page (i.e. there is no nonfull page to bump-allocate from).
+    pub(crate) fn needs_new_page(&self) -> bool {
+        self.other_pages.is_null()
+    }
+
+    /// Deallocate and return to the allocator any pages in this set that
+    /// currently hold no live objects.
+    ///
+    /// This doesn't move surviving objects the way a real mark-compact or
+    /// copying collector would — cell-gc pages never move, since there's no
+    /// pointer-relocation hook in `IntoHeap` to update every in-heap and
+    /// stack reference. But for allocation patterns with lots of short-lived
+    /// objects, releasing pages that end up entirely empty after a
+    /// collection reclaims most of the memory a moving collector would.
+    ///
+    /// # Safety
+    ///
+    /// Must be called only when nothing is in the middle of allocating from
+    /// this page set, and should be called right after a full collection so
+    /// that "empty" reflects the current liveness of objects.
+    pub unsafe fn release_empty_pages(&mut self) -> usize {
+        // Fully allocated pages (`self.full_pages`) are never empty: as soon
+        // as sweeping frees anything on one, `sweep()` moves it over to
+        // `self.other_pages`.
+        let mut released = 0;
+        let mut prev: *mut *mut PageHeader = &mut self.other_pages;
+        let mut page = self.other_pages;
+        while !page.is_null() {
+            let next = (*page).next_page;
+            if (*page).is_empty() {
+                *prev = next;
+                ptr::drop_in_place(page);
+                (*self.heap).dealloc_page_bytes(page as *mut u8, PAGE_SIZE, PAGE_ALIGN);
+                self.page_count -= 1;
+                released += 1;
+            } else {
+                prev = &mut (*page).next_page;
+            }
+            page = next;
+        }
+        released
+    }
+
+    /// Drop every currently allocated object in this page set, ignoring mark
+    /// and pin bits, and return their space to the free lists, keeping the
+    /// pages themselves allocated. Returns the number of objects dropped.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure no live `GcRef`, `GcFrozenRef`, or `PinnedRef`
+    /// points into this page set: unlike `sweep`, this drops pinned objects
+    /// too, which would otherwise leave such references dangling.
+    pub unsafe fn clear_all(&mut self) -> usize {
+        let mut num_cleared = 0;
+        let mut roots_to_ignore = vec![];
+        for page_list in &[self.full_pages, self.other_pages] {
+            each_page_mut(*page_list, |page| {
+                page.clear_mark_bits(&mut roots_to_ignore);
+                num_cleared += (self.sweep_fn)(page).0;
+            });
+        }
+
+        // Every page is non-full now; move them all onto `other_pages`.
+        if !self.full_pages.is_null() {
+            let mut last = self.full_pages;
+            while !(*last).next_page.is_null() {
+                last = (*last).next_page;
+            }
+            (*last).next_page = self.other_pages;
+            self.other_pages = self.full_pages;
+            self.full_pages = ptr::null_mut();
+        }
+
+        num_cleared
+    }
 }
 
 pub struct PageSetRef<'a, U: InHeap> {
@@ -666,12 +1243,45 @@ impl<'a, U: InHeap> PageSetRef<'a, U> {
         }
     }
 
+    /// Create enough new, empty pages to hold at least `n` allocations of
+    /// type `U`, without allocating from them, so that a later run of `n`
+    /// or fewer `try_alloc` calls can't hit the page allocator. Stops early
+    /// if `limit` (see `set_page_limit`) is reached, returning the number
+    /// of allocations' worth of pages it wasn't able to create.
+    ///
+    /// This doesn't look at how much free space already exists in this
+    /// page set's non-full pages; it always creates pages for `n` more
+    /// allocations. Call it before allocating anything of this type if you
+    /// want an exact guarantee.
+    pub fn reserve(&mut self, n: usize) -> usize {
+        let capacity = TypedPage::<U>::capacity();
+        let mut remaining = n;
+        while remaining > 0 {
+            if let Some(limit) = self.limit {
+                if self.page_count >= limit {
+                    break;
+                }
+            }
+            self.new_page();
+            remaining = remaining.saturating_sub(capacity);
+        }
+        remaining
+    }
+
     /// Allocate a page from the operating system.
     ///
     /// Initialize its header and freelist and link it into this page set's
     /// linked list of pages.
     fn new_page(&mut self) -> &mut TypedPage<U> {
         let capacity = TypedPage::<U>::capacity();
+        assert!(
+            capacity > 0,
+            "type is too large to fit even one allocation in a {}-byte page; \
+             cell-gc has no large-object space, so store bulky data (e.g. a \
+             big buffer) behind a `Vec` or `Box` field instead of embedding \
+             it directly in a GC type",
+            PAGE_SIZE
+        );
         assert!({
             let size_of_page = mem::size_of::<TypedPage<U>>();
             let alloc_offset = TypedPage::<U>::first_allocation_offset();
@@ -692,11 +1302,14 @@ impl<'a, U: InHeap> PageSetRef<'a, U> {
                     "Types with exotic alignment requirements are not supported");
         }
 
-        let mut vec: Vec<u8> = Vec::with_capacity(PAGE_SIZE);
-        let raw_page = vec.as_mut_ptr() as *mut ();
+        let raw_page = unsafe {
+            (*self.page_set.heap).alloc_page_bytes(PAGE_SIZE, PAGE_ALIGN) as *mut ()
+        };
+        assert!(!raw_page.is_null(), "cell-gc: page allocation failed");
 
-        // Rust makes no guarantee whatsoever that this will work.
-        // If it doesn't, panic.
+        // The PageSource contract guarantees PAGE_ALIGN-aligned memory, but
+        // check anyway: a custom PageSource that got this wrong would
+        // otherwise corrupt the low bits `PageHeader::find` relies on.
         assert!(is_aligned(raw_page));
 
         let page_ptr: *mut TypedPage<U> = raw_page as *mut TypedPage<U>;
@@ -719,6 +1332,9 @@ impl<'a, U: InHeap> PageSetRef<'a, U> {
                         next_page: *list_head,
                         type_id: heap_type_id::<U>(),
                         mark_fn: mark_entry_point::<U>,
+                        dump_fn: dump::dump_entry_point::<U>,
+                        verify_fn: verify::verify_entry_point::<U>,
+                        freeze_fn: freeze::freeze_entry_point::<U>,
                         freelist: ptr::null_mut(),
                         allocation_size: TypedPage::<U>::allocation_size()
                     },
@@ -729,9 +1345,6 @@ impl<'a, U: InHeap> PageSetRef<'a, U> {
             let page = &mut *page_ptr;
             page.init_mark_words_and_freelist();
 
-            // Remove the memory from the vector and link it into
-            // the PageSet's linked list.
-            mem::forget(vec);
             *list_head = &mut page.header;
             self.page_set.page_count += 1;
 
@@ -769,6 +1382,14 @@ impl<U: InHeap> UninitializedAllocation<U> {
         self.ptr.as_mut()
     }
 
+    /// The address this allocation will occupy once initialized. Useful when
+    /// something needs to know an object's future address before its
+    /// contents are ready to write, e.g. to memoize it against cycles; see
+    /// `adopt::Adopter::adopt_ptr`.
+    pub(crate) fn ptr(&self) -> Pointer<U> {
+        self.ptr
+    }
+
     /// # Safety
     ///
     /// This is safe as long as we've followed all the rules: GC has not occurred
@@ -780,6 +1401,27 @@ impl<U: InHeap> UninitializedAllocation<U> {
         mem::forget(self);
         ptr
     }
+
+    /// Like `init`, but write `U` in place via `f` instead of moving a
+    /// complete `U` into heap storage. This is the placement-construction
+    /// alternative to `init`: `f` gets a raw pointer to `U`'s future home
+    /// and writes every field of it directly there, e.g. with
+    /// `ptr::addr_of_mut!` and `ptr::write` per field (the standard pattern
+    /// for initializing behind a raw pointer; see the `std::mem::MaybeUninit`
+    /// docs), so a large `U` never needs to exist as a complete value on the
+    /// stack first. See `GcHeapSession::alloc_init`.
+    ///
+    /// # Safety
+    ///
+    /// Safe under the same rules as `init`, plus: `f` must fully initialize
+    /// every field of the pointee before returning.
+    pub unsafe fn init_with<F: FnOnce(*mut U)>(self, f: F) -> Pointer<U> {
+        debug_assert!(MarkWord::from_ptr(self.ptr, |mw| mw.is_allocated()));
+        let ptr = self.ptr;
+        f(ptr.as_mut());
+        mem::forget(self);
+        ptr
+    }
 }
 
 impl<U: InHeap> Drop for UninitializedAllocation<U> {
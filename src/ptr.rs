@@ -5,6 +5,7 @@ use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 use std::mem;
+use std::ptr::NonNull;
 use traits::InHeap;
 
 /// A pointer to some `U` in the GC heap.
@@ -83,7 +84,6 @@ impl<U: InHeap> Pointer<U> {
     /// will break loose.
     #[inline]
     pub unsafe fn as_ref(&self) -> &U {
-        assert!(!self.ptr.0.is_null());
         &*self.as_raw()
     }
 
@@ -154,10 +154,14 @@ impl<U: InHeap> From<Pointer<U>> for usize {
 ///
 /// See `Pointer<U>`.
 ///
-// TODO: The pointer should probably be wrapped in `Option<Shared<...>>` once
-// `Shared` and `NonZero` are stabilized.
+/// Wrapping `NonNull` rather than a plain `*const ()` isn't just
+/// documentation: it gives Rust a niche to exploit, so `Option<Pointer<U>>`
+/// (and therefore `Option<SomeRef<'h>>`, `Option<T>` for any `T: IntoHeap`
+/// backed by a `Pointer`) is the same size as `Pointer<U>` itself, no
+/// separate discriminant needed. It also means there's no such thing as a
+/// null `Pointer<U>` to accidentally construct or forget to check for.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
-pub struct UntypedPointer(*const ());
+pub struct UntypedPointer(NonNull<()>);
 
 impl UntypedPointer {
     /// Construct a new untyped pointer into the GC heap.
@@ -168,7 +172,6 @@ impl UntypedPointer {
     /// `Pointer<U>`.
     #[inline]
     pub unsafe fn new(ptr: *const ()) -> UntypedPointer {
-        assert!(!ptr.is_null(), "GC heap pointers can't be null.");
         assert_eq!(
             ptr as usize & (mem::size_of::<usize>() - 1),
             0,
@@ -183,7 +186,7 @@ impl UntypedPointer {
             },
             "heap pointers shouldn't clobber the PageHeader"
         );
-        UntypedPointer(ptr)
+        UntypedPointer(NonNull::new(ptr as *mut ()).expect("GC heap pointers can't be null."))
     }
 
     /// Convert this `UntypedPointer` into a `Pointer<U>`.
@@ -195,18 +198,18 @@ impl UntypedPointer {
     /// safety rules.
     #[inline]
     pub unsafe fn as_typed_ptr<U: InHeap>(&self) -> Pointer<U> {
-        Pointer::new(self.0 as *const U)
+        Pointer::new(self.0.as_ptr() as *const U)
     }
 
     /// Get the underlying raw pointer.
     #[inline]
     pub fn as_void(&self) -> *const () {
-        self.0
+        self.0.as_ptr()
     }
 
     /// Get the underlying raw pointer as a `usize`.
     #[inline]
     pub fn as_usize(&self) -> usize {
-        self.0 as usize
+        self.0.as_ptr() as usize
     }
 }
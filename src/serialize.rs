@@ -0,0 +1,299 @@
+//! Exact byte-level serialization of an object graph, so it can be
+//! reconstructed later --- in this heap, a different heap, or even a later
+//! run of the program. See `GcHeapSession::serialize` and
+//! `GcHeapSession::deserialize`.
+
+use heap::GcHeap;
+use pages::UninitializedAllocation;
+use ptr::{Pointer, UntypedPointer};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::mem;
+use traits::GcSerialize;
+
+/// Encodes and decodes the leaf (non-GC-pointer) values that can appear in a
+/// serialized heap value: primitives and `String`. `Serializer` and
+/// `Deserializer` take care of everything involving GC pointers; this trait
+/// takes care of everything else.
+pub trait Codec: Sized {
+    /// Append this value's byte encoding to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Read back a value written by `encode`.
+    fn decode(cur: &mut Cursor) -> Self;
+}
+
+macro_rules! codec_int_impl {
+    ($t:ty) => {
+        impl Codec for $t {
+            fn encode(&self, buf: &mut Vec<u8>) {
+                buf.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn decode(cur: &mut Cursor) -> $t {
+                let width = mem::size_of::<$t>();
+                let mut bytes = [0u8; mem::size_of::<$t>()];
+                bytes.copy_from_slice(cur.read_bytes(width));
+                <$t>::from_le_bytes(bytes)
+            }
+        }
+    }
+}
+
+codec_int_impl!(i8);
+codec_int_impl!(u8);
+codec_int_impl!(i16);
+codec_int_impl!(u16);
+codec_int_impl!(i32);
+codec_int_impl!(u32);
+codec_int_impl!(i64);
+codec_int_impl!(u64);
+codec_int_impl!(isize);
+codec_int_impl!(usize);
+codec_int_impl!(f32);
+codec_int_impl!(f64);
+codec_int_impl!(i128);
+codec_int_impl!(u128);
+
+impl Codec for ::std::time::Duration {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.as_secs().encode(buf);
+        self.subsec_nanos().encode(buf);
+    }
+
+    fn decode(cur: &mut Cursor) -> ::std::time::Duration {
+        let secs = u64::decode(cur);
+        let nanos = u32::decode(cur);
+        ::std::time::Duration::new(secs, nanos)
+    }
+}
+
+impl Codec for ::std::cmp::Ordering {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let tag: i8 = match *self {
+            ::std::cmp::Ordering::Less => -1,
+            ::std::cmp::Ordering::Equal => 0,
+            ::std::cmp::Ordering::Greater => 1,
+        };
+        tag.encode(buf);
+    }
+
+    fn decode(cur: &mut Cursor) -> ::std::cmp::Ordering {
+        match i8::decode(cur) {
+            -1 => ::std::cmp::Ordering::Less,
+            0 => ::std::cmp::Ordering::Equal,
+            1 => ::std::cmp::Ordering::Greater,
+            tag => panic!("cell-gc: corrupt serialized data (bad Ordering byte {})", tag),
+        }
+    }
+}
+
+impl Codec for bool {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(*self as u8);
+    }
+
+    fn decode(cur: &mut Cursor) -> bool {
+        match cur.read_u8() {
+            0 => false,
+            1 => true,
+            tag => panic!("cell-gc: corrupt serialized data (bad bool byte {})", tag),
+        }
+    }
+}
+
+impl Codec for char {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (*self as u32).encode(buf);
+    }
+
+    fn decode(cur: &mut Cursor) -> char {
+        let code = u32::decode(cur);
+        ::std::char::from_u32(code)
+            .unwrap_or_else(|| panic!("cell-gc: corrupt serialized data (bad char {:x})", code))
+    }
+}
+
+impl Codec for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (self.len() as u64).encode(buf);
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(cur: &mut Cursor) -> String {
+        let len = u64::decode(cur) as usize;
+        String::from_utf8(cur.read_bytes(len).to_vec())
+            .expect("cell-gc: corrupt serialized data (bad utf-8 in String)")
+    }
+}
+
+/// A cursor over the bytes of a single serialized object, handed to
+/// `GcSerialize::read_fields` so it can read its own fields back out in the
+/// order `write_fields` wrote them.
+pub struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(bytes: &'a [u8]) -> Cursor<'a> {
+        Cursor { bytes, pos: 0 }
+    }
+
+    pub(crate) fn read_u8(&mut self) -> u8 {
+        self.read_bytes(1)[0]
+    }
+
+    pub(crate) fn read_bytes(&mut self, n: usize) -> &[u8] {
+        let slice = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        slice
+    }
+}
+
+/// Context passed to `GcSerialize::write_fields` while walking an object
+/// graph out to bytes.
+///
+/// Serializing is two-phase: rather than writing bytes straight to the
+/// output stream as it walks the graph, `Serializer` builds up one in-memory
+/// record per distinct object first, in the order it first encounters them,
+/// and only turns those into bytes once the whole graph has been visited
+/// (`finish`). That's what makes cycles and shared substructure work: a GC
+/// pointer is encoded as its target's record number rather than its bytes
+/// inline, and `serialize_ptr` assigns that number to a target *before*
+/// recursing into it, the same "reserve first, then recurse" trick
+/// `Adopter::adopt_ptr` uses to break cycles when adopting.
+pub struct Serializer {
+    records: Vec<Vec<u8>>,
+    memo: HashMap<UntypedPointer, u64>,
+}
+
+impl Serializer {
+    fn new() -> Serializer {
+        Serializer {
+            records: Vec::new(),
+            memo: HashMap::new(),
+        }
+    }
+
+    /// Serialize the object `ptr` points at, returning the record number
+    /// other objects should use to refer to it. Returns the same number
+    /// every time it's called for the same pointer.
+    pub(crate) unsafe fn serialize_ptr<U: GcSerialize>(&mut self, ptr: Pointer<U>) -> u64 {
+        let source: UntypedPointer = ptr.into();
+        if let Some(&id) = self.memo.get(&source) {
+            return id;
+        }
+        let id = self.records.len() as u64;
+        self.memo.insert(source, id);
+        self.records.push(Vec::new());
+        let mut buf = Vec::new();
+        ptr.as_ref().write_fields(self, &mut buf);
+        self.records[id as usize] = buf;
+        id
+    }
+
+    fn finish(self, out: &mut dyn Write) -> io::Result<()> {
+        (self.records.len() as u64).encode_to(out)?;
+        for record in &self.records {
+            (record.len() as u64).encode_to(out)?;
+            out.write_all(record)?;
+        }
+        Ok(())
+    }
+}
+
+trait EncodeTo {
+    fn encode_to(&self, out: &mut dyn Write) -> io::Result<()>;
+}
+
+impl EncodeTo for u64 {
+    fn encode_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(&self.to_le_bytes())
+    }
+}
+
+/// Context passed to `GcSerialize::read_fields` while rebuilding an object
+/// graph from bytes.
+///
+/// Mirrors `Serializer`: `deserialize_ptr` allocates and memoizes a record's
+/// destination storage *before* reading its fields, so a record that refers
+/// back to itself (directly or through some other record) gets the same
+/// destination pointer every time, instead of allocating a fresh copy or
+/// recursing forever.
+pub struct Deserializer<'a> {
+    dest: &'a mut GcHeap,
+    records: Vec<Vec<u8>>,
+    memo: HashMap<u64, UntypedPointer>,
+}
+
+impl<'a> Deserializer<'a> {
+    fn new(dest: &'a mut GcHeap, records: Vec<Vec<u8>>) -> Deserializer<'a> {
+        Deserializer {
+            dest,
+            records,
+            memo: HashMap::new(),
+        }
+    }
+
+    /// Get the (possibly not yet fully initialized) destination pointer for
+    /// the record numbered `id`, deserializing it first if this is the
+    /// first time it's been requested.
+    pub(crate) unsafe fn deserialize_ptr<U: GcSerialize>(&mut self, id: u64) -> Pointer<U> {
+        if let Some(&p) = self.memo.get(&id) {
+            return p.as_typed_ptr::<U>();
+        }
+        let allocation = self.alloc_storage::<U>();
+        let dest_ptr = allocation.ptr();
+        self.memo.insert(id, dest_ptr.into());
+        let record = mem::replace(&mut self.records[id as usize], Vec::new());
+        let mut cur = Cursor::new(&record);
+        let value = U::read_fields(self, &mut cur);
+        allocation.init(value);
+        dest_ptr
+    }
+
+    fn alloc_storage<U: GcSerialize>(&mut self) -> UninitializedAllocation<U> {
+        let mut page_set = self.dest.get_page_set::<U>();
+        if let Some(allocation) = unsafe { page_set.try_fast_alloc() } {
+            return allocation;
+        }
+        page_set.reserve(1);
+        unsafe { page_set.try_fast_alloc() }
+            .expect("cell-gc: deserialize: just reserved a page for this allocation")
+    }
+}
+
+fn read_u64(input: &mut dyn Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_records(input: &mut dyn Read) -> io::Result<Vec<Vec<u8>>> {
+    let num_records = read_u64(input)? as usize;
+    let mut records = Vec::with_capacity(num_records);
+    for _ in 0..num_records {
+        let len = read_u64(input)? as usize;
+        let mut bytes = vec![0u8; len];
+        input.read_exact(&mut bytes)?;
+        records.push(bytes);
+    }
+    Ok(records)
+}
+
+/// Serialize the object graph rooted at `ptr` to `out`. See
+/// `GcHeapSession::serialize`.
+pub(crate) unsafe fn serialize<U: GcSerialize>(ptr: Pointer<U>, out: &mut dyn Write) -> io::Result<()> {
+    let mut serializer = Serializer::new();
+    serializer.serialize_ptr(ptr);
+    serializer.finish(out)
+}
+
+/// Deserialize an object graph written by `serialize` into `dest`, returning
+/// a pointer to the root. See `GcHeapSession::deserialize`.
+pub(crate) unsafe fn deserialize<U: GcSerialize>(dest: &mut GcHeap, input: &mut dyn Read) -> io::Result<Pointer<U>> {
+    let records = read_records(input)?;
+    let mut deserializer = Deserializer::new(dest, records);
+    Ok(deserializer.deserialize_ptr(0))
+}
@@ -0,0 +1,21 @@
+//! Deep-copy checkpoints of an object graph, so it can be restored later
+//! even though the live graph it was copied from keeps mutating.
+//!
+//! See `GcHeapSession::snapshot` and `GcHeapSession::restore`.
+
+use gc_ref::PinnedRef;
+use traits::IntoHeapBase;
+
+/// A deep copy of an object graph, pinned so it survives collection,
+/// independent of whatever happens to the live graph it was copied from.
+///
+/// Values in this heap are mutable in place, so merely holding onto a
+/// reference to the original root wouldn't do: by the time you wanted to go
+/// back to it, its fields might have been overwritten. A `Snapshot` instead
+/// owns its own copy of every object reachable from the root at the moment
+/// it was taken.
+///
+/// Create one with `GcHeapSession::snapshot`; get an independent, live copy
+/// of its contents back with `GcHeapSession::restore`; release it with
+/// `GcHeapSession::discard_snapshot`.
+pub struct Snapshot<T: IntoHeapBase>(pub(crate) PinnedRef<T>);
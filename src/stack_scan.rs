@@ -0,0 +1,54 @@
+//! Conservative scanning of the native stack for values that look like heap
+//! pointers, as an alternative to explicit rooting. Opt-in via
+//! `GcHeap::set_conservative_stack_scanning`; see its docs for the
+//! tradeoffs.
+
+use ptr::UntypedPointer;
+use std::collections::HashSet;
+use std::mem;
+
+/// Capture the current stack pointer (approximately: the address of a local
+/// variable in this function's frame), for use as one end of the range
+/// `scan` searches.
+#[inline(never)]
+pub(crate) fn capture_stack_pointer() -> usize {
+    let here: usize = 0;
+    &here as *const usize as usize
+}
+
+/// Conservatively scan the native stack from `top` (deeper in the call
+/// stack, i.e. closer to where a collection was actually triggered) up to
+/// `bottom` (shallower, i.e. closer to where the enclosing heap session
+/// began), treating every word whose value is exactly the address of some
+/// object in `live_objects` as a root.
+///
+/// This is deliberately "exact", not "interior": a stack slot has to hold
+/// the very address `alloc` returned, not merely an offset into the object,
+/// to be recognized. Every `Ref` cell-gc hands out satisfies that; nothing
+/// else does, so this can't (for instance) find a `&i64` borrowed out of the
+/// middle of a heap struct.
+///
+/// # Safety
+///
+/// `top` and `bottom` must both be addresses of local variables still live
+/// on this thread's call stack, with `top` at a stack depth at or below
+/// `bottom`'s (i.e. `top <= bottom`, since the stack grows down on every
+/// platform cell-gc supports), or this reads memory it has no business
+/// reading.
+pub(crate) unsafe fn scan(
+    top: usize,
+    bottom: usize,
+    live_objects: &HashSet<usize>,
+) -> Vec<UntypedPointer> {
+    let mut roots = Vec::new();
+    let word_size = mem::size_of::<usize>();
+    let mut addr = top;
+    while addr + word_size <= bottom {
+        let word = *(addr as *const usize);
+        if live_objects.contains(&word) {
+            roots.push(UntypedPointer::new(word as *const ()));
+        }
+        addr += word_size;
+    }
+    roots
+}
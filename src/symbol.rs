@@ -0,0 +1,182 @@
+//! Interned strings ("symbols"): interning the same text twice returns the
+//! same `SymbolRef`, so two symbols can be compared for equality by
+//! comparing pointers instead of comparing their text. This is the exact
+//! use case `GcInterned`'s own docs point at; `Symbols` is just that
+//! generic weak intern table specialized to a `Symbol` heap type, so a
+//! caller doesn't have to build one for itself.
+//!
+//! `Symbol`/`SymbolRef` are written out by hand instead of via
+//! `#[derive(IntoHeap)]`, for the same reason `bench_support`'s types are:
+//! the derive's generated code refers to its own crate as `::cell_gc`,
+//! which only resolves from a downstream crate with `extern crate
+//! cell_gc;`, not from inside `cell_gc` itself.
+//!
+//! ```rust
+//! use cell_gc::Symbols;
+//!
+//! cell_gc::with_heap(|hs| {
+//!     let mut symbols = Symbols::new();
+//!     let a = symbols.intern(hs, "foo");
+//!     let b = symbols.intern(hs, "foo");
+//!     assert_eq!(a, b);
+//!
+//!     let c = symbols.intern(hs, "bar");
+//!     assert_ne!(a, c);
+//! });
+//! ```
+
+use borrow_flag;
+use gc_ref::GcRef;
+use heap::GcHeapSession;
+use intern::GcInterned;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use traits::{InHeap, IntoHeap, IntoHeapAllocation, IntoHeapBase, Tracer};
+
+/// A single interned string. Allocate one through `Symbols::intern`, not
+/// directly (there's no public way to build one any other way): that's what
+/// makes the pointer-equality guarantee on `SymbolRef` hold.
+#[derive(Clone, Eq, Hash, PartialEq)]
+pub struct Symbol<'h> {
+    text: String,
+    phantom: PhantomData<&'h ()>,
+}
+
+impl<'h> fmt::Debug for Symbol<'h> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Symbol({:?})", self.text)
+    }
+}
+
+/// The in-heap storage form of `Symbol<'h>`.
+#[doc(hidden)]
+pub struct SymbolStorage {
+    text: String,
+}
+
+impl InHeap for SymbolStorage {
+    #[inline]
+    unsafe fn trace<R: Tracer>(&self, _tracer: &mut R) {}
+}
+
+impl<'h> IntoHeapBase for Symbol<'h> {
+    type In = SymbolStorage;
+
+    fn into_heap(self) -> SymbolStorage {
+        SymbolStorage { text: self.text }
+    }
+
+    unsafe fn from_heap(storage: &SymbolStorage) -> Symbol<'h> {
+        Symbol {
+            text: storage.text.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+unsafe impl<'h> IntoHeap<'h> for Symbol<'h> {}
+
+impl<'h> IntoHeapAllocation<'h> for Symbol<'h> {
+    type Ref = SymbolRef<'h>;
+
+    fn wrap_gc_ref(gc_ref: GcRef<'h, Symbol<'h>>) -> SymbolRef<'h> {
+        SymbolRef(gc_ref)
+    }
+
+    fn into_gc_ref(r: SymbolRef<'h>) -> GcRef<'h, Symbol<'h>> {
+        r.0
+    }
+}
+
+/// A reference to an interned `Symbol`, allocated in the heap. See the
+/// module docs.
+pub struct SymbolRef<'h>(GcRef<'h, Symbol<'h>>);
+
+impl<'h> SymbolRef<'h> {
+    /// The interned text.
+    pub fn as_str(&self) -> String {
+        borrow_flag::check_not_borrowed(self.0.address());
+        let ptr = self.0.as_ptr();
+        unsafe { (*ptr).text.clone() }
+    }
+}
+
+impl<'h> Clone for SymbolRef<'h> {
+    fn clone(&self) -> SymbolRef<'h> {
+        SymbolRef(self.0.clone())
+    }
+}
+
+impl<'h> fmt::Debug for SymbolRef<'h> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SymbolRef({:?})", self.as_str())
+    }
+}
+
+impl<'h> PartialEq for SymbolRef<'h> {
+    fn eq(&self, other: &SymbolRef<'h>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<'h> Eq for SymbolRef<'h> {}
+
+impl<'h> Hash for SymbolRef<'h> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<'h> IntoHeapBase for SymbolRef<'h> {
+    type In = <GcRef<'h, Symbol<'h>> as IntoHeapBase>::In;
+
+    fn into_heap(self) -> Self::In {
+        self.0.into_heap()
+    }
+
+    unsafe fn from_heap(storage: &Self::In) -> SymbolRef<'h> {
+        SymbolRef(GcRef::new(*storage))
+    }
+}
+
+unsafe impl<'h> IntoHeap<'h> for SymbolRef<'h> {}
+
+/// A symbol table: interning the same text through it twice returns the
+/// same `SymbolRef`, weakly, per `GcInterned`. See the module docs.
+#[derive(Default)]
+pub struct Symbols<'h> {
+    interned: GcInterned<'h, Symbol<'h>>,
+}
+
+impl<'h> Symbols<'h> {
+    /// Create a new, empty symbol table.
+    pub fn new() -> Symbols<'h> {
+        Symbols {
+            interned: GcInterned::new(),
+        }
+    }
+
+    /// Return the interned symbol for `name`, allocating it in `hs` the
+    /// first time it's interned (or if the previous allocation has since
+    /// been collected).
+    pub fn intern(&mut self, hs: &mut GcHeapSession<'h>, name: &str) -> SymbolRef<'h> {
+        let symbol = Symbol {
+            text: name.to_string(),
+            phantom: PhantomData,
+        };
+        self.interned.intern(hs, symbol)
+    }
+
+    /// Returns the number of entries in the table, including any whose
+    /// target has already been collected but hasn't been looked up (and
+    /// thus pruned) since.
+    pub fn len(&self) -> usize {
+        self.interned.len()
+    }
+
+    /// Returns `true` if the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.interned.is_empty()
+    }
+}
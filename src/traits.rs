@@ -8,7 +8,7 @@
 //! ```text
 //! machine types
 //!     ----> themselves
-//! String, &'static T, GcLeaf<T: Clone>, Box<T: Clone>, Arc<T: Sync>
+//! String, &'static T, GcLeaf<T: Clone>, Box<T: Clone>, Arc<T: Send + Sync>
 //!     ----> themselves
 //! PhantomData<&'h T>
 //!     ----> itself
@@ -20,15 +20,19 @@
 //!     ----> Pointer<FooStorage>
 //! tuples of IntoHeap types
 //!     ----> tuples of corresponding InHeap types
+//! [T; N] where T: IntoHeap
+//!     ----> [T::In; N]
 //! structs/enums with IntoHeap fields and #[derive(IntoHeap)]
 //!     ----> structs/enums with corresponding InHeap fields
 //! Vec<T: IntoHeap>, VecRef<'h, T>
 //!     ----> Vec<T::In>, Pointer<Vec<T::In>>
 //! ```
 
+use adopt::Adopter;
 use gc_leaf::GcLeaf;
 use gc_ref::GcRef;
-use ptr::Pointer;
+use ptr::{Pointer, UntypedPointer};
+use serialize::{Codec, Cursor, Deserializer, Serializer};
 use std::any::Any;
 use std::hash::Hash;
 use std::marker::PhantomData;
@@ -201,6 +205,141 @@ pub trait Tracer {
     /// Tell the `Tracer` about an outgoing edge of the object currently being
     /// traced.
     fn visit<U: InHeap>(&mut self, Pointer<U>);
+
+    /// Tell the `Tracer` about an `Ephemeron`'s key/value pair: `value`
+    /// should be kept alive only if `key` turns out to be reachable some
+    /// other way, and `value_slot` is where to write `None` if it doesn't.
+    ///
+    /// The default implementation just treats this as two ordinary edges,
+    /// which is conservative (it never frees `value` too early) but doesn't
+    /// give `Ephemeron` its key-dependent weakness. `MarkingTracer` overrides
+    /// this to defer the value edge until the key's liveness is known; see
+    /// its impl for the details.
+    fn visit_ephemeron<K: InHeap, V: InHeap>(
+        &mut self,
+        key: Pointer<K>,
+        value: Pointer<V>,
+        _value_slot: &::std::cell::Cell<Option<Pointer<V>>>,
+    ) {
+        self.visit(key);
+        self.visit(value);
+    }
+
+    /// Tell the `Tracer` about an outgoing edge whose concrete pointee type
+    /// isn't known at the call site --- what a `GcDyn` trait-object
+    /// reference (see the `gc_dyn` module) traces through. This dispatches
+    /// via the pointee's own page header, the same type-erased trick
+    /// `PageHeader::mark_fn` and friends use elsewhere in this crate, so it
+    /// still reaches the right concrete `trace` impl even though the caller
+    /// never names the pointee's type.
+    ///
+    /// Default: ignore the edge. Every `Tracer` this crate ships overrides
+    /// this to do the right thing (mark it, freeze it, collect it into a
+    /// dump, ...); only implement it yourself if a custom `Tracer` needs to
+    /// see edges that pass through a `GcDyn`.
+    fn visit_untyped(&mut self, _ptr: UntypedPointer) {}
+}
+
+/// Deep-copies a heap value into a different heap, recursively adopting any
+/// nested GC references along the way. See `GcHeapSession::adopt`.
+///
+/// Implemented for every type `InHeap` is implemented for in this module
+/// (primitives, `String`, tuples, `Option<U>`, `Pointer<U>`, `Vec<U>`), and
+/// generated by `#[derive(IntoHeap)]` for a struct's storage type as long as
+/// every field is either one of those, or a reference back to the struct
+/// itself (`FooRef<'h>`, directly or wrapped in `Option`, as a field of
+/// `Foo`). That covers self-referential structures like a tree of nodes
+/// that each point at other nodes of the same type: `Adopter` breaks the
+/// cycle at run time, via its memo table, so the recursive call just works
+/// once the impl exists.
+///
+/// Deliberately **not** implemented for `Box<T>`, `Arc<T>`, `&'static T`, or
+/// `GcLeaf<T>`: those are opaque to the GC (`trace` on them
+/// is a no-op), so
+/// there's no generic way to duplicate whatever they point to for a new,
+/// independent heap. Nor for a struct or enum that embeds *another*
+/// `#[derive(IntoHeap)]` type, whether inline or behind a `*Ref`, since a
+/// single `#[derive(IntoHeap)]` expansion has no way to know whether that
+/// other type ends up supporting `Adopt` too. A type with one of those as a
+/// field simply doesn't get an `Adopt` impl, so `GcHeapSession::adopt` won't
+/// compile for it; adopt a hand-rebuilt copy of such a field instead.
+pub trait Adopt: InHeap {
+    /// Copy `self` into the heap `adopter` is adopting into, translating any
+    /// nested GC pointers via `adopter` so that shared substructure and
+    /// cycles come out the other side with the same shape, rather than being
+    /// copied repeatedly or causing infinite recursion.
+    ///
+    /// # Safety
+    ///
+    /// Same rule as `trace`: `self` must be a direct, unwrapped reference to
+    /// a value stored in the heap `adopter` is reading from.
+    unsafe fn adopt(&self, adopter: &mut Adopter) -> Self
+    where
+        Self: Sized;
+}
+
+/// Writes a heap value's own encoding out to bytes, and reads it back,
+/// recursively serializing or resolving any nested GC references along the
+/// way. See `GcHeapSession::serialize` and `GcHeapSession::deserialize`.
+///
+/// Implemented for exactly the same types `Adopt` is (see its docs for why:
+/// the same architectural limit applies here, since a single
+/// `#[derive(IntoHeap)]` expansion still has no way to know whether some
+/// other, foreign type ends up supporting `GcSerialize`), and generated by
+/// `#[derive(IntoHeap)]` under the same conditions `Adopt` is.
+pub trait GcSerialize: InHeap {
+    /// Append `self`'s own field data to `buf`, recording any nested GC
+    /// pointers via `ctx` so `read_fields` can resolve them again later.
+    ///
+    /// # Safety
+    ///
+    /// Same rule as `trace`: `self` must be a direct, unwrapped reference to
+    /// a value stored in the heap `ctx` is reading from.
+    unsafe fn write_fields(&self, ctx: &mut Serializer, buf: &mut Vec<u8>);
+
+    /// Read back a value written by `write_fields`, resolving any nested GC
+    /// pointers via `ctx`.
+    unsafe fn read_fields(ctx: &mut Deserializer, buf: &mut Cursor) -> Self
+    where
+        Self: Sized;
+}
+
+/// Opt-in tracing for the in-heap storage form of a foreign type that the
+/// derive macro can't see inside of --- typically a third-party collection,
+/// like `HashMap<Pointer<SymbolStorage>, Pointer<ValueStorage>>`, standing in
+/// for `HashMap<SymbolRef<'h>, ValueRef<'h>>` while it's in the heap.
+///
+/// This only covers the part of a hand-written `IntoHeap` implementation
+/// that's tied to the collector's internals: walking `self` and calling
+/// `tracer.visit` (via [`trace_field`]) on every GC pointer it owns. You
+/// still write `IntoHeapBase` for the live, `SymbolRef`-and-`ValueRef`-based
+/// type yourself, converting each element to and from this storage type's
+/// `Pointer`s --- only you know how to walk a foreign collection's elements,
+/// so the derive macro's approach of generating that conversion doesn't
+/// apply here. Pass the storage type to [`impl_custom_trace!`] to turn the
+/// one method below into an `InHeap` impl.
+///
+/// # Safety
+///
+/// `trace` must call [`trace_field`] (or an equivalent `Tracer` call) on
+/// every GC pointer reachable from `self`, directly or indirectly. Missing
+/// one means its referent can be collected while `self` still points to it,
+/// leaving a dangling pointer behind.
+pub unsafe trait CustomTrace: 'static {
+    /// Visit every GC pointer reachable from `self`. See [`trace_field`].
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R);
+}
+
+/// Call from a [`CustomTrace::trace`] implementation to visit one field ---
+/// a `Pointer<U>`, or anything else implementing `InHeap`, such as an
+/// `Option` or `Vec` of one.
+///
+/// # Safety
+///
+/// Same rule as `InHeap::trace`: this must only be called on a `field` that
+/// is part of a value that is itself being traced by the collector.
+pub unsafe fn trace_field<U: InHeap, R: Tracer>(field: &U, tracer: &mut R) {
+    field.trace(tracer);
 }
 
 // === Provided implmentations for primitive types
@@ -221,6 +360,17 @@ macro_rules! gc_trivial_impl {
             #[inline] fn wrap_gc_ref(gc_ref: GcRef<'h, Self>) -> Self::Ref { gc_ref }
             #[inline] fn into_gc_ref(gc_ref: Self::Ref) -> GcRef<'h, Self> { gc_ref }
         }
+        impl Adopt for $t {
+            #[inline] unsafe fn adopt(&self, _adopter: &mut Adopter) -> $t { self.clone() }
+        }
+        impl GcSerialize for $t {
+            #[inline] unsafe fn write_fields(&self, _ctx: &mut Serializer, buf: &mut Vec<u8>) {
+                Codec::encode(self, buf);
+            }
+            #[inline] unsafe fn read_fields(_ctx: &mut Deserializer, buf: &mut Cursor) -> $t {
+                Codec::decode(buf)
+            }
+        }
     }
 }
 
@@ -238,8 +388,12 @@ gc_trivial_impl!(isize, 0x17a31e16220b9ec8);
 gc_trivial_impl!(usize, 0xbc3d03b0a285f9a7);
 gc_trivial_impl!(f32, 0xd08d8d94baf44a74);
 gc_trivial_impl!(f64, 0x80bff0f49d51f22);
+gc_trivial_impl!(i128, 0x9e6f0f1f7f7c8b21);
+gc_trivial_impl!(u128, 0x6a2b9db1e5b6dcaf);
 
 gc_trivial_impl!(String, 0x1c66d28939b11111);
+gc_trivial_impl!(::std::time::Duration, 0x3b6b3a7d6a7cf6c1);
+gc_trivial_impl!(::std::cmp::Ordering, 0x7e6b52f2ac2f56a8);
 
 macro_rules! gc_generic_trivial_impl {
     (@as_item $it:item) => { $it };
@@ -276,7 +430,11 @@ macro_rules! gc_generic_trivial_impl {
 gc_generic_trivial_impl!([T: ?Sized + Sync] &'static T, 0x2c90082b4b071552);
 gc_generic_trivial_impl!([T: Clone + Send + 'static] GcLeaf<T>, 0x3f2cff0110e82982);
 gc_generic_trivial_impl!([T: Clone + Send + ?Sized + 'static] Box<T>, 0x5d55e2e560c89ec2);
-gc_generic_trivial_impl!([T: Sync + ?Sized + 'static] ::std::sync::Arc<T>, 0x4d920888eb74e08);
+gc_generic_trivial_impl!([T: Send + Sync + ?Sized + 'static] ::std::sync::Arc<T>, 0x4d920888eb74e08);
+// Deliberately no impl for `std::rc::Rc<T>`: `Rc`'s refcount isn't atomic,
+// so it can never be `Send` no matter what bound is put on `T`, and `GcHeap`
+// needs every value it can hold to be `Send` in order to itself be `Send`
+// (see the note on `unsafe impl Send for GcHeap`). Use `Arc<T>` instead.
 
 /// Currently, `#[derive(IntoHeap)]` only works for types that have a lifetime
 /// parameter.  This poses a problem because sometimes you want to store stuff
@@ -298,6 +456,24 @@ impl<U: InHeap> InHeap for Pointer<U> {
     }
 }
 
+impl<U: Adopt> Adopt for Pointer<U> {
+    unsafe fn adopt(&self, adopter: &mut Adopter) -> Pointer<U> {
+        adopter.adopt_ptr(*self)
+    }
+}
+
+impl<U: GcSerialize> GcSerialize for Pointer<U> {
+    unsafe fn write_fields(&self, ctx: &mut Serializer, buf: &mut Vec<u8>) {
+        let id = ctx.serialize_ptr(*self);
+        id.encode(buf);
+    }
+
+    unsafe fn read_fields(ctx: &mut Deserializer, buf: &mut Cursor) -> Pointer<U> {
+        let id = u64::decode(buf);
+        ctx.deserialize_ptr(id)
+    }
+}
+
 // GCRef has a special implementation.
 impl<'h, T: IntoHeapAllocation<'h>> IntoHeapBase for GcRef<'h, T> {
     type In = Pointer<T::In>;
@@ -341,6 +517,35 @@ impl<T: IntoHeapBase> IntoHeapBase for Option<T> {
 
 unsafe impl<'h, T: IntoHeap<'h>> IntoHeap<'h> for Option<T> {}
 
+impl<U: Adopt> Adopt for Option<U> {
+    unsafe fn adopt(&self, adopter: &mut Adopter) -> Option<U> {
+        match self {
+            &None => None,
+            &Some(ref u) => Some(u.adopt(adopter)),
+        }
+    }
+}
+
+impl<U: GcSerialize> GcSerialize for Option<U> {
+    unsafe fn write_fields(&self, ctx: &mut Serializer, buf: &mut Vec<u8>) {
+        match self {
+            &None => buf.push(0),
+            &Some(ref u) => {
+                buf.push(1);
+                u.write_fields(ctx, buf);
+            }
+        }
+    }
+
+    unsafe fn read_fields(ctx: &mut Deserializer, buf: &mut Cursor) -> Option<U> {
+        match buf.read_u8() {
+            0 => None,
+            1 => Some(U::read_fields(ctx, buf)),
+            tag => panic!("cell-gc: corrupt serialized data (bad Option tag {})", tag),
+        }
+    }
+}
+
 macro_rules! gc_trivial_tuple_impl {
     (@as_item $it:item) => { $it };
     ($($t:ident),*) => {
@@ -385,6 +590,50 @@ macro_rules! gc_trivial_tuple_impl {
             @as_item
             unsafe impl<'h, $($t: IntoHeap<'h>,)*> IntoHeap<'h> for ($($t,)*) {}
         }
+
+        gc_trivial_tuple_impl! {
+            @as_item
+            impl<$($t: Adopt,)*> Adopt for ($($t,)*) {
+                #[allow(non_snake_case)]
+                unsafe fn adopt(&self, adopter: &mut Adopter) -> Self {
+                    let &($(ref $t,)*) = self;
+
+                    let result = ($( <$t as $crate::traits::Adopt>::adopt($t, adopter), )*);
+
+                    // Quiet unused variable warnings when `$(...)*` expands
+                    // to nothing.
+                    let _ = adopter;
+
+                    result
+                }
+            }
+        }
+
+        gc_trivial_tuple_impl! {
+            @as_item
+            impl<$($t: GcSerialize,)*> GcSerialize for ($($t,)*) {
+                #[allow(non_snake_case)]
+                unsafe fn write_fields(&self, ctx: &mut Serializer, buf: &mut Vec<u8>) {
+                    let &($(ref $t,)*) = self;
+
+                    $(
+                        <$t as $crate::traits::GcSerialize>::write_fields($t, ctx, buf);
+                    )*
+
+                    // Quiet unused variable warnings when `$(...)*` expands
+                    // to nothing.
+                    let _ = ctx;
+                    let _ = buf;
+                }
+
+                #[allow(non_snake_case)]
+                unsafe fn read_fields(ctx: &mut Deserializer, buf: &mut Cursor) -> Self {
+                    let _ = &ctx;
+                    let _ = &buf;
+                    ($( <$t as $crate::traits::GcSerialize>::read_fields(ctx, buf), )*)
+                }
+            }
+        }
     }
 }
 
@@ -396,3 +645,48 @@ gc_trivial_tuple_impl!(T, U, V, W);
 gc_trivial_tuple_impl!(T, U, V, W, X);
 gc_trivial_tuple_impl!(T, U, V, W, X, Y);
 gc_trivial_tuple_impl!(T, U, V, W, X, Y, Z);
+gc_trivial_tuple_impl!(A, B, C, D, E, F, G, H);
+gc_trivial_tuple_impl!(A, B, C, D, E, F, G, H, I);
+gc_trivial_tuple_impl!(A, B, C, D, E, F, G, H, I, J);
+gc_trivial_tuple_impl!(A, B, C, D, E, F, G, H, I, J, K);
+gc_trivial_tuple_impl!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+impl<U: InHeap, const N: usize> InHeap for [U; N] {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        for u in self.iter() {
+            u.trace(tracer);
+        }
+    }
+}
+
+impl<T: IntoHeapBase, const N: usize> IntoHeapBase for [T; N] {
+    type In = [T::In; N];
+
+    fn into_heap(self) -> [T::In; N] {
+        self.map(|t| t.into_heap())
+    }
+
+    unsafe fn from_heap(storage: &[T::In; N]) -> [T; N] {
+        ::std::array::from_fn(|i| T::from_heap(&storage[i]))
+    }
+}
+
+unsafe impl<'h, T: IntoHeap<'h>, const N: usize> IntoHeap<'h> for [T; N] {}
+
+impl<U: Adopt, const N: usize> Adopt for [U; N] {
+    unsafe fn adopt(&self, adopter: &mut Adopter) -> [U; N] {
+        ::std::array::from_fn(|i| self[i].adopt(adopter))
+    }
+}
+
+impl<U: GcSerialize, const N: usize> GcSerialize for [U; N] {
+    unsafe fn write_fields(&self, ctx: &mut Serializer, buf: &mut Vec<u8>) {
+        for u in self.iter() {
+            u.write_fields(ctx, buf);
+        }
+    }
+
+    unsafe fn read_fields(ctx: &mut Deserializer, buf: &mut Cursor) -> [U; N] {
+        ::std::array::from_fn(|_| U::read_fields(ctx, buf))
+    }
+}
@@ -0,0 +1,90 @@
+//! Debug-mode heap consistency checks. See `GcHeap::verify`.
+//!
+//! This is also the natural home for a *barrier* verification mode ---
+//! re-scanning the whole heap at collection time and cross-checking it
+//! against a remembered set, to catch a generated or hand-written setter
+//! that forgot to record an edge a write barrier needed to see. cell-gc
+//! doesn't have that mode because it doesn't have write barriers or a
+//! remembered set to check in the first place: every collection is a full,
+//! stop-the-world mark and sweep (see `GcHeapSession::force_gc`), so there's
+//! no partial scan whose correctness a barrier could silently undermine.
+//! `step_collection`'s incremental mark phase comes closest to needing one,
+//! and sidesteps it the same way `force_gc` does: rather than trust a
+//! barrier to flag mutation during the scan, it forbids mutation outright
+//! and panics if that's violated (see `IncrementalMark`'s docs). If a
+//! generational or truly concurrent mode is ever added, it will need real
+//! write barriers, and this module is where their verification pass
+//! belongs, built the same way `verify` below is: a `Tracer` that reports
+//! problems instead of fixing them.
+
+use heap::GcHeap;
+use pages::{self, PageHeader};
+use ptr::{Pointer, UntypedPointer};
+use std::any::TypeId;
+use traits::{InHeap, Tracer};
+
+/// A `Tracer` that doesn't mark anything: for each edge it visits, it checks
+/// that the edge points at a live allocation of the statically expected
+/// type, recording a description of anything wrong instead. See
+/// `GcHeap::verify`.
+pub(crate) struct VerifyTracer {
+    problems: Vec<String>,
+}
+
+impl Tracer for VerifyTracer {
+    fn visit<U: InHeap>(&mut self, ptr: Pointer<U>) {
+        let untyped: UntypedPointer = ptr.into();
+        let header = unsafe { &*PageHeader::find(untyped) };
+        if header.type_id() != TypeId::of::<U>() {
+            self.problems.push(format!(
+                "{:p}: page holding this address doesn't match the type it was traced as",
+                ptr.as_raw()
+            ));
+        } else if !unsafe { pages::is_allocated_untyped(untyped) } {
+            self.problems.push(format!(
+                "{:p}: dangling reference to a freed slot",
+                ptr.as_raw()
+            ));
+        }
+    }
+
+    fn visit_untyped(&mut self, ptr: UntypedPointer) {
+        // No statically expected type to check this edge against --- that's
+        // the whole point of a `GcDyn` edge --- so just check it hasn't
+        // been swept out from under us.
+        if !unsafe { pages::is_allocated_untyped(ptr) } {
+            self.problems.push(format!(
+                "{:p}: dangling reference to a freed slot",
+                ptr.as_void()
+            ));
+        }
+    }
+}
+
+/// Trace the object at `ptr`, checking its outgoing edges. Monomorphized
+/// per allocation type and stored in `PageHeader::verify_fn`, the same
+/// type-erased dispatch trick `PageHeader::mark_fn` uses.
+pub(crate) unsafe fn verify_entry_point<U: InHeap>(ptr: UntypedPointer, tracer: &mut VerifyTracer) {
+    ptr.as_typed_ptr::<U>().as_ref().trace(tracer);
+}
+
+/// Walk every live object in `heap` and check that every edge it traces
+/// points at a live allocation of the type it was traced as. Returns a
+/// description of each problem found; empty if the heap looks consistent.
+///
+/// This is necessarily incomplete: mark bits are legitimately still set on
+/// survivors between the end of one collection and the start of the next,
+/// so this doesn't check that they're clear, and pin counts aren't tracked
+/// anywhere else for this to cross-check against. It catches the failure
+/// mode this crate's `unsafe` blocks are most likely to produce: a
+/// hand-written or macro-generated `trace` implementation that visits stale
+/// or mistyped pointers.
+pub(crate) fn verify(heap: &GcHeap) -> Vec<String> {
+    let mut tracer = VerifyTracer { problems: Vec::new() };
+    heap.for_each_live_object(|ptr, page| {
+        unsafe {
+            page.verify_edges(ptr, &mut tracer);
+        }
+    });
+    tracer.problems
+}
@@ -0,0 +1,78 @@
+//! Helpers for exposing GC values across a `wasm32` / JavaScript boundary
+//! as opaque integer handles, instead of raw pointers a JS caller could
+//! corrupt or hold onto past a collection.
+//!
+//! This module doesn't make cell-gc build on `wasm32-unknown-unknown` by
+//! itself --- see `pages::PageSource`'s docs for what else stands in the
+//! way (`std::sync`, `std::time::Instant`, `std::io`, threads). What it
+//! does provide is the other half of the ask: once compiled in, a
+//! `HandleTable` lets an embedder hand JavaScript a `u32` it can pass back
+//! into an exported function, in place of a `Ref` or a `Rooted` it has no
+//! business holding directly.
+
+use gc_ref::Rooted;
+use heap::GcHeapSession;
+use std::collections::HashMap;
+use traits::IntoHeap;
+
+/// An opaque handle standing in for a `Rooted<'h, T>` on the other side of
+/// an FFI boundary.
+///
+/// Handles from different `HandleTable`s aren't interchangeable, and a
+/// `HandleTable` doesn't validate that a handle it's given actually came
+/// from it: passing back a stale handle just gets `None` from `get`, or a
+/// no-op from `free`, since handle numbers are never reused within a table.
+/// A foreign handle is looked up by its number like any other, so it can
+/// name an unrelated entry of this table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Handle(u32);
+
+/// A table of `T` values, each kept alive by a `Rooted` handle for as long
+/// as its `Handle` is in the table.
+///
+/// `T` is fixed per table rather than erased: a JS embedder that needs to
+/// hand out handles to several unrelated types keeps one `HandleTable` per
+/// type, the same way it would keep one JS class per type.
+pub struct HandleTable<'h, T: IntoHeap<'h>> {
+    next: u32,
+    entries: HashMap<u32, Rooted<'h, T>>,
+}
+
+impl<'h, T: IntoHeap<'h>> HandleTable<'h, T> {
+    /// Create an empty handle table.
+    pub fn new() -> HandleTable<'h, T> {
+        HandleTable {
+            next: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Root `value` and return a handle for it. The value stays alive until
+    /// the handle is passed to `free`.
+    pub fn insert(&mut self, hs: &mut GcHeapSession<'h>, value: T) -> Handle {
+        let id = self.next;
+        self.next = self.next
+            .checked_add(1)
+            .expect("cell-gc: HandleTable ran out of handles");
+        self.entries.insert(id, hs.root(value));
+        Handle(id)
+    }
+
+    /// Get the value `handle` stands for, or `None` if it's been freed (or
+    /// never existed in this table).
+    pub fn get(&self, handle: Handle) -> Option<T> {
+        self.entries.get(&handle.0).map(Rooted::get)
+    }
+
+    /// Stop rooting the value behind `handle`. Freeing an unknown or
+    /// already-freed handle is a no-op.
+    pub fn free(&mut self, handle: Handle) {
+        self.entries.remove(&handle.0);
+    }
+}
+
+impl<'h, T: IntoHeap<'h>> Default for HandleTable<'h, T> {
+    fn default() -> Self {
+        HandleTable::new()
+    }
+}
@@ -0,0 +1,88 @@
+//! References into the heap that don't keep their target alive.
+
+use gc_ref::GcRef;
+use heap::{GcHeapSession, HeapSessionId};
+use pages;
+use ptr::Pointer;
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use traits::{InHeap, IntoHeapAllocation};
+
+/// The cell shared between a `WeakRef` and the callback it registers with
+/// `GcHeapSession::register_weak_ref`.
+///
+/// # Safety
+///
+/// `Cell` is ordinarily `!Sync`/`!Send`, since unsynchronized interior
+/// mutation shared across threads is unsound. A `WeakSlot` gets away with
+/// it because it's never actually touched from two threads at once: the
+/// strong handle (owned by a `WeakRef<'h, T>`) can't outlive the session
+/// that created it, so by the time the `GcHeap` --- and the weak handle its
+/// `weak_refs` callback closes over --- can be sent to another thread,
+/// every strong handle from the old session is already gone. See the note
+/// on `unsafe impl Send for GcHeap`.
+struct WeakSlot<U: InHeap>(Cell<Option<Pointer<U>>>);
+
+unsafe impl<U: InHeap> Send for WeakSlot<U> {}
+unsafe impl<U: InHeap> Sync for WeakSlot<U> {}
+
+/// A reference to a heap object that doesn't keep it alive.
+///
+/// Unlike `GcRef`, a `WeakRef` is not a root: it has no effect on whether its
+/// target gets collected. Call `upgrade()` to get a strong `GcRef` back; it
+/// returns `None` once the target has actually been collected. This is what
+/// you want for caches and interning tables, where the whole point is that
+/// entries shouldn't be kept alive just because they're in the cache.
+///
+/// A `WeakRef<'h, T>` is tied to the session it was created in, the same way
+/// `GcRef<'h, T>` is, so it can't outlive `'h` or be mixed up with a
+/// different heap.
+///
+/// See also `Ephemeron<K, V>`, which is the same idea attached to a pair: a
+/// strong value kept alive only while some other, weakly-held key survives.
+pub struct WeakRef<'h, T: IntoHeapAllocation<'h>> {
+    heap_id: HeapSessionId<'h>,
+    slot: Arc<WeakSlot<T::In>>,
+}
+
+impl<'h, T: IntoHeapAllocation<'h>> WeakRef<'h, T> {
+    /// Create a `WeakRef` pointing at the same object as `target`, without
+    /// pinning it: `target` keeps its own referent alive for as long as
+    /// `target` exists, but the new `WeakRef` does not.
+    pub fn new(session: &mut GcHeapSession<'h>, target: &GcRef<'h, T>) -> WeakRef<'h, T> {
+        let ptr = target.ptr();
+        let slot = Arc::new(WeakSlot(Cell::new(Some(ptr))));
+        let weak_slot = Arc::downgrade(&slot);
+        session.register_weak_ref(move || match weak_slot.upgrade() {
+            None => false, // the WeakRef itself is gone; forget about it
+            Some(slot) => {
+                if let Some(ptr) = slot.0.get() {
+                    if !unsafe { pages::get_mark_bit(ptr) } {
+                        slot.0.set(None);
+                    }
+                }
+                true
+            }
+        });
+        WeakRef {
+            heap_id: PhantomData,
+            slot: slot,
+        }
+    }
+
+    /// Get a strong reference to the target, or `None` if it's already been
+    /// collected.
+    pub fn upgrade(&self) -> Option<GcRef<'h, T>> {
+        self.slot.0.get().map(|ptr| unsafe { GcRef::new(ptr) })
+    }
+}
+
+impl<'h, T: IntoHeapAllocation<'h>> Clone for WeakRef<'h, T> {
+    fn clone(&self) -> WeakRef<'h, T> {
+        WeakRef {
+            heap_id: PhantomData,
+            slot: self.slot.clone(),
+        }
+    }
+}
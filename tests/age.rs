@@ -0,0 +1,39 @@
+//! `age()`/`is_tenured()` let embedders make policy decisions based on how
+//! many collections an object has survived (e.g. only intern strings once
+//! they've survived a collection). They live on `GcRef`, but every
+//! `#[derive(IntoHeap)]` generated `*Ref` type forwards them too, the same
+//! way it forwards `address()`/`object_id()`.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+use cell_gc::TENURING_AGE;
+
+#[test]
+fn a_fresh_allocation_has_age_zero_and_is_not_tenured() {
+    cell_gc::with_heap(|hs| {
+        let pair = alloc_null_pair(hs);
+        assert_eq!(pair.age(), 0);
+        assert!(!pair.is_tenured());
+    });
+}
+
+#[test]
+fn age_climbs_by_one_per_collection_survived_until_tenured() {
+    cell_gc::with_heap(|hs| {
+        let pair = alloc_null_pair(hs);
+
+        for expected_age in 1..=TENURING_AGE {
+            hs.force_gc();
+            assert_eq!(pair.age(), expected_age);
+            assert_eq!(pair.is_tenured(), expected_age >= TENURING_AGE);
+        }
+
+        // Further collections keep it tenured (the age itself keeps
+        // climbing until it saturates at 3, its two-bit field's maximum).
+        hs.force_gc();
+        assert!(pair.is_tenured());
+    });
+}
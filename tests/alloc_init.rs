@@ -0,0 +1,69 @@
+//! `alloc_init` writes a value's fields directly into its final heap
+//! address, instead of building a complete `T::In` on the stack first and
+//! moving it in like `alloc` does. See `inline_array.rs` for the `[T; N]`
+//! field type this exists to make cheaper to construct.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+use std::marker::PhantomData;
+use std::ptr;
+
+#[derive(IntoHeap)]
+struct Cells<'h> {
+    marker: PhantomData<&'h ()>,
+    values: [i32; 8],
+}
+
+#[test]
+fn alloc_init_writes_every_field_in_place() {
+    cell_gc::with_heap(|hs| {
+        let cells = unsafe {
+            hs.alloc_init::<Cells, _>(|dest| {
+                ptr::write(&mut (*dest).marker, PhantomData);
+                for (i, slot) in (*dest).values.iter_mut().enumerate() {
+                    ptr::write(slot, i as i32 * 10);
+                }
+            })
+        };
+
+        assert_eq!(cells.values(), [0, 10, 20, 30, 40, 50, 60, 70]);
+    });
+}
+
+#[test]
+fn try_alloc_init_reports_failure_the_same_way_try_alloc_does() {
+    cell_gc::with_heap(|hs| {
+        hs.set_page_limit::<Cells>(Some(1));
+        let n = cell_gc::page_capacity::<Cells>();
+
+        // Keep every allocation rooted: an unrooted one would be swept by
+        // the GC the page limit triggers below, freeing its slot and
+        // letting allocation continue past the limit instead of exhausting
+        // the single page this test means to fill.
+        let mut kept = Vec::with_capacity(n);
+        for i in 0..n {
+            let cells = unsafe {
+                hs.try_alloc_init::<Cells, _>(|dest| {
+                    ptr::write(&mut (*dest).marker, PhantomData);
+                    for slot in (*dest).values.iter_mut() {
+                        ptr::write(slot, i as i32);
+                    }
+                })
+            };
+            assert!(cells.is_some(), "allocation {} of {} should still fit on the page", i, n);
+            kept.push(cells);
+        }
+
+        let overflow = unsafe {
+            hs.try_alloc_init::<Cells, _>(|dest| {
+                ptr::write(&mut (*dest).marker, PhantomData);
+                for slot in (*dest).values.iter_mut() {
+                    ptr::write(slot, 0);
+                }
+            })
+        };
+        assert!(overflow.is_none());
+    });
+}
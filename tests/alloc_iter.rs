@@ -0,0 +1,45 @@
+//! `alloc_iter` allocates a whole batch of same-typed values at once,
+//! reserving heap pages and checking the GC trigger policy once for the
+//! batch instead of once per value.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+use std::marker::PhantomData;
+
+#[derive(IntoHeap)]
+struct Cell<'h> {
+    value: i32,
+    phantom: PhantomData<&'h u8>,
+}
+
+#[test]
+fn alloc_iter_returns_one_ref_per_value() {
+    cell_gc::with_heap(|hs| {
+        let cells = hs.alloc_iter((0..1000).map(|i| Cell { value: i, phantom: PhantomData }));
+        assert_eq!(cells.len(), 1000);
+        for (i, cell) in cells.iter().enumerate() {
+            assert_eq!(cell.value(), i as i32);
+        }
+    });
+}
+
+#[test]
+fn alloc_iter_of_empty_iterator_returns_empty_vec() {
+    cell_gc::with_heap(|hs| {
+        let cells: Vec<_> = hs.alloc_iter(Vec::<Cell>::new());
+        assert!(cells.is_empty());
+    });
+}
+
+#[test]
+fn alloc_iter_survives_collection() {
+    cell_gc::with_heap(|hs| {
+        let cells = hs.alloc_iter((0..10).map(|i| Cell { value: i, phantom: PhantomData }));
+        hs.force_gc();
+        for (i, cell) in cells.iter().enumerate() {
+            assert_eq!(cell.value(), i as i32);
+        }
+    });
+}
@@ -0,0 +1,32 @@
+//! `GcHeap::allocation_report` and `GcHeap::top_allocation_sites` report
+//! real data when built with the `alloc-profile` feature, and are harmless
+//! no-ops (always empty) when it's off. Run with `cargo test --features
+//! alloc-profile` to exercise the feature-enabled half of this test.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+use cell_gc::GcHeap;
+
+#[test]
+fn top_allocation_sites_reports_the_heaviest_call_site() {
+    let mut heap = GcHeap::new();
+    heap.enter(|hs| {
+        for _ in 0..10 {
+            alloc_null_pair(hs);
+        }
+    });
+
+    let sites = heap.top_allocation_sites(5);
+
+    if cfg!(feature = "alloc-profile") {
+        let heaviest = sites.first().expect("some site should have allocated the 10 pairs");
+        assert_eq!(heaviest.count, 10);
+        assert!(heaviest.bytes > 0);
+        assert!(heaviest.site.contains("pairs.rs"));
+    } else {
+        assert!(sites.is_empty());
+    }
+}
@@ -0,0 +1,31 @@
+//! `alloc`'s result is pinned (see `GcRef`'s docs) from the moment it's
+//! returned, so holding it in a local variable is already enough to survive
+//! any further allocation --- there's no window where a freshly allocated
+//! object is unrooted and at risk of being collected before it's stored
+//! somewhere reachable.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+
+#[test]
+fn a_freshly_allocated_object_survives_further_allocation_before_its_stored_anywhere() {
+    cell_gc::with_heap(|hs| {
+        let first = alloc_null_pair(hs);
+
+        // Allocate enough more objects to trigger a collection, without
+        // ever linking `first` into the object graph. If `alloc`'s result
+        // weren't already rooted, one of these collections could reclaim
+        // `first`, since nothing else points to it yet.
+        for _ in 0..10_000 {
+            let _ = alloc_null_pair(hs);
+        }
+        hs.force_gc();
+
+        // Still alive and untouched.
+        assert_eq!(first.head(), Value::Null);
+        assert_eq!(first.tail(), Value::Null);
+    });
+}
@@ -0,0 +1,47 @@
+//! In debug builds, `update_<field>`'s callback holds an exclusive borrow
+//! on its object for as long as it runs: calling back into that same
+//! object through another getter or setter during the callback panics
+//! instead of aliasing the `&mut` the callback was given.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+use std::marker::PhantomData;
+
+#[derive(IntoHeap)]
+struct Journal<'h> {
+    text: String,
+    phantom: PhantomData<&'h u8>,
+}
+
+#[test]
+fn non_reentrant_update_succeeds() {
+    cell_gc::with_heap(|hs| {
+        let journal = hs.alloc(Journal { text: String::from("hello"), phantom: PhantomData });
+        journal.update_text(|s| s.push_str(", world"));
+        assert_eq!(journal.text(), "hello, world");
+    });
+}
+
+#[test]
+#[should_panic(expected = "reentrant access")]
+fn reentrant_getter_during_update_panics() {
+    cell_gc::with_heap(|hs| {
+        let journal = hs.alloc(Journal { text: String::from("hello"), phantom: PhantomData });
+        journal.update_text(|_| {
+            journal.text();
+        });
+    });
+}
+
+#[test]
+#[should_panic(expected = "reentrant access")]
+fn reentrant_setter_during_update_panics() {
+    cell_gc::with_heap(|hs| {
+        let journal = hs.alloc(Journal { text: String::from("hello"), phantom: PhantomData });
+        journal.update_text(|_| {
+            journal.set_text(String::from("oops"));
+        });
+    });
+}
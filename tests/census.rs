@@ -0,0 +1,63 @@
+//! `GcHeap::census` snapshots live object counts by type; `Census::diff`
+//! compares two snapshots to report exactly what changed. See
+//! `tests/type_registry.rs` for the `TypeInfo` list a census is built from.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+use cell_gc::{CensusDelta, GcHeap};
+
+#[test]
+fn diff_reports_net_allocations_by_type() {
+    let mut heap = GcHeap::new();
+    let before = heap.census();
+
+    heap.enter(|hs| {
+        let mut v = Value::Null;
+        for _ in 0..10 {
+            v = Value::Pair(alloc_pair(hs, Value::Null, v));
+        }
+        let _ = v;
+    });
+
+    let after = heap.census();
+    let diff = before.diff(&after);
+    let pair_delta = diff
+        .iter()
+        .find(|d| d.type_name.ends_with("::PairStorage"))
+        .expect("allocating Pairs should show up in the diff");
+    assert_eq!(pair_delta.delta, 10);
+}
+
+#[test]
+fn diff_is_empty_between_two_censuses_of_the_same_heap() {
+    let mut heap = GcHeap::new();
+    heap.enter(|hs| {
+        let _ = alloc_pair(hs, Value::Null, Value::Null);
+    });
+
+    let a = heap.census();
+    let b = heap.census();
+    assert_eq!(a.diff(&b), Vec::<CensusDelta>::new());
+}
+
+#[test]
+fn a_collected_object_shows_up_as_a_negative_delta() {
+    let mut heap = GcHeap::new();
+    heap.enter(|hs| {
+        let _ = alloc_pair(hs, Value::Null, Value::Null);
+    });
+
+    let before_gc = heap.census();
+    heap.enter(|hs| hs.force_gc());
+    let after_gc = heap.census();
+
+    let pair_delta = before_gc
+        .diff(&after_gc)
+        .into_iter()
+        .find(|d| d.type_name.ends_with("::PairStorage"))
+        .expect("freeing the only Pair should show up in the diff");
+    assert_eq!(pair_delta.delta, -1);
+}
@@ -0,0 +1,100 @@
+//! `channel` deep-copies GC values from one heap into another, preserving
+//! shared substructure just like `GcHeapSession::serialize` does (see
+//! `tests/session_persistence.rs` for the same guarantee applied to a single
+//! heap re-entered over time instead of two distinct heaps).
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+use cell_gc::channel;
+use cell_gc::GcHeap;
+
+#[derive(IntoHeap)]
+struct Pair<'h> {
+    value: i32,
+    left: Option<PairRef<'h>>,
+    right: Option<PairRef<'h>>,
+}
+
+#[test]
+fn send_and_recv_a_simple_value() {
+    let (tx, rx) = channel::channel();
+
+    let mut sender_heap = GcHeap::new();
+    sender_heap.enter(|hs| {
+        let leaf = hs.alloc(Pair {
+            value: 42,
+            left: None,
+            right: None,
+        });
+        let pinned: cell_gc::PinnedRef<Pair> = hs.pin(leaf);
+        tx.send(hs, &pinned).unwrap();
+        hs.unpin(pinned);
+    });
+
+    let mut receiver_heap = GcHeap::new();
+    receiver_heap.enter(|hs| {
+        let leaf: PairRef = rx.recv::<Pair>(hs).unwrap();
+        assert_eq!(leaf.value(), 42);
+        assert!(leaf.left().is_none());
+    });
+}
+
+#[test]
+fn shared_substructure_survives_the_trip() {
+    let (tx, rx) = channel::channel();
+
+    let mut sender_heap = GcHeap::new();
+    sender_heap.enter(|hs| {
+        let leaf = hs.alloc(Pair {
+            value: 1,
+            left: None,
+            right: None,
+        });
+        let root = hs.alloc(Pair {
+            value: 0,
+            left: Some(leaf.clone()),
+            right: Some(leaf.clone()),
+        });
+        let pinned: cell_gc::PinnedRef<Pair> = hs.pin(root);
+        tx.send(hs, &pinned).unwrap();
+        hs.unpin(pinned);
+    });
+
+    let mut receiver_heap = GcHeap::new();
+    receiver_heap.enter(|hs| {
+        let root: PairRef = rx.recv::<Pair>(hs).unwrap();
+        let left = root.left().unwrap();
+        let right = root.right().unwrap();
+        assert_eq!(left, right);
+        assert_eq!(left.value(), 1);
+    });
+}
+
+#[test]
+fn sender_keeps_its_own_copy_after_sending() {
+    let (tx, rx) = channel::channel();
+
+    let mut sender_heap = GcHeap::new();
+    sender_heap.enter(|hs| {
+        let leaf = hs.alloc(Pair {
+            value: 7,
+            left: None,
+            right: None,
+        });
+        let pinned: cell_gc::PinnedRef<Pair> = hs.pin(leaf.clone());
+        tx.send(hs, &pinned).unwrap();
+        hs.unpin(pinned);
+
+        // The sender's own reference is still good: sending is a deep copy,
+        // not a move.
+        assert_eq!(leaf.value(), 7);
+    });
+
+    let mut receiver_heap = GcHeap::new();
+    receiver_heap.enter(|hs| {
+        let leaf: PairRef = rx.recv::<Pair>(hs).unwrap();
+        assert_eq!(leaf.value(), 7);
+    });
+}
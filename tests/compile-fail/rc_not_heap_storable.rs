@@ -0,0 +1,16 @@
+//! `Rc<T>`'s refcount isn't atomic, so it can never be stored directly in
+//! the heap, no matter what `T` is: `GcHeap` needs to be `Send`, and no
+//! bound on `T` can make `Rc<T>` itself `Send`. Use `Arc<T>` instead.
+
+extern crate cell_gc;
+
+use cell_gc::GcHeap;
+use std::rc::Rc;
+
+fn main() {
+    let mut heap = GcHeap::new();
+    heap.enter(|hs| {
+        let _ = hs.alloc(Rc::new(5i32));
+        //~^ ERROR: the trait bound `Rc<i32>: IntoHeapAllocation<'_>` is not satisfied
+    });
+}
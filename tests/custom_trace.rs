@@ -0,0 +1,71 @@
+// Tests for CustomTrace, the escape hatch for tracing a foreign type
+// `#[derive(IntoHeap)]` can't see inside of.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+#[path = "aux/pairs.rs"]
+mod pairs;
+
+use cell_gc::ptr::Pointer;
+use cell_gc::traits::{CustomTrace, IntoHeap, IntoHeapBase, Tracer};
+use pairs::{PairRef, PairStorage, Value};
+
+// A little wrapper around `Vec<PairRef<'h>>`, standing in for some
+// third-party collection type the derive macro couldn't decompose into
+// fields.
+struct PairBag<'h>(Vec<PairRef<'h>>);
+
+// The in-heap storage form of `PairBag`, holding raw `Pointer`s instead of
+// live `PairRef`s.
+struct PairBagStorage(Vec<Pointer<PairStorage>>);
+
+unsafe impl CustomTrace for PairBagStorage {
+    unsafe fn trace<R: Tracer>(&self, tracer: &mut R) {
+        for ptr in &self.0 {
+            cell_gc::traits::trace_field(ptr, tracer);
+        }
+    }
+}
+
+cell_gc::impl_custom_trace!(PairBagStorage);
+
+impl<'h> IntoHeapBase for PairBag<'h> {
+    type In = PairBagStorage;
+
+    fn into_heap(self) -> PairBagStorage {
+        PairBagStorage(self.0.into_iter().map(|r| r.into_heap()).collect())
+    }
+
+    unsafe fn from_heap(storage: &PairBagStorage) -> PairBag<'h> {
+        PairBag(
+            storage
+                .0
+                .iter()
+                .map(|ptr| IntoHeapBase::from_heap(ptr))
+                .collect(),
+        )
+    }
+}
+
+unsafe impl<'h> IntoHeap<'h> for PairBag<'h> {}
+
+#[derive(IntoHeap)]
+struct Bucket<'h> {
+    bag: PairBag<'h>,
+}
+
+#[test]
+fn custom_trace_keeps_referents_alive() {
+    cell_gc::with_heap(|hs| {
+        let pair = pairs::alloc_pair(hs, Value::Int(1), Value::Int(2));
+        let bucket = hs.alloc(Bucket { bag: PairBag(vec![pair]) });
+
+        hs.force_gc();
+
+        let bag = bucket.bag();
+        assert_eq!(bag.0[0].head(), Value::Int(1));
+        assert_eq!(bag.0[0].tail(), Value::Int(2));
+    });
+}
@@ -0,0 +1,38 @@
+//! `mark`'s generated field-tracing is recursive (see `marking::MarkingTracer`),
+//! which would overflow the native stack walking a very long linked list one
+//! node at a time. `MarkingTracer` already guards against this: it only
+//! recurses `fuel` levels deep (see `DEFAULT_FUEL`) before pushing the
+//! remaining edge onto its own heap-allocated mark stack and unwinding, so
+//! collecting a million-node list costs one indirect call per `fuel` nodes,
+//! not one stack frame per node.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+
+#[test]
+fn collecting_a_very_long_list_does_not_overflow_the_stack() {
+    cell_gc::with_heap(|hs| {
+        let mut v = Value::Null;
+        for i in 0..1_000_000 {
+            v = Value::Pair(alloc_pair(hs, Value::Int(i), v));
+        }
+
+        hs.force_gc();
+
+        let mut count = 0;
+        loop {
+            match v {
+                Value::Null => break,
+                Value::Pair(p) => {
+                    count += 1;
+                    v = p.tail();
+                }
+                _ => panic!("list corrupted"),
+            }
+        }
+        assert_eq!(count, 1_000_000);
+    });
+}
@@ -0,0 +1,53 @@
+//! `cell_gc::ffi::ExternalHandleTable` is meant for a C host embedding a
+//! cell-gc-based interpreter: it has no safe way to hold a pointer into the
+//! GC heap between calls, so it holds an `ExternalHandle` instead.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+use cell_gc::ffi::ExternalHandleTable;
+use cell_gc::traits::IntoHeapAllocation;
+
+#[test]
+fn a_handle_keeps_its_referent_alive_across_a_collection() {
+    cell_gc::with_heap(|hs| {
+        let pair = alloc_pair(hs, Value::Int(1), Value::Int(2));
+        let mut table = ExternalHandleTable::new();
+        let handle = table.create(Pair::into_gc_ref(pair));
+
+        hs.force_gc();
+
+        let any = table.resolve(handle).unwrap();
+        let pair = any.downcast::<Pair>().unwrap();
+        assert_eq!(pair.head(), Value::Int(1));
+        assert_eq!(pair.tail(), Value::Int(2));
+    });
+}
+
+#[test]
+fn releasing_a_handle_invalidates_it() {
+    cell_gc::with_heap(|hs| {
+        let pair = alloc_pair(hs, Value::Null, Value::Null);
+        let mut table = ExternalHandleTable::new();
+        let handle = table.create(Pair::into_gc_ref(pair));
+
+        table.release(handle);
+
+        assert!(table.resolve(handle).is_none());
+    });
+}
+
+#[test]
+fn downcasting_to_the_wrong_type_hands_the_gcany_back() {
+    cell_gc::with_heap(|hs| {
+        let pair = alloc_pair(hs, Value::Null, Value::Null);
+        let mut table = ExternalHandleTable::new();
+        let handle = table.create(Pair::into_gc_ref(pair));
+
+        let any = table.resolve(handle).unwrap();
+        let any = any.downcast::<i32>().unwrap_err();
+        any.downcast::<Pair>().unwrap();
+    });
+}
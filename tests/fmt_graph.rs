@@ -0,0 +1,56 @@
+//! `cell_gc::debug::fmt_graph` prints an object graph without looping
+//! forever on cycles. See `tests/cycle.rs` for the corresponding GC-safety
+//! test of the same self-referential shape.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+use std::fmt;
+
+struct DebugPair<'a, 'h: 'a>(&'a PairRef<'h>);
+
+impl<'a, 'h> fmt::Debug for DebugPair<'a, 'h> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        cell_gc::debug::fmt_graph::<Pair>(self.0, f)
+    }
+}
+
+#[test]
+fn fmt_graph_terminates_on_a_cycle() {
+    cell_gc::with_heap(|hs| {
+        // Set up obj1 and obj2 to point to each other, same as tests/cycle.rs.
+        let obj1 = alloc_null_pair(hs);
+        let obj2 = alloc_pair(hs, Value::Pair(obj1.clone()), Value::Pair(obj1.clone()));
+        obj1.set_head(Value::Pair(obj2.clone()));
+        obj1.set_tail(Value::Pair(obj2.clone()));
+
+        let text = format!("{:?}", DebugPair(&obj1));
+
+        // obj1 is #0 (the root); obj2 is discovered from it and gets #1.
+        // Each is expanded exactly once, however many edges point at it.
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.contains("#0 = "));
+        assert!(text.contains("#1 = "));
+        // Printed with the IntoHeap macro's generated storage type name, the
+        // same as `census.rs` matches on, not `Pair` itself.
+        assert!(text.contains("::PairStorage -> [#1, #1]") || text.contains("::PairStorage -> [#0, #0]"));
+    });
+}
+
+#[test]
+fn fmt_graph_on_an_acyclic_graph() {
+    cell_gc::with_heap(|hs| {
+        let leaf = alloc_null_pair(hs);
+        let root = alloc_pair(hs, Value::Pair(leaf.clone()), Value::Pair(leaf.clone()));
+
+        let text = format!("{:?}", DebugPair(&root));
+
+        // leaf is shared by both fields of root but is only expanded once.
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.contains("#0 = "));
+        assert!(text.contains("-> [#1, #1]"));
+        assert!(text.contains("#1 = ") && text.contains("-> []"));
+    });
+}
@@ -0,0 +1,82 @@
+//! `FrozenHeap` lets several threads read one `freeze_reachable`d object
+//! graph at once (see `tests/frozen_refs.rs` for `GcFrozenRef`, the
+//! single-threaded way to hold onto a frozen root).
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+use cell_gc::traits::IntoHeapAllocation;
+use cell_gc::{FrozenHeap, GcHeap};
+use std::sync::Arc;
+use std::thread;
+
+#[derive(IntoHeap)]
+struct Pair<'h> {
+    value: i32,
+    left: Option<PairRef<'h>>,
+    right: Option<PairRef<'h>>,
+}
+
+fn build_frozen_pair() -> Arc<FrozenHeap> {
+    // Boxed before the first `enter`, so the back-pointers pages allocate
+    // during `enter` stay valid once `heap` is moved into `FrozenHeap::new`
+    // below (see that function's docs).
+    let mut heap = Box::new(GcHeap::new());
+    let root_ptr = heap.enter(|hs| {
+        let leaf = hs.alloc(Pair {
+            value: 1,
+            left: None,
+            right: None,
+        });
+        let root = hs.alloc(Pair {
+            value: 0,
+            left: Some(leaf.clone()),
+            right: Some(leaf.clone()),
+        });
+        hs.freeze_reachable::<Pair>(root.clone());
+        Pair::into_gc_ref(root).ptr().into()
+    });
+    unsafe { FrozenHeap::new(heap, root_ptr) }
+}
+
+#[test]
+fn read_a_frozen_value() {
+    let frozen = build_frozen_pair();
+    let value = unsafe { frozen.read::<Pair, _, _>(|root| root.value()) };
+    assert_eq!(value, 0);
+}
+
+#[test]
+fn shared_substructure_is_still_shared() {
+    let frozen = build_frozen_pair();
+    unsafe {
+        frozen.read::<Pair, _, _>(|root| {
+            let left = root.left().unwrap();
+            let right = root.right().unwrap();
+            assert_eq!(left, right);
+            assert_eq!(left.value(), 1);
+        });
+    }
+}
+
+#[test]
+fn many_threads_read_the_same_frozen_heap_concurrently() {
+    let frozen = build_frozen_pair();
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let frozen = frozen.clone();
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    let value = unsafe { frozen.read::<Pair, _, _>(|root| root.value()) };
+                    assert_eq!(value, 0);
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
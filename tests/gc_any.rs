@@ -0,0 +1,61 @@
+//! `GcAny` erases a heap value's type down to just a `TypeId`-tagged
+//! pointer, for a dynamically typed interpreter that stores "an object" and
+//! only finds out (and checks) its concrete type when it needs to.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+use cell_gc::GcAny;
+use cell_gc::traits::IntoHeapAllocation;
+use std::marker::PhantomData;
+
+#[derive(IntoHeap)]
+struct Point<'h> {
+    x: i32,
+    y: i32,
+    phantom: PhantomData<&'h u8>,
+}
+
+#[derive(IntoHeap)]
+struct Label<'h> {
+    text: String,
+    phantom: PhantomData<&'h u8>,
+}
+
+#[test]
+fn downcast_to_the_right_type_succeeds() {
+    cell_gc::with_heap(|hs| {
+        let point = hs.alloc(Point { x: 1, y: 2, phantom: PhantomData });
+        let any: GcAny = GcAny::new(Point::into_gc_ref(point));
+
+        let point = any.downcast::<Point>().unwrap();
+        assert_eq!(point.x(), 1);
+        assert_eq!(point.y(), 2);
+    });
+}
+
+#[test]
+fn downcast_to_the_wrong_type_hands_back_the_gc_any() {
+    cell_gc::with_heap(|hs| {
+        let point = hs.alloc(Point { x: 1, y: 2, phantom: PhantomData });
+        let any: GcAny = GcAny::new(Point::into_gc_ref(point));
+
+        let any = any.downcast::<Label>().unwrap_err();
+        let point = any.downcast::<Point>().unwrap();
+        assert_eq!(point.x(), 1);
+    });
+}
+
+#[test]
+fn gc_any_target_survives_collection() {
+    cell_gc::with_heap(|hs| {
+        let label = hs.alloc(Label { text: "hi".to_string(), phantom: PhantomData });
+        let any: GcAny = GcAny::new(Label::into_gc_ref(label));
+
+        hs.force_gc();
+
+        let label = any.downcast::<Label>().unwrap();
+        assert_eq!(label.text(), "hi");
+    });
+}
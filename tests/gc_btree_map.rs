@@ -0,0 +1,52 @@
+//! `GcBTreeMap` is a heap-resident ordered map, for sorted symbol listings
+//! and interval maps that a `GcHashMap` can't provide.
+
+extern crate cell_gc;
+
+use cell_gc::collections::GcBTreeMap;
+use std::collections::BTreeMap;
+
+#[test]
+fn insert_get_and_remove() {
+    cell_gc::with_heap(|hs| {
+        let map: GcBTreeMap<i32, i32> = hs.alloc(BTreeMap::new());
+        assert_eq!(map.insert(3, 300), None);
+        assert_eq!(map.insert(1, 100), None);
+        assert_eq!(map.insert(2, 200), None);
+        assert_eq!(map.insert(1, 111), Some(100));
+        assert_eq!(map.get(1), Some(111));
+        assert_eq!(map.len(), 3);
+        assert!(map.contains_key(2));
+        assert_eq!(map.remove(2), Some(200));
+        assert!(!map.contains_key(2));
+    });
+}
+
+#[test]
+fn iteration_and_range_queries_are_ordered_by_key() {
+    cell_gc::with_heap(|hs| {
+        let map: GcBTreeMap<i32, i32> = hs.alloc(BTreeMap::new());
+        for k in [3, 1, 4, 1, 5, 9, 2, 6] {
+            map.insert(k, k * 10);
+        }
+        assert_eq!(map.first(), Some((1, 10)));
+        assert_eq!(map.last(), Some((9, 90)));
+        assert_eq!(map.range(2..6), vec![(2, 20), (3, 30), (4, 40), (5, 50)]);
+        assert_eq!(
+            map.iter(),
+            vec![(1, 10), (2, 20), (3, 30), (4, 40), (5, 50), (6, 60), (9, 90)]
+        );
+    });
+}
+
+#[test]
+fn gc_btree_map_survives_collection() {
+    cell_gc::with_heap(|hs| {
+        let map: GcBTreeMap<i32, i32> = hs.alloc(BTreeMap::new());
+        map.insert(1, 100);
+        map.insert(2, 200);
+        hs.force_gc();
+        assert_eq!(map.get(1), Some(100));
+        assert_eq!(map.get(2), Some(200));
+    });
+}
@@ -0,0 +1,52 @@
+//! `GcBTreeSet` is a heap-resident ordered set, for sorted membership
+//! queries that a `GcHashSet` can't provide.
+
+extern crate cell_gc;
+
+use cell_gc::collections::GcBTreeSet;
+
+#[test]
+fn insert_contains_and_remove() {
+    cell_gc::with_heap(|hs| {
+        let set: GcBTreeSet<i32> = GcBTreeSet::new(hs);
+        assert!(set.insert(2));
+        assert!(set.insert(1));
+        assert!(!set.insert(1));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(1));
+        assert!(set.remove(1));
+        assert!(!set.contains(1));
+        assert_eq!(set.len(), 1);
+    });
+}
+
+#[test]
+fn iter_union_and_intersection_are_ordered() {
+    cell_gc::with_heap(|hs| {
+        let a: GcBTreeSet<i32> = GcBTreeSet::new(hs);
+        for v in [3, 1, 2] {
+            a.insert(v);
+        }
+        assert_eq!(a.iter(), vec![1, 2, 3]);
+
+        let b: GcBTreeSet<i32> = GcBTreeSet::new(hs);
+        for v in [4, 2, 3] {
+            b.insert(v);
+        }
+
+        assert_eq!(a.union(&b), vec![1, 2, 3, 4]);
+        assert_eq!(a.intersection(&b), vec![2, 3]);
+    });
+}
+
+#[test]
+fn gc_btree_set_survives_collection() {
+    cell_gc::with_heap(|hs| {
+        let set: GcBTreeSet<i32> = GcBTreeSet::new(hs);
+        set.insert(1);
+        set.insert(2);
+        hs.force_gc();
+        assert!(set.contains(1));
+        assert!(set.contains(2));
+    });
+}
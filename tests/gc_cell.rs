@@ -0,0 +1,50 @@
+//! `GcCell<'h, T>` is a single mutable GC-managed slot, for a Scheme box
+//! or a mutably captured variable, without declaring a whole
+//! `#[derive(IntoHeap)]` struct just to get one.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+use cell_gc::GcCell;
+use std::marker::PhantomData;
+
+#[derive(IntoHeap)]
+struct Pair<'h> {
+    car: i32,
+    cdr: i32,
+    phantom: PhantomData<&'h u8>,
+}
+
+#[test]
+fn get_and_set_a_leaf_value() {
+    cell_gc::with_heap(|hs| {
+        let boxed = hs.alloc(GcCell::new(1));
+        assert_eq!(boxed.get(), 1);
+        boxed.set(2);
+        assert_eq!(boxed.get(), 2);
+    });
+}
+
+#[test]
+fn cloning_a_gc_cell_ref_shares_the_same_slot() {
+    cell_gc::with_heap(|hs| {
+        let boxed = hs.alloc(GcCell::new(1));
+        let alias = boxed.clone();
+        boxed.set(42);
+        assert_eq!(alias.get(), 42);
+        assert_eq!(boxed, alias);
+    });
+}
+
+#[test]
+fn gc_cell_can_hold_a_gc_ref() {
+    cell_gc::with_heap(|hs| {
+        let pair = hs.alloc(Pair { car: 1, cdr: 2, phantom: PhantomData });
+        let boxed = hs.alloc(GcCell::new(pair));
+
+        hs.force_gc();
+
+        assert_eq!(boxed.get().car(), 1);
+    });
+}
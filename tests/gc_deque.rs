@@ -0,0 +1,51 @@
+//! `GcDeque` is a heap-resident double-ended queue, for work queues and BFS
+//! frontiers over GC objects that a `VecRef` (with its O(n) `remove(0)`)
+//! handles poorly.
+
+extern crate cell_gc;
+
+use cell_gc::collections::GcDeque;
+use std::collections::VecDeque;
+
+#[test]
+fn push_and_pop_both_ends() {
+    cell_gc::with_heap(|hs| {
+        let q: GcDeque<i32> = hs.alloc(VecDeque::new());
+        q.push_back(1);
+        q.push_back(2);
+        q.push_front(0);
+        assert_eq!(q.len(), 3);
+        assert_eq!(q.front(), Some(0));
+        assert_eq!(q.back(), Some(2));
+        assert_eq!(q.get_all(), vec![0, 1, 2]);
+
+        assert_eq!(q.pop_front(), Some(0));
+        assert_eq!(q.pop_back(), Some(2));
+        assert_eq!(q.len(), 1);
+        assert_eq!(q.pop_front(), Some(1));
+        assert_eq!(q.pop_front(), None);
+        assert!(q.is_empty());
+    });
+}
+
+#[test]
+fn cloning_a_gc_deque_shares_the_same_storage() {
+    cell_gc::with_heap(|hs| {
+        let q: GcDeque<i32> = hs.alloc(VecDeque::new());
+        let alias = q.clone();
+        q.push_back(1);
+        assert_eq!(alias.front(), Some(1));
+        assert_eq!(q, alias);
+    });
+}
+
+#[test]
+fn gc_deque_survives_collection() {
+    cell_gc::with_heap(|hs| {
+        let q: GcDeque<i32> = hs.alloc(VecDeque::new());
+        q.push_back(1);
+        q.push_back(2);
+        hs.force_gc();
+        assert_eq!(q.get_all(), vec![1, 2]);
+    });
+}
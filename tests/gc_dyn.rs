@@ -0,0 +1,87 @@
+//! `gc_dyn_trait!` builds a small hand-rolled vtable for one trait, so a
+//! `GcDyn<'h, V>` reference can point at any heap value implementing that
+//! trait without knowing its concrete type --- useful for a heterogeneous
+//! container (a display list of different drawable shapes, say) that
+//! doesn't want one giant enum.
+
+#[macro_use]
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+use cell_gc::GcDyn;
+use cell_gc::traits::IntoHeapAllocation;
+use std::f64::consts::PI;
+use std::marker::PhantomData;
+
+gc_dyn_trait! {
+    trait Shape / ShapeVTable / ShapeDyn {
+        fn area(&self) -> f64;
+        fn scale(&self, factor: f64) -> f64;
+    }
+}
+
+#[derive(IntoHeap)]
+pub struct Circle<'h> {
+    pub radius: f64,
+    pub phantom: PhantomData<&'h u8>,
+}
+
+impl Shape for CircleStorage {
+    fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+
+    fn scale(&self, factor: f64) -> f64 {
+        self.radius * factor
+    }
+}
+
+static CIRCLE_VTABLE: ShapeVTable = ShapeVTable::of::<CircleStorage>();
+
+#[derive(IntoHeap)]
+pub struct Square<'h> {
+    pub side: f64,
+    pub phantom: PhantomData<&'h u8>,
+}
+
+impl Shape for SquareStorage {
+    fn area(&self) -> f64 {
+        self.side * self.side
+    }
+
+    fn scale(&self, factor: f64) -> f64 {
+        self.side * factor
+    }
+}
+
+static SQUARE_VTABLE: ShapeVTable = ShapeVTable::of::<SquareStorage>();
+
+#[test]
+fn heterogeneous_shapes_dispatch_through_one_vtable() {
+    cell_gc::with_heap(|hs| {
+        let circle = hs.alloc(Circle { radius: 2.0, phantom: PhantomData });
+        let square = hs.alloc(Square { side: 3.0, phantom: PhantomData });
+
+        let shapes: Vec<ShapeDyn> = vec![
+            unsafe { GcDyn::new(Circle::into_gc_ref(circle), &CIRCLE_VTABLE) },
+            unsafe { GcDyn::new(Square::into_gc_ref(square), &SQUARE_VTABLE) },
+        ];
+
+        assert!((shapes[0].area() - PI * 4.0).abs() < 0.0001);
+        assert_eq!(shapes[1].area(), 9.0);
+        assert_eq!(shapes[0].scale(2.0), 4.0);
+    });
+}
+
+#[test]
+fn gc_dyn_target_survives_collection() {
+    cell_gc::with_heap(|hs| {
+        let circle = hs.alloc(Circle { radius: 5.0, phantom: PhantomData });
+        let shape: ShapeDyn = unsafe { GcDyn::new(Circle::into_gc_ref(circle), &CIRCLE_VTABLE) };
+
+        hs.force_gc();
+
+        assert!((shape.area() - PI * 25.0).abs() < 0.0001);
+    });
+}
@@ -0,0 +1,62 @@
+//! `GcGrid` is a flat, row-major, heap-resident 2D grid, for game maps and
+//! cellular-automaton state that would otherwise need a separate heap
+//! allocation per row.
+
+extern crate cell_gc;
+
+use cell_gc::collections::GcGrid;
+
+#[test]
+fn get_set_and_row_iteration() {
+    cell_gc::with_heap(|hs| {
+        let grid = hs.alloc(GcGrid::new(3, 2, 0));
+        assert_eq!(grid.width(), 3);
+        assert_eq!(grid.height(), 2);
+
+        grid.set(1, 0, 10);
+        grid.set(2, 1, 20);
+        assert_eq!(grid.get(1, 0), 10);
+        assert_eq!(grid.get(0, 0), 0);
+
+        assert_eq!(grid.row(0), vec![0, 10, 0]);
+        assert_eq!(grid.row(1), vec![0, 0, 20]);
+        assert_eq!(grid.rows(), vec![vec![0, 10, 0], vec![0, 0, 20]]);
+    });
+}
+
+#[test]
+#[should_panic(expected = "out of bounds")]
+fn get_out_of_bounds_panics() {
+    cell_gc::with_heap(|hs| {
+        let grid = hs.alloc(GcGrid::new(2, 2, 0));
+        grid.get(5, 0);
+    });
+}
+
+#[test]
+fn resize_preserves_overlap_and_fills_new_cells() {
+    cell_gc::with_heap(|hs| {
+        let grid = hs.alloc(GcGrid::new(2, 2, 1));
+        grid.set(0, 0, 100);
+        grid.set(1, 1, 200);
+
+        grid.resize(3, 3, 9);
+
+        assert_eq!(grid.width(), 3);
+        assert_eq!(grid.height(), 3);
+        assert_eq!(grid.get(0, 0), 100);
+        assert_eq!(grid.get(1, 1), 200);
+        assert_eq!(grid.row(2), vec![9, 9, 9]);
+        assert_eq!(grid.get(2, 0), 9);
+    });
+}
+
+#[test]
+fn gc_grid_survives_collection() {
+    cell_gc::with_heap(|hs| {
+        let grid = hs.alloc(GcGrid::new(2, 2, 0));
+        grid.set(0, 0, 42);
+        hs.force_gc();
+        assert_eq!(grid.get(0, 0), 42);
+    });
+}
@@ -0,0 +1,59 @@
+//! `GcHashMap` is a heap-resident hash map, with keys and values traced by
+//! the collector, so building a symbol table or object property map doesn't
+//! require pairing up two parallel `VecRef`s.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+use cell_gc::collections::GcHashMap;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+#[derive(IntoHeap)]
+struct Pair<'h> {
+    car: i32,
+    cdr: i32,
+    phantom: PhantomData<&'h u8>,
+}
+
+#[test]
+fn insert_get_and_remove() {
+    cell_gc::with_heap(|hs| {
+        let map: GcHashMap<i32, i32> = hs.alloc(HashMap::new());
+        assert_eq!(map.insert(1, 100), None);
+        assert_eq!(map.insert(2, 200), None);
+        assert_eq!(map.insert(1, 111), Some(100));
+        assert_eq!(map.get(1), Some(111));
+        assert_eq!(map.get(3), None);
+        assert_eq!(map.len(), 2);
+        assert!(map.contains_key(2));
+        assert_eq!(map.remove(2), Some(200));
+        assert!(!map.contains_key(2));
+        assert_eq!(map.len(), 1);
+    });
+}
+
+#[test]
+fn cloning_a_gc_hash_map_shares_the_same_table() {
+    cell_gc::with_heap(|hs| {
+        let map: GcHashMap<i32, i32> = hs.alloc(HashMap::new());
+        let alias = map.clone();
+        map.insert(1, 100);
+        assert_eq!(alias.get(1), Some(100));
+        assert_eq!(map, alias);
+    });
+}
+
+#[test]
+fn values_can_be_gc_refs() {
+    cell_gc::with_heap(|hs| {
+        let map: GcHashMap<i32, _> = hs.alloc(HashMap::new());
+        let pair = hs.alloc(Pair { car: 1, cdr: 2, phantom: PhantomData });
+        map.insert(1, pair);
+
+        hs.force_gc();
+
+        assert_eq!(map.get(1).unwrap().car(), 1);
+    });
+}
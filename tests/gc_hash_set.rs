@@ -0,0 +1,55 @@
+//! `GcHashSet` is a heap-resident hash set, for dedup sets of interned
+//! symbols or object references keyed by identity.
+
+extern crate cell_gc;
+
+use cell_gc::collections::GcHashSet;
+
+#[test]
+fn insert_contains_and_remove() {
+    cell_gc::with_heap(|hs| {
+        let set: GcHashSet<i32> = GcHashSet::new(hs);
+        assert!(set.insert(1));
+        assert!(set.insert(2));
+        assert!(!set.insert(1));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(1));
+        assert!(set.remove(1));
+        assert!(!set.contains(1));
+        assert_eq!(set.len(), 1);
+    });
+}
+
+#[test]
+fn union_and_intersection_have_no_duplicates() {
+    cell_gc::with_heap(|hs| {
+        let a: GcHashSet<i32> = GcHashSet::new(hs);
+        for v in [1, 2, 3] {
+            a.insert(v);
+        }
+        let b: GcHashSet<i32> = GcHashSet::new(hs);
+        for v in [2, 3, 4] {
+            b.insert(v);
+        }
+
+        let mut union = a.union(&b);
+        union.sort();
+        assert_eq!(union, vec![1, 2, 3, 4]);
+
+        let mut intersection = a.intersection(&b);
+        intersection.sort();
+        assert_eq!(intersection, vec![2, 3]);
+    });
+}
+
+#[test]
+fn gc_hash_set_survives_collection() {
+    cell_gc::with_heap(|hs| {
+        let set: GcHashSet<i32> = GcHashSet::new(hs);
+        set.insert(1);
+        set.insert(2);
+        hs.force_gc();
+        assert!(set.contains(1));
+        assert!(set.contains(2));
+    });
+}
@@ -0,0 +1,40 @@
+//! `GcInterned` weakly interns values in the heap, for a symbol table that
+//! doesn't leak every symbol ever interned.
+
+extern crate cell_gc;
+
+use cell_gc::GcInterned;
+
+#[test]
+fn interning_the_same_value_returns_the_same_reference() {
+    cell_gc::with_heap(|hs| {
+        let mut symbols: GcInterned<String> = GcInterned::new();
+        let a = symbols.intern(hs, "foo".to_string());
+        let b = symbols.intern(hs, "foo".to_string());
+        assert_eq!(a, b);
+
+        let c = symbols.intern(hs, "bar".to_string());
+        assert_ne!(a, c);
+
+        assert_eq!(symbols.len(), 2);
+    });
+}
+
+#[test]
+fn dropping_all_strong_references_allows_collection() {
+    cell_gc::with_heap(|hs| {
+        let mut symbols: GcInterned<String> = GcInterned::new();
+        {
+            let a = symbols.intern(hs, "foo".to_string());
+            assert_eq!(unsafe { a.with_storage(|s| s.clone()) }, "foo");
+        }
+
+        hs.force_gc();
+
+        // Re-interning after the only strong reference is gone and a
+        // collection has run allocates a fresh value rather than reusing a
+        // dangling one.
+        let a2 = symbols.intern(hs, "foo".to_string());
+        assert_eq!(unsafe { a2.with_storage(|s| s.clone()) }, "foo");
+    });
+}
@@ -0,0 +1,58 @@
+//! `GcF64Vec`/`GcI32Vec`/`GcU8Vec` store primitives contiguously and
+//! unboxed, the foundation for Scheme bytevectors and other numerics code
+//! that can't afford to box every element the way `VecRef<f64>` would.
+
+extern crate cell_gc;
+
+use cell_gc::collections::{GcF64Vec, GcI32Vec, GcU8Vec};
+
+#[test]
+fn f64_vec_bulk_ops() {
+    cell_gc::with_heap(|hs| {
+        let v = hs.alloc(GcF64Vec::from(&[1.0, 2.0, 3.0][..]));
+        assert_eq!(v.len(), 3);
+        assert_eq!(v.get(1), 2.0);
+
+        v.set(1, 20.0);
+        assert_eq!(v.get_all(), vec![1.0, 20.0, 3.0]);
+
+        v.fill(0.0);
+        assert_eq!(v.get_all(), vec![0.0, 0.0, 0.0]);
+
+        v.copy_from_slice(&[9.0, 8.0]);
+        assert_eq!(v.get_all(), vec![9.0, 8.0]);
+
+        let sum = v.as_slice_with(|s| s.iter().sum::<f64>());
+        assert_eq!(sum, 17.0);
+    });
+}
+
+#[test]
+fn i32_vec_push_and_mutate_through_slice() {
+    cell_gc::with_heap(|hs| {
+        let v: cell_gc::collections::GcI32VecRef = hs.alloc(GcI32Vec::new());
+        v.push(1);
+        v.push(2);
+        v.push(3);
+        assert_eq!(v.get_all(), vec![1, 2, 3]);
+
+        v.as_mut_slice_with(|s| {
+            for x in s.iter_mut() {
+                *x *= 10;
+            }
+        });
+        assert_eq!(v.get_all(), vec![10, 20, 30]);
+
+        v.clear();
+        assert!(v.is_empty());
+    });
+}
+
+#[test]
+fn u8_vec_survives_collection() {
+    cell_gc::with_heap(|hs| {
+        let v = hs.alloc(GcU8Vec::from(&b"hello"[..]));
+        hs.force_gc();
+        assert_eq!(v.get_all(), b"hello".to_vec());
+    });
+}
@@ -0,0 +1,70 @@
+//! `GcPersistentMapRef` is an immutable, structurally-shared hash map:
+//! `insert` returns a new reference instead of mutating in place, and old
+//! versions stay valid (and traced) alongside new ones.
+
+extern crate cell_gc;
+
+use cell_gc::collections::GcPersistentMapRef;
+
+#[test]
+fn insert_and_get() {
+    cell_gc::with_heap(|hs| {
+        let mut m = GcPersistentMapRef::new(hs);
+        for i in 0..200 {
+            m = m.insert(hs, i, i * i);
+        }
+        assert_eq!(m.len(), 200);
+        for i in 0..200 {
+            assert_eq!(m.get(i), Some(i * i));
+        }
+        assert_eq!(m.get(200), None);
+    });
+}
+
+#[test]
+fn inserting_an_existing_key_overwrites_without_growing() {
+    cell_gc::with_heap(|hs| {
+        let m0 = GcPersistentMapRef::new(hs);
+        let m1 = m0.insert(hs, "a".to_string(), 1);
+        let m2 = m1.insert(hs, "a".to_string(), 2);
+
+        assert_eq!(m2.len(), 1);
+        assert_eq!(m2.get("a".to_string()), Some(2));
+    });
+}
+
+#[test]
+fn old_versions_are_unaffected_by_later_inserts() {
+    cell_gc::with_heap(|hs| {
+        let m0: GcPersistentMapRef<String, i32> = GcPersistentMapRef::new(hs);
+        let m1 = m0.insert(hs, "a".to_string(), 1);
+        let m2 = m1.insert(hs, "b".to_string(), 2);
+
+        assert!(m0.is_empty());
+        assert_eq!(m1.get("a".to_string()), Some(1));
+        assert_eq!(m1.get("b".to_string()), None);
+        assert_eq!(m2.get("a".to_string()), Some(1));
+        assert_eq!(m2.get("b".to_string()), Some(2));
+    });
+}
+
+#[test]
+fn survives_collection_and_shares_structure_across_versions() {
+    cell_gc::with_heap(|hs| {
+        let mut versions = Vec::new();
+        let mut m = GcPersistentMapRef::new(hs);
+        for i in 0..50 {
+            m = m.insert(hs, i, i * 10);
+            versions.push(m.clone());
+        }
+
+        hs.force_gc();
+
+        for (i, version) in versions.iter().enumerate() {
+            assert_eq!(version.len(), i + 1);
+            for k in 0..=i as i32 {
+                assert_eq!(version.get(k), Some(k * 10));
+            }
+        }
+    });
+}
@@ -0,0 +1,66 @@
+//! `GcPersistentVectorRef` is an immutable, structurally-shared vector:
+//! `push_back` and `set` return a new reference instead of mutating in
+//! place, and old versions stay valid (and traced) alongside new ones.
+
+extern crate cell_gc;
+
+use cell_gc::collections::GcPersistentVectorRef;
+
+#[test]
+fn push_back_and_get() {
+    cell_gc::with_heap(|hs| {
+        let mut v = GcPersistentVectorRef::new(hs);
+        for i in 0..100i32 {
+            v = v.push_back(hs, i);
+        }
+        assert_eq!(v.len(), 100);
+        for i in 0..100usize {
+            assert_eq!(v.get(i), i as i32);
+        }
+        assert_eq!(v.get_all(), (0..100i32).collect::<Vec<i32>>());
+    });
+}
+
+#[test]
+fn old_versions_are_unaffected_by_later_operations() {
+    cell_gc::with_heap(|hs| {
+        let v0 = GcPersistentVectorRef::new(hs);
+        let v1 = v0.push_back(hs, 10);
+        let v2 = v1.push_back(hs, 20);
+        let v3 = v2.set(hs, 0, 99);
+
+        assert_eq!(v0.len(), 0);
+        assert_eq!(v1.get_all(), vec![10]);
+        assert_eq!(v2.get_all(), vec![10, 20]);
+        assert_eq!(v3.get_all(), vec![99, 20]);
+    });
+}
+
+#[test]
+#[should_panic(expected = "out of bounds")]
+fn get_out_of_bounds_panics() {
+    cell_gc::with_heap(|hs| {
+        let v = GcPersistentVectorRef::<i32>::new(hs);
+        let v = v.push_back(hs, 1);
+        v.get(1);
+    });
+}
+
+#[test]
+fn survives_collection_and_shares_structure_across_versions() {
+    cell_gc::with_heap(|hs| {
+        let mut versions = Vec::new();
+        let mut v = GcPersistentVectorRef::new(hs);
+        for i in 0..40 {
+            v = v.push_back(hs, i);
+            versions.push(v.clone());
+        }
+
+        hs.force_gc();
+
+        for (i, version) in versions.iter().enumerate() {
+            assert_eq!(version.len(), i + 1);
+            assert_eq!(version.get_all(), (0..=i as i32).collect::<Vec<i32>>());
+        }
+    });
+}
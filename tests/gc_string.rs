@@ -0,0 +1,46 @@
+//! `GcString` is a heap-resident, growable string that can be mutated in
+//! place, unlike a plain `String` field which gets copied on every getter.
+
+extern crate cell_gc;
+
+use cell_gc::collections::GcString;
+
+#[test]
+fn push_str_appends_in_place() {
+    cell_gc::with_heap(|hs| {
+        let s = hs.alloc(GcString::from("hello"));
+        s.push_str(", world");
+        assert_eq!(s.len(), 12);
+        assert_eq!(s.as_string(), "hello, world");
+    });
+}
+
+#[test]
+fn slice_copies_out_a_byte_range() {
+    cell_gc::with_heap(|hs| {
+        let s = hs.alloc(GcString::from("hello, world"));
+        assert_eq!(s.slice(0..5), "hello");
+        assert_eq!(s.slice(7..12), "world");
+    });
+}
+
+#[test]
+fn cloning_a_gc_string_ref_shares_the_same_slot() {
+    cell_gc::with_heap(|hs| {
+        let s = hs.alloc(GcString::new());
+        let alias = s.clone();
+        s.push_str("shared");
+        assert_eq!(alias.as_string(), "shared");
+        assert_eq!(s, alias);
+    });
+}
+
+#[test]
+fn gc_string_survives_collection() {
+    cell_gc::with_heap(|hs| {
+        let s = hs.alloc(GcString::from("hello"));
+        hs.force_gc();
+        s.push_str(", world");
+        assert_eq!(s.as_string(), "hello, world");
+    });
+}
@@ -0,0 +1,63 @@
+//! `GcHeap` is `Send`, so a heap created on one thread can be handed off to
+//! another and entered there, as long as no session is open while it
+//! crosses the boundary.
+
+extern crate cell_gc;
+
+use cell_gc::{GcFrozenRef, GcHeap};
+use cell_gc::collections::VecRef;
+use std::sync::mpsc::channel;
+use std::thread;
+
+#[test]
+fn a_heap_created_on_one_thread_can_be_used_on_another() {
+    let (sender, receiver) = channel();
+
+    let producer = thread::spawn(move || {
+        let mut heap = GcHeap::new();
+        // `GcFrozenRef` is the tool for carrying a root not just across
+        // sessions but across threads (see its docs); `PinnedRef` isn't
+        // `Send`, since it's meant for same-thread C interop instead.
+        let frozen: GcFrozenRef<Vec<i32>> = heap.enter(|hs| {
+            let v: VecRef<i32> = hs.alloc(vec![1, 2, 3]);
+            hs.freeze(v)
+        });
+        sender.send((heap, frozen)).unwrap();
+    });
+
+    let consumer = thread::spawn(move || {
+        let (mut heap, frozen) = receiver.recv().unwrap();
+        heap.enter(|hs| {
+            hs.force_gc();
+            let v: VecRef<i32> = hs.thaw(frozen);
+            assert_eq!(v.get_all(), vec![1, 2, 3]);
+        });
+    });
+
+    producer.join().unwrap();
+    consumer.join().unwrap();
+}
+
+#[test]
+fn a_thread_pool_can_own_a_pool_of_heaps() {
+    // A minimal stand-in for a thread pool that owns several heaps and
+    // hands each one to a worker thread in turn.
+    let heaps: Vec<GcHeap> = (0..4).map(|_| GcHeap::new()).collect();
+
+    let workers: Vec<_> = heaps
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut heap)| {
+            thread::spawn(move || {
+                heap.enter(|hs| {
+                    let v: VecRef<i32> = hs.alloc(vec![i as i32]);
+                    hs.force_gc();
+                    v.get(0)
+                })
+            })
+        })
+        .collect();
+
+    let results: Vec<i32> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+    assert_eq!(results, vec![0, 1, 2, 3]);
+}
@@ -0,0 +1,42 @@
+//! `HeapSession` factors the operations a builtin needs (alloc, force_gc,
+//! root management) out of `GcHeapSession` into a trait, so builtin-style
+//! code can be written generically over `H: HeapSession<'h>` instead of the
+//! concrete `GcHeapSession<'h>`. Its non-generic operations also work
+//! through a `&mut dyn HeapSession<'h>` trait object.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+use cell_gc::{GcHeap, HeapSession};
+
+fn make_pair<'h, H: HeapSession<'h>>(hs: &mut H, head: Value<'h>, tail: Value<'h>) -> PairRef<'h> {
+    hs.alloc(Pair { head: head, tail: tail })
+}
+
+#[test]
+fn builtin_style_code_can_be_written_against_the_trait() {
+    let mut heap = GcHeap::new();
+    heap.enter(|hs| {
+        let pair = make_pair(hs, Value::Int(1), Value::Int(2));
+        assert_eq!(pair.head(), Value::Int(1));
+        assert_eq!(pair.tail(), Value::Int(2));
+    });
+}
+
+fn collect_through_trait_object<'h>(hs: &mut dyn HeapSession<'h>) -> bool {
+    hs.safepoint();
+    hs.force_gc();
+    hs.is_empty()
+}
+
+#[test]
+fn the_non_generic_operations_work_through_a_trait_object() {
+    let mut heap = GcHeap::new();
+    heap.enter(|hs| {
+        assert!(collect_through_trait_object(hs));
+        let _pair = alloc_null_pair(hs);
+        assert!(!collect_through_trait_object(hs));
+    });
+}
@@ -0,0 +1,52 @@
+//! `IdentityMap` keys entries by object identity, not contents, for
+//! `eq?`-style hash tables.
+
+extern crate cell_gc;
+
+use cell_gc::IdentityMap;
+
+#[test]
+fn distinct_equal_content_objects_are_distinct_keys() {
+    cell_gc::with_heap(|hs| {
+        let a = hs.alloc("hello".to_string());
+        let b = hs.alloc("hello".to_string());
+
+        let mut map = IdentityMap::new();
+        map.insert(&a, 1);
+
+        assert_eq!(map.get(&a), Some(&1));
+        assert_eq!(map.get(&b), None);
+        assert_eq!(map.len(), 1);
+    });
+}
+
+#[test]
+fn insert_overwrites_and_remove_deletes() {
+    cell_gc::with_heap(|hs| {
+        let a = hs.alloc("hello".to_string());
+
+        let mut map = IdentityMap::new();
+        assert_eq!(map.insert(&a, 1), None);
+        assert_eq!(map.insert(&a, 2), Some(1));
+        assert_eq!(map.get(&a), Some(&2));
+
+        assert_eq!(map.remove(&a), Some(2));
+        assert_eq!(map.get(&a), None);
+        assert!(map.is_empty());
+    });
+}
+
+#[test]
+fn survives_collection_as_long_as_the_key_reference_is_kept() {
+    cell_gc::with_heap(|hs| {
+        let a = hs.alloc("hello".to_string());
+
+        let mut map = IdentityMap::new();
+        map.insert(&a, 42);
+
+        hs.force_gc();
+
+        assert!(map.contains_key(&a));
+        assert_eq!(map.get(&a), Some(&42));
+    });
+}
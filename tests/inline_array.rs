@@ -0,0 +1,33 @@
+//! `[T; N]` is a plain `IntoHeap` field type when `T` is, so a fixed-fanout
+//! tree node can hold its children inline, without a separately allocated
+//! `VecRef` per node.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+#[derive(IntoHeap)]
+struct Node<'h> {
+    value: i32,
+    children: [Option<NodeRef<'h>>; 4],
+}
+
+#[test]
+fn array_field_holds_and_traces_children() {
+    cell_gc::with_heap(|hs| {
+        let leaf = hs.alloc(Node { value: 1, children: [None, None, None, None] });
+        let parent = hs.alloc(Node {
+            value: 0,
+            children: [Some(leaf.clone()), None, None, None],
+        });
+
+        hs.force_gc();
+
+        let children = parent.children();
+        assert_eq!(children[0].as_ref().map(|c| c.value()), Some(1));
+        assert!(children[1].is_none());
+
+        parent.set_children([None, Some(leaf.clone()), None, None]);
+        assert_eq!(parent.children()[1].as_ref().map(|c| c.value()), Some(1));
+    });
+}
@@ -0,0 +1,78 @@
+//! A `#[derive(IntoHeap)]` struct field doesn't have to be wrapped in a
+//! `Ref`: embedding the struct itself stores it inline, by value, with no
+//! separate heap allocation and no extra pointer hop to reach it.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+use std::marker::PhantomData;
+
+#[derive(Clone, IntoHeap)]
+struct Vec3<'h> {
+    x: f64,
+    y: f64,
+    z: f64,
+    phantom: PhantomData<&'h u8>,
+}
+
+#[derive(IntoHeap)]
+struct Transform<'h> {
+    position: Vec3<'h>,
+    scale: Vec3<'h>,
+}
+
+fn vec3(x: f64, y: f64, z: f64) -> Vec3<'static> {
+    Vec3 { x: x, y: y, z: z, phantom: PhantomData }
+}
+
+#[test]
+fn inline_field_reads_back() {
+    cell_gc::with_heap(|hs| {
+        let t = hs.alloc(Transform {
+            position: vec3(1.0, 2.0, 3.0),
+            scale: vec3(1.0, 1.0, 1.0),
+        });
+
+        assert_eq!(t.position().x, 1.0);
+        assert_eq!(t.scale().z, 1.0);
+
+        t.set_position(vec3(4.0, 5.0, 6.0));
+        assert_eq!(t.position().x, 4.0);
+    });
+}
+
+#[test]
+fn inline_field_is_not_a_separate_allocation() {
+    cell_gc::with_heap(|hs| {
+        // `Vec3` gets its own `Ref` and is separately `hs.alloc()`-able, like
+        // any other `#[derive(IntoHeap)]` struct --- but embedding two of
+        // them inside `Transform` doesn't touch `Vec3`'s own page set at
+        // all. Capping it at zero pages and then successfully allocating a
+        // `Transform` (which holds two inline `Vec3`s) proves they're stored
+        // inline in `Transform`'s own storage, not as separate objects.
+        hs.set_page_limit::<Vec3>(Some(0));
+        assert_eq!(hs.try_alloc(vec3(0.0, 0.0, 0.0)), None);
+
+        let t = hs.alloc(Transform {
+            position: vec3(1.0, 2.0, 3.0),
+            scale: vec3(1.0, 1.0, 1.0),
+        });
+        assert_eq!(t.position().x, 1.0);
+    });
+}
+
+#[test]
+fn inline_fields_are_traced() {
+    cell_gc::with_heap(|hs| {
+        let t = hs.alloc(Transform {
+            position: vec3(1.0, 2.0, 3.0),
+            scale: vec3(1.0, 1.0, 1.0),
+        });
+
+        hs.force_gc();
+
+        assert_eq!(t.position().x, 1.0);
+        assert_eq!(t.scale().z, 1.0);
+    });
+}
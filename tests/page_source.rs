@@ -0,0 +1,73 @@
+//! `GcHeap::with_page_source`/`set_page_source` let an embedder supply its
+//! own backing memory for pages instead of the process's global allocator.
+//! This exercises the extension point with a custom `PageSource` (not just
+//! the default `GlobalPageSource`), to prove it's actually pluggable.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+use cell_gc::{GcHeap, PageSource};
+use std::alloc::{self, Layout};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A `PageSource` that just forwards to the global allocator, but counts
+/// how many pages it has handed out and taken back.
+struct CountingPageSource {
+    live_pages: Arc<AtomicUsize>,
+}
+
+unsafe impl PageSource for CountingPageSource {
+    unsafe fn alloc_page(&mut self, size: usize, align: usize) -> *mut u8 {
+        let ptr = alloc::alloc(Layout::from_size_align_unchecked(size, align));
+        if !ptr.is_null() {
+            self.live_pages.fetch_add(1, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc_page(&mut self, ptr: *mut u8, size: usize, align: usize) {
+        alloc::dealloc(ptr, Layout::from_size_align_unchecked(size, align));
+        self.live_pages.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn a_custom_page_source_is_used_for_every_page() {
+    let live_pages = Arc::new(AtomicUsize::new(0));
+    let mut heap = GcHeap::with_page_source(CountingPageSource {
+        live_pages: live_pages.clone(),
+    });
+
+    heap.enter(|hs| {
+        assert_eq!(live_pages.load(Ordering::SeqCst), 0);
+
+        let mut pairs = Vec::new();
+        for _ in 0..cell_gc::page_capacity::<Pair>() + 1 {
+            pairs.push(alloc_null_pair(hs));
+        }
+        assert!(live_pages.load(Ordering::SeqCst) >= 2);
+
+        drop(pairs);
+        hs.force_gc();
+        hs.shrink_to_fit();
+        assert_eq!(live_pages.load(Ordering::SeqCst), 0);
+    });
+}
+
+#[test]
+fn set_page_source_also_installs_a_custom_source() {
+    let live_pages = Arc::new(AtomicUsize::new(0));
+    let mut heap = GcHeap::new();
+    heap.set_page_source(CountingPageSource {
+        live_pages: live_pages.clone(),
+    });
+
+    heap.enter(|hs| {
+        let pair = alloc_null_pair(hs);
+        assert_eq!(live_pages.load(Ordering::SeqCst), 1);
+        drop(pair);
+    });
+}
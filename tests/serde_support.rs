@@ -0,0 +1,69 @@
+//! Optional `serde::Serialize`/`Deserialize` support for a
+//! `#[derive(IntoHeap)]` struct that opts in with `#[into_heap(serde)]` and
+//! has only plain, non-GC fields. Run with `cargo test --features serde` to
+//! exercise it; with the feature off, this file is an empty no-op crate.
+//!
+//! A field pointing back into the heap (a `FooRef<'h>`, say) doesn't get
+//! this treatment: serializing one soundly needs an id scheme to survive
+//! sharing and cycles, which this doesn't attempt yet, so `#[into_heap(serde)]`
+//! on a struct with such a field is a compile error instead of silently
+//! doing nothing.
+
+#![cfg(feature = "serde")]
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+extern crate serde;
+extern crate serde_json;
+
+use cell_gc::GcHeap;
+use serde::de::DeserializeSeed;
+use std::marker::PhantomData;
+
+#[derive(Debug, IntoHeap)]
+#[into_heap(serde)]
+struct Point<'h> {
+    x: i32,
+    y: i32,
+    label: Option<String>,
+    marker: PhantomData<&'h ()>,
+}
+
+#[test]
+fn a_point_round_trips_through_json() {
+    let mut heap = GcHeap::new();
+    heap.enter(|hs| {
+        let point = hs.alloc(Point {
+            x: 3,
+            y: -4,
+            label: Some("origin-ish".to_string()),
+            marker: PhantomData,
+        });
+
+        let json = serde_json::to_string(&point).unwrap();
+
+        let seed = PointSeed { heap: hs };
+        let round_tripped = seed.deserialize(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+
+        assert_eq!(round_tripped.x(), 3);
+        assert_eq!(round_tripped.y(), -4);
+        assert_eq!(round_tripped.label(), Some("origin-ish".to_string()));
+    });
+}
+
+#[test]
+fn a_deserialized_point_survives_a_collection() {
+    let mut heap = GcHeap::new();
+    heap.enter(|hs| {
+        let json = r#"{"x":1,"y":2,"label":null,"marker":null}"#;
+        let seed = PointSeed { heap: hs };
+        let point = seed.deserialize(&mut serde_json::Deserializer::from_str(json)).unwrap();
+
+        hs.force_gc();
+
+        assert_eq!(point.x(), 1);
+        assert_eq!(point.y(), 2);
+        assert_eq!(point.label(), None);
+    });
+}
@@ -0,0 +1,63 @@
+//! `GcHeap::enter` can be called any number of times on the same heap, one
+//! session after another: each call gets its own fresh `'h`, but the heap's
+//! contents persist across the boundary. A `PinnedRef`, not being tied to
+//! any one session's `'h`, is how something is carried across that
+//! boundary as a root.
+
+extern crate cell_gc;
+
+use cell_gc::GcHeap;
+use cell_gc::collections::VecRef;
+
+#[test]
+fn reopening_a_heap_preserves_a_pinned_root() {
+    let mut heap = GcHeap::new();
+
+    let pinned = heap.enter(|hs| hs.alloc_pinned(vec![1, 2, 3]));
+
+    // The session that created `v` is over; a fresh session, with its own
+    // `'h`, picks the heap back up without having lost `v`.
+    heap.enter(|hs| {
+        hs.force_gc();
+        let v: VecRef<i32> = hs.unpin(pinned);
+        assert_eq!(v.get_all(), vec![1, 2, 3]);
+    });
+}
+
+#[test]
+fn a_pinned_root_survives_several_reentries() {
+    let mut heap = GcHeap::new();
+
+    let pinned = heap.enter(|hs| hs.alloc_pinned(vec![10i32]));
+
+    let pinned: cell_gc::PinnedRef<Vec<i32>> = heap.enter(|hs| {
+        let v: VecRef<i32> = hs.unpin(pinned);
+        v.push(20);
+        hs.pin(v)
+    });
+
+    heap.enter(|hs| {
+        hs.force_gc();
+        let v: VecRef<i32> = hs.unpin(pinned);
+        assert_eq!(v.get_all(), vec![10, 20]);
+    });
+}
+
+#[test]
+fn values_left_unpinned_across_a_session_boundary_are_still_collected() {
+    // Re-entering the same heap isn't a way to keep everything alive
+    // forever; only what's actually rooted survives, exactly as within a
+    // single session.
+    let mut heap = GcHeap::new();
+
+    heap.enter(|hs| {
+        let _v: VecRef<i32> = hs.alloc(vec![1, 2, 3]);
+        // `_v` isn't pinned or returned, so nothing roots it once this
+        // session ends.
+    });
+
+    heap.enter(|hs| {
+        hs.force_gc();
+        assert!(hs.is_empty());
+    });
+}
@@ -0,0 +1,159 @@
+//! `step_collection` breaks one collection's mark phase into several
+//! bounded calls instead of the single pause `force_gc` (see
+//! `tests/full_heap.rs` and others) takes. `safepoint` is the cooperative
+//! yield point built on top of it, for code that doesn't want to manage
+//! `step_collection` calls itself. `collect_with_deadline` is the
+//! wall-clock-bounded counterpart to `step_collection`'s fuel-bounded one.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+
+use aux::pairs::*;
+use cell_gc::CollectionStep;
+use std::time::{Duration, Instant};
+
+#[test]
+fn step_collection_reaches_the_same_result_as_force_gc() {
+    cell_gc::with_heap(|hs| {
+        let mut v = Value::Null;
+        for _ in 0..50 {
+            v = Value::Pair(alloc_pair(hs, Value::Null, v));
+        }
+        let root = match v {
+            Value::Pair(p) => p,
+            _ => unreachable!(),
+        };
+
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            match hs.step_collection(1) {
+                CollectionStep::InProgress => continue,
+                CollectionStep::Finished(stats) => {
+                    assert!(stats.objects_marked >= 50);
+                    break;
+                }
+            }
+        }
+        assert!(
+            steps > 1,
+            "a fuel of 1 should take more than one call to mark a 50-long chain"
+        );
+
+        assert_eq!(root.head(), Value::Null);
+    });
+}
+
+#[test]
+fn safepoint_is_a_no_op_with_no_collection_in_progress() {
+    cell_gc::with_heap(|hs| {
+        let pair = alloc_null_pair(hs);
+        hs.safepoint();
+        hs.safepoint();
+        assert_eq!(pair.head(), Value::Null);
+    });
+}
+
+#[test]
+fn repeated_safepoints_finish_an_open_collection() {
+    cell_gc::with_heap(|hs| {
+        let mut v = Value::Null;
+        for _ in 0..50 {
+            v = Value::Pair(alloc_pair(hs, Value::Null, v));
+        }
+        let root = match v {
+            Value::Pair(p) => p,
+            _ => unreachable!(),
+        };
+
+        assert!(matches!(hs.step_collection(1), CollectionStep::InProgress));
+
+        // Each safepoint does a small, fixed amount of mark work; enough of
+        // them must finish off the collection `step_collection(1)` started.
+        for _ in 0..1000 {
+            hs.safepoint();
+        }
+
+        // The collection is done, so a fresh allocation should be fine.
+        alloc_null_pair(hs);
+        assert_eq!(root.head(), Value::Null);
+    });
+}
+
+#[test]
+fn collect_with_deadline_finishes_given_plenty_of_time() {
+    cell_gc::with_heap(|hs| {
+        let mut v = Value::Null;
+        for _ in 0..50 {
+            v = Value::Pair(alloc_pair(hs, Value::Null, v));
+        }
+        let root = match v {
+            Value::Pair(p) => p,
+            _ => unreachable!(),
+        };
+
+        match hs.collect_with_deadline(Instant::now() + Duration::from_secs(60)) {
+            CollectionStep::Finished(stats) => {
+                assert!(stats.objects_marked >= 50);
+                assert!(!stats.deadline_missed);
+            }
+            CollectionStep::InProgress => panic!("a 60-second deadline should be plenty"),
+        }
+
+        assert_eq!(root.head(), Value::Null);
+    });
+}
+
+#[test]
+fn collect_with_deadline_reports_in_progress_when_out_of_time() {
+    cell_gc::with_heap(|hs| {
+        let mut v = Value::Null;
+        // Longer than `SAFEPOINT_FUEL`: `collect_with_deadline` always runs
+        // one fuel-bounded slice of mark work before it ever looks at the
+        // clock, so the chain has to be too long for a single
+        // `SAFEPOINT_FUEL`-sized slice to finish, or an already-past
+        // deadline would still (correctly) come back `Finished`.
+        for _ in 0..500 {
+            v = Value::Pair(alloc_pair(hs, Value::Null, v));
+        }
+
+        // A deadline already in the past: at most one fuel-bounded slice of
+        // mark work can run before it's checked, so a chain this long
+        // can't possibly be done yet.
+        let step = hs.collect_with_deadline(Instant::now());
+        assert!(matches!(step, CollectionStep::InProgress));
+
+        // Finish it off so the heap is left in a consistent state.
+        loop {
+            if let CollectionStep::Finished(_) = hs.step_collection(1000) {
+                break;
+            }
+        }
+        let _ = v;
+    });
+}
+
+#[test]
+#[should_panic(expected = "cannot allocate while a step_collection is in progress")]
+fn allocating_mid_collection_panics() {
+    cell_gc::with_heap(|hs| {
+        let mut v = Value::Null;
+        for _ in 0..50 {
+            v = Value::Pair(alloc_pair(hs, Value::Null, v));
+        }
+
+        let step = hs.step_collection(1);
+        assert!(
+            matches!(step, CollectionStep::InProgress),
+            "expected the collection to still be open after one fuel-1 step"
+        );
+
+        // Still mid-collection: this must panic rather than let a new,
+        // not-yet-marked object slip past the mark phase undetected.
+        alloc_null_pair(hs);
+
+        let _ = v;
+    });
+}
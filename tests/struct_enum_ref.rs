@@ -0,0 +1,37 @@
+//! Struct-like enum variants work with `#[into_heap(make_ref)]` too, so the enum
+//! itself can be allocated directly, not just nested inside a struct.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+#[derive(Clone, Debug, IntoHeap)]
+struct ThingBox<'h> {
+    thing: ThingRef<'h>,
+}
+
+#[derive(Clone, Debug, PartialEq, IntoHeap)]
+#[into_heap(make_ref)]
+enum Thing<'h> {
+    Zero,
+    One { it: ThingBoxRef<'h> },
+    Two {
+        left: ThingBoxRef<'h>,
+        right: ThingBoxRef<'h>,
+    },
+}
+
+#[test]
+fn struct_enum_ref() {
+    cell_gc::with_heap(|hs| {
+        let zero = hs.alloc(Thing::Zero);
+        let zero_box = hs.alloc(ThingBox { thing: zero.clone() });
+        let one = hs.alloc(Thing::One { it: zero_box.clone() });
+
+        assert_eq!(zero.get(), Thing::Zero);
+        assert_eq!(one.get(), Thing::One { it: zero_box.clone() });
+
+        one.set(Thing::Zero);
+        assert_eq!(one.get(), Thing::Zero);
+    });
+}
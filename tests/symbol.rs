@@ -0,0 +1,50 @@
+//! `Symbols` specializes `GcInterned` to a dedicated `Symbol` heap type, so
+//! interning the same text twice returns the same `SymbolRef`. See
+//! `tests/gc_interned.rs` for the underlying weak-interning behavior.
+
+extern crate cell_gc;
+
+use cell_gc::Symbols;
+
+#[test]
+fn interning_the_same_text_returns_the_same_symbol() {
+    cell_gc::with_heap(|hs| {
+        let mut symbols = Symbols::new();
+        let a = symbols.intern(hs, "foo");
+        let b = symbols.intern(hs, "foo");
+        assert_eq!(a, b);
+
+        let c = symbols.intern(hs, "bar");
+        assert_ne!(a, c);
+
+        assert_eq!(symbols.len(), 2);
+    });
+}
+
+#[test]
+fn a_symbol_remembers_its_text() {
+    cell_gc::with_heap(|hs| {
+        let mut symbols = Symbols::new();
+        let a = symbols.intern(hs, "foo");
+        assert_eq!(a.as_str(), "foo");
+    });
+}
+
+#[test]
+fn dropping_all_strong_references_allows_collection() {
+    cell_gc::with_heap(|hs| {
+        let mut symbols = Symbols::new();
+        {
+            let a = symbols.intern(hs, "foo");
+            assert_eq!(a.as_str(), "foo");
+        }
+
+        hs.force_gc();
+
+        // Re-interning after the only strong reference is gone and a
+        // collection has run allocates a fresh value rather than reusing a
+        // dangling one.
+        let a2 = symbols.intern(hs, "foo");
+        assert_eq!(a2.as_str(), "foo");
+    });
+}
@@ -0,0 +1,35 @@
+//! `GcHeap::types` lists every allocation type a heap has used, for tools
+//! (debuggers, heap-usage summaries) that need to introspect object types
+//! they didn't statically know about. See `tests/leaks.rs` for the closely
+//! related `check_for_leaks`, which reports counts the same way but only
+//! for unexpectedly-live objects.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+use cell_gc::GcHeap;
+
+#[test]
+fn types_reports_name_size_and_live_count() {
+    let mut heap = GcHeap::new();
+    heap.enter(|hs| {
+        let mut v = Value::Null;
+        for _ in 0..10 {
+            v = Value::Pair(alloc_pair(hs, Value::Null, v));
+        }
+        let _ = v;
+    });
+
+    let types = heap.types();
+    // `name` is `std::any::type_name` on the storage type the `IntoHeap`
+    // macro generates (see `GcHeap::types`'s docs), not on `Pair` itself --
+    // `tests/census.rs` matches on the same `Storage` suffix.
+    let pair_type = types
+        .iter()
+        .find(|info| info.name.ends_with("::PairStorage"))
+        .expect("Pair should be in the type registry after allocating one");
+    assert_eq!(pair_type.live_count, 10);
+    assert!(pair_type.size > 0);
+}
@@ -0,0 +1,38 @@
+//! `update_<field>` (and, for tuple structs, `updateN`) mutates a field's
+//! in-heap storage in place, instead of the get/mutate/set round trip of
+//! copying the whole field out via the getter and converting a new value
+//! back into storage.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+
+use cell_gc::GcLeaf;
+use std::marker::PhantomData;
+
+#[derive(IntoHeap)]
+struct Journal<'h> {
+    text: String,
+    phantom: PhantomData<&'h u8>,
+}
+
+#[derive(IntoHeap)]
+struct Wrapper<'h>(GcLeaf<Vec<i32>>, PhantomData<&'h u8>);
+
+#[test]
+fn update_named_field_in_place() {
+    cell_gc::with_heap(|hs| {
+        let journal = hs.alloc(Journal { text: String::from("hello"), phantom: PhantomData });
+        journal.update_text(|s| s.push_str(", world"));
+        assert_eq!(journal.text(), "hello, world");
+    });
+}
+
+#[test]
+fn update_tuple_field_in_place() {
+    cell_gc::with_heap(|hs| {
+        let wrapper = hs.alloc(Wrapper(GcLeaf::new(vec![1, 2, 3]), PhantomData));
+        wrapper.update0(|v| v.push(4));
+        assert_eq!(wrapper.get0().unwrap(), vec![1, 2, 3, 4]);
+    });
+}
@@ -0,0 +1,64 @@
+//! The four user flag bits in the object header (`GcRef::get_user_flag` /
+//! `set_user_flag`) are for embedders that want a cheap per-object mark ---
+//! a "visited" bit for a cycle-aware printer, an "immutable" bit for
+//! literal data --- without adding a whole field to every object. Unlike
+//! the mark bit, cell-gc never touches them, so they survive collection.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+
+#[test]
+fn user_flags_default_to_false() {
+    cell_gc::with_heap(|hs| {
+        let pair = alloc_null_pair(hs);
+        for i in 0..4 {
+            assert!(!pair.get_user_flag(i));
+        }
+    });
+}
+
+#[test]
+fn user_flags_are_independent_of_each_other() {
+    cell_gc::with_heap(|hs| {
+        let pair = alloc_null_pair(hs);
+        pair.set_user_flag(1, true);
+        pair.set_user_flag(3, true);
+
+        assert!(!pair.get_user_flag(0));
+        assert!(pair.get_user_flag(1));
+        assert!(!pair.get_user_flag(2));
+        assert!(pair.get_user_flag(3));
+
+        pair.set_user_flag(1, false);
+        assert!(!pair.get_user_flag(1));
+        assert!(pair.get_user_flag(3));
+    });
+}
+
+#[test]
+fn user_flags_survive_a_collection() {
+    cell_gc::with_heap(|hs| {
+        let pair = alloc_null_pair(hs);
+        pair.set_user_flag(2, true);
+
+        for _ in 0..10_000 {
+            let _ = alloc_null_pair(hs);
+        }
+        hs.force_gc();
+
+        assert!(pair.get_user_flag(2));
+        assert!(!pair.get_user_flag(0));
+    });
+}
+
+#[test]
+#[should_panic(expected = "user flag index out of range")]
+fn out_of_range_index_panics() {
+    cell_gc::with_heap(|hs| {
+        let pair = alloc_null_pair(hs);
+        pair.get_user_flag(4);
+    });
+}
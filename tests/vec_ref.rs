@@ -30,3 +30,43 @@ fn vec_ref() {
         assert_eq!(car.wheels().get(3), "rr");
     });
 }
+
+#[test]
+fn vec_ref_std_vec_parity() {
+    let mut heap = GcHeap::new();
+    heap.enter(|hs| {
+        let r: VecRef<i32> = hs.alloc(vec![1, 2, 3, 4, 5]);
+
+        assert!(r.contains(3));
+        assert!(!r.contains(9));
+
+        assert_eq!(r.get_range(1..4), vec![2, 3, 4]);
+
+        r.extend(vec![6, 7]);
+        assert_eq!(r.get_all(), vec![1, 2, 3, 4, 5, 6, 7]);
+
+        r.retain(|&x| x % 2 == 0);
+        assert_eq!(r.get_all(), vec![2, 4, 6]);
+
+        assert_eq!(r.binary_search_by(|x| x.cmp(&4)), Ok(1));
+        assert_eq!(r.binary_search_by(|x| x.cmp(&5)), Err(2));
+    });
+}
+
+#[test]
+fn vec_ref_iteration_helpers() {
+    let mut heap = GcHeap::new();
+    heap.enter(|hs| {
+        let r: VecRef<i32> = hs.alloc(vec![1, 2, 3]);
+
+        let collected: Vec<i32> = r.iter().collect();
+        assert_eq!(collected, vec![1, 2, 3]);
+
+        let mut sum = 0;
+        r.for_each(|x| sum += x);
+        assert_eq!(sum, 6);
+
+        r.map_in_place(|x| x * 10);
+        assert_eq!(r.get_all(), vec![10, 20, 30]);
+    });
+}
@@ -0,0 +1,38 @@
+//! `VecRef::push` delegates straight to the in-heap `std::vec::Vec`'s own
+//! `push`, so it inherits `Vec`'s amortized O(1) growth (geometric
+//! reallocation of the backing buffer) rather than reallocating
+//! conservatively or copying existing elements out and back in on every
+//! push.
+
+extern crate cell_gc;
+
+use cell_gc::collections::VecRef;
+
+#[test]
+fn push_reallocates_a_logarithmic_number_of_times() {
+    cell_gc::with_heap(|hs| {
+        let v: VecRef<i32> = hs.alloc(Vec::new());
+
+        let n = 20_000;
+        let mut reallocations = 0;
+        let mut last_capacity = v.capacity();
+        for i in 0..n {
+            v.push(i);
+            let capacity = v.capacity();
+            if capacity != last_capacity {
+                reallocations += 1;
+                last_capacity = capacity;
+            }
+        }
+
+        assert_eq!(v.len(), n as usize);
+        // Geometric growth reallocates O(log n) times; a conservative,
+        // grow-by-a-fixed-amount scheme would reallocate O(n) times.
+        assert!(
+            reallocations < 40,
+            "expected O(log n) reallocations for {} pushes, saw {}",
+            n,
+            reallocations
+        );
+    });
+}
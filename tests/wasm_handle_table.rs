@@ -0,0 +1,48 @@
+//! `cell_gc::wasm::HandleTable` is meant for embedders exposing GC values
+//! across a JS/wasm boundary as opaque integer handles rather than raw
+//! pointers.
+
+extern crate cell_gc;
+#[macro_use]
+extern crate cell_gc_derive;
+mod aux;
+use aux::pairs::*;
+use cell_gc::wasm::HandleTable;
+
+#[test]
+fn a_handle_keeps_its_value_alive_across_a_collection() {
+    cell_gc::with_heap(|hs| {
+        let mut table = HandleTable::new();
+        let handle = table.insert(hs, Value::Int(42));
+
+        hs.force_gc();
+
+        assert_eq!(table.get(handle), Some(Value::Int(42)));
+    });
+}
+
+#[test]
+fn freeing_a_handle_invalidates_it() {
+    cell_gc::with_heap(|hs| {
+        let mut table = HandleTable::new();
+        let handle = table.insert(hs, Value::Int(1));
+
+        table.free(handle);
+
+        assert_eq!(table.get(handle), None);
+    });
+}
+
+#[test]
+fn handles_are_independent() {
+    cell_gc::with_heap(|hs| {
+        let mut table = HandleTable::new();
+        let a = table.insert(hs, Value::Int(1));
+        let b = table.insert(hs, Value::Int(2));
+
+        table.free(a);
+
+        assert_eq!(table.get(a), None);
+        assert_eq!(table.get(b), Some(Value::Int(2)));
+    });
+}